@@ -0,0 +1,73 @@
+//! Compares JSON and MessagePack encoding of a large listing payload.
+//!
+//! Run with `cargo bench --bench ipc_payload`. The entry type mirrors
+//! `scanner::FileEntry`, which is private to the app crate.
+
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+const ENTRY_COUNT: usize = 100_000;
+const ROUNDS: u32 = 10;
+
+#[derive(Serialize)]
+struct FileEntry {
+    name: String,
+    path: String,
+    is_dir: bool,
+    size: u64,
+    size_complete: bool,
+}
+
+fn entries() -> Vec<FileEntry> {
+    (0..ENTRY_COUNT)
+        .map(|index| {
+            let name = format!("file_{:06}.dat", index);
+            FileEntry {
+                path: format!(
+                    "/Users/someone/Projects/dunefiles/data/{}/{}",
+                    index % 97,
+                    name
+                ),
+                name,
+                is_dir: index % 10 == 0,
+                size: (index as u64) * 4_096 + 17,
+                size_complete: index % 13 != 0,
+            }
+        })
+        .collect()
+}
+
+fn measure(label: &str, mut encode: impl FnMut() -> usize) -> Duration {
+    let bytes = encode();
+    let started = Instant::now();
+
+    for _ in 0..ROUNDS {
+        std::hint::black_box(encode());
+    }
+
+    let per_round = started.elapsed() / ROUNDS;
+    println!(
+        "{:<12} {:>10.2} ms/encode {:>10.2} MB",
+        label,
+        per_round.as_secs_f64() * 1_000.0,
+        bytes as f64 / 1_000_000.0
+    );
+
+    per_round
+}
+
+fn main() {
+    let entries = entries();
+
+    println!("Encoding {} entries, {} rounds", ENTRY_COUNT, ROUNDS);
+
+    let json = measure("json", || serde_json::to_string(&entries).unwrap().len());
+    let msgpack = measure("msgpack", || {
+        rmp_serde::to_vec_named(&entries).unwrap().len()
+    });
+
+    println!(
+        "msgpack encodes in {:.0}% of the json time",
+        msgpack.as_secs_f64() / json.as_secs_f64() * 100.0
+    );
+}
@@ -0,0 +1,93 @@
+use crate::format;
+use crate::i18n::tr;
+use crate::scan::ScanTree;
+use serde::Serialize;
+
+/// What a screen reader announces for one ring segment
+#[derive(Debug, Clone, Serialize)]
+pub struct SegmentDescription {
+    pub path: String,
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub files: u64,
+    /// Percent of the parent's size; `None` at the scan root
+    pub share_of_parent: Option<f64>,
+    /// 1 for the largest of its siblings
+    pub rank: Option<usize>,
+    pub siblings: usize,
+    /// All of the above as one sentence in `Settings.language`
+    pub text: String,
+}
+
+/// The node's name, or the whole path for the scan root
+fn label(tree: &ScanTree, index: u32) -> String {
+    let node = &tree.nodes[index as usize];
+    match node.parent {
+        Some(_) => node.name.to_string(),
+        None => tree.path(index).to_string_lossy().into_owned(),
+    }
+}
+
+/// Name, size, share of its parent and rank among its siblings of `tree.nodes[index]`
+pub fn describe(tree: &ScanTree, index: u32) -> SegmentDescription {
+    let node = &tree.nodes[index as usize];
+    let path = tree.path(index).to_string_lossy().into_owned();
+    let name = label(tree, index);
+
+    let mut text = tr(
+        if node.is_dir {
+            "segment.folder"
+        } else {
+            "segment.file"
+        },
+        &[
+            ("name", &name),
+            ("size", &format::size(node.size)),
+            ("files", &format::count(node.file_count)),
+        ],
+    );
+
+    let (mut share_of_parent, mut rank, mut siblings) = (None, None, 0);
+    if let Some(parent) = node.parent {
+        let parent_node = &tree.nodes[parent as usize];
+        let sizes: Vec<u64> = tree
+            .nodes
+            .iter()
+            .filter(|other| other.parent == Some(parent))
+            .map(|other| other.size)
+            .collect();
+        siblings = sizes.len();
+        let position = 1 + sizes.iter().filter(|&&size| size > node.size).count();
+        let share = if parent_node.size == 0 {
+            0.0
+        } else {
+            node.size as f64 * 100.0 / parent_node.size as f64
+        };
+
+        text.push(' ');
+        text.push_str(&tr(
+            "segment.in_parent",
+            &[
+                ("share", &format!("{:.1}%", share)),
+                ("parent", &label(tree, parent)),
+                ("rank", &position.to_string()),
+                ("siblings", &siblings.to_string()),
+            ],
+        ));
+        share_of_parent = Some(share);
+        rank = Some(position);
+    }
+
+    SegmentDescription {
+        path,
+        name,
+        is_dir: node.is_dir,
+        size: node.size,
+        files: node.file_count,
+        share_of_parent,
+        rank,
+        siblings,
+        text,
+    }
+}
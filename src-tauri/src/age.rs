@@ -0,0 +1,174 @@
+use crate::error::DuneError;
+use crate::scanner::{self, DirEntryInfo, DirEnumerator, EntryKind, Hidden};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+const WEEK: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+const MONTH: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+const QUARTER: Duration = Duration::from_secs(91 * 24 * 60 * 60);
+const YEAR: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// Bucket of an age, from 0 for the last week to 4 for older than a year
+pub fn bucket(age: Duration) -> usize {
+    [WEEK, MONTH, QUARTER, YEAR]
+        .iter()
+        .position(|&limit| age < limit)
+        .unwrap_or(4)
+}
+
+/// Bytes by last-modified age; each bucket excludes the newer ones
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct AgeBuckets {
+    pub week: u64,
+    pub month: u64,
+    pub quarter: u64,
+    pub year: u64,
+    /// Older than a year, or with no known modification time
+    pub older: u64,
+}
+
+impl AgeBuckets {
+    fn add(&mut self, age: Duration, bytes: u64) {
+        let bucket = match bucket(age) {
+            0 => &mut self.week,
+            1 => &mut self.month,
+            2 => &mut self.quarter,
+            3 => &mut self.year,
+            _ => &mut self.older,
+        };
+
+        *bucket = bucket.saturating_add(bytes);
+    }
+
+    fn merge(&mut self, other: &AgeBuckets) {
+        self.week = self.week.saturating_add(other.week);
+        self.month = self.month.saturating_add(other.month);
+        self.quarter = self.quarter.saturating_add(other.quarter);
+        self.year = self.year.saturating_add(other.year);
+        self.older = self.older.saturating_add(other.older);
+    }
+
+    fn total(&self) -> u64 {
+        self.week
+            .saturating_add(self.month)
+            .saturating_add(self.quarter)
+            .saturating_add(self.year)
+            .saturating_add(self.older)
+    }
+}
+
+/// Age breakdown for one child of the requested folder
+#[derive(Debug, Clone, Serialize)]
+pub struct AgeEntry {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub buckets: AgeBuckets,
+    /// Days since the newest file in this entry changed, `None` if it holds no files
+    pub newest_days: Option<u64>,
+    pub complete: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AgeHistogram {
+    pub path: String,
+    pub total: AgeBuckets,
+    /// Children, largest first, for coloring the ring by age
+    pub entries: Vec<AgeEntry>,
+    pub complete: bool,
+}
+
+#[derive(Default)]
+struct Walk {
+    buckets: AgeBuckets,
+    newest: Option<SystemTime>,
+    complete: bool,
+}
+
+impl Walk {
+    fn add_file(&mut self, now: SystemTime, modified: Option<SystemTime>, bytes: u64) {
+        // Files stamped in the future count as brand new
+        let age = modified.map_or(Duration::MAX, |modified| {
+            now.duration_since(modified).unwrap_or_default()
+        });
+
+        self.buckets.add(age, bytes);
+        self.newest = self.newest.max(modified);
+    }
+}
+
+/// Files at and under `path`, bucketed by their age at `now`
+fn walk(
+    enumerator: &dyn DirEnumerator,
+    path: &Path,
+    entry: &DirEntryInfo,
+    now: SystemTime,
+) -> Walk {
+    let mut walked = Walk::default();
+    let complete = scanner::visit(
+        enumerator,
+        path,
+        entry,
+        Hidden::Skip,
+        &mut |_: &Path, entry: &DirEntryInfo| {
+            if entry.kind == EntryKind::File {
+                walked.add_file(now, entry.modified, entry.len);
+            }
+            true
+        },
+    );
+    walked.complete = complete;
+
+    walked
+}
+
+/// Bucket every file under `path` by age, per child, with the same hidden-file rules as listings
+pub fn age_histogram(path: &str) -> Result<AgeHistogram, DuneError> {
+    let dir_path = scanner::require_dir(path)?;
+    let enumerator = scanner::default_enumerator();
+    let listing = enumerator
+        .read_dir(dir_path)
+        .map_err(|e| DuneError::io(dir_path, e))?;
+    let now = SystemTime::now();
+
+    let mut entries: Vec<AgeEntry> = listing
+        .entries
+        .into_par_iter()
+        .filter(|entry| !scanner::is_hidden_name(&entry.name))
+        .filter_map(|entry| {
+            if !matches!(entry.kind, EntryKind::File | EntryKind::Dir) {
+                return None;
+            }
+            let entry_path = dir_path.join(&entry.name);
+            let walked = walk(enumerator, &entry_path, &entry, now);
+
+            Some(AgeEntry {
+                name: entry.name.to_string_lossy().to_string(),
+                path: entry_path.to_string_lossy().to_string(),
+                is_dir: entry.kind == EntryKind::Dir,
+                buckets: walked.buckets,
+                newest_days: walked.newest.map(|newest| {
+                    now.duration_since(newest).unwrap_or_default().as_secs() / DAY.as_secs()
+                }),
+                complete: walked.complete,
+            })
+        })
+        .collect();
+
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.buckets.total()));
+
+    let mut total = AgeBuckets::default();
+    for entry in &entries {
+        total.merge(&entry.buckets);
+    }
+
+    Ok(AgeHistogram {
+        path: path.to_string(),
+        total,
+        complete: listing.complete && entries.iter().all(|entry| entry.complete),
+        entries,
+    })
+}
@@ -0,0 +1,280 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Why a file's length says little about the space it takes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageFlags {
+    /// Shares blocks with an APFS clone, so deleting it frees less than its size
+    pub cloned: bool,
+    /// Holes take no space; allocated is less than the length
+    pub sparse: bool,
+    /// Stored compressed by the filesystem (APFS, HFS+ or NTFS)
+    pub compressed: bool,
+}
+
+impl StorageFlags {
+    pub fn is_plain(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Where one file's bytes actually go
+#[derive(Debug, Clone, Copy)]
+pub struct Allocation {
+    pub allocated: u64,
+    pub flags: StorageFlags,
+    /// Clone family and the bytes of `allocated` it may share with the rest of it
+    pub shared: Option<(u64, u64)>,
+}
+
+/// Totals across a scan; see `AllocationSummary`
+#[derive(Debug, Default)]
+pub struct Totals {
+    allocated: u64,
+    /// Bytes only one file owns
+    private: u64,
+    /// Largest shared part seen per clone family, counted once
+    shared: HashMap<u64, u64>,
+    cloned_files: u64,
+    sparse_files: u64,
+    compressed_files: u64,
+}
+
+impl Totals {
+    pub fn add(&mut self, allocation: &Allocation) {
+        self.allocated = self.allocated.saturating_add(allocation.allocated);
+        let shared = match allocation.shared {
+            Some((family, shared)) => {
+                let largest = self.shared.entry(family).or_default();
+                *largest = (*largest).max(shared);
+                shared
+            }
+            None => 0,
+        };
+        self.private = self
+            .private
+            .saturating_add(allocation.allocated.saturating_sub(shared));
+
+        self.cloned_files += u64::from(allocation.flags.cloned);
+        self.sparse_files += u64::from(allocation.flags.sparse);
+        self.compressed_files += u64::from(allocation.flags.compressed);
+    }
+
+    pub fn merge(&mut self, other: Totals) {
+        self.allocated = self.allocated.saturating_add(other.allocated);
+        self.private = self.private.saturating_add(other.private);
+        for (family, shared) in other.shared {
+            let largest = self.shared.entry(family).or_default();
+            *largest = (*largest).max(shared);
+        }
+        self.cloned_files += other.cloned_files;
+        self.sparse_files += other.sparse_files;
+        self.compressed_files += other.compressed_files;
+    }
+
+    pub fn summary(&self) -> AllocationSummary {
+        AllocationSummary {
+            allocated: self.allocated,
+            unique_allocated: self
+                .private
+                .saturating_add(self.shared.values().sum::<u64>()),
+            cloned_files: self.cloned_files,
+            sparse_files: self.sparse_files,
+            compressed_files: self.compressed_files,
+        }
+    }
+}
+
+/// Space a scanned tree takes on disk, from `ScanOptions.allocation`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AllocationSummary {
+    /// Allocated blocks of every file, clones counted each time
+    pub allocated: u64,
+    /// Clone families' shared blocks counted once; an estimate, since clones can share
+    /// different parts of each other
+    pub unique_allocated: u64,
+    pub cloned_files: u64,
+    pub sparse_files: u64,
+    pub compressed_files: u64,
+}
+
+/// Allocation of a regular file, not following links; `None` if it cannot be read
+pub fn of(path: &Path) -> Option<Allocation> {
+    platform::allocation(path)
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{Allocation, StorageFlags};
+    use std::ffi::CString;
+    use std::fs;
+    use std::os::macos::fs::MetadataExt as _;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::Path;
+
+    // `EF_MAY_SHARE_BLOCKS` from <sys/attr.h>
+    const EF_MAY_SHARE_BLOCKS: u64 = 0x1;
+
+    fn read_u64(buffer: &[u8], offset: usize) -> u64 {
+        u64::from_ne_bytes(buffer[offset..offset + 8].try_into().unwrap())
+    }
+
+    pub fn allocation(path: &Path) -> Option<Allocation> {
+        let metadata = fs::symlink_metadata(path).ok()?;
+        let allocated = metadata.blocks() * 512;
+        let compressed = metadata.st_flags() & libc::UF_COMPRESSED != 0;
+        let sparse = !compressed && allocated + metadata.blksize() <= metadata.len();
+        let plain = Allocation {
+            allocated,
+            flags: StorageFlags {
+                cloned: false,
+                sparse,
+                compressed,
+            },
+            shared: None,
+        };
+
+        let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+        // With FSOPT_ATTR_CMN_EXTENDED the fork group holds the extended common attributes
+        let mut attributes = libc::attrlist {
+            bitmapcount: libc::ATTR_BIT_MAP_COUNT,
+            reserved: 0,
+            commonattr: libc::ATTR_CMN_RETURNED_ATTRS,
+            volattr: 0,
+            dirattr: 0,
+            fileattr: 0,
+            forkattr: libc::ATTR_CMNEXT_PRIVATESIZE
+                | libc::ATTR_CMNEXT_CLONEID
+                | libc::ATTR_CMNEXT_EXT_FLAGS,
+        };
+        let mut buffer = [0u8; 64];
+        let result = unsafe {
+            libc::getattrlist(
+                c_path.as_ptr(),
+                &mut attributes as *mut libc::attrlist as *mut libc::c_void,
+                buffer.as_mut_ptr() as *mut libc::c_void,
+                buffer.len(),
+                libc::FSOPT_NOFOLLOW | libc::FSOPT_ATTR_CMN_EXTENDED | libc::FSOPT_PACK_INVAL_ATTRS,
+            )
+        };
+        // Only APFS knows about clones
+        if result != 0 {
+            return Some(plain);
+        }
+
+        // Length, returned attributes, then private size, clone id and extended flags
+        let set = 4;
+        let returned = u32::from_ne_bytes(buffer[set + 16..set + 20].try_into().unwrap());
+        let values = set + std::mem::size_of::<libc::attribute_set_t>();
+        let wanted = libc::ATTR_CMNEXT_PRIVATESIZE | libc::ATTR_CMNEXT_CLONEID;
+        if returned & wanted != wanted {
+            return Some(plain);
+        }
+
+        let private = read_u64(&buffer, values).min(allocated);
+        let clone_id = read_u64(&buffer, values + 8);
+        let may_share = returned & libc::ATTR_CMNEXT_EXT_FLAGS == 0
+            || read_u64(&buffer, values + 16) & EF_MAY_SHARE_BLOCKS != 0;
+        if !may_share || private >= allocated {
+            return Some(plain);
+        }
+
+        Some(Allocation {
+            flags: StorageFlags {
+                cloned: true,
+                ..plain.flags
+            },
+            shared: Some((clone_id, allocated - private)),
+            ..plain
+        })
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{Allocation, StorageFlags};
+    use std::fs;
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::fs::MetadataExt;
+    use std::path::Path;
+    use windows_sys::Win32::Foundation::{GetLastError, NO_ERROR};
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetCompressedFileSizeW, FILE_ATTRIBUTE_COMPRESSED, FILE_ATTRIBUTE_SPARSE_FILE,
+        INVALID_FILE_SIZE,
+    };
+
+    /// Bytes on disk of compressed and sparse files, the length of any other
+    fn compressed_size(path: &Path) -> Option<u64> {
+        let wide: Vec<u16> = path
+            .as_os_str()
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        let mut high = 0u32;
+        let low = unsafe { GetCompressedFileSizeW(wide.as_ptr(), &mut high) };
+        if low == INVALID_FILE_SIZE && unsafe { GetLastError() } != NO_ERROR {
+            return None;
+        }
+
+        Some(u64::from(high) << 32 | u64::from(low))
+    }
+
+    pub fn allocation(path: &Path) -> Option<Allocation> {
+        let metadata = fs::symlink_metadata(path).ok()?;
+        let attributes = metadata.file_attributes();
+        let sparse = attributes & FILE_ATTRIBUTE_SPARSE_FILE != 0;
+        let compressed = attributes & FILE_ATTRIBUTE_COMPRESSED != 0;
+        let allocated = if sparse || compressed {
+            compressed_size(path).unwrap_or(metadata.len())
+        } else {
+            metadata.len()
+        };
+
+        Some(Allocation {
+            allocated,
+            flags: StorageFlags {
+                cloned: false,
+                sparse,
+                compressed,
+            },
+            shared: None,
+        })
+    }
+}
+
+/// Blocks are all there is; compressed extents on btrfs or ZFS look sparse
+#[cfg(all(unix, not(target_os = "macos")))]
+mod platform {
+    use super::{Allocation, StorageFlags};
+    use std::fs;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::Path;
+
+    pub fn allocation(path: &Path) -> Option<Allocation> {
+        let metadata = fs::symlink_metadata(path).ok()?;
+        let allocated = metadata.blocks() * 512;
+
+        Some(Allocation {
+            allocated,
+            flags: StorageFlags {
+                cloned: false,
+                sparse: allocated + metadata.blksize() <= metadata.len(),
+                compressed: false,
+            },
+            shared: None,
+        })
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod platform {
+    use super::Allocation;
+    use std::path::Path;
+
+    pub fn allocation(_path: &Path) -> Option<Allocation> {
+        None
+    }
+}
@@ -0,0 +1,94 @@
+use serde::Deserialize;
+use std::hash::{Hash, Hasher};
+use std::sync::OnceLock;
+
+/// Longer "extensions", as in `Notes v1.2 for Acme`, are part of the name
+const MAX_EXTENSION: usize = 8;
+
+/// How names are replaced so a layout can be shared without them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Redaction {
+    /// `Folder 3fa2c1`, the same for the same name until the app restarts
+    Hashed,
+    /// `Folder A`, `Folder B`, `File A.pdf`, in the order names are given out
+    Lettered,
+}
+
+/// Mixed into every hash, so labels cannot be looked up from a list of likely names
+fn salt() -> u64 {
+    static SALT: OnceLock<u64> = OnceLock::new();
+    *SALT.get_or_init(|| {
+        let mut bytes = [0u8; 8];
+        if let Err(err) = getrandom::getrandom(&mut bytes) {
+            log::warn!("No random salt for redacted names: {}", err);
+        }
+        u64::from_le_bytes(bytes)
+    })
+}
+
+/// `A` to `Z`, then `AA`, `AB` and so on
+fn letters(mut number: usize) -> String {
+    let mut out = Vec::new();
+    loop {
+        out.push(b'A' + (number % 26) as u8);
+        if number < 26 {
+            break;
+        }
+        number = number / 26 - 1;
+    }
+    out.reverse();
+    String::from_utf8(out).unwrap_or_default()
+}
+
+/// Gives out replacement names; files keep their extension, which says what kind of
+/// file it is but nothing about whose
+pub struct Anonymizer {
+    mode: Redaction,
+    folders: usize,
+    files: usize,
+}
+
+impl Anonymizer {
+    pub fn new(mode: Redaction) -> Self {
+        Self {
+            mode,
+            folders: 0,
+            files: 0,
+        }
+    }
+
+    pub fn name(&mut self, name: &str, is_dir: bool) -> String {
+        let label = match self.mode {
+            Redaction::Hashed => {
+                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                salt().hash(&mut hasher);
+                name.hash(&mut hasher);
+                format!("{:06x}", hasher.finish() & 0xff_ffff)
+            }
+            Redaction::Lettered => {
+                let counter = if is_dir {
+                    &mut self.folders
+                } else {
+                    &mut self.files
+                };
+                *counter += 1;
+                letters(*counter - 1)
+            }
+        };
+
+        if is_dir {
+            return format!("Folder {}", label);
+        }
+        match name.rsplit_once('.') {
+            Some((stem, extension))
+                if !stem.is_empty()
+                    && (1..=MAX_EXTENSION).contains(&extension.len())
+                    && extension.chars().all(char::is_alphanumeric) =>
+            {
+                format!("File {}.{}", label, extension)
+            }
+            _ => format!("File {}", label),
+        }
+    }
+}
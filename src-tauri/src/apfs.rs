@@ -0,0 +1,102 @@
+use crate::scanner::DiskInfo;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// The APFS container a volume draws its space from, shared with its other volumes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApfsContainer {
+    /// Synthesized disk of the container, such as `disk3`
+    pub id: String,
+    /// Capacity and free space of the whole container; each volume's own totals repeat
+    /// these, so adding volumes up counts the container once per volume
+    pub total_space: u64,
+    pub available_space: u64,
+    /// Bytes this volume alone takes up in the container
+    pub volume_used: Option<u64>,
+    /// Mount points of the other listed volumes in the container
+    pub shared_with: Vec<String>,
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::*;
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    /// `disk3` for `/dev/disk3s1s1`, the sealed system snapshot of volume 1 in container 3
+    pub fn container_of(device: &str) -> Option<String> {
+        let rest = device.strip_prefix("/dev/disk")?;
+        let number: String = rest.chars().take_while(char::is_ascii_digit).collect();
+        (!number.is_empty()).then(|| format!("disk{}", number))
+    }
+
+    /// `ATTR_VOL_SPACEUSED`, what the volume itself holds, which `statfs` cannot tell
+    /// apart from the rest of the container
+    pub fn volume_used(mount: &Path) -> Option<u64> {
+        let c_path = CString::new(mount.as_os_str().as_bytes()).ok()?;
+        let mut attributes = libc::attrlist {
+            bitmapcount: libc::ATTR_BIT_MAP_COUNT,
+            reserved: 0,
+            commonattr: 0,
+            volattr: libc::ATTR_VOL_INFO | libc::ATTR_VOL_SPACEUSED,
+            dirattr: 0,
+            fileattr: 0,
+            forkattr: 0,
+        };
+        // Length, then the `off_t`
+        let mut buffer = [0u8; 12];
+        let result = unsafe {
+            libc::getattrlist(
+                c_path.as_ptr(),
+                &mut attributes as *mut libc::attrlist as *mut libc::c_void,
+                buffer.as_mut_ptr() as *mut libc::c_void,
+                buffer.len(),
+                0,
+            )
+        };
+        if result != 0 {
+            return None;
+        }
+
+        let used = i64::from_ne_bytes(buffer[4..12].try_into().ok()?);
+        u64::try_from(used).ok()
+    }
+}
+
+/// The container of the APFS volume mounted at `mount`, with `shared_with` left empty
+#[cfg(target_os = "macos")]
+pub fn container(mount: &Path, total_space: u64, available_space: u64) -> Option<ApfsContainer> {
+    let device = crate::scanner::mounted_from(mount)?;
+    Some(ApfsContainer {
+        id: platform::container_of(&device)?,
+        total_space,
+        available_space,
+        volume_used: platform::volume_used(mount),
+        shared_with: Vec::new(),
+    })
+}
+
+/// Containers are an APFS notion, and APFS volumes only mount natively on macOS
+#[cfg(not(target_os = "macos"))]
+pub fn container(_mount: &Path, _total_space: u64, _available_space: u64) -> Option<ApfsContainer> {
+    None
+}
+
+/// Fill in `shared_with` of each volume in a container from the other listed volumes
+pub fn mark_shared(disks: &mut [DiskInfo]) {
+    let members: Vec<(String, String)> = disks
+        .iter()
+        .filter_map(|disk| Some((disk.container.as_ref()?.id.clone(), disk.path.clone())))
+        .collect();
+
+    for disk in disks.iter_mut() {
+        let Some(container) = disk.container.as_mut() else {
+            continue;
+        };
+        container.shared_with = members
+            .iter()
+            .filter(|(id, other)| *id == container.id && *other != disk.path)
+            .map(|(_, other)| other.clone())
+            .collect();
+    }
+}
@@ -0,0 +1,478 @@
+use crate::allocation::AllocationSummary;
+use crate::error::DuneError;
+use crate::format::TreeSummary;
+use crate::metrics::{self, ScanRecord};
+use crate::scan::{ScanOptions, ScanStats};
+use crate::service::ScannerService;
+use crate::settings::{self, ApiSettings};
+use crate::{forecast, scanner, snapshot};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+/// How often the accept loop checks whether it should stop
+const ACCEPT_POLL: Duration = Duration::from_millis(100);
+/// Slow or idle clients are dropped after this long without sending anything
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_HEADER_BYTES: u64 = 16 * 1024;
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+/// Scans block their connection, so this also caps concurrent API scans
+const MAX_CONNECTIONS: usize = 8;
+const JSON: &str = "application/json";
+
+/// The running server, held in managed state and restarted when its settings change
+#[derive(Default)]
+pub struct ApiServer {
+    running: Mutex<Option<Running>>,
+}
+
+struct Running {
+    settings: Arc<ApiSettings>,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl Running {
+    fn shut_down(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.thread.join();
+        log::info!("HTTP API on port {} stopped", self.settings.port);
+    }
+}
+
+impl ApiServer {
+    /// Start, stop or restart the server to match `settings`
+    pub fn apply(&self, app: &AppHandle, settings: &ApiSettings) -> Result<(), DuneError> {
+        let mut running = self.running.lock().unwrap();
+        let unchanged = running
+            .as_ref()
+            .is_some_and(|server| *server.settings == *settings);
+        if settings.enabled && unchanged {
+            return Ok(());
+        }
+
+        if let Some(server) = running.take() {
+            server.shut_down();
+        }
+        if !settings.enabled {
+            return Ok(());
+        }
+        if settings.token.is_empty() {
+            return Err(DuneError::InvalidInput(
+                "The HTTP API needs a token before it can start".into(),
+            ));
+        }
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, settings.port)).map_err(|err| {
+            DuneError::Io {
+                path: None,
+                message: format!("Could not listen on port {}: {}", settings.port, err),
+            }
+        })?;
+        listener
+            .set_nonblocking(true)
+            .map_err(DuneError::internal)?;
+
+        let settings = Arc::new(settings.clone());
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let (app, settings, stop) = (app.clone(), settings.clone(), stop.clone());
+            thread::spawn(move || accept_loop(&app, listener, &settings, &stop))
+        };
+        log::info!("HTTP API listening on 127.0.0.1:{}", settings.port);
+
+        *running = Some(Running {
+            settings,
+            stop,
+            thread,
+        });
+
+        Ok(())
+    }
+}
+
+/// 32 random bytes as hex
+pub fn generate_token() -> Result<String, DuneError> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).map_err(DuneError::internal)?;
+
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Keep the stored token when saved settings come without one, and make one
+/// when the API is first enabled
+pub fn ensure_token(settings: &mut ApiSettings, stored: &ApiSettings) -> Result<(), DuneError> {
+    if settings.token.is_empty() {
+        settings.token.clone_from(&stored.token);
+    }
+    if settings.enabled && settings.token.is_empty() {
+        settings.token = generate_token()?;
+    }
+
+    Ok(())
+}
+
+fn accept_loop(
+    app: &AppHandle,
+    listener: TcpListener,
+    settings: &Arc<ApiSettings>,
+    stop: &AtomicBool,
+) {
+    let connections = Arc::new(AtomicUsize::new(0));
+
+    while !stop.load(Ordering::Relaxed) {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_POLL);
+                continue;
+            }
+            Err(err) => {
+                log::warn!("HTTP API accept failed: {}", err);
+                thread::sleep(ACCEPT_POLL);
+                continue;
+            }
+        };
+
+        if connections.fetch_add(1, Ordering::Relaxed) >= MAX_CONNECTIONS {
+            connections.fetch_sub(1, Ordering::Relaxed);
+            let _ = write_response(&stream, 503, JSON, b"{\"message\":\"Too many requests\"}");
+            continue;
+        }
+
+        let (app, settings, connections) = (app.clone(), settings.clone(), connections.clone());
+        thread::spawn(move || {
+            if let Err(err) = serve(&app, stream, &settings) {
+                log::debug!("HTTP API connection failed: {}", err);
+            }
+            connections.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    host: Option<String>,
+    authorization: Option<String>,
+    body: Vec<u8>,
+}
+
+fn read_request(stream: &TcpStream) -> io::Result<Request> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let mut reader = BufReader::new(stream);
+    let mut header_bytes = 0;
+    let mut read_line = |reader: &mut BufReader<&TcpStream>, line: &mut String| {
+        line.clear();
+        let read = reader
+            .by_ref()
+            .take(MAX_HEADER_BYTES - header_bytes)
+            .read_line(line)?;
+        header_bytes += read as u64;
+        if read == 0 || !line.ends_with('\n') {
+            return Err(invalid("Headers too long or cut off"));
+        }
+        Ok(())
+    };
+
+    let mut line = String::new();
+    read_line(&mut reader, &mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(invalid("Malformed request line"));
+    };
+    let mut request = Request {
+        method: method.to_string(),
+        // Nothing takes query parameters, so they are dropped
+        path: target.split('?').next().unwrap_or(target).to_string(),
+        host: None,
+        authorization: None,
+        body: Vec::new(),
+    };
+
+    let mut content_length = 0;
+    loop {
+        read_line(&mut reader, &mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return Err(invalid("Malformed header"));
+        };
+        let value = value.trim();
+
+        match name.trim().to_ascii_lowercase().as_str() {
+            "host" => request.host = Some(value.to_string()),
+            "authorization" => request.authorization = Some(value.to_string()),
+            "content-length" => {
+                content_length = value.parse().map_err(|_| invalid("Bad Content-Length"))?
+            }
+            _ => {}
+        }
+    }
+
+    if content_length > MAX_BODY_BYTES {
+        return Err(invalid("Body too large"));
+    }
+    request.body = vec![0; content_length];
+    reader.read_exact(&mut request.body)?;
+
+    Ok(request)
+}
+
+fn status_text(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        409 => "Conflict",
+        421 => "Misdirected Request",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+fn write_response(
+    mut stream: &TcpStream,
+    status: u16,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        status_text(status),
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+/// Compare without stopping at the first difference, so timing does not leak the token
+fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Browsers send the name they resolved, so anything but a loopback name is a
+/// DNS rebinding attempt from a web page
+fn is_loopback_host(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(bracketed) => bracketed.split(']').next().unwrap_or(bracketed),
+        None => host.rsplit_once(':').map_or(host, |(name, _)| name),
+    };
+
+    matches!(name, "127.0.0.1" | "localhost" | "::1")
+}
+
+fn status_for(err: &DuneError) -> u16 {
+    match err {
+        DuneError::NotFound { .. } => 404,
+        DuneError::PermissionDenied { .. } | DuneError::Protected { .. } => 403,
+        DuneError::NotADirectory { .. } | DuneError::InvalidInput(_) => 400,
+        DuneError::Cancelled => 409,
+        DuneError::Unsupported(_) => 501,
+        DuneError::Io { .. } | DuneError::Internal(_) => 500,
+    }
+}
+
+fn serve(app: &AppHandle, stream: TcpStream, settings: &ApiSettings) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+    let request = match read_request(&stream) {
+        Ok(request) => request,
+        Err(err) => {
+            let body = serde_json::to_vec(&DuneError::InvalidInput(err.to_string()))?;
+            return write_response(&stream, 400, JSON, &body);
+        }
+    };
+
+    let started = Instant::now();
+    let (status, content_type, body) = respond(app, &request, settings);
+    log::info!(
+        "HTTP API {} {} -> {} in {:?}",
+        request.method,
+        request.path,
+        status,
+        started.elapsed()
+    );
+
+    write_response(&stream, status, content_type, &body)
+}
+
+fn error_response(status: u16, err: &DuneError) -> (u16, &'static str, Vec<u8>) {
+    (status, JSON, serde_json::to_vec(err).unwrap_or_default())
+}
+
+fn respond(
+    app: &AppHandle,
+    request: &Request,
+    settings: &ApiSettings,
+) -> (u16, &'static str, Vec<u8>) {
+    if !request.host.as_deref().is_some_and(is_loopback_host) {
+        let err = DuneError::InvalidInput("Host must be 127.0.0.1 or localhost".into());
+        return error_response(421, &err);
+    }
+
+    let authorized = request
+        .authorization
+        .as_deref()
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| token_matches(given.trim(), &settings.token));
+    if !authorized {
+        let err = DuneError::InvalidInput("Missing or wrong bearer token".into());
+        return error_response(401, &err);
+    }
+
+    if settings.metrics && request.method == "GET" && request.path == "/metrics" {
+        return (200, metrics::CONTENT_TYPE, render_metrics(app).into_bytes());
+    }
+
+    match route(app, request) {
+        Ok(Some(body)) => (200, JSON, body),
+        Ok(None) => {
+            let err = DuneError::NotFound {
+                path: format!("{} {}", request.method, request.path),
+            };
+            error_response(404, &err)
+        }
+        Err(err) => error_response(status_for(&err), &err),
+    }
+}
+
+/// Every disk, and the latest scan of each watched or scheduled folder
+fn render_metrics(app: &AppHandle) -> String {
+    let service = app.state::<ScannerService>();
+    let mut roots = service.watched_roots();
+    roots.extend(settings::load().background.scheduled_paths);
+    roots.sort();
+    roots.dedup();
+
+    let scanned: Vec<(String, ScanRecord)> = roots
+        .into_iter()
+        .filter_map(|root| service.last_scan(&root).map(|record| (root, record)))
+        .collect();
+
+    metrics::render(&scanner::overview_disks(), &scanned)
+}
+
+/// Body of `POST /v1/scans`
+#[derive(Debug, Deserialize)]
+struct ScanRequest {
+    path: String,
+    /// Without options, the named profile or the one chosen for `path` applies
+    options: Option<ScanOptions>,
+    profile: Option<String>,
+}
+
+/// Answer to `POST /v1/scans`; fetch the tree with `GET /v1/scans/<scan_id>`
+#[derive(Debug, Serialize)]
+struct ScanSummary {
+    scan_id: u64,
+    root: String,
+    total_size: u64,
+    file_count: u64,
+    node_count: u64,
+    /// `total_size` and `file_count` in the app's units and locale
+    total_size_text: String,
+    file_count_text: String,
+    /// With `options.allocation`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allocation: Option<AllocationSummary>,
+    complete: bool,
+    elapsed_ms: u64,
+    /// Folders and files visited, throughput, cache hits and errors, for local scans
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats: Option<ScanStats>,
+}
+
+fn json(value: &impl Serialize) -> Result<Option<Vec<u8>>, DuneError> {
+    serde_json::to_vec(value)
+        .map(Some)
+        .map_err(DuneError::internal)
+}
+
+/// The JSON body of a matched route, or `None` when nothing matches
+fn route(app: &AppHandle, request: &Request) -> Result<Option<Vec<u8>>, DuneError> {
+    let service = app.state::<ScannerService>();
+    let segments: Vec<&str> = request
+        .path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["v1", "status"]) => json(&serde_json::json!({
+            "app": app.package_info().name,
+            "version": app.package_info().version.to_string(),
+        })),
+        ("GET", ["v1", "disks"]) => {
+            let mut disks = scanner::overview_disks();
+            forecast::mark_filling_disks(&snapshot::snapshot_dir(app)?, &mut disks);
+            json(&disks)
+        }
+        ("POST", ["v1", "scans"]) => {
+            let scan: ScanRequest = serde_json::from_slice(&request.body)
+                .map_err(|err| DuneError::InvalidInput(err.to_string()))?;
+            let options = match scan.options {
+                Some(options) => options,
+                None => settings::load()
+                    .scan
+                    .options_for(&scan.path, scan.profile.as_deref())?,
+            };
+            let started = Instant::now();
+            let handle = service.register_scan(scan.path);
+            let tree = service.run_scan(&handle, &options, &|_| {})?;
+            let root = tree.nodes.first();
+            let summary = TreeSummary::of(&tree);
+
+            json(&ScanSummary {
+                scan_id: tree.scan_id,
+                root: tree.root.clone(),
+                total_size: root.map_or(0, |node| node.size),
+                file_count: root.map_or(0, |node| node.file_count),
+                node_count: tree.nodes.len() as u64,
+                total_size_text: summary.total_size_text,
+                file_count_text: summary.file_count_text,
+                allocation: tree.allocation,
+                complete: root.map_or(true, |node| node.size_complete),
+                elapsed_ms: started.elapsed().as_millis() as u64,
+                stats: tree.stats.clone(),
+            })
+        }
+        ("GET", ["v1", "scans", id]) => {
+            let tree = service.finished_scan(parse_scan_id(id)?)?;
+            json(&snapshot::unsaved(&tree)?)
+        }
+        ("POST", ["v1", "scans", id, "snapshot"]) => {
+            let tree = service.finished_scan(parse_scan_id(id)?)?;
+            json(&snapshot::save(&snapshot::snapshot_dir(app)?, &tree)?)
+        }
+        ("GET", ["v1", "snapshots"]) => json(&snapshot::list(&snapshot::snapshot_dir(app)?)?),
+        ("GET", ["v1", "snapshots", id]) => {
+            json(&snapshot::load(&snapshot::snapshot_dir(app)?, id)?)
+        }
+        _ => Ok(None),
+    }
+}
+
+fn parse_scan_id(id: &str) -> Result<u64, DuneError> {
+    id.parse()
+        .map_err(|_| DuneError::InvalidInput(format!("Not a scan id: {}", id)))
+}
@@ -0,0 +1,11 @@
+use crate::error::DuneError;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager, Runtime};
+
+/// `name` in the app's data folder, where its stores, snapshots and catalogs live
+pub fn path<R: Runtime>(app: &AppHandle<R>, name: &str) -> Result<PathBuf, DuneError> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(name))
+        .map_err(DuneError::internal)
+}
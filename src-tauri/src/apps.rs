@@ -0,0 +1,313 @@
+use crate::scanner;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// One folder or file attributed to an app
+#[derive(Debug, Clone, Serialize)]
+pub struct AppLocation {
+    pub path: String,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppUsage {
+    pub name: String,
+    /// Bundle id, package id, or snap name when the platform has one
+    pub id: Option<String>,
+    /// Where the app itself is installed
+    pub install: Vec<AppLocation>,
+    /// Settings, data, and caches kept elsewhere
+    pub data: Vec<AppLocation>,
+    pub install_bytes: u64,
+    pub data_bytes: u64,
+    pub total_bytes: u64,
+    pub complete: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppsReport {
+    /// Apps, largest first
+    pub apps: Vec<AppUsage>,
+    pub total_bytes: u64,
+}
+
+/// An app found on disk, before measuring
+struct AppCandidate {
+    name: String,
+    id: Option<String>,
+    install: Vec<PathBuf>,
+    data: Vec<PathBuf>,
+}
+
+fn child_dirs(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(any(windows, target_os = "linux"))]
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Measure `paths`, dropping the ones that do not exist
+fn measure(paths: &[PathBuf], complete: &mut bool) -> Vec<AppLocation> {
+    let mut seen = Vec::new();
+
+    paths
+        .iter()
+        .filter(|path| path.exists())
+        // Name and id lookups can point at the same folder
+        .filter(|path| {
+            let key = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+            if seen.contains(&key) {
+                return false;
+            }
+            seen.push(key);
+            true
+        })
+        .map(|path| {
+            let size = scanner::path_size(path);
+            *complete &= size.complete;
+            AppLocation {
+                path: path.to_string_lossy().to_string(),
+                bytes: size.bytes,
+            }
+        })
+        .collect()
+}
+
+/// Measure every installed app found in the platform's usual places
+pub fn installed_apps_report() -> AppsReport {
+    let mut apps: Vec<AppUsage> = platform::candidates()
+        .into_par_iter()
+        .map(|candidate| {
+            let mut complete = true;
+            let install = measure(&candidate.install, &mut complete);
+            let data = measure(&candidate.data, &mut complete);
+            let install_bytes = install.iter().map(|location| location.bytes).sum();
+            let data_bytes = data.iter().map(|location| location.bytes).sum();
+
+            AppUsage {
+                name: candidate.name,
+                id: candidate.id,
+                install,
+                data,
+                install_bytes,
+                data_bytes,
+                total_bytes: install_bytes + data_bytes,
+                complete,
+            }
+        })
+        .filter(|app| app.total_bytes > 0)
+        .collect();
+
+    apps.sort_by_key(|app| std::cmp::Reverse(app.total_bytes));
+    log::info!("Measured {} installed apps", apps.len());
+
+    AppsReport {
+        total_bytes: apps.iter().map(|app| app.total_bytes).sum(),
+        apps,
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{child_dirs, AppCandidate};
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    fn bundle_id(bundle: &Path) -> Option<String> {
+        let output = Command::new("defaults")
+            .arg("read")
+            .arg(bundle.join("Contents").join("Info"))
+            .arg("CFBundleIdentifier")
+            .output()
+            .ok()?;
+
+        let id = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (output.status.success() && !id.is_empty()).then_some(id)
+    }
+
+    pub fn candidates() -> Vec<AppCandidate> {
+        let Some(home) = dirs::home_dir() else {
+            return Vec::new();
+        };
+        let library = home.join("Library");
+        let mut bundles: Vec<PathBuf> = child_dirs(Path::new("/Applications"));
+        bundles.extend(child_dirs(&home.join("Applications")));
+
+        bundles
+            .into_iter()
+            .filter(|bundle| crate::filetype::is_bundle(bundle))
+            .map(|bundle| {
+                let name = bundle
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let id = bundle_id(&bundle);
+
+                let mut data = Vec::new();
+                for key in std::iter::once(&name).chain(id.as_ref()) {
+                    data.push(library.join("Application Support").join(key));
+                    data.push(library.join("Caches").join(key));
+                }
+                if let Some(id) = &id {
+                    data.push(library.join("Containers").join(id));
+                    data.push(
+                        library
+                            .join("Saved Application State")
+                            .join(format!("{}.savedState", id)),
+                    );
+                }
+
+                AppCandidate {
+                    name,
+                    id,
+                    install: vec![bundle],
+                    data,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{child_dirs, file_name, AppCandidate};
+    use std::path::PathBuf;
+
+    /// Shared folders under Program Files that are not apps of their own
+    const NOT_APPS: &[&str] = &[
+        "Common Files",
+        "WindowsApps",
+        "Windows NT",
+        "ModifiableWindowsApps",
+    ];
+
+    pub fn candidates() -> Vec<AppCandidate> {
+        let program_dirs: Vec<PathBuf> = ["ProgramFiles", "ProgramFiles(x86)"]
+            .iter()
+            .filter_map(std::env::var_os)
+            .map(PathBuf::from)
+            .collect();
+        let data_roots: Vec<PathBuf> = [dirs::data_dir(), dirs::data_local_dir()]
+            .into_iter()
+            .flatten()
+            .collect();
+
+        program_dirs
+            .iter()
+            .flat_map(|dir| child_dirs(dir))
+            .filter(|install| !NOT_APPS.contains(&file_name(install).as_str()))
+            .map(|install| {
+                let name = file_name(&install);
+                let data = data_roots.iter().map(|root| root.join(&name)).collect();
+
+                AppCandidate {
+                    name,
+                    id: None,
+                    install: vec![install],
+                    data,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{child_dirs, file_name, AppCandidate};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    /// Installed snaps are squashfs images; `/snap/<name>` only mounts them
+    const SNAP_IMAGES: &str = "/var/lib/snapd/snaps";
+
+    fn flatpaks(home: &Path) -> Vec<AppCandidate> {
+        let mut installs = child_dirs(Path::new("/var/lib/flatpak/app"));
+        installs.extend(child_dirs(&home.join(".local/share/flatpak/app")));
+
+        installs
+            .into_iter()
+            .map(|install| {
+                let id = file_name(&install);
+
+                AppCandidate {
+                    name: id.rsplit('.').next().unwrap_or(&id).to_string(),
+                    data: vec![home.join(".var/app").join(&id)],
+                    id: Some(id),
+                    install: vec![install],
+                }
+            })
+            .collect()
+    }
+
+    fn snaps(home: &Path) -> Vec<AppCandidate> {
+        let images: Vec<PathBuf> = fs::read_dir(SNAP_IMAGES)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "snap"))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        child_dirs(Path::new("/snap"))
+            .into_iter()
+            .map(|mount| file_name(&mount))
+            .filter(|name| name != "bin")
+            .map(|name| {
+                // Images are named <snap>_<revision>.snap, one per kept revision
+                let install = images
+                    .iter()
+                    .filter(|image| {
+                        image
+                            .file_stem()
+                            .and_then(|stem| stem.to_str())
+                            .and_then(|stem| stem.rsplit_once('_'))
+                            .is_some_and(|(snap, _)| snap == name)
+                    })
+                    .cloned()
+                    .collect();
+
+                AppCandidate {
+                    data: vec![home.join("snap").join(&name)],
+                    id: Some(name.clone()),
+                    name,
+                    install,
+                }
+            })
+            .collect()
+    }
+
+    pub fn candidates() -> Vec<AppCandidate> {
+        let Some(home) = dirs::home_dir() else {
+            return Vec::new();
+        };
+
+        let mut candidates = flatpaks(&home);
+        candidates.extend(snaps(&home));
+        candidates
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows, target_os = "linux")))]
+mod platform {
+    use super::AppCandidate;
+
+    pub fn candidates() -> Vec<AppCandidate> {
+        Vec::new()
+    }
+}
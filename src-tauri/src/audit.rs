@@ -0,0 +1,111 @@
+use crate::app_data;
+use crate::error::DuneError;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Runtime};
+
+const FILE_NAME: &str = "operations.jsonl";
+/// Records `get_operation_log` returns when the filter does not say
+const DEFAULT_LIMIT: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Delete,
+    Trash,
+    Move,
+    Rename,
+    /// Moved into the "Recently removed" folder, see `removed`
+    #[serde(rename = "soft_delete")]
+    SoftDelete,
+    /// Moved back out of it
+    Restore,
+}
+
+/// One file operation as it happened, one JSON line in `operations.jsonl`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationRecord {
+    /// Milliseconds since the Unix epoch
+    pub at: u64,
+    pub operation: Operation,
+    pub path: String,
+    /// New path of moves and renames
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+    /// Measured before the operation; folders count everything inside
+    pub bytes: u64,
+    pub ok: bool,
+    /// What went wrong, when `ok` is false
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Which records `get_operation_log` returns; every field narrows it down
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct OperationFilter {
+    /// Milliseconds since the Unix epoch, inclusive
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    pub operations: Vec<Operation>,
+    /// Records whose path or destination is under this folder
+    pub path: Option<String>,
+    pub failed_only: bool,
+    pub limit: Option<usize>,
+}
+
+impl OperationFilter {
+    fn matches(&self, record: &OperationRecord) -> bool {
+        let under = |path: &str| {
+            self.path
+                .as_deref()
+                .map_or(true, |root| Path::new(path).starts_with(root))
+        };
+
+        self.since.map_or(true, |since| record.at >= since)
+            && self.until.map_or(true, |until| record.at <= until)
+            && (self.operations.is_empty() || self.operations.contains(&record.operation))
+            && (under(&record.path) || record.destination.as_deref().is_some_and(under))
+            && (!self.failed_only || !record.ok)
+    }
+}
+
+pub fn store_path<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, DuneError> {
+    app_data::path(app, FILE_NAME)
+}
+
+/// Add a record to the end of the log; earlier lines are never rewritten
+pub fn append(store: &Path, record: &OperationRecord) -> Result<(), DuneError> {
+    if let Some(parent) = store.parent() {
+        fs::create_dir_all(parent).map_err(|e| DuneError::io(parent, e))?;
+    }
+
+    let mut line = serde_json::to_vec(record).map_err(DuneError::internal)?;
+    line.push(b'\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(store)
+        .and_then(|mut file| file.write_all(&line))
+        .map_err(|e| DuneError::io(store, e))
+}
+
+/// Matching records, newest first; lines that do not parse, such as one cut short by a
+/// crash, are skipped
+pub fn read(store: &Path, filter: &OperationFilter) -> Result<Vec<OperationRecord>, DuneError> {
+    let text = match fs::read_to_string(store) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(DuneError::io(store, err)),
+    };
+
+    Ok(text
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str::<OperationRecord>(line).ok())
+        .filter(|record| filter.matches(record))
+        .take(filter.limit.unwrap_or(DEFAULT_LIMIT))
+        .collect())
+}
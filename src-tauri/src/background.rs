@@ -0,0 +1,482 @@
+use crate::budgets;
+use crate::catalogs::{self, VolumeWatch};
+use crate::cleanup::{self, CleanupRule};
+use crate::error::DuneError;
+use crate::metrics::ScanRecord;
+use crate::power::{self, ThrottledEnumerator};
+use crate::scanner::FileEntry;
+use crate::service::ScannerService;
+use crate::settings::Settings;
+use crate::webhooks::{self, Notice, WebhookEvent};
+use crate::{audit, format, i18n, removed, scan, scanner, settings};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::menu::{Menu, MenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_autostart::ManagerExt;
+use tauri_plugin_notification::NotificationExt;
+
+/// Passed by the login item so the app starts hidden in the tray
+pub const BACKGROUND_ARG: &str = "--background";
+
+const SCHEDULER_TICK: Duration = Duration::from_secs(60);
+/// How soon a reinserted drive is matched with its catalogs
+const VOLUME_POLL: Duration = Duration::from_secs(5);
+/// How often running scans are checked for stalls
+const STALL_POLL: Duration = Duration::from_secs(1);
+const BYTES_PER_GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// Handle to the scheduler thread, held in managed state
+pub struct Scheduler {
+    trigger: Mutex<Sender<()>>,
+}
+
+impl Scheduler {
+    /// Run a scheduled scan now instead of waiting for the next interval
+    pub fn trigger(&self) {
+        if let Ok(trigger) = self.trigger.lock() {
+            let _ = trigger.send(());
+        }
+    }
+}
+
+pub fn launched_in_background() -> bool {
+    std::env::args().any(|arg| arg == BACKGROUND_ARG)
+}
+
+pub fn show_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+    }
+}
+
+pub fn set_launch_at_login(app: &AppHandle, enabled: bool) -> Result<(), DuneError> {
+    let autolaunch = app.autolaunch();
+    let is_enabled = autolaunch.is_enabled().map_err(DuneError::internal)?;
+
+    match (enabled, is_enabled) {
+        (true, false) => autolaunch.enable().map_err(DuneError::internal),
+        (false, true) => autolaunch.disable().map_err(DuneError::internal),
+        _ => Ok(()),
+    }
+}
+
+/// The tray menu in the configured language
+fn tray_menu(app: &AppHandle) -> tauri::Result<Menu<tauri::Wry>> {
+    let item =
+        |id: &str, key: &str| MenuItem::with_id(app, id, i18n::tr(key, &[]), true, None::<&str>);
+    let show = item("show", "tray.show")?;
+    let scan = item("scan", "tray.scan")?;
+    let quit = item("quit", "tray.quit")?;
+    Menu::with_items(app, &[&show, &scan, &quit])
+}
+
+/// Rebuild the tray menu after the language changed
+pub fn refresh_tray(app: &AppHandle) -> tauri::Result<()> {
+    match app.tray_by_id("main") {
+        Some(tray) => tray.set_menu(Some(tray_menu(app)?)),
+        None => Ok(()),
+    }
+}
+
+pub fn setup_tray(app: &AppHandle) -> tauri::Result<()> {
+    let menu = tray_menu(app)?;
+
+    let mut tray = TrayIconBuilder::with_id("main")
+        .menu(&menu)
+        .tooltip("Dunefiles")
+        .show_menu_on_left_click(false)
+        .on_menu_event(|app, event| match event.id.as_ref() {
+            "show" => show_main_window(app),
+            "scan" => app.state::<Scheduler>().trigger(),
+            "quit" => app.exit(0),
+            _ => {}
+        })
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                show_main_window(tray.app_handle());
+            }
+        });
+
+    if let Some(icon) = app.default_window_icon() {
+        tray = tray.icon(icon.clone());
+    }
+
+    tray.build(app)?;
+
+    Ok(())
+}
+
+/// Start the thread that runs scheduled scans and low-space checks
+pub fn spawn_scheduler(app: AppHandle) -> Scheduler {
+    let (trigger, requests) = mpsc::channel();
+
+    thread::spawn(move || {
+        let mut last_scan: Option<Instant> = None;
+        let mut low_disks = HashSet::new();
+        let mut over_budget = HashSet::new();
+
+        loop {
+            let triggered = match requests.recv_timeout(SCHEDULER_TICK) {
+                Ok(()) => true,
+                Err(RecvTimeoutError::Timeout) => false,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+
+            let settings = settings::load();
+            if settings.monitoring.enabled {
+                check_free_space(&app, &settings, &mut low_disks);
+            }
+            purge_removed(&app, &settings);
+
+            let background = &settings.background;
+            let interval =
+                Duration::from_secs(u64::from(background.scan_interval_minutes.max(1)) * 60);
+            let due = last_scan.map_or(true, |at| at.elapsed() >= interval);
+            let scheduled = background.enabled && due;
+
+            if !triggered && !scheduled {
+                continue;
+            }
+
+            last_scan = Some(Instant::now());
+            run_scheduled_scan(&app, &settings);
+            check_budgets(&app, &settings, &mut over_budget);
+            run_cleanup_rules(&app, &settings);
+        }
+    });
+
+    Scheduler {
+        trigger: Mutex::new(trigger),
+    }
+}
+
+/// Delete recently removed items past their retention, window open or not
+fn purge_removed(app: &AppHandle, settings: &Settings) {
+    let result = audit::store_path(app).and_then(|store| {
+        let folder = removed::folder_path(app)?;
+        removed::purge(&store, &folder, settings.recently_removed.retention_days)
+    });
+    if let Err(err) = result {
+        log::warn!("Could not purge recently removed items: {}", err);
+    }
+}
+
+/// Start the thread that notices cataloged drives being mounted again and sends
+/// `disks://reconnected` for each, so the frontend can offer to rescan what changed
+pub fn spawn_volume_watch(app: AppHandle) {
+    let dir = match catalogs::catalog_dir(&app) {
+        Ok(dir) => dir,
+        Err(err) => {
+            log::warn!("Not watching for cataloged drives: {}", err);
+            return;
+        }
+    };
+
+    thread::spawn(move || {
+        let mut watch = VolumeWatch::default();
+        // Drives mounted before the app started are not news
+        watch.poll(&dir);
+
+        loop {
+            thread::sleep(VOLUME_POLL);
+            for reconnected in watch.poll(&dir) {
+                let _ = app.emit("disks://reconnected", reconnected);
+            }
+        }
+    });
+}
+
+/// Rescan budgeted folders and warn, once per crossing, about those over their budget
+/// with `budgets://over`, a notification and the `over_budget` webhook
+fn check_budgets(app: &AppHandle, settings: &Settings, over_budget: &mut HashSet<String>) {
+    let budgets = &settings.monitoring.budgets;
+    if budgets.is_empty() {
+        return;
+    }
+    over_budget.retain(|path| budgets.contains_key(path));
+
+    for status in budgets::check(&app.state::<ScannerService>(), budgets, true) {
+        if status.over_by == 0 {
+            over_budget.remove(&status.path);
+            continue;
+        }
+        if !over_budget.insert(status.path.clone()) {
+            continue;
+        }
+
+        let body = i18n::tr(
+            "notification.over_budget.body",
+            &[
+                ("path", &status.path),
+                ("over", &format::size(status.over_by)),
+                ("budget", &format::size(status.budget)),
+            ],
+        );
+
+        webhooks::fire(
+            &settings.webhooks,
+            Notice {
+                event: WebhookEvent::OverBudget,
+                message: body.clone(),
+                fields: vec![
+                    ("name", folder_name(&status.path)),
+                    ("path", status.path.clone()),
+                    ("size_bytes", status.size.to_string()),
+                    ("budget_bytes", status.budget.to_string()),
+                    ("over_bytes", status.over_by.to_string()),
+                ],
+            },
+        );
+
+        if let Err(err) = app
+            .notification()
+            .builder()
+            .title(i18n::tr("notification.over_budget.title", &[]))
+            .body(body)
+            .show()
+        {
+            log::warn!("Failed to show budget notification: {}", err);
+        }
+
+        let _ = app.emit("budgets://over", status);
+    }
+}
+
+fn folder_name(path: &str) -> String {
+    std::path::Path::new(path).file_name().map_or_else(
+        || path.to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+/// Evaluate the scheduled cleanup rules, sending `cleanup://suggestions` and a
+/// notification when they found files not suggested before
+fn run_cleanup_rules(app: &AppHandle, settings: &Settings) {
+    let rules: Vec<CleanupRule> = settings
+        .cleanup_rules
+        .iter()
+        .filter(|rule| rule.scheduled)
+        .cloned()
+        .collect();
+    if rules.is_empty() {
+        return;
+    }
+
+    let refreshed = match cleanup::store_path(app)
+        .and_then(|store| cleanup::run(&app.state::<ScannerService>(), &store, &rules))
+    {
+        Ok(refreshed) => refreshed,
+        Err(err) => {
+            log::warn!("Scheduled cleanup rules failed: {}", err);
+            return;
+        }
+    };
+    if refreshed.new_files == 0 {
+        return;
+    }
+
+    if let Err(err) = app
+        .notification()
+        .builder()
+        .title(i18n::tr("notification.cleanup.title", &[]))
+        .body(i18n::tr(
+            "notification.cleanup.body",
+            &[
+                ("files", &format::count(refreshed.new_files)),
+                ("size", &format::size(refreshed.new_bytes)),
+            ],
+        ))
+        .show()
+    {
+        log::warn!("Failed to show cleanup notification: {}", err);
+    }
+
+    let _ = app.emit("cleanup://suggestions", refreshed);
+}
+
+/// Start the thread that sends `scan://stalled` for scans stuck reading one folder, so
+/// the frontend can offer to skip it
+pub fn spawn_scan_watchdog(app: AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(STALL_POLL);
+        for stalled in app.state::<ScannerService>().stalled_scans() {
+            log::warn!(
+                "Scan {} has been reading {} for {} s",
+                stalled.scan_id,
+                stalled.path,
+                stalled.stalled_for_ms / 1000
+            );
+            let _ = app.emit("scan://stalled", stalled);
+        }
+    });
+}
+
+/// Payload of `disks://low_space`, sent once each time a disk drops below its threshold
+#[derive(Debug, Clone, Serialize)]
+pub struct LowSpaceAlert {
+    pub name: String,
+    pub path: String,
+    pub available_space: u64,
+    pub total_space: u64,
+    pub threshold_bytes: u64,
+}
+
+fn check_free_space(app: &AppHandle, settings: &Settings, low_disks: &mut HashSet<String>) {
+    let monitoring = &settings.monitoring;
+    for disk in scanner::overview_disks() {
+        if disk.total_space == 0 {
+            continue;
+        }
+
+        let threshold_bytes = monitoring
+            .threshold_for(&disk.path)
+            .limit_bytes(disk.total_space);
+
+        if disk.available_space >= threshold_bytes {
+            low_disks.remove(&disk.path);
+            continue;
+        }
+
+        // Alert once per crossing, not on every tick while the disk stays low
+        if !low_disks.insert(disk.path.clone()) {
+            continue;
+        }
+
+        let body = i18n::tr(
+            "notification.low_space.body",
+            &[
+                ("name", &disk.name),
+                ("free", &format::size(disk.available_space)),
+            ],
+        );
+
+        webhooks::fire(
+            &settings.webhooks,
+            Notice {
+                event: WebhookEvent::LowSpace,
+                message: i18n::tr("webhook.low_space", &[("details", &body)]),
+                fields: vec![
+                    ("name", disk.name.clone()),
+                    ("path", disk.path.clone()),
+                    ("available_bytes", disk.available_space.to_string()),
+                    ("total_bytes", disk.total_space.to_string()),
+                    ("threshold_bytes", threshold_bytes.to_string()),
+                ],
+            },
+        );
+
+        if let Err(err) = app
+            .notification()
+            .builder()
+            .title(i18n::tr("notification.low_space.title", &[]))
+            .body(body)
+            .show()
+        {
+            log::warn!("Failed to show low space notification: {}", err);
+        }
+
+        let _ = app.emit(
+            "disks://low_space",
+            LowSpaceAlert {
+                name: disk.name,
+                path: disk.path,
+                available_space: disk.available_space,
+                total_space: disk.total_space,
+                threshold_bytes,
+            },
+        );
+    }
+}
+
+fn run_scheduled_scan(app: &AppHandle, settings: &Settings) {
+    // Folder sizing is single threaded already, so only the IO pause applies here
+    let budget = power::scan_budget(settings.scan.power_mode, 1);
+    let enumerator = ThrottledEnumerator::new(scanner::default_enumerator(), &budget);
+    let service = app.state::<ScannerService>();
+
+    for path in &settings.background.scheduled_paths {
+        let started = Instant::now();
+        let previous = service.last_scan(path);
+        match scanner::measure_folder(path, &enumerator) {
+            Ok(entry) => {
+                notify_scanned(settings, &entry, previous.map(|record| record.bytes));
+                service.record_scan(
+                    path,
+                    ScanRecord {
+                        bytes: entry.size,
+                        files: None,
+                        duration: started.elapsed(),
+                        complete: entry.size_complete,
+                        finished_at: scan::now_millis(),
+                    },
+                );
+                let _ = app.emit("background://scan-complete", entry);
+            }
+            Err(err) => log::warn!("Scheduled scan of {} failed: {}", path, err),
+        }
+    }
+}
+
+/// Webhooks for a finished scheduled scan, and for growth past the threshold since the last one
+fn notify_scanned(settings: &Settings, entry: &FileEntry, previous_bytes: Option<u64>) {
+    webhooks::fire(
+        &settings.webhooks,
+        Notice {
+            event: WebhookEvent::ScanCompleted,
+            message: i18n::tr(
+                "webhook.scan_completed",
+                &[("path", &entry.path), ("size", &format::size(entry.size))],
+            ),
+            fields: vec![
+                ("name", entry.name.clone()),
+                ("path", entry.path.clone()),
+                ("size_bytes", entry.size.to_string()),
+            ],
+        },
+    );
+
+    let (Some(threshold_gb), Some(previous_bytes)) =
+        (settings.monitoring.growth_threshold_gb, previous_bytes)
+    else {
+        return;
+    };
+    let growth = entry.size.saturating_sub(previous_bytes);
+    if (growth as f64) <= threshold_gb.max(0.0) * BYTES_PER_GB {
+        return;
+    }
+
+    webhooks::fire(
+        &settings.webhooks,
+        Notice {
+            event: WebhookEvent::Growth,
+            message: i18n::tr(
+                "webhook.growth",
+                &[
+                    ("path", &entry.path),
+                    ("growth", &format::size(growth)),
+                    ("size", &format::size(entry.size)),
+                ],
+            ),
+            fields: vec![
+                ("name", entry.name.clone()),
+                ("path", entry.path.clone()),
+                ("size_bytes", entry.size.to_string()),
+                ("previous_bytes", previous_bytes.to_string()),
+                ("growth_bytes", growth.to_string()),
+            ],
+        },
+    );
+}
@@ -0,0 +1,143 @@
+use crate::error::DuneError;
+use crate::scan::{self, ScanHandle, ScanOptions};
+use crate::scanner::{self, DirEnumerator, DirListing};
+use serde::Serialize;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+/// One scan of the benchmark matrix
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkRun {
+    pub backend: &'static str,
+    pub threads: usize,
+    pub elapsed_ms: f64,
+    pub nodes: u64,
+    pub bytes: u64,
+    pub directories_read: u64,
+    pub entries_listed: u64,
+    pub syscalls: u64,
+    pub entries_per_second: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkReport {
+    pub path: String,
+    pub os: &'static str,
+    pub available_threads: usize,
+    pub runs: Vec<BenchmarkRun>,
+}
+
+/// Wraps a backend and counts what it does
+struct CountingEnumerator {
+    inner: &'static dyn DirEnumerator,
+    directories: AtomicU64,
+    entries: AtomicU64,
+    syscalls: AtomicU64,
+}
+
+impl CountingEnumerator {
+    fn new(inner: &'static dyn DirEnumerator) -> Self {
+        Self {
+            inner,
+            directories: AtomicU64::new(0),
+            entries: AtomicU64::new(0),
+            syscalls: AtomicU64::new(0),
+        }
+    }
+}
+
+impl DirEnumerator for CountingEnumerator {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<DirListing> {
+        let listing = self.inner.read_dir(path)?;
+
+        self.directories.fetch_add(1, Ordering::Relaxed);
+        self.entries
+            .fetch_add(listing.entries.len() as u64, Ordering::Relaxed);
+        self.syscalls.fetch_add(listing.syscalls, Ordering::Relaxed);
+
+        Ok(listing)
+    }
+}
+
+/// 1, 2, 4, ... up to the number of cores, always including the core count
+fn thread_counts(available: usize) -> Vec<usize> {
+    let mut counts: Vec<usize> = std::iter::successors(Some(1), |count| Some(count * 2))
+        .take_while(|&count| count < available)
+        .collect();
+    counts.push(available);
+    counts
+}
+
+fn run(
+    path: &str,
+    options: &ScanOptions,
+    backend: &'static dyn DirEnumerator,
+    threads: usize,
+) -> Result<BenchmarkRun, DuneError> {
+    let handle = ScanHandle::new(0, path.to_string());
+    let enumerator = CountingEnumerator::new(backend);
+    let started = Instant::now();
+    let tree = scan::scan_tree(&handle, options, threads, &enumerator, &|_| {})?;
+    let elapsed = started.elapsed().as_secs_f64();
+
+    let entries = enumerator.entries.load(Ordering::Relaxed);
+
+    Ok(BenchmarkRun {
+        backend: backend.name(),
+        threads,
+        elapsed_ms: elapsed * 1000.0,
+        nodes: tree.nodes.len() as u64,
+        bytes: tree.nodes[0].size,
+        directories_read: enumerator.directories.load(Ordering::Relaxed),
+        entries_listed: entries,
+        syscalls: enumerator.syscalls.load(Ordering::Relaxed),
+        entries_per_second: if elapsed > 0.0 {
+            entries as f64 / elapsed
+        } else {
+            0.0
+        },
+    })
+}
+
+/// Scan `path` with every backend and thread count after one warm-up pass
+///
+/// The warm-up fills the OS metadata cache so runs compare the scanner, not the disk.
+pub fn benchmark_scan(path: &str) -> Result<BenchmarkReport, DuneError> {
+    let available = std::thread::available_parallelism().map_or(1, |count| count.get());
+    let options = ScanOptions {
+        include_hidden: true,
+        progress_events_per_second: 0,
+        ..ScanOptions::default()
+    };
+
+    run(path, &options, scanner::default_enumerator(), available)?;
+
+    let mut runs = Vec::new();
+    for backend in scanner::enumerators() {
+        for threads in thread_counts(available) {
+            let result = run(path, &options, backend, threads)?;
+            log::info!(
+                "Benchmark {} with {} on {} threads: {:.1} ms, {} syscalls",
+                path,
+                result.backend,
+                threads,
+                result.elapsed_ms,
+                result.syscalls
+            );
+            runs.push(result);
+        }
+    }
+
+    Ok(BenchmarkReport {
+        path: path.to_string(),
+        os: std::env::consts::OS,
+        available_threads: available,
+        runs,
+    })
+}
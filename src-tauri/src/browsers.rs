@@ -0,0 +1,374 @@
+use crate::scanner;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Cache folders inside each Chromium profile
+const CHROMIUM_PROFILE_CACHES: &[&str] = &[
+    "Cache",
+    "Code Cache",
+    "GPUCache",
+    "DawnCache",
+    "Service Worker/CacheStorage",
+];
+/// Cache folders shared by all Chromium profiles, next to them in the user data folder
+const CHROMIUM_SHARED_CACHES: &[&str] = &["ShaderCache", "GrShaderCache", "GraphiteDawnCache"];
+/// Cache folders inside each Firefox profile; most platforms keep `cache2` outside instead
+const FIREFOX_PROFILE_CACHES: &[&str] = &["cache2", "startupCache", "shader-cache"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PartKind {
+    Cache,
+    Profile,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BrowserPart {
+    pub kind: PartKind,
+    /// Profile name for per-profile parts
+    pub profile: Option<String>,
+    pub path: String,
+    /// Profile bytes exclude the caches listed as their own parts
+    pub bytes: u64,
+    /// Caches are rebuilt by the browser; quit it before clearing
+    pub safe_to_clear: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BrowserUsage {
+    pub name: String,
+    pub profiles: Vec<String>,
+    /// Largest first
+    pub parts: Vec<BrowserPart>,
+    pub cache_bytes: u64,
+    pub profile_bytes: u64,
+    pub total_bytes: u64,
+    pub complete: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BrowserReport {
+    /// Browsers, largest first
+    pub browsers: Vec<BrowserUsage>,
+    pub total_bytes: u64,
+    /// Bytes in parts flagged safe to clear
+    pub reclaimable_bytes: u64,
+}
+
+#[derive(Clone, Copy)]
+enum ProfileStyle {
+    /// `Default` and `Profile N` folders inside the user data folder
+    Chromium,
+    /// `<salt>.<name>` folders inside `Profiles`
+    Firefox,
+    /// The data folder is the only profile, as with Safari
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    Single,
+}
+
+/// Where a browser keeps its data on this platform
+struct Layout {
+    name: &'static str,
+    style: ProfileStyle,
+    data: PathBuf,
+    /// Cache folders kept outside `data`
+    caches: Vec<PathBuf>,
+}
+
+fn child_dirs(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut dirs: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+fn is_chromium_profile(name: &str) -> bool {
+    name == "Default" || name.starts_with("Profile ") || name == "Guest Profile"
+}
+
+fn profile_dirs(layout: &Layout) -> Vec<PathBuf> {
+    match layout.style {
+        ProfileStyle::Chromium => child_dirs(&layout.data)
+            .into_iter()
+            .filter(|dir| {
+                dir.file_name()
+                    .is_some_and(|name| is_chromium_profile(&name.to_string_lossy()))
+            })
+            .collect(),
+        // Skips `Crash Reports` and the like on Linux
+        ProfileStyle::Firefox => child_dirs(&layout.data)
+            .into_iter()
+            .filter(|dir| {
+                dir.file_name()
+                    .is_some_and(|name| name.to_string_lossy().contains('.'))
+            })
+            .collect(),
+        ProfileStyle::Single => vec![layout.data.clone()],
+    }
+}
+
+fn profile_caches(style: ProfileStyle) -> &'static [&'static str] {
+    match style {
+        ProfileStyle::Chromium => CHROMIUM_PROFILE_CACHES,
+        ProfileStyle::Firefox => FIREFOX_PROFILE_CACHES,
+        ProfileStyle::Single => &[],
+    }
+}
+
+fn cache_part(path: &Path, profile: Option<String>, complete: &mut bool) -> Option<BrowserPart> {
+    if !path.exists() {
+        return None;
+    }
+
+    let size = scanner::path_size(path);
+    *complete &= size.complete;
+
+    Some(BrowserPart {
+        kind: PartKind::Cache,
+        profile,
+        path: path.to_string_lossy().to_string(),
+        bytes: size.bytes,
+        safe_to_clear: true,
+    })
+}
+
+fn measure(layout: Layout) -> BrowserUsage {
+    let mut complete = true;
+    let mut parts = Vec::new();
+    let mut profiles = Vec::new();
+
+    for dir in profile_dirs(&layout) {
+        let name = dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let size = scanner::path_size(&dir);
+        complete &= size.complete;
+
+        let caches: Vec<BrowserPart> = profile_caches(layout.style)
+            .iter()
+            .filter_map(|cache| cache_part(&dir.join(cache), Some(name.clone()), &mut complete))
+            .collect();
+        let cache_bytes: u64 = caches.iter().map(|part| part.bytes).sum();
+
+        parts.push(BrowserPart {
+            kind: PartKind::Profile,
+            profile: Some(name.clone()),
+            path: dir.to_string_lossy().to_string(),
+            bytes: size.bytes.saturating_sub(cache_bytes),
+            safe_to_clear: false,
+        });
+        parts.extend(caches);
+        profiles.push(name);
+    }
+
+    if matches!(layout.style, ProfileStyle::Chromium) {
+        parts.extend(
+            CHROMIUM_SHARED_CACHES
+                .iter()
+                .filter_map(|cache| cache_part(&layout.data.join(cache), None, &mut complete)),
+        );
+    }
+
+    for cache in &layout.caches {
+        // Firefox mirrors its profile folders under the cache root
+        match layout.style {
+            ProfileStyle::Firefox => {
+                for dir in child_dirs(cache) {
+                    let profile = dir
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string());
+                    parts.extend(cache_part(&dir, profile, &mut complete));
+                }
+            }
+            ProfileStyle::Chromium | ProfileStyle::Single => {
+                parts.extend(cache_part(cache, None, &mut complete));
+            }
+        }
+    }
+
+    parts.retain(|part| part.bytes > 0);
+    parts.sort_by_key(|part| std::cmp::Reverse(part.bytes));
+
+    let bytes_of = |kind| {
+        parts
+            .iter()
+            .filter(|part| part.kind == kind)
+            .map(|part| part.bytes)
+            .sum::<u64>()
+    };
+    let cache_bytes = bytes_of(PartKind::Cache);
+    let profile_bytes = bytes_of(PartKind::Profile);
+
+    BrowserUsage {
+        name: layout.name.to_string(),
+        profiles,
+        parts,
+        cache_bytes,
+        profile_bytes,
+        total_bytes: cache_bytes + profile_bytes,
+        complete,
+    }
+}
+
+/// Profile and cache sizes for the browsers found in their default locations
+pub fn browser_report() -> BrowserReport {
+    let mut browsers: Vec<BrowserUsage> = platform::layouts()
+        .into_iter()
+        .filter(|layout| layout.data.exists() || layout.caches.iter().any(|c| c.exists()))
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(measure)
+        .filter(|browser| browser.total_bytes > 0)
+        .collect();
+
+    browsers.sort_by_key(|browser| std::cmp::Reverse(browser.total_bytes));
+    log::info!("Measured {} browsers", browsers.len());
+
+    BrowserReport {
+        total_bytes: browsers.iter().map(|browser| browser.total_bytes).sum(),
+        reclaimable_bytes: browsers
+            .iter()
+            .flat_map(|browser| &browser.parts)
+            .filter(|part| part.safe_to_clear)
+            .map(|part| part.bytes)
+            .sum(),
+        browsers,
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{Layout, ProfileStyle};
+
+    pub fn layouts() -> Vec<Layout> {
+        let Some(home) = dirs::home_dir() else {
+            return Vec::new();
+        };
+        let support = home.join("Library").join("Application Support");
+        let caches = home.join("Library").join("Caches");
+
+        vec![
+            Layout {
+                name: "Chrome",
+                style: ProfileStyle::Chromium,
+                data: support.join("Google/Chrome"),
+                caches: vec![caches.join("Google/Chrome")],
+            },
+            Layout {
+                name: "Edge",
+                style: ProfileStyle::Chromium,
+                data: support.join("Microsoft Edge"),
+                caches: vec![caches.join("Microsoft Edge")],
+            },
+            Layout {
+                name: "Firefox",
+                style: ProfileStyle::Firefox,
+                data: support.join("Firefox/Profiles"),
+                caches: vec![caches.join("Firefox/Profiles")],
+            },
+            // Reading these needs Full Disk Access; without it the sizes come back incomplete
+            Layout {
+                name: "Safari",
+                style: ProfileStyle::Single,
+                data: home.join("Library/Safari"),
+                caches: vec![
+                    caches.join("com.apple.Safari"),
+                    home.join("Library/Containers/com.apple.Safari/Data/Library/Caches"),
+                ],
+            },
+        ]
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{Layout, ProfileStyle};
+
+    pub fn layouts() -> Vec<Layout> {
+        let (Some(roaming), Some(local)) = (dirs::data_dir(), dirs::data_local_dir()) else {
+            return Vec::new();
+        };
+
+        // Chromium keeps its caches inside each profile on Windows
+        vec![
+            Layout {
+                name: "Chrome",
+                style: ProfileStyle::Chromium,
+                data: local.join("Google\\Chrome\\User Data"),
+                caches: Vec::new(),
+            },
+            Layout {
+                name: "Edge",
+                style: ProfileStyle::Chromium,
+                data: local.join("Microsoft\\Edge\\User Data"),
+                caches: Vec::new(),
+            },
+            Layout {
+                name: "Firefox",
+                style: ProfileStyle::Firefox,
+                data: roaming.join("Mozilla\\Firefox\\Profiles"),
+                caches: vec![local.join("Mozilla\\Firefox\\Profiles")],
+            },
+        ]
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{Layout, ProfileStyle};
+
+    pub fn layouts() -> Vec<Layout> {
+        let (Some(config), Some(cache)) = (dirs::config_dir(), dirs::cache_dir()) else {
+            return Vec::new();
+        };
+        let Some(home) = dirs::home_dir() else {
+            return Vec::new();
+        };
+
+        vec![
+            Layout {
+                name: "Chrome",
+                style: ProfileStyle::Chromium,
+                data: config.join("google-chrome"),
+                caches: vec![cache.join("google-chrome")],
+            },
+            Layout {
+                name: "Chromium",
+                style: ProfileStyle::Chromium,
+                data: config.join("chromium"),
+                caches: vec![cache.join("chromium")],
+            },
+            Layout {
+                name: "Edge",
+                style: ProfileStyle::Chromium,
+                data: config.join("microsoft-edge"),
+                caches: vec![cache.join("microsoft-edge")],
+            },
+            Layout {
+                name: "Firefox",
+                style: ProfileStyle::Firefox,
+                data: home.join(".mozilla/firefox"),
+                caches: vec![cache.join("mozilla/firefox")],
+            },
+        ]
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows, target_os = "linux")))]
+mod platform {
+    use super::Layout;
+
+    pub fn layouts() -> Vec<Layout> {
+        Vec::new()
+    }
+}
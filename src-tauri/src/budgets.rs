@@ -0,0 +1,106 @@
+use crate::error::DuneError;
+use crate::scan::ScanTree;
+use crate::service::ScannerService;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Largest entries listed for a folder over its budget
+const MAX_OFFENDERS: usize = 5;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Offender {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+}
+
+/// One folder measured against its `Settings.monitoring.budgets` entry
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetStatus {
+    pub path: String,
+    pub budget: u64,
+    pub size: u64,
+    /// Bytes past the budget, 0 while within it
+    pub over_by: u64,
+    /// Percent of the budget used
+    pub used_percent: f64,
+    /// Largest entries of the folder, when it is over
+    pub offenders: Vec<Offender>,
+    pub complete: bool,
+}
+
+fn status(path: &str, budget: u64, tree: &ScanTree, index: u32) -> BudgetStatus {
+    let node = &tree.nodes[index as usize];
+    let over_by = node.size.saturating_sub(budget);
+
+    let mut offenders = Vec::new();
+    if over_by > 0 {
+        let mut children: Vec<u32> = tree
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, child)| child.parent == Some(index))
+            .map(|(child, _)| child as u32)
+            .collect();
+        children.sort_by_key(|&child| std::cmp::Reverse(tree.nodes[child as usize].size));
+        offenders = children
+            .into_iter()
+            .take(MAX_OFFENDERS)
+            .map(|child| {
+                let child_node = &tree.nodes[child as usize];
+                Offender {
+                    name: child_node.name.to_string(),
+                    path: tree.path(child).to_string_lossy().into_owned(),
+                    is_dir: child_node.is_dir,
+                    size: child_node.size,
+                }
+            })
+            .collect();
+    }
+
+    BudgetStatus {
+        path: path.to_string(),
+        budget,
+        size: node.size,
+        over_by,
+        used_percent: if budget == 0 {
+            100.0
+        } else {
+            node.size as f64 * 100.0 / budget as f64
+        },
+        offenders,
+        complete: node.size_complete,
+    }
+}
+
+/// Every budgeted folder against its budget, most over first. `fresh` scans each folder
+/// again instead of reusing a finished scan of it; folders that cannot be scanned are
+/// left out.
+pub fn check(
+    service: &ScannerService,
+    budgets: &BTreeMap<String, u64>,
+    fresh: bool,
+) -> Vec<BudgetStatus> {
+    let mut statuses: Vec<BudgetStatus> = budgets
+        .iter()
+        .filter_map(|(path, &budget)| {
+            let scanned: Result<(Arc<ScanTree>, u32), DuneError> = if fresh {
+                service.rescan(path).map(|tree| (tree, 0))
+            } else {
+                service.subtree(path)
+            };
+            match scanned {
+                Ok((tree, index)) => Some(status(path, budget, &tree, index)),
+                Err(err) => {
+                    log::warn!("Could not check the budget of {}: {}", path, err);
+                    None
+                }
+            }
+        })
+        .collect();
+    statuses.sort_by(|a, b| b.used_percent.total_cmp(&a.used_percent));
+
+    statuses
+}
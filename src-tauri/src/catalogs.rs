@@ -0,0 +1,538 @@
+use crate::app_data;
+use crate::compare::MTIME_TOLERANCE;
+use crate::error::DuneError;
+use crate::query::{Query, Subject};
+use crate::scan::ScanTree;
+use crate::search::SearchOptions;
+use crate::snapshot::{self, SnapshotInfo};
+use crate::{eject, fingerprint, names, scanner};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use sysinfo::Disks;
+use tauri::{AppHandle, Runtime};
+
+/// Next to the catalog trees, which are stored like snapshots
+const INDEX: &str = "index.json";
+/// Files listed per kind of change in a comparison, largest first
+const MAX_LISTED: usize = 500;
+
+/// Held while the index is read, changed and written back
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// The last scan of a folder on a drive other than the system's, kept for searching
+/// while the drive is unplugged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Catalog {
+    /// See `fingerprint::volume_id`
+    pub volume_id: String,
+    pub volume_name: String,
+    /// Where the volume was mounted when it was scanned
+    pub mount: String,
+    pub total_space: u64,
+    /// Free space when it was scanned
+    #[serde(default)]
+    pub available_space: u64,
+    pub snapshot: SnapshotInfo,
+    /// Where the volume is mounted now, `None` while it is unplugged
+    #[serde(skip_deserializing)]
+    pub current_mount: Option<String>,
+    /// The scanned folder under `current_mount`, which is what to rescan
+    #[serde(skip_deserializing)]
+    pub current_root: Option<String>,
+}
+
+impl Catalog {
+    /// The scanned folder below the mount point, so a rescan replaces it wherever the
+    /// volume was mounted
+    fn folder(&self) -> &Path {
+        Path::new(&self.snapshot.root)
+            .strip_prefix(&self.mount)
+            .unwrap_or(Path::new(""))
+    }
+
+    fn mounted_at(&mut self, mount: Option<String>) {
+        self.current_root = mount.as_ref().map(|mount| {
+            Path::new(mount)
+                .join(self.folder())
+                .to_string_lossy()
+                .into_owned()
+        });
+        self.current_mount = mount;
+    }
+}
+
+/// Payload of `disks://reconnected`, sent when a volume with catalogs is mounted again
+#[derive(Debug, Clone, Serialize)]
+pub struct Reconnected {
+    pub volume_id: String,
+    pub mount: String,
+    /// Its catalogs, each with the `current_root` to rescan
+    pub catalogs: Vec<Catalog>,
+    /// Free space now minus free space at the newest catalog; 0 suggests nothing was
+    /// written since and a rescan can wait
+    pub available_space_change: i64,
+}
+
+/// Mounted volumes and their ids, to notice the ones that come back
+#[derive(Default)]
+pub struct VolumeWatch {
+    /// Volume id by mount point, `None` for volumes without one
+    mounted: HashMap<String, Option<String>>,
+}
+
+impl VolumeWatch {
+    /// Volumes mounted since the last poll that have catalogs; ids are only looked up
+    /// for new mount points, so polling often is cheap
+    pub fn poll(&mut self, dir: &Path) -> Vec<Reconnected> {
+        let disks = Disks::new_with_refreshed_list();
+        let mounts: Vec<(String, u64)> = disks
+            .list()
+            .iter()
+            .map(|disk| {
+                (
+                    disk.mount_point().to_string_lossy().into_owned(),
+                    disk.available_space(),
+                )
+            })
+            .filter(|(mount, _)| !eject::is_system_volume(mount))
+            .collect();
+        self.mounted
+            .retain(|mount, _| mounts.iter().any(|(current, _)| current == mount));
+
+        let mut arrived = Vec::new();
+        for (mount, available_space) in mounts {
+            if self.mounted.contains_key(&mount) {
+                continue;
+            }
+            let id = fingerprint::volume_id(Path::new(&mount));
+            if let Some(id) = &id {
+                arrived.push((id.clone(), mount.clone(), available_space));
+            }
+            self.mounted.insert(mount, id);
+        }
+        if arrived.is_empty() {
+            return Vec::new();
+        }
+
+        let catalogs = match read(dir) {
+            Ok(catalogs) => catalogs,
+            Err(err) => {
+                log::warn!("Could not read the catalogs: {}", err);
+                return Vec::new();
+            }
+        };
+        arrived
+            .into_iter()
+            .filter_map(|(volume_id, mount, available_space)| {
+                let mut matching: Vec<Catalog> = catalogs
+                    .iter()
+                    .filter(|catalog| catalog.volume_id == volume_id)
+                    .cloned()
+                    .collect();
+                matching.sort_by_key(|catalog| std::cmp::Reverse(catalog.snapshot.created_at));
+                let newest = matching.first()?.available_space;
+                for catalog in &mut matching {
+                    catalog.mounted_at(Some(mount.clone()));
+                }
+
+                log::info!("Cataloged volume {} is back at {}", volume_id, mount);
+                Some(Reconnected {
+                    volume_id,
+                    mount,
+                    catalogs: matching,
+                    available_space_change: available_space as i64 - newest as i64,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogHit {
+    pub volume_id: String,
+    pub volume_name: String,
+    pub current_mount: Option<String>,
+    /// As it was when scanned
+    pub path: String,
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    /// Higher is a better match; only comparable within one search
+    pub score: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogSearch {
+    /// Best matches first
+    pub hits: Vec<CatalogHit>,
+    pub searched: Vec<Catalog>,
+    /// Stopped at `limit`
+    pub truncated: bool,
+}
+
+pub fn catalog_dir<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, DuneError> {
+    app_data::path(app, "catalogs")
+}
+
+fn read(dir: &Path) -> Result<Vec<Catalog>, DuneError> {
+    let path = dir.join(INDEX);
+    match fs::read(&path) {
+        Ok(json) => serde_json::from_slice(&json).map_err(|e| DuneError::Io {
+            path: Some(path.to_string_lossy().to_string()),
+            message: e.to_string(),
+        }),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(DuneError::io(&path, err)),
+    }
+}
+
+fn write(dir: &Path, catalogs: &[Catalog]) -> Result<(), DuneError> {
+    fs::create_dir_all(dir).map_err(|e| DuneError::io(dir, e))?;
+
+    let path = dir.join(INDEX);
+    let json = serde_json::to_vec_pretty(catalogs).map_err(DuneError::internal)?;
+    fs::write(&path, json).map_err(|e| DuneError::io(&path, e))
+}
+
+/// Store `tree` as the catalog of its folder when it was scanned on a drive other than
+/// the system's or home's, replacing the previous catalog of that folder
+pub fn record(dir: &Path, tree: &ScanTree) -> Result<Option<Catalog>, DuneError> {
+    if tree.is_combined() {
+        return Ok(None);
+    }
+    let Some(disk) = scanner::disk_for(Path::new(&tree.root)) else {
+        return Ok(None);
+    };
+    if disk.mtp || disk.ram_disk || eject::is_system_volume(&disk.path) {
+        return Ok(None);
+    }
+    let Some(volume_id) = fingerprint::volume_id(Path::new(&disk.path)) else {
+        log::debug!("{} has no volume id to catalog it by", disk.path);
+        return Ok(None);
+    };
+
+    let _lock = INDEX_LOCK.lock().unwrap();
+    let mut catalogs = read(dir)?;
+    let mut catalog = Catalog {
+        volume_id,
+        volume_name: disk.original_name.unwrap_or(disk.name),
+        mount: disk.path.clone(),
+        total_space: disk.total_space,
+        available_space: disk.available_space,
+        snapshot: snapshot::save(dir, tree)?,
+        current_mount: None,
+        current_root: None,
+    };
+
+    catalogs.retain(|old| {
+        let replaced = old.volume_id == catalog.volume_id && old.folder() == catalog.folder();
+        if replaced {
+            if let Err(err) = snapshot::delete(dir, &old.snapshot.id) {
+                log::warn!("Could not delete catalog {}: {}", old.snapshot.id, err);
+            }
+        }
+        !replaced
+    });
+    catalogs.push(catalog.clone());
+    write(dir, &catalogs)?;
+    catalog.mounted_at(Some(disk.path));
+
+    log::info!(
+        "Cataloged {} on volume {}",
+        catalog.snapshot.root,
+        catalog.volume_id
+    );
+
+    Ok(Some(catalog))
+}
+
+/// Every catalog, with where its volume is mounted now; most recently scanned first
+pub fn list(dir: &Path) -> Result<Vec<Catalog>, DuneError> {
+    let mut catalogs = read(dir)?;
+    let mounted: Vec<(String, String)> = scanner::get_disks()
+        .into_iter()
+        .filter(|disk| !disk.mtp && !disk.ram_disk && !eject::is_system_volume(&disk.path))
+        .filter_map(|disk| Some((fingerprint::volume_id(Path::new(&disk.path))?, disk.path)))
+        .collect();
+
+    for catalog in &mut catalogs {
+        let mount = mounted
+            .iter()
+            .find(|(id, _)| *id == catalog.volume_id)
+            .map(|(_, mount)| mount.clone());
+        catalog.mounted_at(mount);
+    }
+    catalogs.sort_by_key(|catalog| std::cmp::Reverse(catalog.snapshot.created_at));
+
+    Ok(catalogs)
+}
+
+/// Forget the catalog held in snapshot `id`
+pub fn delete(dir: &Path, id: &str) -> Result<(), DuneError> {
+    let _lock = INDEX_LOCK.lock().unwrap();
+    let mut catalogs = read(dir)?;
+    let before = catalogs.len();
+    catalogs.retain(|catalog| catalog.snapshot.id != id);
+    if catalogs.len() == before {
+        return Err(DuneError::NotFound {
+            path: id.to_string(),
+        });
+    }
+
+    snapshot::delete(dir, id)?;
+    write(dir, &catalogs)
+}
+
+/// Match `query` against every catalog, as `search_index` does against snapshots;
+/// `options.roots` narrows it down to volume ids
+pub fn search(
+    dir: &Path,
+    query: &str,
+    options: &SearchOptions,
+) -> Result<CatalogSearch, DuneError> {
+    let parsed = Query::parse(query, options.text_mode())?;
+    let searched: Vec<Catalog> = list(dir)?
+        .into_iter()
+        .filter(|catalog| options.roots.is_empty() || options.roots.contains(&catalog.volume_id))
+        .collect();
+
+    let mut hits = Vec::new();
+    for catalog in &searched {
+        let tree = match snapshot::load(dir, &catalog.snapshot.id) {
+            Ok(snapshot) => snapshot.tree,
+            Err(err) => {
+                log::warn!("Skipping catalog {}: {}", catalog.snapshot.id, err);
+                continue;
+            }
+        };
+
+        let found: Vec<(u32, u32)> = (0..tree.nodes.len() as u32)
+            .into_par_iter()
+            .filter(|&index| {
+                let node = &tree.nodes[index as usize];
+                (options.include_dirs || !node.is_dir) && node.size >= options.min_size
+            })
+            .filter_map(|index| Some((index, parsed.score(&Subject::new(&tree, index))?)))
+            .collect();
+        hits.extend(found.into_iter().map(|(index, score)| {
+            let node = &tree.nodes[index as usize];
+            CatalogHit {
+                volume_id: catalog.volume_id.clone(),
+                volume_name: catalog.volume_name.clone(),
+                current_mount: catalog.current_mount.clone(),
+                path: tree.path(index).to_string_lossy().into_owned(),
+                name: names::display(OsStr::new(&*node.name)),
+                is_dir: node.is_dir,
+                size: node.size,
+                score,
+            }
+        }));
+    }
+
+    hits.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| b.size.cmp(&a.size)));
+    let truncated = hits.len() > options.limit;
+    hits.truncate(options.limit);
+    log::info!(
+        "Catalog search for {:?} found {} matches",
+        query,
+        hits.len()
+    );
+
+    Ok(CatalogSearch {
+        hits,
+        searched,
+        truncated,
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangedFile {
+    /// Below the compared folder
+    pub path: String,
+    /// `None` for files added since the catalog
+    pub catalog_size: Option<u64>,
+    /// `None` for files removed since the catalog
+    pub live_size: Option<u64>,
+}
+
+/// How a folder differs from a catalog of it, or of its backup
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogComparison {
+    pub path: String,
+    pub catalog: Catalog,
+    /// The catalogued folder compared against, as it was scanned
+    pub catalog_path: String,
+    /// File counts by what happened since the catalog
+    pub added: u64,
+    pub removed: u64,
+    pub changed: u64,
+    pub same: u64,
+    pub bytes_added: u64,
+    pub bytes_removed: u64,
+    /// Largest first, up to `MAX_LISTED` of each
+    pub added_files: Vec<ChangedFile>,
+    pub removed_files: Vec<ChangedFile>,
+    pub changed_files: Vec<ChangedFile>,
+    /// Newest modification among added and changed files, milliseconds since the Unix
+    /// epoch: how far behind the catalog is
+    pub newest_change: Option<u64>,
+    /// Both trees were read completely and without a noise floor
+    pub complete: bool,
+}
+
+/// Files under `tree.nodes[start]` by their path below it, with size and modification
+fn files_below(tree: &ScanTree, start: u32) -> HashMap<String, (u64, Option<u64>)> {
+    let start = start as usize;
+    let mut relative: Vec<Option<String>> = vec![None; tree.nodes.len() - start];
+    relative[0] = Some(String::new());
+    let mut files = HashMap::new();
+
+    for (offset, node) in tree.nodes[start..].iter().enumerate().skip(1) {
+        let Some(parent) = node
+            .parent
+            .and_then(|parent| (parent as usize).checked_sub(start))
+            .and_then(|parent| relative[parent].as_deref())
+        else {
+            continue;
+        };
+        let path = if parent.is_empty() {
+            node.name.to_string()
+        } else {
+            format!("{}/{}", parent, node.name)
+        };
+
+        let index = (start + offset) as u32;
+        if tree.small_items.contains(&index) {
+            continue;
+        }
+        if node.is_dir {
+            relative[offset] = Some(path);
+        } else {
+            files.insert(path, (node.size, node.modified));
+        }
+    }
+
+    files
+}
+
+fn largest_first(files: &mut Vec<ChangedFile>) {
+    files.sort_by_key(|file| std::cmp::Reverse(file.live_size.max(file.catalog_size).unwrap_or(0)));
+    files.truncate(MAX_LISTED);
+}
+
+/// Files added, removed and changed in `live.nodes[index]` since the catalog in
+/// snapshot `catalog_id`. Inside the catalogued folder on the reconnected drive, the
+/// same folder of the catalog is compared; anywhere else, such as the original of a
+/// backup, the whole catalog is.
+pub fn compare(
+    dir: &Path,
+    live: &ScanTree,
+    index: u32,
+    catalog_id: &str,
+) -> Result<CatalogComparison, DuneError> {
+    let catalog = list(dir)?
+        .into_iter()
+        .find(|catalog| catalog.snapshot.id == catalog_id)
+        .ok_or_else(|| DuneError::NotFound {
+            path: catalog_id.to_string(),
+        })?;
+    let stored = snapshot::load(dir, catalog_id)?.tree;
+    let path = live.path(index);
+
+    let start = catalog
+        .current_root
+        .as_deref()
+        .and_then(|root| path.strip_prefix(root).ok())
+        .and_then(|below| stored.find(&Path::new(&stored.root).join(below)))
+        .unwrap_or(0);
+    let mut before = files_below(&stored, start);
+    let now = files_below(live, index);
+
+    let (mut added, mut changed) = (Vec::new(), Vec::new());
+    let (mut same, mut bytes_added) = (0, 0u64);
+    let mut newest_change: Option<u64> = None;
+    let tolerance = MTIME_TOLERANCE.as_millis() as u64;
+    for (file, (size, modified)) in now {
+        match before.remove(&file) {
+            Some((old_size, old_modified)) => {
+                let moved = match (modified, old_modified) {
+                    (Some(a), Some(b)) => a.abs_diff(b) > tolerance,
+                    _ => false,
+                };
+                if size == old_size && !moved {
+                    same += 1;
+                    continue;
+                }
+                changed.push(ChangedFile {
+                    path: file,
+                    catalog_size: Some(old_size),
+                    live_size: Some(size),
+                });
+            }
+            None => {
+                bytes_added = bytes_added.saturating_add(size);
+                added.push(ChangedFile {
+                    path: file,
+                    catalog_size: None,
+                    live_size: Some(size),
+                });
+            }
+        }
+        newest_change = newest_change.max(modified);
+    }
+    let mut removed: Vec<ChangedFile> = before
+        .into_iter()
+        .map(|(file, (size, _))| ChangedFile {
+            path: file,
+            catalog_size: Some(size),
+            live_size: None,
+        })
+        .collect();
+    let bytes_removed = removed
+        .iter()
+        .filter_map(|file| file.catalog_size)
+        .fold(0u64, u64::saturating_add);
+
+    let counts = (
+        added.len() as u64,
+        removed.len() as u64,
+        changed.len() as u64,
+    );
+    for files in [&mut added, &mut removed, &mut changed] {
+        largest_first(files);
+    }
+
+    log::info!(
+        "{} against catalog {}: {} added, {} removed, {} changed",
+        path.display(),
+        catalog_id,
+        counts.0,
+        counts.1,
+        counts.2
+    );
+
+    Ok(CatalogComparison {
+        path: path.to_string_lossy().into_owned(),
+        catalog_path: stored.path(start).to_string_lossy().into_owned(),
+        catalog,
+        added: counts.0,
+        removed: counts.1,
+        changed: counts.2,
+        same,
+        bytes_added,
+        bytes_removed,
+        added_files: added,
+        removed_files: removed,
+        changed_files: changed,
+        newest_change,
+        complete: live.nodes[index as usize].size_complete
+            && stored.nodes[start as usize].size_complete
+            && live.small_items.is_empty()
+            && stored.small_items.is_empty(),
+    })
+}
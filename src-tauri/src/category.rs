@@ -0,0 +1,140 @@
+use crate::error::DuneError;
+use crate::filetype::{self, FileCategory};
+use crate::scanner::{self, DirEntryInfo, DirEnumerator, EntryKind, Hidden};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryTotal {
+    pub category: FileCategory,
+    pub bytes: u64,
+    pub files: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryStats {
+    pub path: String,
+    /// Non-empty categories, largest first
+    pub categories: Vec<CategoryTotal>,
+    pub total_bytes: u64,
+    pub complete: bool,
+}
+
+#[derive(Clone, Copy)]
+struct Tally {
+    bytes: [u64; FileCategory::ALL.len()],
+    files: [u64; FileCategory::ALL.len()],
+    complete: bool,
+}
+
+impl Default for Tally {
+    fn default() -> Self {
+        Self {
+            bytes: [0; FileCategory::ALL.len()],
+            files: [0; FileCategory::ALL.len()],
+            complete: true,
+        }
+    }
+}
+
+impl Tally {
+    fn add(&mut self, category: FileCategory, bytes: u64, files: u64) {
+        let index = category.index();
+        self.bytes[index] = self.bytes[index].saturating_add(bytes);
+        self.files[index] += files;
+    }
+
+    fn merge(mut self, other: Tally) -> Tally {
+        for index in 0..FileCategory::ALL.len() {
+            self.bytes[index] = self.bytes[index].saturating_add(other.bytes[index]);
+            self.files[index] += other.files[index];
+        }
+        self.complete &= other.complete;
+        self
+    }
+}
+
+/// Total bytes and files of a bundle, ignoring the categories of what is inside
+fn walk_bundle(enumerator: &dyn DirEnumerator, path: &Path) -> (u64, u64, bool) {
+    let (mut bytes, mut files) = (0u64, 0u64);
+    let complete = scanner::walk(
+        enumerator,
+        path,
+        Hidden::Include,
+        &mut |_: &Path, entry: &DirEntryInfo| {
+            if entry.kind == EntryKind::File {
+                bytes = bytes.saturating_add(entry.len);
+                files += 1;
+            }
+            true
+        },
+    );
+
+    (bytes, files, complete)
+}
+
+/// Tally `entry`, found at `path`, and everything under it
+fn add_entry(enumerator: &dyn DirEnumerator, path: &Path, entry: &DirEntryInfo, tally: &mut Tally) {
+    let complete = scanner::visit(
+        enumerator,
+        path,
+        entry,
+        Hidden::Skip,
+        &mut |path: &Path, entry: &DirEntryInfo| match entry.kind {
+            EntryKind::File => {
+                tally.add(filetype::category_for(path), entry.len, 1);
+                false
+            }
+            EntryKind::Dir if filetype::is_bundle(path) => {
+                let (bytes, files, complete) = walk_bundle(enumerator, path);
+                tally.add(FileCategory::Apps, bytes, files);
+                tally.complete &= complete;
+                false
+            }
+            EntryKind::Dir => true,
+            EntryKind::Symlink | EntryKind::Other => false,
+        },
+    );
+    tally.complete &= complete;
+}
+
+/// Bytes under `path` per file category, with the same hidden-file rules as listings
+pub fn category_stats(path: &str) -> Result<CategoryStats, DuneError> {
+    let dir_path = scanner::require_dir(path)?;
+    let enumerator = scanner::default_enumerator();
+    let listing = enumerator
+        .read_dir(dir_path)
+        .map_err(|e| DuneError::io(dir_path, e))?;
+
+    // Top-level entries are tallied in parallel, then merged
+    let tally = listing
+        .entries
+        .into_par_iter()
+        .filter(|entry| !scanner::is_hidden_name(&entry.name))
+        .map(|entry| {
+            let mut tally = Tally::default();
+            add_entry(enumerator, &dir_path.join(&entry.name), &entry, &mut tally);
+            tally
+        })
+        .reduce(Tally::default, Tally::merge);
+
+    let mut categories: Vec<CategoryTotal> = FileCategory::ALL
+        .iter()
+        .map(|&category| CategoryTotal {
+            category,
+            bytes: tally.bytes[category.index()],
+            files: tally.files[category.index()],
+        })
+        .filter(|total| total.files > 0)
+        .collect();
+
+    categories.sort_by_key(|total| std::cmp::Reverse(total.bytes));
+
+    Ok(CategoryStats {
+        path: path.to_string(),
+        total_bytes: categories.iter().map(|total| total.bytes).sum(),
+        categories,
+        complete: listing.complete && tally.complete,
+    })
+}
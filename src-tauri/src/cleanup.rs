@@ -0,0 +1,530 @@
+use crate::app_data;
+use crate::audit::{Operation, OperationRecord};
+use crate::error::DuneError;
+use crate::file_ops;
+use crate::i18n::tr;
+use crate::removed;
+use crate::scan::{self, ScanTree};
+use crate::service::ScannerService;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Runtime};
+
+const FILE_NAME: &str = "cleanup_suggestions.json";
+const DAY_MILLIS: u64 = 24 * 60 * 60 * 1000;
+/// Files listed in a dry run, largest first
+const MAX_PREVIEW: usize = 500;
+
+/// Held while the suggestions are read, changed and written back
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+/// What happens to a file a rule matches, once the user approves it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CleanupAction {
+    #[default]
+    Trash,
+    /// Into the app's "Recently removed" folder, see `removed`
+    RecentlyRemoved,
+    Delete,
+}
+
+/// "Files under `path` older than `older_than_days` and larger than `min_size`: suggest
+/// `action`". Conditions left unset always hold; only files are matched, never folders.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CleanupRule {
+    /// Shown with its suggestions; a file matched by several rules goes to the first
+    pub name: String,
+    pub enabled: bool,
+    /// Glob of full paths: `*` and `?` within a name, `**` across folders and `~` for the
+    /// home folder, as in `~/Downloads/**`. It must start with a folder, which is what
+    /// gets scanned.
+    pub path: String,
+    /// Days since the file was last modified
+    pub older_than_days: Option<u32>,
+    /// Bytes
+    pub min_size: Option<u64>,
+    pub action: CleanupAction,
+    /// Also evaluated by the background scheduler whenever it runs its scans
+    pub scheduled: bool,
+}
+
+impl Default for CleanupRule {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            enabled: true,
+            path: String::new(),
+            older_than_days: None,
+            min_size: None,
+            action: CleanupAction::Trash,
+            scheduled: false,
+        }
+    }
+}
+
+/// A file a rule matched, waiting for the user to approve or dismiss it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Suggestion {
+    pub path: String,
+    pub size: u64,
+    /// Milliseconds since the Unix epoch
+    pub modified: Option<u64>,
+    pub rule: String,
+    pub action: CleanupAction,
+    /// When a rule first matched it
+    pub found_at: u64,
+}
+
+/// What a rule would suggest right now, without keeping any of it
+#[derive(Debug, Clone, Serialize)]
+pub struct RulePreview {
+    /// The folder scanned for the rule
+    pub folder: String,
+    pub files: u64,
+    pub bytes: u64,
+    /// Largest first, up to `MAX_PREVIEW`
+    pub matches: Vec<Suggestion>,
+    /// The folder was read completely
+    pub complete: bool,
+}
+
+/// Pending suggestions after evaluating rules, and how many of them are new
+#[derive(Debug, Clone, Serialize)]
+pub struct Refreshed {
+    pub suggestions: Vec<Suggestion>,
+    pub new_files: u64,
+    pub new_bytes: u64,
+}
+
+/// A file the user said no to, asked about again once it changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Dismissed {
+    path: String,
+    modified: Option<u64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Stored {
+    pending: Vec<Suggestion>,
+    dismissed: Vec<Dismissed>,
+}
+
+pub fn store_path<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, DuneError> {
+    app_data::path(app, FILE_NAME)
+}
+
+fn read(store: &Path) -> Result<Stored, DuneError> {
+    match fs::read(store) {
+        Ok(json) => serde_json::from_slice(&json).map_err(|e| DuneError::Io {
+            path: Some(store.to_string_lossy().to_string()),
+            message: e.to_string(),
+        }),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Stored::default()),
+        Err(err) => Err(DuneError::io(store, err)),
+    }
+}
+
+fn write(store: &Path, stored: &Stored) -> Result<(), DuneError> {
+    if let Some(parent) = store.parent() {
+        fs::create_dir_all(parent).map_err(|e| DuneError::io(parent, e))?;
+    }
+
+    let json = serde_json::to_vec_pretty(stored).map_err(DuneError::internal)?;
+    fs::write(store, json).map_err(|e| DuneError::io(store, e))
+}
+
+/// `pattern` with a leading `~` replaced by the home folder
+fn expand_home(pattern: &str) -> Result<String, DuneError> {
+    let Some(rest) = pattern.strip_prefix('~') else {
+        return Ok(pattern.to_string());
+    };
+    let home = dirs::home_dir()
+        .ok_or_else(|| DuneError::Internal("Could not determine the home folder".into()))?;
+    Ok(format!("{}{}", home.to_string_lossy(), rest))
+}
+
+/// Forward slashes only, so one pattern works on every platform
+fn comparable(path: &str) -> Cow<'_, str> {
+    if cfg!(windows) {
+        Cow::Owned(path.replace('\\', "/"))
+    } else {
+        Cow::Borrowed(path)
+    }
+}
+
+fn is_wildcard(part: &str) -> bool {
+    part.contains(['*', '?'])
+}
+
+/// The full-path regular expression of a rule's glob, and the folder it starts in
+fn compile(pattern: &str) -> Result<(Regex, PathBuf), DuneError> {
+    let pattern = expand_home(pattern.trim())?;
+    let pattern = comparable(&pattern).into_owned();
+
+    let mut parts: Vec<&str> = pattern.split('/').collect();
+    match parts.iter().position(|part| is_wildcard(part)) {
+        Some(wildcard) => parts.truncate(wildcard),
+        // A single file, so its folder is scanned
+        None => {
+            parts.pop();
+        }
+    }
+    let folder = PathBuf::from(parts.join("/"));
+    let rooted = folder.is_absolute()
+        && folder
+            .components()
+            .any(|component| matches!(component, Component::Normal(_)));
+    if !rooted {
+        return Err(DuneError::InvalidInput(format!(
+            "Cleanup rule path {} must start with a folder below the root",
+            pattern
+        )));
+    }
+
+    let mut regex = String::from(if cfg!(any(windows, target_os = "macos")) {
+        "(?i)^"
+    } else {
+        "^"
+    });
+    let mut rest = pattern.as_str();
+    while let Some(position) = rest.find(['*', '?']) {
+        regex.push_str(&regex::escape(&rest[..position]));
+        rest = &rest[position..];
+        if let Some(after) = rest.strip_prefix("**/") {
+            regex.push_str("(?:.*/)?");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("**") {
+            regex.push_str(".*");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('*') {
+            regex.push_str("[^/]*");
+            rest = after;
+        } else {
+            regex.push_str("[^/]");
+            rest = &rest[1..];
+        }
+    }
+    regex.push_str(&regex::escape(rest));
+    regex.push('$');
+
+    let regex = Regex::new(&regex)
+        .map_err(|e| DuneError::InvalidInput(format!("Cleanup rule path {}: {}", pattern, e)))?;
+    Ok((regex, folder))
+}
+
+/// Every rule's glob compiles, and no two rules share a name
+pub fn validate(rules: &[CleanupRule]) -> Result<(), DuneError> {
+    let mut names = HashSet::new();
+    for rule in rules {
+        if rule.name.trim().is_empty() {
+            return Err(DuneError::InvalidInput("Cleanup rules need a name".into()));
+        }
+        if !names.insert(rule.name.as_str()) {
+            return Err(DuneError::InvalidInput(format!(
+                "Two cleanup rules are named {}",
+                rule.name
+            )));
+        }
+        compile(&rule.path)?;
+    }
+
+    Ok(())
+}
+
+/// Files below `tree.nodes[index]` that `rule` matches, as of `now`
+fn matches(
+    rule: &CleanupRule,
+    regex: &Regex,
+    tree: &ScanTree,
+    index: u32,
+    now: u64,
+) -> Vec<Suggestion> {
+    let start = index as usize;
+    let min_age = rule
+        .older_than_days
+        .map(|days| u64::from(days) * DAY_MILLIS);
+    let small_items: HashSet<u32> = tree.small_items.iter().copied().collect();
+    let mut folders: Vec<Option<PathBuf>> = vec![None; tree.nodes.len() - start];
+    folders[0] = Some(tree.path(index));
+    let mut found = Vec::new();
+
+    for (offset, node) in tree.nodes[start..].iter().enumerate().skip(1) {
+        let Some(parent) = node
+            .parent
+            .and_then(|parent| (parent as usize).checked_sub(start))
+            .and_then(|parent| folders[parent].as_deref())
+        else {
+            continue;
+        };
+        if small_items.contains(&((start + offset) as u32)) {
+            continue;
+        }
+        let path = parent.join(&*node.name);
+
+        if node.is_dir {
+            folders[offset] = Some(path);
+            continue;
+        }
+        if rule.min_size.is_some_and(|min_size| node.size < min_size) {
+            continue;
+        }
+        if let Some(min_age) = min_age {
+            if !node
+                .modified
+                .is_some_and(|modified| now.saturating_sub(modified) >= min_age)
+            {
+                continue;
+            }
+        }
+        let path = path.to_string_lossy().into_owned();
+        if !regex.is_match(&comparable(&path)) {
+            continue;
+        }
+
+        found.push(Suggestion {
+            path,
+            size: node.size,
+            modified: node.modified,
+            rule: rule.name.clone(),
+            action: rule.action,
+            found_at: now,
+        });
+    }
+
+    found
+}
+
+/// Scan the folder `rule` starts in, or reuse a finished scan of it, and match the rule
+fn evaluate(
+    service: &ScannerService,
+    rule: &CleanupRule,
+    now: u64,
+) -> Result<(Vec<Suggestion>, String, bool), DuneError> {
+    let (regex, folder) = compile(&rule.path)?;
+    let folder = folder.to_string_lossy().into_owned();
+    let (tree, index) = service.subtree(&folder)?;
+    let complete = tree.nodes[index as usize].size_complete;
+
+    Ok((matches(rule, &regex, &tree, index, now), folder, complete))
+}
+
+/// What `rule` would suggest, for trying it out before saving it
+pub fn preview(service: &ScannerService, rule: &CleanupRule) -> Result<RulePreview, DuneError> {
+    let (mut found, folder, complete) = evaluate(service, rule, scan::now_millis())?;
+    let files = found.len() as u64;
+    let bytes = found
+        .iter()
+        .map(|suggestion| suggestion.size)
+        .fold(0u64, u64::saturating_add);
+    found.sort_by_key(|suggestion| std::cmp::Reverse(suggestion.size));
+    found.truncate(MAX_PREVIEW);
+
+    Ok(RulePreview {
+        folder,
+        files,
+        bytes,
+        matches: found,
+        complete,
+    })
+}
+
+/// Evaluate the enabled `rules` and make what they match their pending suggestions,
+/// leaving out files the user dismissed and have not changed since. Suggestions of rules
+/// that are not evaluated, or fail to, stay as they were.
+pub fn run(
+    service: &ScannerService,
+    store: &Path,
+    rules: &[CleanupRule],
+) -> Result<Refreshed, DuneError> {
+    let now = scan::now_millis();
+    let mut evaluated = HashSet::new();
+    let mut found = Vec::new();
+    for rule in rules.iter().filter(|rule| rule.enabled) {
+        match evaluate(service, rule, now) {
+            Ok((matched, _, _)) => {
+                evaluated.insert(rule.name.clone());
+                found.extend(matched);
+            }
+            Err(err) => log::warn!("Cleanup rule {} failed: {}", rule.name, err),
+        }
+    }
+
+    let _lock = STORE_LOCK.lock().unwrap();
+    let mut stored = read(store)?;
+    stored
+        .dismissed
+        .retain(|dismissed| fs::symlink_metadata(&dismissed.path).is_ok());
+
+    let (previous, kept): (Vec<_>, Vec<_>) = stored
+        .pending
+        .into_iter()
+        .partition(|suggestion| evaluated.contains(&suggestion.rule));
+    let mut seen: HashSet<String> = kept
+        .iter()
+        .map(|suggestion| suggestion.path.clone())
+        .collect();
+    let (mut new_files, mut new_bytes) = (0, 0u64);
+    let mut pending = kept;
+    for mut suggestion in found {
+        let dismissed = stored.dismissed.iter().any(|dismissed| {
+            dismissed.path == suggestion.path && dismissed.modified == suggestion.modified
+        });
+        if dismissed || !seen.insert(suggestion.path.clone()) {
+            continue;
+        }
+
+        match previous.iter().find(|old| old.path == suggestion.path) {
+            Some(old) => suggestion.found_at = old.found_at,
+            None => {
+                new_files += 1;
+                new_bytes = new_bytes.saturating_add(suggestion.size);
+            }
+        }
+        pending.push(suggestion);
+    }
+    pending.sort_by_key(|suggestion| std::cmp::Reverse(suggestion.size));
+
+    stored.pending = pending;
+    write(store, &stored)?;
+    log::info!(
+        "Cleanup rules suggest {} files, {} of them new",
+        stored.pending.len(),
+        new_files
+    );
+
+    Ok(Refreshed {
+        suggestions: stored.pending,
+        new_files,
+        new_bytes,
+    })
+}
+
+/// Suggestions waiting for approval, largest first
+pub fn pending(store: &Path) -> Result<Vec<Suggestion>, DuneError> {
+    Ok(read(store)?.pending)
+}
+
+/// Whether the file at the suggestion's path is still the one the rule matched; a file
+/// replaced since, say downloaded again under the same name, must not go under the old
+/// approval
+fn unchanged(suggestion: &Suggestion) -> bool {
+    fs::symlink_metadata(&suggestion.path).is_ok_and(|metadata| {
+        metadata.is_file()
+            && metadata.len() == suggestion.size
+            && metadata.modified().ok().and_then(scan::millis_since_epoch) == suggestion.modified
+    })
+}
+
+fn operation(action: CleanupAction) -> Operation {
+    match action {
+        CleanupAction::Trash => Operation::Trash,
+        CleanupAction::RecentlyRemoved => Operation::SoftDelete,
+        CleanupAction::Delete => Operation::Delete,
+    }
+}
+
+/// Carry out the pending suggestions for `paths`, each with its rule's action; paths
+/// without a pending suggestion are left alone, and failed ones stay pending. Files
+/// changed or gone since they were suggested are refused and dropped from the pending
+/// list, to be suggested again if a rule still matches them.
+pub fn apply(
+    audit: &Path,
+    removed_folder: &Path,
+    store: &Path,
+    paths: &[String],
+) -> Result<Vec<OperationRecord>, DuneError> {
+    let _lock = STORE_LOCK.lock().unwrap();
+    let mut stored = read(store)?;
+    let paths: HashSet<&str> = paths.iter().map(String::as_str).collect();
+
+    let mut records = Vec::new();
+    let mut stale = HashSet::new();
+    for suggestion in &stored.pending {
+        if paths.contains(suggestion.path.as_str()) && !unchanged(suggestion) {
+            let path = Path::new(&suggestion.path);
+            records.push(file_ops::perform(
+                audit,
+                operation(suggestion.action),
+                path,
+                None,
+                || {
+                    Err(DuneError::InvalidInput(tr(
+                        "error.changed_since_suggested",
+                        &[("path", &suggestion.path)],
+                    )))
+                },
+            ));
+            stale.insert(suggestion.path.clone());
+        }
+    }
+
+    for action in [
+        CleanupAction::Trash,
+        CleanupAction::RecentlyRemoved,
+        CleanupAction::Delete,
+    ] {
+        let approved: Vec<String> = stored
+            .pending
+            .iter()
+            .filter(|suggestion| {
+                suggestion.action == action
+                    && paths.contains(suggestion.path.as_str())
+                    && !stale.contains(&suggestion.path)
+            })
+            .map(|suggestion| suggestion.path.clone())
+            .collect();
+        if approved.is_empty() {
+            continue;
+        }
+
+        records.extend(match action {
+            CleanupAction::Trash => file_ops::move_to_trash(audit, &approved),
+            CleanupAction::RecentlyRemoved => {
+                removed::soft_delete(audit, removed_folder, &approved)?
+            }
+            CleanupAction::Delete => file_ops::delete(audit, &approved),
+        });
+    }
+
+    let done: HashSet<&str> = records
+        .iter()
+        .filter(|record| record.ok)
+        .map(|record| record.path.as_str())
+        .collect();
+    stored.pending.retain(|suggestion| {
+        !done.contains(suggestion.path.as_str()) && !stale.contains(&suggestion.path)
+    });
+    write(store, &stored)?;
+
+    Ok(records)
+}
+
+/// Drop the pending suggestions for `paths` and stop suggesting them until they change
+pub fn dismiss(store: &Path, paths: &[String]) -> Result<Vec<Suggestion>, DuneError> {
+    let _lock = STORE_LOCK.lock().unwrap();
+    let mut stored = read(store)?;
+    let paths: HashSet<&str> = paths.iter().map(String::as_str).collect();
+
+    let (dismissed, pending): (Vec<_>, Vec<_>) = stored
+        .pending
+        .into_iter()
+        .partition(|suggestion| paths.contains(suggestion.path.as_str()));
+    stored
+        .dismissed
+        .extend(dismissed.into_iter().map(|suggestion| Dismissed {
+            path: suggestion.path,
+            modified: suggestion.modified,
+        }));
+    stored.pending = pending;
+    write(store, &stored)?;
+
+    Ok(stored.pending)
+}
@@ -0,0 +1,61 @@
+use crate::console;
+use crate::error::DuneError;
+use std::io::Write;
+use std::process::Stdio;
+
+/// Programs that read new clipboard contents from stdin, tried in order
+#[cfg(target_os = "macos")]
+const COPIERS: &[&[&str]] = &[&["pbcopy"]];
+
+/// clip.exe reads the console code page, so PowerShell is told to expect UTF-8 instead
+#[cfg(target_os = "windows")]
+const COPIERS: &[&[&str]] = &[&[
+    "powershell",
+    "-NoProfile",
+    "-NonInteractive",
+    "-Command",
+    "[Console]::InputEncoding = [Text.Encoding]::UTF8; Set-Clipboard -Value ([Console]::In.ReadToEnd())",
+]];
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const COPIERS: &[&[&str]] = &[
+    &["wl-copy"],
+    &["xclip", "-selection", "clipboard"],
+    &["xsel", "--clipboard", "--input"],
+];
+
+fn copy_with(program: &[&str], text: &str) -> std::io::Result<bool> {
+    let mut command = console::command(program[0]);
+    command
+        .args(&program[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    let mut child = command.spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+
+    Ok(child.wait()?.success())
+}
+
+/// Put text on the system clipboard
+pub fn set_text(text: &str) -> Result<(), DuneError> {
+    for program in COPIERS {
+        match copy_with(program, text) {
+            Ok(true) => return Ok(()),
+            Ok(false) => log::debug!("{} could not set the clipboard", program[0]),
+            Err(err) => log::debug!("Could not run {}: {}", program[0], err),
+        }
+    }
+
+    Err(DuneError::Unsupported(format!(
+        "No clipboard program worked (tried {})",
+        COPIERS
+            .iter()
+            .map(|program| program[0])
+            .collect::<Vec<_>>()
+            .join(", ")
+    )))
+}
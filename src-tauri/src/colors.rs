@@ -0,0 +1,163 @@
+use crate::age;
+use crate::filetype::{self, FileCategory};
+use crate::owner;
+use crate::ring::RingSegment;
+use crate::scan::ScanTree;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Colors a series can use before they repeat, one per `FileCategory`; the palette adds
+/// folders and "other"
+pub const SERIES: usize = FileCategory::ALL.len();
+/// Palette index of folders when coloring by type
+pub const FOLDER: u8 = SERIES as u8;
+/// Palette index of "other" buckets
+pub const OTHER: u8 = SERIES as u8 + 1;
+
+/// The frontend's theme, as in `src/theme.ts`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    #[default]
+    Red,
+    Green,
+    NeonBlue,
+    /// Okabe-Ito colors, told apart with any common color vision deficiency
+    ColorblindSafe,
+    /// Saturated colors and white on black, for low vision and bright rooms
+    HighContrast,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorBy {
+    /// `FileCategory` of files and bundles; other folders share one color
+    #[default]
+    Type,
+    /// Last modification, newest file for folders, in `age_histogram` buckets
+    Age,
+    Owner,
+    /// Depth below the scan root, so zooming in keeps a folder's color
+    Depth,
+}
+
+/// `SERIES` colors, then folders, then "other", as `0xRRGGBB`
+pub fn palette(theme: Theme) -> [u32; SERIES + 2] {
+    match theme {
+        Theme::Red => [
+            0xff2f2f, 0xff7a2f, 0xffb02f, 0xff2f7a, 0xc22f2f, 0xff8e8e, 0xd9487f, 0xffd27a,
+            0x6a3a3a, 0x3a2020,
+        ],
+        Theme::Green => [
+            0x4cff63, 0xa8ff4c, 0x4cffb0, 0x2fbf4a, 0xafffb8, 0xe0ff4c, 0x36cc4a, 0x7dffd0,
+            0x3a5a3e, 0x203a24,
+        ],
+        Theme::NeonBlue => [
+            0x12d7ff, 0x5682ff, 0x8bf4ff, 0x00a4ce, 0x12ffd0, 0xa07cff, 0x56e9ff, 0x2f6bff,
+            0x2f4a66, 0x1a2a3a,
+        ],
+        Theme::ColorblindSafe => [
+            0xe69f00, 0x56b4e9, 0x009e73, 0xf0e442, 0x0072b2, 0xd55e00, 0xcc79a7, 0xf5f5f5,
+            0x5a5a5a, 0x2e2e2e,
+        ],
+        Theme::HighContrast => [
+            0xffff00, 0x00ffff, 0xff00ff, 0x00ff00, 0xffffff, 0xff8000, 0x80a0ff, 0xff4040,
+            0x808080, 0x404040,
+        ],
+    }
+}
+
+/// Dark to light stops of the theme (`deepBackgroundHex`, `meshBaseHex`, `primaryHex`,
+/// `softHex` in `src/theme.ts`), for the dither palette
+pub fn ramp(theme: Theme) -> [u32; 4] {
+    match theme {
+        Theme::Red => [0x140808, 0x442a2a, 0xff2f2f, 0xff8e8e],
+        Theme::Green => [0x081408, 0x26442a, 0x4cff63, 0xafffb8],
+        Theme::NeonBlue => [0x081424, 0x1f334a, 0x12d7ff, 0x8bf4ff],
+        Theme::ColorblindSafe => [0x0b0f14, 0x2a3440, 0x56b4e9, 0xb3dcf5],
+        Theme::HighContrast => [0x000000, 0x303030, 0xffff00, 0xffffff],
+    }
+}
+
+/// FNV-1a, which unlike `DefaultHasher` gives the same value in every build
+fn stable_hash(value: &str) -> u64 {
+    value.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Newest file modification under every node, for coloring folders by age
+pub fn newest_modified(tree: &ScanTree) -> Vec<Option<u64>> {
+    let mut newest: Vec<Option<u64>> = tree.nodes.iter().map(|node| node.modified).collect();
+    // Children come after their parents, so walking backwards finishes each child first
+    for index in (1..tree.nodes.len()).rev() {
+        if let Some(parent) = tree.nodes[index].parent {
+            newest[parent as usize] = newest[parent as usize].max(newest[index]);
+        }
+    }
+
+    newest
+}
+
+/// Nodes between `index` and the scan root
+pub fn root_depth(tree: &ScanTree, index: u32) -> u32 {
+    let mut depth = 0;
+    let mut current = index;
+    while let Some(parent) = tree.nodes[current as usize].parent {
+        depth += 1;
+        current = parent;
+    }
+
+    depth
+}
+
+/// Palette index of one node `depth` below the scan root; the same node gets the same
+/// color in every scan and export
+pub fn color_of(
+    tree: &ScanTree,
+    newest: &[Option<u64>],
+    index: u32,
+    depth: u32,
+    by: ColorBy,
+) -> u8 {
+    let node = &tree.nodes[index as usize];
+    match by {
+        ColorBy::Type => {
+            let path = Path::new(&*node.name);
+            if node.is_dir && !filetype::is_bundle(path) {
+                FOLDER
+            } else {
+                filetype::category_for(path).index() as u8
+            }
+        }
+        ColorBy::Age => {
+            let age = newest[index as usize].map_or(Duration::MAX, |modified| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH + Duration::from_millis(modified))
+                    .unwrap_or_default()
+            });
+            age::bucket(age) as u8
+        }
+        ColorBy::Owner => owner::owner_id(&tree.path(index))
+            .map_or(OTHER, |id| (stable_hash(&id) % SERIES as u64) as u8),
+        ColorBy::Depth => (depth as usize % SERIES) as u8,
+    }
+}
+
+/// Stamp `color` on the segments of a ring level whose focus is `focus`
+pub fn stamp(
+    tree: &ScanTree,
+    newest: &[Option<u64>],
+    focus: u32,
+    segments: &mut [RingSegment],
+    by: ColorBy,
+) {
+    let focus_depth = root_depth(tree, focus);
+    for segment in segments {
+        segment.color = match segment.node {
+            Some(node) => color_of(tree, newest, node, focus_depth + segment.depth, by),
+            None => OTHER,
+        };
+    }
+}
@@ -0,0 +1,466 @@
+use crate::error::DuneError;
+use crate::scan::ScanHandle;
+use crate::scanner::{self, DirEntryInfo, DirEnumerator, EntryKind};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+/// FAT and SMB keep modification times at two-second precision, so copies can differ by that much
+pub const MTIME_TOLERANCE: Duration = Duration::from_secs(2);
+const READ_CHUNK: usize = 256 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompareOptions {
+    /// Read files of equal size on both sides instead of trusting size and modification time
+    pub compare_contents: bool,
+    /// Keep matching entries in the tree; otherwise only differences are returned
+    pub include_same: bool,
+    pub include_hidden: bool,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        Self {
+            compare_contents: false,
+            include_same: false,
+            include_hidden: true,
+        }
+    }
+}
+
+/// Payload of `compare://started`; cancel with `cancel_scan(compare_id)`
+#[derive(Debug, Clone, Serialize)]
+pub struct CompareStarted {
+    pub compare_id: u64,
+    pub a: String,
+    pub b: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffStatus {
+    OnlyA,
+    OnlyB,
+    Same,
+    Differs,
+}
+
+/// One entry of a diff tree. Folders that exist on one side only, or match entirely,
+/// are not expanded and carry the totals of their contents.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffNode {
+    /// Index of the parent node, `None` for the root
+    pub parent: Option<u32>,
+    pub name: String,
+    pub is_dir: bool,
+    pub status: DiffStatus,
+    pub size_a: u64,
+    pub size_b: u64,
+    /// Files inside a folder, each entry counted on the side where it holds more; 1 for files
+    pub file_count: u64,
+}
+
+/// Result of `compare_dirs`; `nodes[0]` is the root and parents always precede children
+#[derive(Debug, Clone, Serialize)]
+pub struct DirDiff {
+    pub compare_id: u64,
+    pub a: String,
+    pub b: String,
+    pub nodes: Vec<DiffNode>,
+    /// File counts by status
+    pub same: u64,
+    pub differing: u64,
+    pub only_a: u64,
+    pub only_b: u64,
+    pub bytes_only_a: u64,
+    pub bytes_only_b: u64,
+    /// False if some folders or files could not be read, so the result is not proof of a match
+    pub complete: bool,
+    pub elapsed_ms: u64,
+}
+
+/// A compared entry with its children, before flattening
+struct Branch {
+    name: String,
+    is_dir: bool,
+    status: DiffStatus,
+    size_a: u64,
+    size_b: u64,
+    file_count: u64,
+    /// Everything inside could be read
+    complete: bool,
+    children: Vec<Branch>,
+}
+
+#[derive(Default)]
+struct Counters {
+    same: AtomicU64,
+    differing: AtomicU64,
+    only_a: AtomicU64,
+    only_b: AtomicU64,
+    bytes_only_a: AtomicU64,
+    bytes_only_b: AtomicU64,
+}
+
+struct Walk<'a> {
+    handle: &'a ScanHandle,
+    enumerator: &'a dyn DirEnumerator,
+    options: &'a CompareOptions,
+    counters: Counters,
+}
+
+enum Side {
+    A,
+    B,
+}
+
+/// Files, folders and bytes under `path`; false if some of it could not be read
+fn measure(enumerator: &dyn DirEnumerator, path: &Path, include_hidden: bool) -> (u64, u64, bool) {
+    let Ok(listing) = enumerator.read_dir(path) else {
+        return (0, 0, false);
+    };
+    let mut complete = listing.complete;
+    let (mut bytes, mut files) = (0, 0);
+
+    for entry in listing.entries {
+        if !include_hidden && scanner::is_hidden_name(&entry.name) {
+            continue;
+        }
+        match entry.kind {
+            EntryKind::File => {
+                bytes += entry.len;
+                files += 1;
+            }
+            EntryKind::Dir => {
+                let (sub_bytes, sub_files, sub_complete) =
+                    measure(enumerator, &path.join(&entry.name), include_hidden);
+                bytes += sub_bytes;
+                files += sub_files;
+                complete &= sub_complete;
+            }
+            _ => {}
+        }
+    }
+
+    (bytes, files, complete)
+}
+
+fn same_time(a: Option<SystemTime>, b: Option<SystemTime>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            let delta = a.duration_since(b).or_else(|_| b.duration_since(a));
+            delta.is_ok_and(|delta| delta <= MTIME_TOLERANCE)
+        }
+        _ => false,
+    }
+}
+
+pub fn same_contents(a: &Path, b: &Path) -> io::Result<bool> {
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    let (mut buf_a, mut buf_b) = (vec![0u8; READ_CHUNK], vec![0u8; READ_CHUNK]);
+
+    loop {
+        let read = a.read(&mut buf_a)?;
+        if read == 0 {
+            // Equal sizes were checked first, but the file may have grown since
+            return Ok(b.read(&mut buf_b[..1])? == 0);
+        }
+        b.read_exact(&mut buf_b[..read])?;
+        if buf_a[..read] != buf_b[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+impl Walk<'_> {
+    fn listing(&self, path: &Path) -> (BTreeMap<OsString, DirEntryInfo>, bool) {
+        match self.enumerator.read_dir(path) {
+            Ok(listing) => (
+                listing
+                    .entries
+                    .into_iter()
+                    .filter(|entry| {
+                        self.options.include_hidden || !scanner::is_hidden_name(&entry.name)
+                    })
+                    .filter(|entry| matches!(entry.kind, EntryKind::File | EntryKind::Dir))
+                    .map(|entry| (entry.name.clone(), entry))
+                    .collect(),
+                listing.complete,
+            ),
+            Err(err) => {
+                log::debug!("Could not read {}: {}", path.display(), err);
+                (BTreeMap::new(), false)
+            }
+        }
+    }
+
+    /// An entry present on one side only
+    fn one_sided(&self, path: &Path, name: String, entry: &DirEntryInfo, side: Side) -> Branch {
+        let (bytes, files, complete) = match entry.kind {
+            EntryKind::Dir => measure(self.enumerator, path, self.options.include_hidden),
+            _ => (entry.len, 1, true),
+        };
+        let (status, count, total) = match side {
+            Side::A => (
+                DiffStatus::OnlyA,
+                &self.counters.only_a,
+                &self.counters.bytes_only_a,
+            ),
+            Side::B => (
+                DiffStatus::OnlyB,
+                &self.counters.only_b,
+                &self.counters.bytes_only_b,
+            ),
+        };
+        count.fetch_add(files, Ordering::Relaxed);
+        total.fetch_add(bytes, Ordering::Relaxed);
+
+        Branch {
+            name,
+            is_dir: entry.kind == EntryKind::Dir,
+            status,
+            size_a: if matches!(side, Side::A) { bytes } else { 0 },
+            size_b: if matches!(side, Side::B) { bytes } else { 0 },
+            file_count: files,
+            complete,
+            children: Vec::new(),
+        }
+    }
+
+    fn compare_files(
+        &self,
+        a: &Path,
+        b: &Path,
+        entry_a: &DirEntryInfo,
+        entry_b: &DirEntryInfo,
+    ) -> (DiffStatus, bool) {
+        let mut complete = true;
+        let same = entry_a.len == entry_b.len
+            && if self.options.compare_contents {
+                same_contents(a, b).unwrap_or_else(|err| {
+                    log::debug!("Could not compare {}: {}", a.display(), err);
+                    complete = false;
+                    false
+                })
+            } else {
+                same_time(entry_a.modified, entry_b.modified)
+            };
+
+        if same {
+            self.counters.same.fetch_add(1, Ordering::Relaxed);
+            (DiffStatus::Same, complete)
+        } else {
+            self.counters.differing.fetch_add(1, Ordering::Relaxed);
+            (DiffStatus::Differs, complete)
+        }
+    }
+
+    /// Children of two folders known to exist, and whether both could be read in full
+    fn children(&self, a: &Path, b: &Path) -> (Vec<Branch>, bool) {
+        if self.handle.is_cancelled() {
+            return (Vec::new(), false);
+        }
+
+        let ((entries_a, complete_a), (entries_b, complete_b)) =
+            rayon::join(|| self.listing(a), || self.listing(b));
+        let mut names: Vec<&OsString> = entries_a.keys().chain(entries_b.keys()).collect();
+        names.sort_unstable();
+        names.dedup();
+
+        let children = names
+            .into_par_iter()
+            .filter_map(|name| {
+                let (path_a, path_b) = (a.join(name), b.join(name));
+                let display = name.to_string_lossy().to_string();
+
+                Some(match (entries_a.get(name), entries_b.get(name)) {
+                    (Some(entry), None) => self.one_sided(&path_a, display, entry, Side::A),
+                    (None, Some(entry)) => self.one_sided(&path_b, display, entry, Side::B),
+                    (Some(entry_a), Some(entry_b)) => {
+                        self.compare_both(&path_a, &path_b, display, entry_a, entry_b)
+                    }
+                    (None, None) => return None,
+                })
+            })
+            .collect();
+
+        (children, complete_a && complete_b)
+    }
+
+    fn compare_both(
+        &self,
+        a: &Path,
+        b: &Path,
+        name: String,
+        entry_a: &DirEntryInfo,
+        entry_b: &DirEntryInfo,
+    ) -> Branch {
+        match (entry_a.kind, entry_b.kind) {
+            (EntryKind::Dir, EntryKind::Dir) => self.folder(a, b, name),
+            (EntryKind::File, EntryKind::File) => {
+                let (status, complete) = self.compare_files(a, b, entry_a, entry_b);
+                Branch {
+                    name,
+                    is_dir: false,
+                    status,
+                    size_a: entry_a.len,
+                    size_b: entry_b.len,
+                    file_count: 1,
+                    complete,
+                    children: Vec::new(),
+                }
+            }
+            // A file on one side and a folder on the other
+            _ => {
+                self.counters.differing.fetch_add(1, Ordering::Relaxed);
+                let measured = |path: &Path, entry: &DirEntryInfo| match entry.kind {
+                    EntryKind::Dir => measure(self.enumerator, path, self.options.include_hidden),
+                    _ => (entry.len, 1, true),
+                };
+                let (size_a, files_a, complete_a) = measured(a, entry_a);
+                let (size_b, files_b, complete_b) = measured(b, entry_b);
+
+                Branch {
+                    name,
+                    is_dir: entry_a.kind == EntryKind::Dir,
+                    status: DiffStatus::Differs,
+                    size_a,
+                    size_b,
+                    file_count: files_a.max(files_b),
+                    complete: complete_a && complete_b,
+                    children: Vec::new(),
+                }
+            }
+        }
+    }
+
+    /// A folder on both sides; it matches only if everything inside it was read and matches
+    fn folder(&self, a: &Path, b: &Path, name: String) -> Branch {
+        let (mut children, listed) = self.children(a, b);
+        let complete = listed && children.iter().all(|child| child.complete);
+        let same = complete
+            && children
+                .iter()
+                .all(|child| child.status == DiffStatus::Same);
+
+        let (size_a, size_b, file_count) = children.iter().fold((0, 0, 0), |total, child| {
+            (
+                total.0 + child.size_a,
+                total.1 + child.size_b,
+                total.2 + child.file_count,
+            )
+        });
+
+        // A matching folder stays collapsed; a differing one shows what differs
+        if same {
+            children.clear();
+        } else if !self.options.include_same {
+            children.retain(|child| child.status != DiffStatus::Same);
+        }
+        children.sort_by_key(|child| std::cmp::Reverse(child.size_a.max(child.size_b)));
+
+        Branch {
+            name,
+            is_dir: true,
+            status: if same {
+                DiffStatus::Same
+            } else {
+                DiffStatus::Differs
+            },
+            size_a,
+            size_b,
+            file_count,
+            complete,
+            children,
+        }
+    }
+}
+
+fn flatten(branch: Branch, parent: Option<u32>, nodes: &mut Vec<DiffNode>) {
+    let index = nodes.len() as u32;
+    nodes.push(DiffNode {
+        parent,
+        name: branch.name,
+        is_dir: branch.is_dir,
+        status: branch.status,
+        size_a: branch.size_a,
+        size_b: branch.size_b,
+        file_count: branch.file_count,
+    });
+
+    for child in branch.children {
+        flatten(child, Some(index), nodes);
+    }
+}
+
+/// Walk the handle's root (side A) and `b` side by side and classify every entry
+pub fn compare_dirs(
+    handle: &ScanHandle,
+    b: &str,
+    options: &CompareOptions,
+) -> Result<DirDiff, DuneError> {
+    let started = Instant::now();
+    let (a, b) = (handle.root.as_str(), b);
+    for path in [a, b] {
+        if !Path::new(path).is_dir() {
+            return Err(if Path::new(path).exists() {
+                DuneError::NotADirectory { path: path.into() }
+            } else {
+                DuneError::NotFound { path: path.into() }
+            });
+        }
+    }
+
+    let walk = Walk {
+        handle,
+        enumerator: scanner::default_enumerator(),
+        options,
+        counters: Counters::default(),
+    };
+    let root = walk.folder(Path::new(a), Path::new(b), a.to_string());
+
+    if handle.is_cancelled() {
+        log::info!("Compare {} of {} and {} cancelled", handle.id, a, b);
+        return Err(DuneError::Cancelled);
+    }
+
+    let complete = root.complete;
+    let mut nodes = Vec::new();
+    flatten(root, None, &mut nodes);
+
+    let counters = walk.counters;
+    let diff = DirDiff {
+        compare_id: handle.id,
+        a: a.to_string(),
+        b: b.to_string(),
+        nodes,
+        same: counters.same.into_inner(),
+        differing: counters.differing.into_inner(),
+        only_a: counters.only_a.into_inner(),
+        only_b: counters.only_b.into_inner(),
+        bytes_only_a: counters.bytes_only_a.into_inner(),
+        bytes_only_b: counters.bytes_only_b.into_inner(),
+        complete,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    };
+
+    log::info!(
+        "Compared {} and {}: {} same, {} differing, {} only in A, {} only in B ({} ms)",
+        a,
+        b,
+        diff.same,
+        diff.differing,
+        diff.only_a,
+        diff.only_b,
+        diff.elapsed_ms
+    );
+
+    Ok(diff)
+}
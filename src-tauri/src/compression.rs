@@ -0,0 +1,229 @@
+use crate::error::DuneError;
+use crate::scanner::{self, DirEntryInfo, DirEnumerator, EntryKind, Hidden};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Files sampled per folder, picked in proportion to their size
+const SAMPLES_PER_FOLDER: usize = 24;
+/// Bytes read from the start of each sampled file
+const SAMPLE_BYTES: u64 = 256 * 1024;
+/// Filesystems compress in independent blocks of about this size
+const FILESYSTEM_BLOCK: usize = 128 * 1024;
+/// Compressed blocks still take whole sectors
+const SECTOR: usize = 4096;
+/// Close to what btrfs, NTFS, and APFS achieve with their fast codecs
+const FILESYSTEM_LEVEL: i32 = 1;
+/// A typical `.tar.zst` or `.7z` setting
+const ARCHIVE_LEVEL: i32 = 9;
+
+/// Formats that are compressed already and would not shrink further
+const COMPRESSED_EXTENSIONS: &[&str] = &[
+    "zip", "gz", "tgz", "bz2", "xz", "zst", "7z", "rar", "lz4", "br", "dmg", "jpg", "jpeg", "png",
+    "gif", "webp", "heic", "heif", "avif", "mp4", "m4v", "mov", "mkv", "webm", "avi", "mp3", "m4a",
+    "aac", "ogg", "opus", "flac", "docx", "xlsx", "pptx", "odt", "ods", "epub", "jar", "apk",
+    "ipa", "woff2", "pdf",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FolderEstimate {
+    pub name: String,
+    pub path: String,
+    pub bytes: u64,
+    /// Estimated size with transparent filesystem compression turned on
+    pub filesystem_bytes: u64,
+    /// Estimated size packed into a compressed archive
+    pub archive_bytes: u64,
+    pub sampled_files: u32,
+    pub sampled_bytes: u64,
+    pub complete: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CompressionEstimate {
+    pub path: String,
+    /// Children, largest possible archive savings first
+    pub folders: Vec<FolderEstimate>,
+    pub bytes: u64,
+    pub filesystem_bytes: u64,
+    pub archive_bytes: u64,
+    pub complete: bool,
+}
+
+struct FileInfo {
+    path: PathBuf,
+    len: u64,
+}
+
+/// Compressed size over original size of one sample, for both targets
+struct Ratios {
+    filesystem: f64,
+    archive: f64,
+    sampled: u64,
+}
+
+fn is_compressed(path: &Path) -> bool {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+        .is_some_and(|extension| COMPRESSED_EXTENSIONS.contains(&extension.as_str()))
+}
+
+fn collect_files(enumerator: &dyn DirEnumerator, path: &Path, files: &mut Vec<FileInfo>) -> bool {
+    scanner::walk(
+        enumerator,
+        path,
+        Hidden::Skip,
+        &mut |path: &Path, entry: &DirEntryInfo| {
+            if entry.kind == EntryKind::File && entry.len > 0 {
+                files.push(FileInfo {
+                    path: path.to_path_buf(),
+                    len: entry.len,
+                });
+            }
+            true
+        },
+    )
+}
+
+/// Systematic sample weighted by size: the files under evenly spaced byte offsets,
+/// each with the number of offsets it covers
+fn pick_samples(files: &[FileInfo], total: u64) -> Vec<(&FileInfo, u32)> {
+    let step = total as f64 / SAMPLES_PER_FOLDER as f64;
+    let mut picked = Vec::new();
+    let mut offset = 0u64;
+    let mut next = step / 2.0;
+
+    for file in files {
+        offset += file.len;
+        let mut hits = 0;
+        while (offset as f64) > next && next < total as f64 {
+            hits += 1;
+            next += step;
+        }
+        if hits > 0 {
+            picked.push((file, hits));
+        }
+    }
+
+    picked
+}
+
+fn measure_sample(file: &FileInfo) -> Option<Ratios> {
+    if is_compressed(&file.path) {
+        return Some(Ratios {
+            filesystem: 1.0,
+            archive: 1.0,
+            sampled: 0,
+        });
+    }
+
+    let mut data = Vec::new();
+    File::open(&file.path)
+        .and_then(|handle| handle.take(SAMPLE_BYTES).read_to_end(&mut data))
+        .inspect_err(|err| log::debug!("Could not sample {}: {}", file.path.display(), err))
+        .ok()?;
+
+    if data.is_empty() {
+        return None;
+    }
+
+    let filesystem: usize = data
+        .chunks(FILESYSTEM_BLOCK)
+        .map(|block| {
+            let compressed = zstd::bulk::compress(block, FILESYSTEM_LEVEL)
+                .map_or(block.len(), |compressed| compressed.len());
+            // Blocks that do not shrink are stored as they are
+            compressed
+                .div_ceil(SECTOR)
+                .saturating_mul(SECTOR)
+                .min(block.len())
+        })
+        .sum();
+    let archive = zstd::bulk::compress(&data, ARCHIVE_LEVEL)
+        .map_or(data.len(), |compressed| compressed.len());
+
+    Some(Ratios {
+        filesystem: filesystem as f64 / data.len() as f64,
+        archive: (archive as f64 / data.len() as f64).min(1.0),
+        sampled: data.len() as u64,
+    })
+}
+
+fn estimate_folder(enumerator: &dyn DirEnumerator, name: String, path: &Path) -> FolderEstimate {
+    let mut files = Vec::new();
+    let complete = collect_files(enumerator, path, &mut files);
+    let bytes: u64 = files.iter().map(|file| file.len).sum();
+
+    let samples = pick_samples(&files, bytes);
+    let ratios: Vec<(Ratios, u32)> = samples
+        .iter()
+        .filter_map(|(file, hits)| Some((measure_sample(file)?, *hits)))
+        .collect();
+
+    // Averaging per-file ratios over a size-weighted pick gives the ratio of bytes
+    let weight: u32 = ratios.iter().map(|(_, hits)| hits).sum();
+    let mean = |ratio: fn(&Ratios) -> f64| {
+        if weight == 0 {
+            return 1.0;
+        }
+        ratios
+            .iter()
+            .map(|(sample, hits)| ratio(sample) * f64::from(*hits))
+            .sum::<f64>()
+            / f64::from(weight)
+    };
+
+    FolderEstimate {
+        name,
+        path: path.to_string_lossy().to_string(),
+        bytes,
+        filesystem_bytes: (bytes as f64 * mean(|sample| sample.filesystem)) as u64,
+        archive_bytes: (bytes as f64 * mean(|sample| sample.archive)) as u64,
+        sampled_files: ratios.len() as u32,
+        sampled_bytes: ratios.iter().map(|(sample, _)| sample.sampled).sum(),
+        complete,
+    }
+}
+
+/// Estimate per child folder of `path` what compression would save, from a size-weighted sample
+pub fn estimate_compression(path: &str) -> Result<CompressionEstimate, DuneError> {
+    let dir_path = scanner::require_dir(path)?;
+    let enumerator = scanner::default_enumerator();
+    let listing = enumerator
+        .read_dir(dir_path)
+        .map_err(|e| DuneError::io(dir_path, e))?;
+
+    let mut folders: Vec<FolderEstimate> = listing
+        .entries
+        .into_par_iter()
+        .filter(|entry| entry.kind == EntryKind::Dir && !scanner::is_hidden_name(&entry.name))
+        .map(|entry| {
+            estimate_folder(
+                enumerator,
+                entry.name.to_string_lossy().to_string(),
+                &dir_path.join(&entry.name),
+            )
+        })
+        .filter(|folder| folder.bytes > 0)
+        .collect();
+
+    folders
+        .sort_by_key(|folder| std::cmp::Reverse(folder.bytes.saturating_sub(folder.archive_bytes)));
+
+    log::info!(
+        "Estimated compression for {} folders under {}",
+        folders.len(),
+        path
+    );
+
+    Ok(CompressionEstimate {
+        path: path.to_string(),
+        bytes: folders.iter().map(|folder| folder.bytes).sum(),
+        filesystem_bytes: folders.iter().map(|folder| folder.filesystem_bytes).sum(),
+        archive_bytes: folders.iter().map(|folder| folder.archive_bytes).sum(),
+        complete: listing.complete && folders.iter().all(|folder| folder.complete),
+        folders,
+    })
+}
@@ -0,0 +1,27 @@
+use std::ffi::OsStr;
+use std::process::Command;
+
+/// Process creation flag that keeps console programs from flashing a window
+#[cfg(windows)]
+const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+/// A command for a console program that runs without showing a window
+pub fn command(program: impl AsRef<OsStr>) -> Command {
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        let mut command = Command::new(program);
+        command.creation_flags(CREATE_NO_WINDOW);
+        command
+    }
+    #[cfg(not(windows))]
+    Command::new(program)
+}
+
+/// PowerShell running `script` without a profile, prompts or window
+#[cfg(windows)]
+pub fn powershell(script: &str) -> Command {
+    let mut command = command("powershell");
+    command.args(["-NoProfile", "-NonInteractive", "-Command", script]);
+    command
+}
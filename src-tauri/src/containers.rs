@@ -0,0 +1,375 @@
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Extensions of files that hold a virtual disk
+const DISK_IMAGE_EXTENSIONS: &[&str] = &["vmdk", "qcow2", "vdi", "vhd", "vhdx", "raw"];
+/// Folders that are a whole VM, counted as one image
+const VM_BUNDLE_EXTENSIONS: &[&str] = &["pvm", "utm", "vmwarevm"];
+/// How deep to look for images under a VM folder
+const IMAGE_SEARCH_DEPTH: usize = 4;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Tool {
+    pub name: &'static str,
+    /// Where to read about reclaiming the space
+    pub link: &'static str,
+    /// Command that frees space, when the tool has one
+    pub cleanup: Option<&'static str>,
+}
+
+const DOCKER: Tool = Tool {
+    name: "Docker",
+    link: "https://docs.docker.com/engine/manage-resources/pruning/",
+    cleanup: Some("docker system prune"),
+};
+const PODMAN: Tool = Tool {
+    name: "Podman",
+    link: "https://docs.podman.io/en/latest/markdown/podman-system-prune.1.html",
+    cleanup: Some("podman system prune"),
+};
+#[cfg(windows)]
+const WSL: Tool = Tool {
+    name: "WSL",
+    link: "https://learn.microsoft.com/windows/wsl/disk-space",
+    cleanup: None,
+};
+const VIRTUALBOX: Tool = Tool {
+    name: "VirtualBox",
+    link: "https://www.virtualbox.org/manual/",
+    cleanup: None,
+};
+const VMWARE: Tool = Tool {
+    name: "VMware",
+    link: "https://techdocs.broadcom.com/",
+    cleanup: None,
+};
+#[cfg(target_os = "macos")]
+const PARALLELS: Tool = Tool {
+    name: "Parallels",
+    link: "https://docs.parallels.com/",
+    cleanup: None,
+};
+#[cfg(target_os = "macos")]
+const UTM: Tool = Tool {
+    name: "UTM",
+    link: "https://docs.getutm.app/",
+    cleanup: None,
+};
+#[cfg(target_os = "linux")]
+const LIBVIRT: Tool = Tool {
+    name: "libvirt",
+    link: "https://libvirt.org/storage.html",
+    cleanup: None,
+};
+#[cfg(unix)]
+const LIMA: Tool = Tool {
+    name: "Lima",
+    link: "https://lima-vm.io/docs/",
+    cleanup: Some("limactl prune"),
+};
+#[cfg(unix)]
+const COLIMA: Tool = Tool {
+    name: "Colima",
+    link: "https://github.com/abiosoft/colima",
+    cleanup: Some("colima prune"),
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    /// A tool's whole storage folder
+    DataRoot,
+    /// One virtual disk or VM bundle
+    DiskImage,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ContainerItem {
+    pub tool: Tool,
+    pub kind: ItemKind,
+    pub path: String,
+    /// Space actually allocated; sparse images are often far smaller than their length
+    pub bytes: u64,
+    pub logical_bytes: u64,
+    pub complete: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ContainersReport {
+    /// Largest first
+    pub items: Vec<ContainerItem>,
+    pub total_bytes: u64,
+}
+
+struct Location {
+    tool: Tool,
+    kind: ItemKind,
+    path: PathBuf,
+}
+
+#[derive(Default)]
+struct Usage {
+    allocated: u64,
+    logical: u64,
+    complete: bool,
+}
+
+#[cfg(unix)]
+pub fn allocated(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+pub fn allocated(metadata: &fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+/// Allocated and logical size of a file or folder, hidden files included
+fn disk_usage(path: &Path) -> Usage {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return Usage::default();
+    };
+
+    if !metadata.is_dir() {
+        return Usage {
+            allocated: allocated(&metadata),
+            logical: metadata.len(),
+            complete: true,
+        };
+    }
+
+    let mut usage = Usage {
+        complete: true,
+        ..Usage::default()
+    };
+
+    let Ok(entries) = fs::read_dir(path) else {
+        usage.complete = false;
+        return usage;
+    };
+
+    for entry in entries {
+        let Ok(entry) = entry else {
+            usage.complete = false;
+            continue;
+        };
+        let child = disk_usage(&entry.path());
+        usage.allocated = usage.allocated.saturating_add(child.allocated);
+        usage.logical = usage.logical.saturating_add(child.logical);
+        usage.complete &= child.complete;
+    }
+
+    usage
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+        .is_some_and(|extension| extensions.contains(&extension.as_str()))
+}
+
+/// Disk images and VM bundles under `dir`
+fn find_images(tool: Tool, dir: &Path, depth: usize, found: &mut Vec<Location>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+
+        if file_type.is_dir() && has_extension(&path, VM_BUNDLE_EXTENSIONS) {
+            found.push(Location {
+                tool,
+                kind: ItemKind::DiskImage,
+                path,
+            });
+        } else if file_type.is_dir() && depth > 0 {
+            find_images(tool, &path, depth - 1, found);
+        } else if file_type.is_file() && has_extension(&path, DISK_IMAGE_EXTENSIONS) {
+            found.push(Location {
+                tool,
+                kind: ItemKind::DiskImage,
+                path,
+            });
+        }
+    }
+}
+
+fn data_root(tool: Tool, path: PathBuf) -> Location {
+    Location {
+        tool,
+        kind: ItemKind::DataRoot,
+        path,
+    }
+}
+
+/// Sizes of container engine storage and VM disks found in their default places
+pub fn containers_report() -> ContainersReport {
+    let mut items: Vec<ContainerItem> = platform::locations()
+        .into_iter()
+        .filter(|location| location.path.exists())
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|location| {
+            let usage = disk_usage(&location.path);
+
+            ContainerItem {
+                tool: location.tool,
+                kind: location.kind,
+                path: location.path.to_string_lossy().to_string(),
+                bytes: usage.allocated,
+                logical_bytes: usage.logical,
+                complete: usage.complete,
+            }
+        })
+        .filter(|item| item.bytes > 0)
+        .collect();
+
+    items.sort_by_key(|item| std::cmp::Reverse(item.bytes));
+    log::info!("Found {} container and VM storage locations", items.len());
+
+    ContainersReport {
+        total_bytes: items.iter().map(|item| item.bytes).sum(),
+        items,
+    }
+}
+
+/// VM product folders every platform shares
+fn vm_image_dirs(home: &Path) -> Vec<(Tool, PathBuf)> {
+    vec![
+        (VIRTUALBOX, home.join("VirtualBox VMs")),
+        (VMWARE, home.join("vmware")),
+        (VMWARE, home.join("Documents").join("Virtual Machines")),
+    ]
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::*;
+
+    pub fn locations() -> Vec<Location> {
+        let Some(home) = dirs::home_dir() else {
+            return Vec::new();
+        };
+        let containers = home.join("Library").join("Containers");
+
+        let mut locations = vec![
+            data_root(DOCKER, containers.join("com.docker.docker").join("Data")),
+            data_root(PODMAN, home.join(".local/share/containers/podman/machine")),
+            data_root(LIMA, home.join(".lima")),
+            data_root(COLIMA, home.join(".colima")),
+        ];
+
+        let mut image_dirs = vm_image_dirs(&home);
+        image_dirs.push((VMWARE, home.join("Virtual Machines.localized")));
+        image_dirs.push((PARALLELS, home.join("Parallels")));
+        image_dirs.push((
+            UTM,
+            containers
+                .join("com.utmapp.UTM")
+                .join("Data")
+                .join("Documents"),
+        ));
+
+        for (tool, dir) in image_dirs {
+            find_images(tool, &dir, IMAGE_SEARCH_DEPTH, &mut locations);
+        }
+
+        locations
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::*;
+
+    /// Custom swap file location from `%USERPROFILE%\.wslconfig`, if set
+    fn wsl_swap_file(home: &Path) -> Option<PathBuf> {
+        let config = fs::read_to_string(home.join(".wslconfig")).ok()?;
+
+        config.lines().find_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            key.trim()
+                .eq_ignore_ascii_case("swapFile")
+                .then(|| PathBuf::from(value.trim().replace("\\\\", "\\")))
+        })
+    }
+
+    pub fn locations() -> Vec<Location> {
+        let (Some(home), Some(local)) = (dirs::home_dir(), dirs::data_local_dir()) else {
+            return Vec::new();
+        };
+
+        let mut locations = vec![
+            data_root(DOCKER, local.join("Docker").join("wsl")),
+            data_root(PODMAN, home.join(".local/share/containers/podman/machine")),
+        ];
+
+        // Store-installed distros keep ext4.vhdx under their package's LocalState
+        if let Ok(packages) = fs::read_dir(local.join("Packages")) {
+            for package in packages.flatten() {
+                find_images(WSL, &package.path().join("LocalState"), 0, &mut locations);
+            }
+        }
+        find_images(WSL, &local.join("wsl"), 1, &mut locations);
+
+        let swap = wsl_swap_file(&home).unwrap_or_else(|| std::env::temp_dir().join("swap.vhdx"));
+        locations.push(Location {
+            tool: WSL,
+            kind: ItemKind::DiskImage,
+            path: swap,
+        });
+
+        for (tool, dir) in vm_image_dirs(&home) {
+            find_images(tool, &dir, IMAGE_SEARCH_DEPTH, &mut locations);
+        }
+
+        locations
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::*;
+
+    pub fn locations() -> Vec<Location> {
+        let Some(home) = dirs::home_dir() else {
+            return Vec::new();
+        };
+
+        let mut locations = vec![
+            data_root(DOCKER, PathBuf::from("/var/lib/docker")),
+            data_root(DOCKER, home.join(".docker/desktop/vms")),
+            data_root(PODMAN, PathBuf::from("/var/lib/containers/storage")),
+            data_root(PODMAN, home.join(".local/share/containers/storage")),
+            data_root(LIMA, home.join(".lima")),
+            data_root(COLIMA, home.join(".colima")),
+        ];
+
+        let mut image_dirs = vm_image_dirs(&home);
+        image_dirs.push((LIBVIRT, PathBuf::from("/var/lib/libvirt/images")));
+        image_dirs.push((LIBVIRT, home.join(".local/share/libvirt/images")));
+
+        for (tool, dir) in image_dirs {
+            find_images(tool, &dir, IMAGE_SEARCH_DEPTH, &mut locations);
+        }
+
+        locations
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows, target_os = "linux")))]
+mod platform {
+    use super::Location;
+
+    pub fn locations() -> Vec<Location> {
+        Vec::new()
+    }
+}
@@ -0,0 +1,117 @@
+use crate::error::DuneError;
+use serde::{Deserialize, Serialize};
+use std::backtrace::Backtrace;
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static LAST_COMMAND: Mutex<Option<String>> = Mutex::new(None);
+
+/// Everything we know about a panic, written locally and never uploaded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub id: String,
+    pub timestamp: u64,
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    pub thread: String,
+    pub message: String,
+    pub location: Option<String>,
+    pub last_command: Option<String>,
+    pub backtrace: String,
+}
+
+/// Remember the most recent IPC command so crash reports can name it
+pub fn record_command(command: &str) {
+    if let Ok(mut last) = LAST_COMMAND.lock() {
+        *last = Some(command.to_string());
+    }
+}
+
+/// Write a crash report for every panic, then defer to the default hook
+pub fn install_panic_hook(crash_dir: PathBuf, app_version: String) {
+    let previous = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        let message = if let Some(message) = info.payload().downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = info.payload().downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "unknown panic payload".to_string()
+        };
+        let location = info
+            .location()
+            .map(|location| format!("{}:{}", location.file(), location.line()));
+
+        let report = build_report(message, location, &app_version);
+
+        match write_report(&crash_dir, &report) {
+            Ok(path) => log::error!("Panic: {} (report: {})", report.message, path.display()),
+            Err(err) => log::error!("Panic: {} (report not written: {})", report.message, err),
+        }
+
+        previous(info);
+    }));
+}
+
+fn build_report(message: String, location: Option<String>, app_version: &str) -> CrashReport {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default();
+
+    // The hook may run while another thread holds the lock mid-panic
+    let last_command = LAST_COMMAND.try_lock().ok().and_then(|last| last.clone());
+
+    let os = sysinfo::System::long_os_version().unwrap_or_else(|| std::env::consts::OS.into());
+
+    CrashReport {
+        id: format!("crash_{}", timestamp),
+        timestamp,
+        app_version: app_version.to_string(),
+        os,
+        arch: std::env::consts::ARCH.to_string(),
+        thread: std::thread::current()
+            .name()
+            .unwrap_or("unnamed")
+            .to_string(),
+        message,
+        location,
+        last_command,
+        backtrace: Backtrace::force_capture().to_string(),
+    }
+}
+
+fn write_report(crash_dir: &Path, report: &CrashReport) -> Result<PathBuf, DuneError> {
+    fs::create_dir_all(crash_dir).map_err(|e| DuneError::io(crash_dir, e))?;
+
+    let path = crash_dir.join(format!("{}.json", report.id));
+    let json = serde_json::to_string_pretty(report).map_err(DuneError::internal)?;
+    fs::write(&path, json).map_err(|e| DuneError::io(&path, e))?;
+
+    Ok(path)
+}
+
+/// All saved crash reports, newest first
+pub fn crash_reports(crash_dir: &Path) -> Result<Vec<CrashReport>, DuneError> {
+    let entries = match fs::read_dir(crash_dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(DuneError::io(crash_dir, err)),
+    };
+
+    let mut reports: Vec<CrashReport> = entries
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+        .filter_map(|content| serde_json::from_str(&content).ok())
+        .collect();
+
+    reports.sort_by_key(|report| std::cmp::Reverse(report.timestamp));
+
+    Ok(reports)
+}
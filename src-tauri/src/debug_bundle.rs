@@ -0,0 +1,432 @@
+use crate::error::DuneError;
+use crate::scan::{self, ScanTree};
+use crate::scanner;
+use crate::settings::Settings;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// What stands in for paths in settings, logs and crash reports
+const REDACTED_PATH: &str = "<path>";
+/// What log messages put right after a path, which ends it there; a name with one of
+/// these in it loses only the rest of its name
+const PATH_ENDS: &[&str] = &[" (", ": ", ", ", " failed", " -> ", "; "];
+
+/// Where to find what goes into a bundle
+pub struct Sources {
+    pub log_dir: PathBuf,
+    pub crash_dir: PathBuf,
+    pub app_version: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DebugBundle {
+    pub path: String,
+    pub bytes: u64,
+    /// Names inside the zip
+    pub entries: Vec<String>,
+}
+
+/// A scan node with nothing but its place in the tree and its sizes
+#[derive(Serialize)]
+struct AnonymousNode {
+    parent: Option<u32>,
+    depth: u32,
+    is_dir: bool,
+    size: u64,
+    files: u64,
+    complete: bool,
+}
+
+#[derive(Serialize)]
+struct AnonymousScan {
+    scan_id: u64,
+    combined: bool,
+    subvolumes: usize,
+    ignored: usize,
+    small_items: usize,
+    nodes: Vec<AnonymousNode>,
+}
+
+#[derive(Serialize)]
+struct AnonymousDisk {
+    total_space: u64,
+    available_space: u64,
+    purgeable_space: u64,
+    total_inodes: Option<u64>,
+    free_inodes: Option<u64>,
+    mtp: bool,
+    ram_disk: bool,
+}
+
+#[derive(Serialize)]
+struct Environment {
+    app_version: String,
+    os: String,
+    os_version: Option<String>,
+    kernel: Option<String>,
+    arch: &'static str,
+    cpus: usize,
+    memory_bytes: u64,
+    disks: Vec<AnonymousDisk>,
+    created_at: u64,
+}
+
+/// Only what a wrong total or a crash can be debugged from: no names, no paths
+fn anonymous_scan(tree: &ScanTree) -> AnonymousScan {
+    let mut depths = vec![0u32; tree.nodes.len()];
+    let nodes = tree
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| {
+            let depth = node.parent.map_or(0, |parent| depths[parent as usize] + 1);
+            depths[index] = depth;
+            AnonymousNode {
+                parent: node.parent,
+                depth,
+                is_dir: node.is_dir,
+                size: node.size,
+                files: node.file_count,
+                complete: node.size_complete,
+            }
+        })
+        .collect();
+
+    AnonymousScan {
+        scan_id: tree.scan_id,
+        combined: tree.is_combined(),
+        subvolumes: tree.subvolumes.len(),
+        ignored: tree.ignored.len(),
+        small_items: tree.small_items.len(),
+        nodes,
+    }
+}
+
+fn environment(app_version: &str) -> Environment {
+    let mut system = sysinfo::System::new();
+    system.refresh_memory();
+
+    Environment {
+        app_version: app_version.to_string(),
+        os: std::env::consts::OS.to_string(),
+        os_version: sysinfo::System::long_os_version(),
+        kernel: sysinfo::System::kernel_version(),
+        arch: std::env::consts::ARCH,
+        cpus: std::thread::available_parallelism().map_or(1, |cpus| cpus.get()),
+        memory_bytes: system.total_memory(),
+        disks: scanner::overview_disks()
+            .into_iter()
+            .map(|disk| AnonymousDisk {
+                total_space: disk.total_space,
+                available_space: disk.available_space,
+                purgeable_space: disk.purgeable_space,
+                total_inodes: disk.total_inodes,
+                free_inodes: disk.free_inodes,
+                mtp: disk.mtp,
+                ram_disk: disk.ram_disk,
+            })
+            .collect(),
+        created_at: scan::millis_since_epoch(SystemTime::now()).unwrap_or(0),
+    }
+}
+
+/// `ssh://user@host/…`, `s3://bucket/…`, `mtp://…`: remote and device locations
+fn has_scheme(text: &str) -> bool {
+    text.split_once("://").is_some_and(|(scheme, _)| {
+        scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+    })
+}
+
+fn is_drive_path(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && matches!(bytes[2], b'\\' | b'/')
+}
+
+fn looks_like_path(text: &str) -> bool {
+    text.starts_with(['/', '~', '\\']) || is_drive_path(text) || has_scheme(text)
+}
+
+/// Stricter than `looks_like_path`, as log text also has `~5 MB` and `1 / 3`
+fn path_starts(rest: &str) -> bool {
+    let mut chars = rest.chars();
+    match chars.next() {
+        Some('/') => chars.next().is_some_and(|next| !next.is_whitespace()),
+        Some('~') => matches!(chars.next(), Some('/' | '\\')),
+        Some('\\') => chars.next() == Some('\\'),
+        _ => is_drive_path(rest) || has_scheme(rest),
+    }
+}
+
+/// Where a path starting a `rest` of a log line ends: at a quote, the end of the line,
+/// one of `PATH_ENDS`, or ` to ` before another path
+fn path_len(rest: &str) -> usize {
+    let mut end = rest
+        .find(['"', '\'', '`', '\n', '\r', '\t'])
+        .unwrap_or(rest.len());
+    for pattern in PATH_ENDS {
+        end = rest[..end].find(pattern).unwrap_or(end);
+    }
+    let mut from = 0;
+    while let Some(at) = rest[from..end].find(" to ") {
+        let at = from + at;
+        if path_starts(&rest[at + " to ".len()..]) {
+            end = at;
+            break;
+        }
+        from = at + 1;
+    }
+    end
+}
+
+/// `text` with every absolute, home-relative, network and `scheme://` path replaced
+fn redact_paths(text: &str) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut after_boundary = true;
+
+    while let Some(c) = rest.chars().next() {
+        if after_boundary && path_starts(rest) {
+            redacted.push_str(REDACTED_PATH);
+            rest = &rest[path_len(rest)..];
+            after_boundary = false;
+            continue;
+        }
+        redacted.push(c);
+        rest = &rest[c.len_utf8()..];
+        after_boundary = c.is_whitespace() || "\"'`([{=,".contains(c);
+    }
+
+    redacted
+}
+
+/// Every path in `value`, as a value or as a key, replaced
+fn strip_paths(value: &mut Value) {
+    match value {
+        Value::String(text) if looks_like_path(text) => *text = REDACTED_PATH.into(),
+        Value::Array(items) => items.iter_mut().for_each(strip_paths),
+        Value::Object(map) => {
+            let entries = std::mem::take(map);
+            for (position, (key, mut item)) in entries.into_iter().enumerate() {
+                strip_paths(&mut item);
+                let key = if looks_like_path(&key) {
+                    format!("{} {}", REDACTED_PATH, position + 1)
+                } else {
+                    key
+                };
+                map.insert(key, item);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The settings without paths, the API token or webhook URLs, which often hold one
+fn anonymous_settings(settings: &Settings) -> Result<Value, DuneError> {
+    let mut settings = settings.clone();
+    settings.api.token.clear();
+    for webhook in &mut settings.webhooks {
+        webhook.url = "<url>".into();
+    }
+
+    let mut value = serde_json::to_value(&settings).map_err(DuneError::internal)?;
+    strip_paths(&mut value);
+    Ok(value)
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<Vec<u8>, DuneError> {
+    serde_json::to_vec_pretty(value).map_err(DuneError::internal)
+}
+
+/// Files directly in `dir` whose name passes `wanted`, by name; none when it is missing
+fn files_in(dir: &Path, wanted: impl Fn(&str) -> bool) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+        .filter(|entry| wanted(&entry.file_name().to_string_lossy()))
+        .map(|entry| entry.path())
+        .collect();
+    files.sort();
+    files
+}
+
+/// Just enough of the zip format for deflated files under 4 GiB
+struct ZipWriter {
+    out: Vec<u8>,
+    central: Vec<u8>,
+    names: Vec<String>,
+    /// MS-DOS time and date every entry gets
+    stamp: (u16, u16),
+}
+
+impl ZipWriter {
+    fn new(now_millis: u64) -> Self {
+        let seconds = now_millis / 1000;
+        let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+        let of_day = seconds % 86_400;
+        let (hours, minutes, seconds) = (of_day / 3600, of_day % 3600 / 60, of_day % 60);
+        // Two-second steps, and years from 1980
+        let time = ((hours << 11) | (minutes << 5) | (seconds / 2)) as u16;
+        let years = (year - 1980).clamp(0, 127) as u64;
+        let date = ((years << 9) | (u64::from(month) << 5) | u64::from(day)) as u16;
+
+        Self {
+            out: Vec::new(),
+            central: Vec::new(),
+            names: Vec::new(),
+            stamp: (time, date),
+        }
+    }
+
+    fn add(&mut self, name: &str, data: &[u8]) -> Result<(), DuneError> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).map_err(DuneError::internal)?;
+        let compressed = encoder.finish().map_err(DuneError::internal)?;
+
+        let too_large = |_| DuneError::Internal(format!("{} is too large for the bundle", name));
+        let size = u32::try_from(data.len()).map_err(too_large)?;
+        let compressed_size = u32::try_from(compressed.len()).map_err(too_large)?;
+        let offset = u32::try_from(self.out.len()).map_err(too_large)?;
+        let crc = crc32fast::hash(data);
+        let (time, date) = self.stamp;
+
+        // Version 2.0, names in UTF-8, deflate
+        let mut common = Vec::new();
+        for field in [20u16, 0x0800, 8, time, date] {
+            common.extend_from_slice(&field.to_le_bytes());
+        }
+        for field in [crc, compressed_size, size] {
+            common.extend_from_slice(&field.to_le_bytes());
+        }
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+
+        self.out.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        self.out.extend_from_slice(&common);
+        self.out.extend_from_slice(name.as_bytes());
+        self.out.extend_from_slice(&compressed);
+
+        self.central
+            .extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        self.central.extend_from_slice(&20u16.to_le_bytes());
+        self.central.extend_from_slice(&common);
+        // Comment length, disk, internal and external attributes
+        self.central.extend_from_slice(&[0; 10]);
+        self.central.extend_from_slice(&offset.to_le_bytes());
+        self.central.extend_from_slice(name.as_bytes());
+
+        self.names.push(name.to_string());
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(Vec<u8>, Vec<String>), DuneError> {
+        let too_large = |_| DuneError::Internal("The bundle is too large".into());
+        let offset = u32::try_from(self.out.len()).map_err(too_large)?;
+        let size = u32::try_from(self.central.len()).map_err(too_large)?;
+        let count = u16::try_from(self.names.len()).map_err(too_large)?;
+
+        self.out.extend_from_slice(&self.central);
+        self.out.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        self.out.extend_from_slice(&[0; 4]);
+        self.out.extend_from_slice(&count.to_le_bytes());
+        self.out.extend_from_slice(&count.to_le_bytes());
+        self.out.extend_from_slice(&size.to_le_bytes());
+        self.out.extend_from_slice(&offset.to_le_bytes());
+        self.out.extend_from_slice(&0u16.to_le_bytes());
+
+        Ok((self.out, self.names))
+    }
+}
+
+/// Year, month and day of a day count since 1970-01-01, the inverse of
+/// `query::days_from_civil`
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let of_era = days.rem_euclid(146_097);
+    let year_of_era = (of_era - of_era / 1460 + of_era / 36_524 - of_era / 146_096) / 365;
+    let of_year = of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * of_year + 2) / 153;
+    let day = (of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// Write a zip to `dest` with the logs and crash reports (every path replaced by `<path>`),
+/// the settings without paths or secrets, `tree` reduced to sizes and depths, and a
+/// description of the machine, for attaching to bug reports
+pub fn export(
+    dest: &Path,
+    sources: &Sources,
+    settings: &Settings,
+    tree: Option<&ScanTree>,
+) -> Result<DebugBundle, DuneError> {
+    let mut zip = ZipWriter::new(scan::millis_since_epoch(SystemTime::now()).unwrap_or(0));
+
+    let logs = files_in(&sources.log_dir, |name| name.ends_with(".log"));
+    let crashes = files_in(&sources.crash_dir, |name| name.ends_with(".json"));
+    for (folder, path) in logs
+        .iter()
+        .map(|path| ("logs", path))
+        .chain(crashes.iter().map(|path| ("crashes", path)))
+    {
+        let text = match fs::read(path) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(err) => {
+                log::warn!(
+                    "Leaving {} out of the debug bundle: {}",
+                    path.display(),
+                    err
+                );
+                continue;
+            }
+        };
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        zip.add(
+            &format!("{}/{}", folder, name),
+            redact_paths(&text).as_bytes(),
+        )?;
+    }
+
+    zip.add("settings.json", &to_json(&anonymous_settings(settings)?)?)?;
+    zip.add(
+        "environment.json",
+        &to_json(&environment(&sources.app_version))?,
+    )?;
+    if let Some(tree) = tree {
+        zip.add("scan.json", &to_json(&anonymous_scan(tree))?)?;
+    }
+
+    let (bytes, entries) = zip.finish()?;
+    fs::write(dest, &bytes).map_err(|e| DuneError::io(dest, e))?;
+    log::info!(
+        "Wrote a debug bundle of {} entries to {}",
+        entries.len(),
+        dest.display()
+    );
+
+    Ok(DebugBundle {
+        path: dest.to_string_lossy().into_owned(),
+        bytes: bytes.len() as u64,
+        entries,
+    })
+}
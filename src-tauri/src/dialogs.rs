@@ -0,0 +1,306 @@
+use crate::app_data;
+use crate::error::DuneError;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tauri::{AppHandle, Runtime};
+
+const FILE_NAME: &str = "dialogs.json";
+/// Purpose of pickers that do not name one
+const DEFAULT_PURPOSE: &str = "default";
+
+/// A file type offered by save pickers, e.g. `JSON` with `["json"]`
+#[derive(Debug, Clone, Deserialize)]
+pub struct FileFilter {
+    pub name: String,
+    /// Without the dot
+    pub extensions: Vec<String>,
+}
+
+pub fn store_path<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, DuneError> {
+    app_data::path(app, FILE_NAME)
+}
+
+/// Last folder by purpose; a missing or broken store just means no memory yet
+fn read(store: &Path) -> BTreeMap<String, String> {
+    fs::read(store)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .unwrap_or_default()
+}
+
+fn remember(store: &Path, purpose: &str, folder: &Path) {
+    let mut folders = read(store);
+    folders.insert(purpose.into(), folder.to_string_lossy().into_owned());
+
+    let written = store
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(store, serde_json::to_vec_pretty(&folders)?));
+    if let Err(err) = written {
+        log::warn!("Could not remember the {} folder: {}", purpose, err);
+    }
+}
+
+/// Where a picker for `purpose` opens: where the last one ended, if that still exists
+fn start_folder(store: &Path, purpose: &str, fallback: Option<PathBuf>) -> PathBuf {
+    read(store)
+        .remove(purpose)
+        .map(PathBuf::from)
+        .filter(|folder| folder.is_dir())
+        .or(fallback)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Run the first picker that exists; `None` when the user cancelled
+fn run(commands: Vec<Command>) -> Result<Option<PathBuf>, DuneError> {
+    let mut tried = Vec::new();
+    for mut command in commands {
+        let program = command.get_program().to_string_lossy().into_owned();
+        let output = match command.output() {
+            Ok(output) => output,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                tried.push(program);
+                continue;
+            }
+            Err(err) => return Err(DuneError::internal(err)),
+        };
+
+        let picked = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        return match output.status.code() {
+            Some(0) if !picked.is_empty() => Ok(Some(PathBuf::from(picked))),
+            // Every picker exits with 1, or prints nothing, when cancelled
+            Some(0) | Some(1) => {
+                log::debug!(
+                    "{} picker closed: {}",
+                    program,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+                Ok(None)
+            }
+            _ => Err(DuneError::Internal(format!(
+                "{} failed: {}",
+                program,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))),
+        };
+    }
+
+    Err(DuneError::Unsupported(format!(
+        "No file picker is installed (tried {})",
+        tried.join(", ")
+    )))
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::FileFilter;
+    use std::path::Path;
+    use std::process::Command;
+
+    /// AppleScript with its arguments passed through, so nothing needs quoting
+    fn osascript(lines: &[&str], args: &[&str]) -> Command {
+        let mut command = Command::new("osascript");
+        for line in lines {
+            command.args(["-e", line]);
+        }
+        command.args(args);
+        command
+    }
+
+    pub fn folder(start: &Path) -> Vec<Command> {
+        vec![osascript(
+            &[
+                "on run argv",
+                "POSIX path of (choose folder default location (POSIX file (item 1 of argv)))",
+                "end run",
+            ],
+            &[&start.to_string_lossy()],
+        )]
+    }
+
+    /// `choose file name` has no type list, so the first type goes in the prompt and
+    /// `super::pick_save_path` adds its extension
+    pub fn save(start: &Path, default_name: &str, filters: &[FileFilter]) -> Vec<Command> {
+        let prompt = filters.first().map_or_else(
+            || "Save as".to_string(),
+            |filter| format!("Save as {}", filter.name),
+        );
+        vec![osascript(
+            &[
+                "on run argv",
+                "POSIX path of (choose file name with prompt (item 3 of argv) default name (item 2 of argv) default location (POSIX file (item 1 of argv)))",
+                "end run",
+            ],
+            &[&start.to_string_lossy(), default_name, &prompt],
+        )]
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::FileFilter;
+    use crate::console;
+    use std::path::Path;
+    use std::process::Command;
+
+    const FOLDER_SCRIPT: &str = "Add-Type -AssemblyName System.Windows.Forms; \
+        [Console]::OutputEncoding = [Text.Encoding]::UTF8; \
+        $dialog = New-Object System.Windows.Forms.FolderBrowserDialog; \
+        $dialog.SelectedPath = $env:DUNE_DIALOG_START; \
+        if ($dialog.ShowDialog() -eq 'OK') { $dialog.SelectedPath }";
+
+    const SAVE_SCRIPT: &str = "Add-Type -AssemblyName System.Windows.Forms; \
+        [Console]::OutputEncoding = [Text.Encoding]::UTF8; \
+        $dialog = New-Object System.Windows.Forms.SaveFileDialog; \
+        $dialog.InitialDirectory = $env:DUNE_DIALOG_START; \
+        $dialog.FileName = $env:DUNE_DIALOG_NAME; \
+        $dialog.Filter = $env:DUNE_DIALOG_FILTER; \
+        if ($dialog.ShowDialog() -eq 'OK') { $dialog.FileName }";
+
+    /// Arguments go through the environment, so nothing needs quoting
+    fn powershell(script: &str, start: &Path) -> Command {
+        let mut command = console::command("powershell");
+        command
+            .args(["-NoProfile", "-NonInteractive", "-STA", "-Command", script])
+            .env("DUNE_DIALOG_START", start);
+        command
+    }
+
+    pub fn folder(start: &Path) -> Vec<Command> {
+        vec![powershell(FOLDER_SCRIPT, start)]
+    }
+
+    pub fn save(start: &Path, default_name: &str, filters: &[FileFilter]) -> Vec<Command> {
+        // `JSON (*.json)|*.json|All files (*.*)|*.*`
+        let filter = filters
+            .iter()
+            .map(|filter| {
+                let patterns: Vec<String> = filter
+                    .extensions
+                    .iter()
+                    .map(|extension| format!("*.{}", extension))
+                    .collect();
+                format!(
+                    "{} ({})|{}",
+                    filter.name,
+                    patterns.join(", "),
+                    patterns.join(";")
+                )
+            })
+            .chain(std::iter::once("All files (*.*)|*.*".to_string()))
+            .collect::<Vec<_>>()
+            .join("|");
+
+        let mut command = powershell(SAVE_SCRIPT, start);
+        command
+            .env("DUNE_DIALOG_NAME", default_name)
+            .env("DUNE_DIALOG_FILTER", filter);
+        vec![command]
+    }
+}
+
+/// zenity on GNOME and most other desktops, kdialog on KDE
+#[cfg(not(any(target_os = "macos", windows)))]
+mod platform {
+    use super::FileFilter;
+    use std::path::Path;
+    use std::process::Command;
+
+    pub fn folder(start: &Path) -> Vec<Command> {
+        // A trailing slash makes zenity open inside the folder
+        let mut zenity = Command::new("zenity");
+        zenity
+            .args(["--file-selection", "--directory", "--filename"])
+            .arg(start.join(""));
+
+        let mut kdialog = Command::new("kdialog");
+        kdialog.arg("--getexistingdirectory").arg(start);
+
+        vec![zenity, kdialog]
+    }
+
+    pub fn save(start: &Path, default_name: &str, filters: &[FileFilter]) -> Vec<Command> {
+        let suggested = start.join(default_name);
+        let patterns = |filter: &FileFilter| {
+            filter
+                .extensions
+                .iter()
+                .map(|extension| format!("*.{}", extension))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        let mut zenity = Command::new("zenity");
+        zenity
+            .args([
+                "--file-selection",
+                "--save",
+                "--confirm-overwrite",
+                "--filename",
+            ])
+            .arg(&suggested);
+        for filter in filters {
+            zenity.arg(format!(
+                "--file-filter={} | {}",
+                filter.name,
+                patterns(filter)
+            ));
+        }
+
+        // `*.json *.txt|JSON` lines
+        let kdialog_filter = filters
+            .iter()
+            .map(|filter| format!("{}|{}", patterns(filter), filter.name))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut kdialog = Command::new("kdialog");
+        kdialog
+            .arg("--getsavefilename")
+            .arg(&suggested)
+            .arg(kdialog_filter);
+
+        vec![zenity, kdialog]
+    }
+}
+
+/// Ask for a folder, starting where the last picker for `purpose` ended
+pub fn pick_folder(store: &Path, purpose: Option<&str>) -> Result<Option<String>, DuneError> {
+    let purpose = purpose.unwrap_or(DEFAULT_PURPOSE);
+    let start = start_folder(store, purpose, dirs::home_dir());
+
+    let Some(folder) = run(platform::folder(&start))? else {
+        return Ok(None);
+    };
+    remember(store, purpose, &folder);
+
+    Ok(Some(folder.to_string_lossy().into_owned()))
+}
+
+/// Ask where to save `default_name`, adding the first filter's extension when the
+/// user typed none
+pub fn pick_save_path(
+    store: &Path,
+    default_name: &str,
+    filters: &[FileFilter],
+    purpose: Option<&str>,
+) -> Result<Option<String>, DuneError> {
+    let purpose = purpose.unwrap_or(DEFAULT_PURPOSE);
+    let start = start_folder(store, purpose, dirs::document_dir().or_else(dirs::home_dir));
+
+    let Some(mut path) = run(platform::save(&start, default_name, filters))? else {
+        return Ok(None);
+    };
+    if path.extension().is_none() {
+        if let Some(extension) = filters.first().and_then(|filter| filter.extensions.first()) {
+            path.set_extension(extension);
+        }
+    }
+    if let Some(folder) = path.parent() {
+        remember(store, purpose, folder);
+    }
+
+    Ok(Some(path.to_string_lossy().into_owned()))
+}
@@ -0,0 +1,163 @@
+use crate::anonymize::Anonymizer;
+use crate::colors::{self, ColorBy};
+use crate::error::DuneError;
+use crate::ring::{ChildIndex, RingStyle};
+use crate::scan::ScanTree;
+use serde::Serialize;
+use std::path::Path;
+
+/// Grid of `2^order` cells per side; 7 gives 128 by 128
+pub const DEFAULT_ORDER: u32 = 7;
+const MIN_ORDER: u32 = 2;
+/// 1024 by 1024, about a million cells
+const MAX_ORDER: u32 = 10;
+/// Cells no file reaches
+const EMPTY: u32 = u32::MAX;
+
+/// Files laid end to end along a Hilbert curve, folder by folder, so each folder fills one
+/// compact region and many small files show up as busy cells
+#[derive(Debug, Clone, Serialize)]
+pub struct DiskMap {
+    pub scan_id: u64,
+    pub focus: u32,
+    pub path: String,
+    /// Cells per side, a power of two
+    pub side: u32,
+    pub cell_bytes: f64,
+    /// Per cell, row by row from the top left: the file taking most of it, `u32::MAX` if none
+    pub nodes: Vec<u32>,
+    /// Files with bytes in each cell
+    pub file_counts: Vec<u32>,
+    /// Index into `palette` for each cell's file, `colors::OTHER` for empty cells
+    pub colors: Vec<u8>,
+    pub color_by: ColorBy,
+    /// `0xRRGGBB` colors of the theme, see `colors::palette`
+    pub palette: Vec<u32>,
+}
+
+/// Grid position of step `distance` along the Hilbert curve through a `side` by `side` grid
+fn hilbert_point(side: u32, distance: u64) -> (u32, u32) {
+    let (mut x, mut y) = (0u32, 0u32);
+    let mut rest = distance;
+    let mut scale = 1;
+    while scale < side {
+        let rx = (1 & (rest / 2)) as u32;
+        let ry = (1 & (rest ^ u64::from(rx))) as u32;
+        if ry == 0 {
+            if rx == 1 {
+                x = scale - 1 - x;
+                y = scale - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x += scale * rx;
+        y += scale * ry;
+        rest /= 4;
+        scale *= 2;
+    }
+
+    (x, y)
+}
+
+/// Cell of every step along the curve, in grid order
+fn curve(side: u32) -> Vec<u32> {
+    (0..u64::from(side) * u64::from(side))
+        .map(|distance| {
+            let (x, y) = hilbert_point(side, distance);
+            y * side + x
+        })
+        .collect()
+}
+
+/// The block map of `focus_path`, every file placed in the order of the ring: largest
+/// child first, depth first
+pub fn map(
+    tree: &ScanTree,
+    index: &ChildIndex,
+    focus_path: &str,
+    order: u32,
+    style: &RingStyle,
+) -> Result<DiskMap, DuneError> {
+    let focus = index
+        .find(tree, Path::new(focus_path))
+        .ok_or_else(|| DuneError::NotFound {
+            path: focus_path.into(),
+        })?;
+    let side = 1u32 << order.clamp(MIN_ORDER, MAX_ORDER);
+    let cells = u64::from(side) * u64::from(side);
+    let total = tree.nodes[focus as usize].size.max(1);
+    let cell_bytes = total as f64 / cells as f64;
+
+    let steps = curve(side);
+    let mut nodes = vec![EMPTY; cells as usize];
+    let mut file_counts = vec![0u32; cells as usize];
+    let mut colors = vec![colors::OTHER; cells as usize];
+    // Bytes the current owner of each step has in it
+    let mut owned = vec![0u64; cells as usize];
+
+    let focus_depth = colors::root_depth(tree, focus);
+    let mut offset = 0u64;
+    let mut stack = vec![(focus, 0u32)];
+    while let Some((node, depth)) = stack.pop() {
+        let entry = &tree.nodes[node as usize];
+        if entry.is_dir {
+            // Reversed so the largest child comes off the stack first
+            stack.extend(index.of(node).iter().rev().map(|&child| (child, depth + 1)));
+            continue;
+        }
+        if entry.size == 0 {
+            continue;
+        }
+
+        let start = offset;
+        let end = offset.saturating_add(entry.size).min(total);
+        offset = end;
+        if end <= start {
+            continue;
+        }
+
+        let step_of =
+            |bytes: u64| (u128::from(bytes) * u128::from(cells) / u128::from(total)) as u64;
+        let step_start =
+            |step: u64| (u128::from(step) * u128::from(total) / u128::from(cells)) as u64;
+        // Only looked up for files that own a cell, since coloring by owner reads the file
+        let mut color = None;
+        for step in step_of(start)..=step_of(end - 1).min(cells - 1) {
+            let overlap = end.min(step_start(step + 1)) - start.max(step_start(step));
+            let cell = steps[step as usize] as usize;
+            file_counts[cell] += 1;
+            if overlap > owned[step as usize] {
+                owned[step as usize] = overlap;
+                nodes[cell] = node;
+                colors[cell] = *color.get_or_insert_with(|| {
+                    colors::color_of(
+                        tree,
+                        index.newest(),
+                        node,
+                        focus_depth + depth,
+                        style.color_by,
+                    )
+                });
+            }
+        }
+    }
+
+    let focus_node = &tree.nodes[focus as usize];
+    let path = match style.redact {
+        Some(mode) => Anonymizer::new(mode).name(&focus_node.name, focus_node.is_dir),
+        None => tree.path(focus).to_string_lossy().into_owned(),
+    };
+
+    Ok(DiskMap {
+        scan_id: tree.scan_id,
+        focus,
+        path,
+        side,
+        cell_bytes,
+        nodes,
+        file_counts,
+        colors,
+        color_by: style.color_by,
+        palette: colors::palette(style.theme).to_vec(),
+    })
+}
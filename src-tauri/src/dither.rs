@@ -0,0 +1,223 @@
+use crate::colors;
+use crate::error::DuneError;
+use crate::settings::{DitherPattern, Settings};
+use base64::Engine;
+use image::{ImageFormat, Rgb, RgbImage};
+use serde::Serialize;
+use std::io::Cursor;
+
+/// Spread of the blue-noise energy filter, in pixels
+const NOISE_SIGMA: f64 = 1.5;
+/// Share of pixels set in the starting pattern of void-and-cluster
+const NOISE_SEED_SHARE: usize = 10;
+
+/// Textures for the dither shader, as base64 PNGs ready for `data:image/png;base64,`
+#[derive(Debug, Clone, Serialize)]
+pub struct DitherAssets {
+    pub pattern: DitherPattern,
+    /// Threshold of each pixel in the red channel, `(rank + 0.5) / size²` scaled to 0-255
+    pub bayer_png: String,
+    pub bayer_size: u32,
+    pub blue_noise_png: String,
+    pub noise_size: u32,
+    /// `levels` by 1: the theme's colors for each step after quantizing, darkest first
+    pub palette_png: String,
+    pub levels: u32,
+    pub pixel_size: f32,
+    pub dither_strength: f32,
+}
+
+fn encode_png(image: &RgbImage) -> Result<String, DuneError> {
+    let mut bytes = Cursor::new(Vec::new());
+    image
+        .write_to(&mut bytes, ImageFormat::Png)
+        .map_err(DuneError::internal)?;
+
+    Ok(base64::engine::general_purpose::STANDARD.encode(bytes.into_inner()))
+}
+
+/// Ranks as a gray threshold texture
+fn threshold_image(size: u32, ranks: &[u32]) -> RgbImage {
+    let count = ranks.len() as f64;
+    RgbImage::from_fn(size, size, |x, y| {
+        let rank = ranks[(y * size + x) as usize];
+        let value = ((f64::from(rank) + 0.5) / count * 256.0) as u8;
+        Rgb([value, value, value])
+    })
+}
+
+/// Ranks of the recursive Bayer matrix of side `size`
+fn bayer(size: u32) -> Vec<u32> {
+    let mut matrix = vec![0u32];
+    let mut side = 1;
+    while side < size {
+        let mut next = vec![0u32; (side * side * 4) as usize];
+        for y in 0..side {
+            for x in 0..side {
+                let value = 4 * matrix[(y * side + x) as usize];
+                let doubled = side * 2;
+                next[(y * doubled + x) as usize] = value;
+                next[(y * doubled + x + side) as usize] = value + 2;
+                next[((y + side) * doubled + x) as usize] = value + 3;
+                next[((y + side) * doubled + x + side) as usize] = value + 1;
+            }
+        }
+        matrix = next;
+        side *= 2;
+    }
+
+    matrix
+}
+
+/// Blue noise by void-and-cluster, with a fixed seed so every run gives the same tile
+struct VoidAndCluster {
+    size: usize,
+    /// Gaussian weight by wrapped offset, `kernel[dy * size + dx]`
+    kernel: Vec<f64>,
+    energy: Vec<f64>,
+    set: Vec<bool>,
+}
+
+impl VoidAndCluster {
+    fn new(size: usize) -> Self {
+        let kernel = (0..size * size)
+            .map(|offset| {
+                let wrap = |delta: usize| delta.min(size - delta) as f64;
+                let (dx, dy) = (wrap(offset % size), wrap(offset / size));
+                (-(dx * dx + dy * dy) / (2.0 * NOISE_SIGMA * NOISE_SIGMA)).exp()
+            })
+            .collect();
+
+        Self {
+            size,
+            kernel,
+            energy: vec![0.0; size * size],
+            set: vec![false; size * size],
+        }
+    }
+
+    fn toggle(&mut self, point: usize) {
+        let sign = if self.set[point] { -1.0 } else { 1.0 };
+        self.set[point] = !self.set[point];
+        let (size, px, py) = (self.size, point % self.size, point / self.size);
+        for (y, row) in self.energy.chunks_mut(size).enumerate() {
+            let dy = (y + size - py) % size;
+            let kernel = &self.kernel[dy * size..(dy + 1) * size];
+            // Columns from `px` on are `x - px` away, the ones before wrap around
+            let (before, after) = row.split_at_mut(px);
+            for (energy, weight) in after.iter_mut().zip(kernel) {
+                *energy += sign * weight;
+            }
+            for (energy, weight) in before.iter_mut().zip(&kernel[size - px..]) {
+                *energy += sign * weight;
+            }
+        }
+    }
+
+    /// Set pixel with the most set neighbors
+    fn tightest_cluster(&self) -> usize {
+        (0..self.set.len())
+            .filter(|&index| self.set[index])
+            .max_by(|&a, &b| self.energy[a].total_cmp(&self.energy[b]))
+            .unwrap_or(0)
+    }
+
+    /// Unset pixel furthest from every set one
+    fn largest_void(&self) -> usize {
+        (0..self.set.len())
+            .filter(|&index| !self.set[index])
+            .min_by(|&a, &b| self.energy[a].total_cmp(&self.energy[b]))
+            .unwrap_or(0)
+    }
+
+    fn ranks(mut self) -> Vec<u32> {
+        let count = self.size * self.size;
+        let seeds = (count / NOISE_SEED_SHARE).max(1);
+
+        // Any fixed scatter works; void-and-cluster evens it out below
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        while self.set.iter().filter(|&&set| set).count() < seeds {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let point = (state % count as u64) as usize;
+            if !self.set[point] {
+                self.toggle(point);
+            }
+        }
+        loop {
+            let cluster = self.tightest_cluster();
+            self.toggle(cluster);
+            let void = self.largest_void();
+            self.toggle(void);
+            if void == cluster {
+                break;
+            }
+        }
+
+        let mut ranks = vec![0u32; count];
+        let initial = self.set.clone();
+        let initial_energy = self.energy.clone();
+
+        // Take the seed pixels away tightest first, then fill voids until the tile is full
+        for rank in (0..seeds).rev() {
+            let cluster = self.tightest_cluster();
+            self.toggle(cluster);
+            ranks[cluster] = rank as u32;
+        }
+        self.set = initial;
+        self.energy = initial_energy;
+        for rank in seeds..count {
+            let void = self.largest_void();
+            self.toggle(void);
+            ranks[void] = rank as u32;
+        }
+
+        ranks
+    }
+}
+
+fn channels(color: u32) -> [f64; 3] {
+    [color >> 16, color >> 8, color].map(|channel| f64::from(channel & 0xff))
+}
+
+/// The theme's ramp sampled at `levels` evenly spaced steps
+fn palette_image(settings: &Settings, levels: u32) -> RgbImage {
+    let stops = colors::ramp(settings.theme).map(channels);
+    RgbImage::from_fn(levels, 1, |x, _| {
+        let position = if levels > 1 {
+            f64::from(x) / f64::from(levels - 1) * (stops.len() - 1) as f64
+        } else {
+            0.0
+        };
+        let stop = (position as usize).min(stops.len() - 2);
+        let t = position - stop as f64;
+        let mix = |channel: usize| {
+            (stops[stop][channel] * (1.0 - t) + stops[stop + 1][channel] * t).round() as u8
+        };
+        Rgb([mix(0), mix(1), mix(2)])
+    })
+}
+
+/// Threshold textures and the quantized palette for `settings.dither` and `settings.theme`
+pub fn generate(settings: &Settings) -> Result<DitherAssets, DuneError> {
+    let dither = &settings.dither;
+    let bayer_size = dither.bayer_size.clamp(2, 16).next_power_of_two();
+    let noise_size = dither.noise_size.clamp(16, 128);
+    let levels = dither.levels.clamp(2, 256);
+
+    Ok(DitherAssets {
+        pattern: dither.pattern,
+        bayer_png: encode_png(&threshold_image(bayer_size, &bayer(bayer_size)))?,
+        bayer_size,
+        blue_noise_png: encode_png(&threshold_image(
+            noise_size,
+            &VoidAndCluster::new(noise_size as usize).ranks(),
+        ))?,
+        noise_size,
+        palette_png: encode_png(&palette_image(settings, levels))?,
+        levels,
+        pixel_size: settings.pixel_size,
+        dither_strength: settings.dither_strength,
+    })
+}
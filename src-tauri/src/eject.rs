@@ -0,0 +1,506 @@
+use crate::error::DuneError;
+use crate::scanner;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// Open files listed before the rest are left out
+const MAX_OPEN_FILES: usize = 100;
+/// Processes named in an error message before the rest are counted
+const MAX_NAMED: usize = 5;
+
+/// A process holding a file or folder open on the volume
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OpenFile {
+    pub pid: u32,
+    pub process: String,
+    /// The open file, or the process's working folder
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EjectOutcome {
+    pub disk: String,
+    pub ejected: bool,
+    /// What kept the volume busy; Windows only says that something did
+    pub open_files: Vec<OpenFile>,
+    /// Why ejecting failed, as the OS put it
+    pub message: Option<String>,
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::*;
+    use std::process::Command;
+
+    /// Open descriptors and working folders of every process the app can inspect;
+    /// other users' processes need root and are skipped
+    pub fn open_files(disk: &Path) -> Vec<OpenFile> {
+        let Ok(processes) = fs::read_dir("/proc") else {
+            return Vec::new();
+        };
+
+        let mut open = Vec::new();
+        for process in processes.flatten() {
+            let Some(pid) = process
+                .file_name()
+                .to_str()
+                .and_then(|pid| pid.parse().ok())
+            else {
+                continue;
+            };
+            let proc = process.path();
+            let name = fs::read_to_string(proc.join("comm"))
+                .map(|name| name.trim_end().to_string())
+                .unwrap_or_default();
+            let descriptors = fs::read_dir(proc.join("fd"))
+                .map(|fds| fds.flatten().map(|fd| fd.path()).collect())
+                .unwrap_or_else(|_| Vec::new());
+
+            for link in descriptors.into_iter().chain([proc.join("cwd")]) {
+                let Ok(target) = fs::read_link(&link) else {
+                    continue;
+                };
+                let entry = OpenFile {
+                    pid,
+                    process: name.clone(),
+                    path: target.to_string_lossy().into_owned(),
+                };
+                if target.starts_with(disk) && !open.contains(&entry) {
+                    open.push(entry);
+                }
+            }
+        }
+
+        open
+    }
+
+    /// The same walk, since descriptors of any path can be matched, not just a volume's
+    pub fn holders(path: &Path) -> Vec<OpenFile> {
+        open_files(path)
+    }
+
+    fn udisksctl(args: &[&str]) -> Result<(), String> {
+        let output = Command::new("udisksctl")
+            .args(args)
+            .arg("--no-user-interaction")
+            .output()
+            .map_err(|e| format!("Ejecting needs udisksctl from udisks2: {}", e))?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+        }
+    }
+
+    /// Unmount through udisks, then power the drive off so it can be unplugged; drives
+    /// that cannot be powered off are still safe to remove once unmounted
+    pub fn eject(disk: &Path) -> Result<(), String> {
+        let mount = disk.to_string_lossy();
+        let device = crate::smart::mounted_device(&mount)
+            .ok_or_else(|| format!("No device is mounted at {}", mount))?;
+        udisksctl(&["unmount", "--block-device", &device])?;
+
+        let drive = crate::smart::whole_drive(&device);
+        if let Err(err) = udisksctl(&["power-off", "--block-device", &drive]) {
+            log::debug!(
+                "Unmounted {} but could not power off {}: {}",
+                mount,
+                drive,
+                err
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::*;
+    use std::process::Command;
+
+    /// `lsof` given a mount point lists everything open on that filesystem
+    pub fn open_files(disk: &Path) -> Vec<OpenFile> {
+        lsof(&[disk.as_os_str()])
+    }
+
+    /// A file itself, or with `+D` everything open inside a folder
+    pub fn holders(path: &Path) -> Vec<OpenFile> {
+        if path.is_dir() {
+            lsof(&["+D".as_ref(), path.as_os_str()])
+        } else {
+            lsof(&[path.as_os_str()])
+        }
+    }
+
+    fn lsof(args: &[&std::ffi::OsStr]) -> Vec<OpenFile> {
+        let output = match Command::new("lsof").args(["-F", "pcn"]).args(args).output() {
+            Ok(output) => output,
+            Err(err) => {
+                log::debug!("Could not run lsof: {}", err);
+                return Vec::new();
+            }
+        };
+
+        let mut open = Vec::new();
+        let (mut pid, mut process) = (0, String::new());
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let (field, value) = line.split_at(line.len().min(1));
+            match field {
+                "p" => pid = value.parse().unwrap_or(0),
+                "c" => process = value.to_string(),
+                "n" => {
+                    let entry = OpenFile {
+                        pid,
+                        process: process.clone(),
+                        path: value.to_string(),
+                    };
+                    if !open.contains(&entry) {
+                        open.push(entry);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        open
+    }
+
+    pub fn eject(disk: &Path) -> Result<(), String> {
+        let output = Command::new("diskutil")
+            .arg("eject")
+            .arg(disk)
+            .output()
+            .map_err(|e| format!("Could not run diskutil: {}", e))?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::*;
+    use std::ffi::OsStr;
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr;
+    use windows_sys::core::PCWSTR;
+    use windows_sys::Win32::Foundation::{
+        CloseHandle, GENERIC_READ, GENERIC_WRITE, HANDLE, INVALID_HANDLE_VALUE,
+    };
+    use windows_sys::Win32::Storage::FileSystem::{
+        CreateFileW, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING,
+    };
+    use windows_sys::Win32::System::Ioctl::{
+        FSCTL_DISMOUNT_VOLUME, FSCTL_LOCK_VOLUME, IOCTL_STORAGE_EJECT_MEDIA,
+        IOCTL_STORAGE_MEDIA_REMOVAL, PREVENT_MEDIA_REMOVAL,
+    };
+    use windows_sys::Win32::System::RestartManager::{
+        RmEndSession, RmGetList, RmRegisterResources, RmStartSession, CCH_RM_SESSION_KEY,
+        RM_PROCESS_INFO,
+    };
+    use windows_sys::Win32::System::IO::DeviceIoControl;
+
+    /// Windows has no public way to list which process holds a volume open
+    pub fn open_files(_disk: &Path) -> Vec<OpenFile> {
+        Vec::new()
+    }
+
+    /// Files of a folder handed to the Restart Manager, which only takes files
+    const MAX_REGISTERED: usize = 1_000;
+    const ERROR_MORE_DATA: u32 = 234;
+
+    fn wide(path: &Path) -> Vec<u16> {
+        path.as_os_str().encode_wide().chain([0]).collect()
+    }
+
+    /// Asked again while more processes show up between calls than there was room for
+    fn processes(session: u32) -> Vec<RM_PROCESS_INFO> {
+        let mut infos: Vec<RM_PROCESS_INFO> = Vec::new();
+        for _ in 0..3 {
+            let (mut needed, mut count, mut reasons) = (0u32, infos.len() as u32, 0u32);
+            let status = unsafe {
+                RmGetList(
+                    session,
+                    &mut needed,
+                    &mut count,
+                    infos.as_mut_ptr(),
+                    &mut reasons,
+                )
+            };
+            match status {
+                0 => {
+                    infos.truncate(count as usize);
+                    return infos;
+                }
+                ERROR_MORE_DATA => infos.resize(needed as usize, unsafe { std::mem::zeroed() }),
+                _ => break,
+            }
+        }
+
+        Vec::new()
+    }
+
+    /// What installers use to find who holds the files they replace; it names the
+    /// processes but not which of a folder's files each one has open
+    pub fn holders(path: &Path) -> Vec<OpenFile> {
+        let mut files = Vec::new();
+        let mut pending = vec![path.to_path_buf()];
+        while let Some(next) = pending.pop() {
+            if files.len() >= MAX_REGISTERED {
+                break;
+            }
+            match std::fs::symlink_metadata(&next) {
+                Ok(metadata) if metadata.is_dir() => {
+                    if let Ok(entries) = std::fs::read_dir(&next) {
+                        pending.extend(entries.flatten().map(|entry| entry.path()));
+                    }
+                }
+                Ok(_) => files.push(wide(&next)),
+                Err(_) => {}
+            }
+        }
+
+        let mut session = 0u32;
+        let mut key = [0u16; CCH_RM_SESSION_KEY as usize + 1];
+        if unsafe { RmStartSession(&mut session, 0, key.as_mut_ptr()) } != 0 {
+            return Vec::new();
+        }
+        let names: Vec<PCWSTR> = files.iter().map(|file| file.as_ptr()).collect();
+        let registered = unsafe {
+            RmRegisterResources(
+                session,
+                names.len() as u32,
+                names.as_ptr(),
+                0,
+                ptr::null(),
+                0,
+                ptr::null(),
+            )
+        };
+        let infos = if registered == 0 {
+            processes(session)
+        } else {
+            Vec::new()
+        };
+        unsafe { RmEndSession(session) };
+
+        infos
+            .iter()
+            .map(|info| {
+                let name = &info.strAppName;
+                let end = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+                OpenFile {
+                    pid: info.Process.dwProcessId,
+                    process: String::from_utf16_lossy(&name[..end]),
+                    path: path.to_string_lossy().into_owned(),
+                }
+            })
+            .collect()
+    }
+
+    struct Volume(HANDLE);
+
+    impl Drop for Volume {
+        fn drop(&mut self) {
+            unsafe { CloseHandle(self.0) };
+        }
+    }
+
+    impl Volume {
+        fn control(&self, code: u32, input: Option<&PREVENT_MEDIA_REMOVAL>) -> io::Result<()> {
+            let mut returned = 0u32;
+            let (buffer, size) = match input {
+                Some(input) => (
+                    ptr::from_ref(input).cast(),
+                    std::mem::size_of::<PREVENT_MEDIA_REMOVAL>() as u32,
+                ),
+                None => (ptr::null(), 0),
+            };
+            let ok = unsafe {
+                DeviceIoControl(
+                    self.0,
+                    code,
+                    buffer,
+                    size,
+                    ptr::null_mut(),
+                    0,
+                    &mut returned,
+                    ptr::null_mut(),
+                )
+            };
+            if ok == 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    /// What Explorer's "Eject" does: lock the volume, which fails while anything has a
+    /// file open on it, dismount it, allow removal and eject the media
+    pub fn eject(disk: &Path) -> Result<(), String> {
+        let letter = disk
+            .to_string_lossy()
+            .chars()
+            .next()
+            .filter(char::is_ascii_alphabetic)
+            .ok_or_else(|| format!("{} is not a drive", disk.display()))?;
+        let device: Vec<u16> = OsStr::new(&format!(r"\\.\{}:", letter))
+            .encode_wide()
+            .chain([0])
+            .collect();
+        let handle = unsafe {
+            CreateFileW(
+                device.as_ptr(),
+                GENERIC_READ | GENERIC_WRITE,
+                FILE_SHARE_READ | FILE_SHARE_WRITE,
+                ptr::null(),
+                OPEN_EXISTING,
+                0,
+                ptr::null_mut(),
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        let volume = Volume(handle);
+
+        volume
+            .control(FSCTL_LOCK_VOLUME, None)
+            .map_err(|e| format!("Files on {} are still open: {}", disk.display(), e))?;
+        volume
+            .control(FSCTL_DISMOUNT_VOLUME, None)
+            .map_err(|e| e.to_string())?;
+        let allow = PREVENT_MEDIA_REMOVAL {
+            PreventMediaRemoval: 0,
+        };
+        volume
+            .control(IOCTL_STORAGE_MEDIA_REMOVAL, Some(&allow))
+            .map_err(|e| e.to_string())?;
+        volume
+            .control(IOCTL_STORAGE_EJECT_MEDIA, None)
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+mod platform {
+    use super::*;
+
+    pub fn open_files(_disk: &Path) -> Vec<OpenFile> {
+        Vec::new()
+    }
+
+    pub fn holders(_path: &Path) -> Vec<OpenFile> {
+        Vec::new()
+    }
+
+    pub fn eject(_disk: &Path) -> Result<(), String> {
+        Err("Ejecting is not available on this platform".into())
+    }
+}
+
+/// Whether the volume mounted at `mount` holds the system or the home folder
+pub fn is_system_volume(mount: &str) -> bool {
+    // `SystemDrive` is `C:`, without the separator mount points end in
+    std::env::var("SystemDrive").is_ok_and(|drive| {
+        mount
+            .get(..drive.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(&drive))
+    }) || Path::new(mount) == Path::new("/")
+        || dirs::home_dir().is_some_and(|home| home.starts_with(mount))
+}
+
+/// The mounted volume at `disk`, refusing the one the system or home folder is on
+fn removable_volume(disk: &str) -> Result<String, DuneError> {
+    let volume = scanner::get_disks()
+        .into_iter()
+        .find(|candidate| Path::new(&candidate.path) == Path::new(disk))
+        .ok_or_else(|| DuneError::NotFound {
+            path: disk.to_string(),
+        })?;
+
+    if is_system_volume(&volume.path) {
+        return Err(DuneError::Protected { path: volume.path });
+    }
+
+    Ok(volume.path)
+}
+
+/// Processes with files open on the volume at `disk`, which would keep it from ejecting
+pub fn open_files(disk: &str) -> Vec<OpenFile> {
+    let mut open = platform::open_files(Path::new(disk));
+    open.truncate(MAX_OPEN_FILES);
+    open
+}
+
+/// Processes holding `path` open, or anything inside it when it is a folder, so a
+/// delete that failed with the file in use can say what to close
+pub fn who_has_open(path: &str) -> Result<Vec<OpenFile>, DuneError> {
+    let path = Path::new(path);
+    fs::symlink_metadata(path).map_err(|e| DuneError::io(path, e))?;
+
+    let mut open = platform::holders(path);
+    open.truncate(MAX_OPEN_FILES);
+    Ok(open)
+}
+
+/// "Word (4212), OneDrive (880) and 3 more", for error messages
+pub fn describe_holders(open: &[OpenFile]) -> String {
+    let mut processes: Vec<String> = Vec::new();
+    for file in open {
+        let process = format!("{} ({})", file.process, file.pid);
+        if !processes.contains(&process) {
+            processes.push(process);
+        }
+    }
+
+    let shown = processes.len().min(MAX_NAMED);
+    let mut text = processes[..shown].join(", ");
+    if processes.len() > shown {
+        text.push_str(&format!(" and {} more", processes.len() - shown));
+    }
+    text
+}
+
+/// Unmount and eject the volume at `disk`; when something still has files open there
+/// nothing is attempted and the files come back instead
+pub fn eject(disk: &str) -> Result<EjectOutcome, DuneError> {
+    let disk = removable_volume(disk)?;
+    let open_files = open_files(&disk);
+    if !open_files.is_empty() {
+        log::info!("Not ejecting {}: {} files open", disk, open_files.len());
+        return Ok(EjectOutcome {
+            disk,
+            ejected: false,
+            open_files,
+            message: None,
+        });
+    }
+
+    match platform::eject(Path::new(&disk)) {
+        Ok(()) => {
+            log::info!("Ejected {}", disk);
+            Ok(EjectOutcome {
+                disk,
+                ejected: true,
+                open_files: Vec::new(),
+                message: None,
+            })
+        }
+        Err(message) => {
+            log::warn!("Could not eject {}: {}", disk, message);
+            Ok(EjectOutcome {
+                open_files: self::open_files(&disk),
+                disk,
+                ejected: false,
+                message: Some(message),
+            })
+        }
+    }
+}
@@ -0,0 +1,333 @@
+use crate::app_data;
+#[cfg(windows)]
+use crate::console;
+use crate::error::DuneError;
+use crate::i18n::tr;
+use crate::scan::{self, ScanHandle, ScanOptions, ScanTree};
+use crate::scanner;
+use crate::settings::Settings;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+#[cfg(windows)]
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+#[cfg(not(windows))]
+use std::process::Command;
+use std::process::Output;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Runtime};
+
+/// The app relaunched with this, a root and a scan request scans as administrator and exits
+pub const HELPER_ARG: &str = "--elevated-scan";
+const FOLDER_NAME: &str = "elevated";
+
+/// Roots of elevated scans whose results are still the latest, see `blocks`
+static ELEVATED_ROOTS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+static ALLOW_FILE_OPERATIONS: AtomicBool = AtomicBool::new(false);
+
+/// What the helper hands back: the tree, or what went wrong
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Reply {
+    Tree(Box<ScanTree>),
+    Error(String),
+}
+
+/// Where the Windows helper leaves its reply, see `write_reply`
+pub fn work_dir<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, DuneError> {
+    app_data::path(app, FOLDER_NAME)
+}
+
+pub fn configure(settings: &Settings) {
+    ALLOW_FILE_OPERATIONS.store(settings.scan.elevated_file_operations, Ordering::Relaxed);
+}
+
+/// Whether file operations on `path` are refused: it lies in an elevated scan, which
+/// shows files the app cannot normally reach, and `Settings.scan.elevated_file_operations`
+/// is off
+pub fn blocks(path: &Path) -> bool {
+    !ALLOW_FILE_OPERATIONS.load(Ordering::Relaxed)
+        && ELEVATED_ROOTS
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|root| path.starts_with(root))
+}
+
+pub fn blocked_error(path: &Path) -> DuneError {
+    DuneError::Unsupported(tr(
+        "error.elevated_results",
+        &[("path", &path.to_string_lossy())],
+    ))
+}
+
+/// A regular scan of `root` replaces the elevated results in and under it
+pub fn forget(root: &str) {
+    ELEVATED_ROOTS
+        .lock()
+        .unwrap()
+        .retain(|elevated| !elevated.starts_with(root));
+}
+
+/// Options go to the helper as base64 JSON on its command line, which every way of
+/// elevating passes through unchanged
+fn encode_request(options: &ScanOptions) -> Result<String, DuneError> {
+    serde_json::to_vec(options)
+        .map(|bytes| URL_SAFE_NO_PAD.encode(bytes))
+        .map_err(DuneError::internal)
+}
+
+fn decode_request(request: &str) -> Result<ScanOptions, DuneError> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(request)
+        .map_err(|e| DuneError::InvalidInput(format!("Bad scan request: {}", e)))?;
+    serde_json::from_slice(&bytes)
+        .map_err(|e| DuneError::InvalidInput(format!("Bad scan request: {}", e)))
+}
+
+/// The reply goes to stdout, which the app reads off the elevating command. Nothing the
+/// user can write is ever opened as root.
+#[cfg(not(windows))]
+fn write_reply(_args: &[String], bytes: &[u8]) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(bytes)?;
+    stdout.flush()
+}
+
+/// UAC gives the elevated process its own console, so the reply goes to a file named on
+/// the command line instead. It is created new without following links, so an existing
+/// file or a link planted in its place makes the helper fail rather than write through it.
+#[cfg(windows)]
+fn write_reply(args: &[String], bytes: &[u8]) -> std::io::Result<()> {
+    use std::os::windows::fs::OpenOptionsExt;
+    use windows_sys::Win32::Storage::FileSystem::FILE_FLAG_OPEN_REPARSE_POINT;
+
+    let Some(path) = args.get(3) else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "no reply file",
+        ));
+    };
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .custom_flags(FILE_FLAG_OPEN_REPARSE_POINT)
+        .open(path)?
+        .write_all(bytes)
+}
+
+/// Run as the helper when the process was started with `HELPER_ARG`; returns the exit
+/// code, or `None` for a normal launch
+pub fn run_helper(args: &[String]) -> Option<i32> {
+    let at = args.iter().position(|arg| arg == HELPER_ARG)?;
+    let args = &args[at..];
+    let (Some(root), Some(request)) = (args.get(1), args.get(2)) else {
+        log::error!("{} needs a folder and a scan request", HELPER_ARG);
+        return Some(2);
+    };
+
+    let result = decode_request(request).and_then(|options| helper_scan(root, &options));
+    let (reply, code) = match result {
+        Ok(tree) => (Reply::Tree(Box::new(tree)), 0),
+        Err(err) => (Reply::Error(err.to_string()), 1),
+    };
+
+    let written = serde_json::to_vec(&reply)
+        .map_err(DuneError::internal)
+        .and_then(|bytes| write_reply(args, &bytes).map_err(DuneError::internal));
+    if let Err(err) = written {
+        log::error!("Could not hand back the scan: {}", err);
+        return Some(1);
+    }
+
+    Some(code)
+}
+
+fn helper_scan(root: &str, options: &ScanOptions) -> Result<ScanTree, DuneError> {
+    let handle = ScanHandle::new(0, root.to_string());
+    let threads = std::thread::available_parallelism().map_or(1, usize::from);
+    scan::scan_tree(
+        &handle,
+        options,
+        threads,
+        scanner::default_enumerator(),
+        &|_| {},
+    )
+}
+
+/// The program to relaunch; an AppImage's own mount is not readable by root
+fn executable() -> Result<PathBuf, DuneError> {
+    if let Some(appimage) = std::env::var_os("APPIMAGE") {
+        return Ok(PathBuf::from(appimage));
+    }
+    std::env::current_exe().map_err(DuneError::internal)
+}
+
+/// How the elevating command ended, and the helper's reply when it ran
+struct Elevated {
+    output: Output,
+    reply: Vec<u8>,
+}
+
+#[cfg(target_os = "linux")]
+fn elevate(exe: &Path, root: &str, request: &str, _work_dir: &Path) -> std::io::Result<Elevated> {
+    let output = Command::new("pkexec")
+        .arg(exe)
+        .arg(HELPER_ARG)
+        .arg(root)
+        .arg(request)
+        .output()?;
+    Ok(Elevated {
+        reply: output.stdout.clone(),
+        output,
+    })
+}
+
+/// 126 when the prompt was dismissed, 127 when the password was wrong three times
+#[cfg(target_os = "linux")]
+fn declined(output: &Output) -> bool {
+    matches!(output.status.code(), Some(126 | 127))
+}
+
+/// `do shell script` returns the helper's stdout, which osascript prints
+#[cfg(target_os = "macos")]
+fn elevate(exe: &Path, root: &str, request: &str, _work_dir: &Path) -> std::io::Result<Elevated> {
+    let output = Command::new("osascript")
+        .args([
+            "-e",
+            "on run argv",
+            "-e",
+            "do shell script (quoted form of item 1 of argv) & \" \" & (quoted form of item 2 \
+             of argv) & \" \" & (quoted form of item 3 of argv) & \" \" & (quoted form of \
+             item 4 of argv) with prompt (\"Dunefiles needs an administrator to scan \" & \
+             item 3 of argv) with administrator privileges",
+            "-e",
+            "end run",
+        ])
+        .arg(exe)
+        .arg(HELPER_ARG)
+        .arg(root)
+        .arg(request)
+        .output()?;
+    Ok(Elevated {
+        reply: output.stdout.clone(),
+        output,
+    })
+}
+
+/// AppleScript's "User canceled." is error -128
+#[cfg(target_os = "macos")]
+fn declined(output: &Output) -> bool {
+    String::from_utf8_lossy(&output.stderr).contains("(-128)")
+}
+
+#[cfg(windows)]
+fn elevate(exe: &Path, root: &str, request: &str, work_dir: &Path) -> std::io::Result<Elevated> {
+    const SCRIPT: &str = "$arguments = $env:DUNE_ELEVATED_ARG + ' \"' + $env:DUNE_ELEVATED_ROOT + \
+        '\" ' + $env:DUNE_ELEVATED_REQUEST + ' \"' + $env:DUNE_ELEVATED_REPLY + '\"'; \
+        try { \
+            $helper = Start-Process -FilePath $env:DUNE_ELEVATED_EXE -ArgumentList $arguments \
+                -Verb RunAs -Wait -PassThru -WindowStyle Hidden; \
+            exit $helper.ExitCode \
+        } catch { \
+            [Console]::Error.WriteLine($_.Exception.Message); \
+            exit 1223 \
+        }";
+
+    fs::create_dir_all(work_dir)?;
+    let reply_file = work_dir.join(format!(
+        "scan-{}-{}.json",
+        std::process::id(),
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos())
+    ));
+    // A backslash before the closing quote would escape it, `C:\` has to go in as `C:\\`
+    let root = if root.ends_with('\\') {
+        format!("{}\\", root)
+    } else {
+        root.to_string()
+    };
+    let output = console::powershell(SCRIPT)
+        .env("DUNE_ELEVATED_EXE", exe)
+        .env("DUNE_ELEVATED_ARG", HELPER_ARG)
+        .env("DUNE_ELEVATED_ROOT", root)
+        .env("DUNE_ELEVATED_REQUEST", request)
+        .env("DUNE_ELEVATED_REPLY", &reply_file)
+        .output()?;
+    let reply = fs::read(&reply_file).unwrap_or_default();
+    let _ = fs::remove_file(&reply_file);
+
+    Ok(Elevated { output, reply })
+}
+
+/// The script exits with `ERROR_CANCELLED` when the UAC prompt is refused
+#[cfg(windows)]
+fn declined(output: &Output) -> bool {
+    output.status.code() == Some(1223)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn elevate(
+    _exe: &Path,
+    _root: &str,
+    _request: &str,
+    _work_dir: &Path,
+) -> std::io::Result<Elevated> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "no way to ask for administrator rights",
+    ))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn declined(_output: &Output) -> bool {
+    false
+}
+
+/// Scan `root` as administrator after the system's password prompt, through a copy of
+/// the app relaunched with `HELPER_ARG`. The tree is marked `elevated`, and file
+/// operations under `root` are refused until a regular scan of it finishes.
+pub fn scan(
+    work_dir: &Path,
+    scan_id: u64,
+    root: &str,
+    options: &ScanOptions,
+) -> Result<ScanTree, DuneError> {
+    let request = encode_request(options)?;
+    let exe = executable()?;
+    log::info!("Asking for administrator rights to scan {}", root);
+    let Elevated { output, reply } = elevate(&exe, root, &request, work_dir).map_err(|err| {
+        DuneError::Unsupported(format!("Could not ask for administrator rights: {}", err))
+    })?;
+
+    match serde_json::from_str(String::from_utf8_lossy(&reply).trim()) {
+        Ok(Reply::Tree(tree)) => {
+            let mut tree = *tree;
+            log::info!("Elevated scan of {} finished", root);
+            tree.scan_id = scan_id;
+            tree.elevated = true;
+            let mut roots = ELEVATED_ROOTS.lock().unwrap();
+            if !roots.iter().any(|elevated| elevated == Path::new(root)) {
+                roots.push(PathBuf::from(root));
+            }
+            Ok(tree)
+        }
+        Ok(Reply::Error(message)) => Err(DuneError::Io {
+            path: Some(root.to_string()),
+            message,
+        }),
+        // The helper never ran
+        _ if declined(&output) => Err(DuneError::Cancelled),
+        _ => Err(DuneError::Io {
+            path: Some(root.to_string()),
+            message: format!(
+                "The elevated scan did not run: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        }),
+    }
+}
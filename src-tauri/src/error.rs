@@ -0,0 +1,119 @@
+use crate::i18n::tr;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// Error returned by every command, serialized as `{ code, message, path }`
+#[derive(Debug, Clone)]
+pub enum DuneError {
+    NotFound {
+        path: String,
+    },
+    PermissionDenied {
+        path: String,
+    },
+    NotADirectory {
+        path: String,
+    },
+    Io {
+        path: Option<String>,
+        message: String,
+    },
+    Cancelled,
+    /// Refused to touch a path the app must never modify
+    Protected {
+        path: String,
+    },
+    /// A query or other argument from the frontend that could not be parsed
+    InvalidInput(String),
+    Unsupported(String),
+    Internal(String),
+}
+
+impl DuneError {
+    /// Classify an IO error that happened while working on `path`
+    pub fn io(path: impl AsRef<Path>, err: io::Error) -> Self {
+        let path = path.as_ref().to_string_lossy().to_string();
+
+        match err.kind() {
+            io::ErrorKind::NotFound => Self::NotFound { path },
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied { path },
+            _ => Self::Io {
+                path: Some(path),
+                message: err.to_string(),
+            },
+        }
+    }
+
+    pub fn internal(err: impl fmt::Display) -> Self {
+        Self::Internal(err.to_string())
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound { .. } => "not_found",
+            Self::PermissionDenied { .. } => "permission_denied",
+            Self::NotADirectory { .. } => "not_a_directory",
+            Self::Io { .. } => "io",
+            Self::Cancelled => "cancelled",
+            Self::Protected { .. } => "protected",
+            Self::InvalidInput(_) => "invalid_input",
+            Self::Unsupported(_) => "unsupported",
+            Self::Internal(_) => "internal",
+        }
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Self::NotFound { path }
+            | Self::PermissionDenied { path }
+            | Self::NotADirectory { path }
+            | Self::Protected { path } => Some(path),
+            Self::Io { path, .. } => path.as_deref(),
+            Self::Cancelled | Self::InvalidInput(_) | Self::Unsupported(_) | Self::Internal(_) => {
+                None
+            }
+        }
+    }
+}
+
+impl fmt::Display for DuneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound { path } => f.write_str(&tr("error.not_found", &[("path", path)])),
+            Self::PermissionDenied { path } => {
+                f.write_str(&tr("error.permission_denied", &[("path", path)]))
+            }
+            Self::NotADirectory { path } => {
+                f.write_str(&tr("error.not_a_directory", &[("path", path)]))
+            }
+            Self::Io {
+                path: Some(path),
+                message,
+            } => write!(f, "{}: {}", path, message),
+            Self::Io {
+                path: None,
+                message,
+            } => write!(f, "{}", message),
+            Self::Cancelled => f.write_str(&tr("error.cancelled", &[])),
+            Self::Protected { path } => f.write_str(&tr("error.protected", &[("path", path)])),
+            Self::InvalidInput(message) | Self::Unsupported(message) | Self::Internal(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DuneError {}
+
+impl Serialize for DuneError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("DuneError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("path", &self.path())?;
+        state.end()
+    }
+}
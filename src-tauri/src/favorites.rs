@@ -0,0 +1,195 @@
+use crate::metrics::ScanRecord;
+use crate::power::{self, ThrottledEnumerator};
+use crate::scan::{self, ScanTree};
+use crate::scanner;
+use crate::service::ScannerService;
+use crate::settings;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Events arriving this close together are handled as one burst
+const SETTLE_DELAY: Duration = Duration::from_secs(2);
+/// Longest a burst is held back, so a folder that never settles still gets updates
+const MAX_SETTLE_DELAY: Duration = Duration::from_secs(10);
+
+/// Payload of `favorites://updated`
+#[derive(Debug, Clone, Serialize)]
+pub struct FavoriteTotals {
+    pub path: String,
+    pub size: u64,
+    pub size_complete: bool,
+    /// Milliseconds since the Unix epoch
+    pub updated_at: u64,
+}
+
+/// Size of each direct child of a favorite, so a change re-measures only the child it
+/// happened in
+struct FavoriteSizes {
+    root: PathBuf,
+    children: HashMap<OsString, (u64, bool)>,
+}
+
+impl FavoriteSizes {
+    /// Child sizes from a finished scan that covers `root`, if there is one
+    fn from_tree(root: &Path, tree: &ScanTree) -> Option<Self> {
+        let index = tree.find(root)?;
+        let children = tree
+            .nodes
+            .iter()
+            .filter(|node| node.parent == Some(index))
+            .map(|node| (OsString::from(&*node.name), (node.size, node.size_complete)))
+            .collect();
+
+        Some(Self {
+            root: root.to_path_buf(),
+            children,
+        })
+    }
+
+    fn measure(root: &Path, enumerator: &ThrottledEnumerator) -> Self {
+        let mut sizes = Self {
+            root: root.to_path_buf(),
+            children: HashMap::new(),
+        };
+        let names: Vec<OsString> = fs::read_dir(root)
+            .map(|entries| entries.flatten().map(|entry| entry.file_name()).collect())
+            .unwrap_or_default();
+        for name in names {
+            sizes.remeasure(name, enumerator);
+        }
+
+        sizes
+    }
+
+    fn remeasure(&mut self, name: OsString, enumerator: &ThrottledEnumerator) {
+        let path = self.root.join(&name);
+        let measured = match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => {
+                scanner::measure_folder(&path.to_string_lossy(), enumerator)
+                    .map(|entry| (entry.size, entry.size_complete))
+                    .unwrap_or((0, false))
+            }
+            Ok(metadata) if metadata.is_file() => (metadata.len(), true),
+            Ok(_) => (0, true),
+            // Removed since the event
+            Err(_) => {
+                self.children.remove(&name);
+                return;
+            }
+        };
+        self.children.insert(name, measured);
+    }
+
+    /// Re-measure the children the `changed` paths are in; a change to the favorite
+    /// itself re-reads its whole listing
+    fn refresh(&mut self, changed: &HashSet<PathBuf>, enumerator: &ThrottledEnumerator) {
+        let mut names = HashSet::new();
+        for path in changed {
+            let Ok(relative) = path.strip_prefix(&self.root) else {
+                continue;
+            };
+            match relative.components().next() {
+                Some(child) => {
+                    names.insert(child.as_os_str().to_owned());
+                }
+                None => {
+                    *self = Self::measure(&self.root, enumerator);
+                    return;
+                }
+            }
+        }
+
+        for name in names {
+            self.remeasure(name, enumerator);
+        }
+    }
+
+    fn totals(&self) -> FavoriteTotals {
+        FavoriteTotals {
+            path: self.root.to_string_lossy().into_owned(),
+            size: self.children.values().map(|&(size, _)| size).sum(),
+            size_complete: self.children.values().all(|&(_, complete)| complete),
+            updated_at: scan::millis_since_epoch(SystemTime::now()).unwrap_or(0),
+        }
+    }
+}
+
+fn publish(app: &AppHandle, sizes: &FavoriteSizes, started: Instant) {
+    let totals = sizes.totals();
+    app.state::<ScannerService>().record_scan(
+        &totals.path,
+        ScanRecord {
+            bytes: totals.size,
+            files: None,
+            duration: started.elapsed(),
+            complete: totals.size_complete,
+            finished_at: totals.updated_at,
+        },
+    );
+    let _ = app.emit("favorites://updated", totals);
+}
+
+/// Keep the totals of the favorite at `root` fresh from the changed paths sent to the
+/// returned channel; stops once the sender, held by the watcher, is dropped
+pub fn spawn_updater(
+    app: AppHandle,
+    root: PathBuf,
+    cached: Option<Arc<ScanTree>>,
+) -> Sender<Vec<PathBuf>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || run_updater(&app, &root, cached, &receiver));
+
+    sender
+}
+
+fn run_updater(
+    app: &AppHandle,
+    root: &Path,
+    cached: Option<Arc<ScanTree>>,
+    receiver: &Receiver<Vec<PathBuf>>,
+) {
+    // Sizing is single threaded, so only the IO pause applies here
+    let budget = power::scan_budget(settings::load().scan.power_mode, 1);
+    let enumerator = ThrottledEnumerator::new(scanner::default_enumerator(), &budget);
+
+    let started = Instant::now();
+    let mut sizes = match cached.and_then(|tree| FavoriteSizes::from_tree(root, &tree)) {
+        Some(sizes) => sizes,
+        None => FavoriteSizes::measure(root, &enumerator),
+    };
+    publish(app, &sizes, started);
+
+    while let Ok(first) = receiver.recv() {
+        let mut changed: HashSet<PathBuf> = first.into_iter().collect();
+        let burst = Instant::now();
+        let mut deadline = burst + SETTLE_DELAY;
+        loop {
+            match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(paths) => {
+                    changed.extend(paths);
+                    deadline = (Instant::now() + SETTLE_DELAY).min(burst + MAX_SETTLE_DELAY);
+                }
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        let started = Instant::now();
+        sizes.refresh(&changed, &enumerator);
+        log::debug!(
+            "Favorite {} refreshed after {} changes in {:?}",
+            root.display(),
+            changed.len(),
+            started.elapsed()
+        );
+        publish(app, &sizes, started);
+    }
+}
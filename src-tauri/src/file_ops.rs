@@ -0,0 +1,411 @@
+use crate::audit::{self, Operation, OperationRecord};
+use crate::eject;
+use crate::elevated;
+use crate::error::DuneError;
+use crate::resolve;
+use crate::scan;
+use crate::scanner;
+use crate::validity;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Folders nothing inside of may be changed
+#[cfg(unix)]
+const PROTECTED_TREES: &[&str] = &[
+    "/System", "/bin", "/sbin", "/usr", "/boot", "/proc", "/sys", "/dev",
+];
+/// Folders that may hold user changes but must not be removed or moved themselves
+#[cfg(unix)]
+const PROTECTED_FOLDERS: &[&str] = &[
+    "/Applications",
+    "/Library",
+    "/Users",
+    "/Volumes",
+    "/etc",
+    "/home",
+    "/lib",
+    "/lib64",
+    "/opt",
+    "/private",
+    "/var",
+];
+
+#[cfg(windows)]
+const PROTECTED_TREES: &[&str] = &["C:\\Windows"];
+#[cfg(windows)]
+const PROTECTED_FOLDERS: &[&str] = &[
+    "C:\\Program Files",
+    "C:\\Program Files (x86)",
+    "C:\\ProgramData",
+    "C:\\Users",
+];
+
+#[cfg(not(any(unix, windows)))]
+const PROTECTED_TREES: &[&str] = &[];
+#[cfg(not(any(unix, windows)))]
+const PROTECTED_FOLDERS: &[&str] = &[];
+
+/// Windows and macOS volumes ignore case unless formatted otherwise, so `/system` is
+/// `/System` there
+fn same_path(a: &Path, b: &Path) -> bool {
+    if cfg!(any(windows, target_os = "macos")) {
+        let trim = |path: &Path| {
+            path.to_string_lossy()
+                .trim_end_matches(std::path::MAIN_SEPARATOR)
+                .to_string()
+        };
+        trim(a).eq_ignore_ascii_case(&trim(b))
+    } else {
+        a == b
+    }
+}
+
+/// `path` with `..` and links in its folders resolved; the last component is kept as is,
+/// since an operation acts on a link and not on what it points to
+fn real_path(path: &Path) -> PathBuf {
+    let canonical = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => fs::canonicalize(parent).map(|parent| parent.join(name)),
+        // `/tmp/..` has no name of its own
+        _ => fs::canonicalize(path),
+    };
+    canonical.map_or_else(|_| path.to_path_buf(), resolve::plain)
+}
+
+/// Volume roots, the home folder, and system folders are never touched
+pub fn is_protected(path: &Path) -> bool {
+    let path = &real_path(path);
+    path.parent().is_none()
+        || dirs::home_dir().is_some_and(|home| same_path(path, &real_path(&home)))
+        || PROTECTED_FOLDERS
+            .iter()
+            .any(|folder| same_path(path, Path::new(folder)))
+        || PROTECTED_TREES.iter().any(|tree| {
+            path.ancestors()
+                .any(|ancestor| same_path(ancestor, Path::new(tree)))
+        })
+        || scanner::get_disks()
+            .iter()
+            .any(|disk| same_path(path, Path::new(&disk.path)))
+}
+
+/// Bytes of a file, or of everything in a folder; links are not followed
+fn size_of(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+
+    fs::read_dir(path).map_or(0, |entries| {
+        entries.flatten().map(|entry| size_of(&entry.path())).sum()
+    })
+}
+
+/// Another process has the file open: sharing and lock violations on Windows, a busy
+/// mount point or running program elsewhere
+fn in_use(err: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    return matches!(err.raw_os_error(), Some(libc::EBUSY | libc::ETXTBSY));
+    #[cfg(windows)]
+    return matches!(err.raw_os_error(), Some(32 | 33));
+    #[cfg(not(any(unix, windows)))]
+    return false;
+}
+
+/// `message` followed by the processes holding `path` open, when any can be found
+fn with_holders(path: &Path, message: String) -> DuneError {
+    let holders = eject::who_has_open(&path.to_string_lossy()).unwrap_or_default();
+    DuneError::Io {
+        path: Some(path.to_string_lossy().into_owned()),
+        message: if holders.is_empty() {
+            message
+        } else {
+            format!("{}; open in {}", message, eject::describe_holders(&holders))
+        },
+    }
+}
+
+pub fn remove(path: &Path) -> Result<(), DuneError> {
+    let metadata = fs::symlink_metadata(path).map_err(|e| DuneError::io(path, e))?;
+    if metadata.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+    .map_err(|e| {
+        if in_use(&e) {
+            with_holders(path, e.to_string())
+        } else {
+            DuneError::io(path, e)
+        }
+    })
+}
+
+/// Programs that move one path to the trash, tried in order
+#[cfg(target_os = "macos")]
+fn trash_commands(path: &Path) -> Vec<Command> {
+    let mut finder = Command::new("osascript");
+    finder
+        .args([
+            "-e",
+            "on run argv",
+            "-e",
+            "tell application \"Finder\" to delete (POSIX file (item 1 of argv) as alias)",
+            "-e",
+            "end run",
+        ])
+        .arg(path);
+    vec![finder]
+}
+
+#[cfg(windows)]
+fn trash_commands(path: &Path) -> Vec<Command> {
+    use crate::console;
+
+    const SCRIPT: &str = "Add-Type -AssemblyName Microsoft.VisualBasic; \
+        $path = $env:DUNE_TRASH_PATH; \
+        if (Test-Path -LiteralPath $path -PathType Container) { \
+            [Microsoft.VisualBasic.FileIO.FileSystem]::DeleteDirectory($path, 'OnlyErrorDialogs', 'SendToRecycleBin') \
+        } else { \
+            [Microsoft.VisualBasic.FileIO.FileSystem]::DeleteFile($path, 'OnlyErrorDialogs', 'SendToRecycleBin') \
+        }";
+
+    let mut powershell = console::powershell(SCRIPT);
+    powershell.env("DUNE_TRASH_PATH", path);
+    vec![powershell]
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn trash_commands(path: &Path) -> Vec<Command> {
+    let mut gio = Command::new("gio");
+    gio.args(["trash", "--"]).arg(path);
+    let mut trash_put = Command::new("trash-put");
+    trash_put.arg("--").arg(path);
+    let mut kioclient = Command::new("kioclient5");
+    kioclient.arg("move").arg(path).arg("trash:/");
+
+    vec![gio, trash_put, kioclient]
+}
+
+fn trash(path: &Path) -> Result<(), DuneError> {
+    let mut tried = Vec::new();
+    for mut command in trash_commands(path) {
+        let program = command.get_program().to_string_lossy().into_owned();
+        match command.output() {
+            Ok(output) if output.status.success() => return Ok(()),
+            Ok(output) => {
+                // The trash programs only say why in their own words
+                return Err(with_holders(
+                    path,
+                    format!(
+                        "{} could not move it to the trash: {}",
+                        program,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ),
+                ));
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => tried.push(program),
+            Err(err) => return Err(DuneError::internal(err)),
+        }
+    }
+
+    Err(DuneError::Unsupported(format!(
+        "No trash program is installed (tried {})",
+        tried.join(", ")
+    )))
+}
+
+/// A new link at `dest` with the same target as the link at `source`
+fn copy_link(source: &Path, dest: &Path) -> std::io::Result<()> {
+    let target = fs::read_link(source)?;
+    #[cfg(unix)]
+    return std::os::unix::fs::symlink(target, dest);
+    #[cfg(windows)]
+    return if fs::metadata(source).is_ok_and(|metadata| metadata.is_dir()) {
+        std::os::windows::fs::symlink_dir(target, dest)
+    } else {
+        std::os::windows::fs::symlink_file(target, dest)
+    };
+    #[cfg(not(any(unix, windows)))]
+    return Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("Cannot copy the link to {}", target.display()),
+    ));
+}
+
+/// Copy a file or folder tree; links are copied as links, not followed
+fn copy_all(source: &Path, dest: &Path) -> Result<(), DuneError> {
+    let metadata = fs::symlink_metadata(source).map_err(|e| DuneError::io(source, e))?;
+    if metadata.is_symlink() {
+        return copy_link(source, dest).map_err(|e| DuneError::io(source, e));
+    }
+    if !metadata.is_dir() {
+        return fs::copy(source, dest)
+            .map(|_| ())
+            .map_err(|e| DuneError::io(source, e));
+    }
+
+    fs::create_dir(dest).map_err(|e| DuneError::io(dest, e))?;
+    for entry in fs::read_dir(source).map_err(|e| DuneError::io(source, e))? {
+        let entry = entry.map_err(|e| DuneError::io(source, e))?;
+        copy_all(&entry.path(), &dest.join(entry.file_name()))?;
+    }
+
+    Ok(())
+}
+
+/// `EXDEV`, or `ERROR_NOT_SAME_DEVICE` on Windows
+fn other_volume(err: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    return err.raw_os_error() == Some(libc::EXDEV);
+    #[cfg(windows)]
+    return err.raw_os_error() == Some(17);
+    #[cfg(not(any(unix, windows)))]
+    return false;
+}
+
+/// Rename, or copy and remove when `dest` is on another volume
+pub fn relocate(source: &Path, dest: &Path) -> Result<(), DuneError> {
+    if dest.exists() {
+        return Err(DuneError::InvalidInput(format!(
+            "{} already exists",
+            dest.display()
+        )));
+    }
+
+    match fs::rename(source, dest) {
+        Ok(()) => Ok(()),
+        Err(err) if other_volume(&err) => {
+            if let Err(err) = copy_all(source, dest) {
+                // Leave the source whole and nothing half-copied behind
+                if fs::symlink_metadata(dest).is_ok() {
+                    if let Err(cleanup) = remove(dest) {
+                        log::warn!("Could not remove the partial copy: {}", cleanup);
+                    }
+                }
+                return Err(err);
+            }
+            remove(source)
+        }
+        Err(err) => Err(DuneError::io(source, err)),
+    }
+}
+
+/// Run one operation on `path` and append what happened to the audit log at `store`
+pub fn perform(
+    store: &Path,
+    operation: Operation,
+    path: &Path,
+    destination: Option<PathBuf>,
+    work: impl FnOnce() -> Result<(), DuneError>,
+) -> OperationRecord {
+    let refusal = if is_protected(path) {
+        Some(DuneError::Protected {
+            path: path.to_string_lossy().into_owned(),
+        })
+    } else if elevated::blocks(path) {
+        Some(elevated::blocked_error(path))
+    } else {
+        None
+    };
+    // Only paths that are acted on get sized; a refused volume root is never walked
+    let (bytes, result) = match refusal {
+        Some(err) => (0, Err(err)),
+        None => (size_of(path), work()),
+    };
+
+    let record = OperationRecord {
+        at: scan::now_millis(),
+        operation,
+        path: path.to_string_lossy().into_owned(),
+        destination: destination.map(|dest| dest.to_string_lossy().into_owned()),
+        bytes,
+        ok: result.is_ok(),
+        error: result.as_ref().err().map(ToString::to_string),
+    };
+    match &result {
+        Ok(()) => log::info!("{:?} {} ({} bytes)", operation, record.path, bytes),
+        Err(err) => log::warn!("{:?} {} failed: {}", operation, record.path, err),
+    }
+    if let Err(err) = audit::append(store, &record) {
+        log::error!("Could not write the operation log: {}", err);
+    }
+
+    record
+}
+
+/// Remove files and folders for good
+pub fn delete(store: &Path, paths: &[String]) -> Vec<OperationRecord> {
+    paths
+        .iter()
+        .map(|path| {
+            let path = Path::new(path);
+            perform(store, Operation::Delete, path, None, || remove(path))
+        })
+        .collect()
+}
+
+/// Move files and folders to the system trash
+pub fn move_to_trash(store: &Path, paths: &[String]) -> Vec<OperationRecord> {
+    paths
+        .iter()
+        .map(|path| {
+            let path = Path::new(path);
+            perform(store, Operation::Trash, path, None, || trash(path))
+        })
+        .collect()
+}
+
+/// Move files and folders into the folder `dest`, keeping their names
+pub fn move_into(store: &Path, paths: &[String], dest: &str) -> Vec<OperationRecord> {
+    let folder = Path::new(dest);
+    paths
+        .iter()
+        .map(|path| {
+            let path = Path::new(path);
+            let target = path.file_name().map(|name| folder.join(name));
+            perform(store, Operation::Move, path, target.clone(), || {
+                if !folder.is_dir() {
+                    return Err(DuneError::NotADirectory { path: dest.into() });
+                }
+                if folder.starts_with(path) {
+                    return Err(DuneError::InvalidInput(format!(
+                        "Cannot move {} into itself",
+                        path.display()
+                    )));
+                }
+                match &target {
+                    Some(target) => relocate(path, target),
+                    None => Err(DuneError::InvalidInput(format!(
+                        "{} has no name to keep",
+                        path.display()
+                    ))),
+                }
+            })
+        })
+        .collect()
+}
+
+/// Give a file or folder a new name in the same folder
+pub fn rename(store: &Path, path: &str, new_name: &str) -> OperationRecord {
+    let path = Path::new(path);
+    let folder = path.parent().unwrap_or(path).to_string_lossy().into_owned();
+    let current = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned());
+    let check = validity::check_name(&folder, new_name, current.as_deref());
+    let target = path.with_file_name(new_name);
+
+    perform(store, Operation::Rename, path, Some(target.clone()), || {
+        let check = check?;
+        if !check.valid {
+            return Err(DuneError::InvalidInput(format!(
+                "{:?} is not a valid name here: {:?}",
+                new_name, check.problems
+            )));
+        }
+        relocate(path, &target)
+    })
+}
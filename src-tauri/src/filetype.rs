@@ -0,0 +1,90 @@
+use serde::Serialize;
+use std::path::Path;
+
+/// Human-level grouping of files, decided by extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileCategory {
+    Photos,
+    Videos,
+    Music,
+    Documents,
+    Code,
+    Archives,
+    Apps,
+    Other,
+}
+
+impl FileCategory {
+    pub const ALL: [FileCategory; 8] = [
+        FileCategory::Photos,
+        FileCategory::Videos,
+        FileCategory::Music,
+        FileCategory::Documents,
+        FileCategory::Code,
+        FileCategory::Archives,
+        FileCategory::Apps,
+        FileCategory::Other,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+const PHOTOS: &[&str] = &[
+    "jpg", "jpeg", "png", "gif", "bmp", "tif", "tiff", "webp", "heic", "heif", "svg", "ico", "raw",
+    "cr2", "cr3", "nef", "arw", "dng", "orf", "rw2", "psd", "xcf",
+];
+const VIDEOS: &[&str] = &[
+    "mp4", "m4v", "mov", "avi", "mkv", "webm", "wmv", "flv", "mpg", "mpeg", "3gp", "mts", "m2ts",
+    "vob",
+];
+const MUSIC: &[&str] = &[
+    "mp3", "m4a", "aac", "flac", "wav", "ogg", "oga", "opus", "wma", "aiff", "aif", "alac", "mid",
+    "midi",
+];
+const DOCUMENTS: &[&str] = &[
+    "pdf", "doc", "docx", "odt", "rtf", "txt", "md", "xls", "xlsx", "ods", "csv", "ppt", "pptx",
+    "odp", "pages", "numbers", "key", "epub", "mobi", "tex",
+];
+const CODE: &[&str] = &[
+    "rs", "c", "h", "cc", "cpp", "hpp", "cs", "go", "java", "kt", "swift", "m", "mm", "py", "rb",
+    "php", "js", "mjs", "cjs", "ts", "tsx", "jsx", "vue", "svelte", "html", "css", "scss", "json",
+    "toml", "yaml", "yml", "xml", "sh", "bash", "zsh", "ps1", "lua", "sql", "ipynb",
+];
+const ARCHIVES: &[&str] = &[
+    "zip", "tar", "gz", "tgz", "bz2", "xz", "zst", "7z", "rar", "lz4", "iso", "dmg", "img", "cab",
+];
+const APPS: &[&str] = &[
+    "app", "exe", "msi", "msix", "appx", "apk", "aab", "ipa", "appimage", "deb", "rpm", "pkg",
+    "flatpak", "snap",
+];
+
+/// Category for a file, or for a bundle folder such as `Foo.app`
+pub fn category_for(path: &Path) -> FileCategory {
+    let Some(extension) = path.extension() else {
+        return FileCategory::Other;
+    };
+    let extension = extension.to_string_lossy().to_ascii_lowercase();
+    let extension = extension.as_str();
+
+    [
+        (PHOTOS, FileCategory::Photos),
+        (VIDEOS, FileCategory::Videos),
+        (MUSIC, FileCategory::Music),
+        (DOCUMENTS, FileCategory::Documents),
+        (CODE, FileCategory::Code),
+        (ARCHIVES, FileCategory::Archives),
+        (APPS, FileCategory::Apps),
+    ]
+    .iter()
+    .find(|(extensions, _)| extensions.contains(&extension))
+    .map_or(FileCategory::Other, |(_, category)| *category)
+}
+
+/// Folders counted whole as one category instead of by their contents
+pub fn is_bundle(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("app"))
+}
@@ -0,0 +1,80 @@
+use std::path::Path;
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::*;
+    use std::fs;
+
+    /// The name of the `/dev/disk/by-uuid` link pointing at the mounted device
+    pub fn volume_id(mount: &Path) -> Option<String> {
+        let device = crate::smart::mounted_device(&mount.to_string_lossy())?;
+        let device = fs::canonicalize(device).ok()?;
+        fs::read_dir("/dev/disk/by-uuid")
+            .ok()?
+            .flatten()
+            .find(|link| fs::canonicalize(link.path()).is_ok_and(|target| target == device))
+            .map(|link| link.file_name().to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::*;
+    use std::process::Command;
+
+    /// `VolumeUUID` from `diskutil`, which it makes up for FAT and exFAT too
+    pub fn volume_id(mount: &Path) -> Option<String> {
+        let output = Command::new("diskutil")
+            .args(["info", "-plist"])
+            .arg(mount)
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        crate::smart::plist_string(&String::from_utf8_lossy(&output.stdout), "VolumeUUID")
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::*;
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr;
+    use windows_sys::Win32::Storage::FileSystem::GetVolumeInformationW;
+
+    /// The serial number formatting writes, as `dir` shows it
+    pub fn volume_id(mount: &Path) -> Option<String> {
+        let root: Vec<u16> = OsStr::new(mount).encode_wide().chain([0]).collect();
+        let mut serial = 0u32;
+        let ok = unsafe {
+            GetVolumeInformationW(
+                root.as_ptr(),
+                ptr::null_mut(),
+                0,
+                &mut serial,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                0,
+            )
+        };
+        (ok != 0).then(|| format!("{:04X}-{:04X}", serial >> 16, serial & 0xffff))
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+mod platform {
+    use super::*;
+
+    pub fn volume_id(_mount: &Path) -> Option<String> {
+        None
+    }
+}
+
+/// What identifies the volume mounted at `mount` wherever it is plugged in next: the
+/// filesystem UUID, or the volume serial number on Windows
+pub fn volume_id(mount: &Path) -> Option<String> {
+    platform::volume_id(mount)
+}
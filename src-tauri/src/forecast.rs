@@ -0,0 +1,201 @@
+use crate::error::DuneError;
+use crate::scanner::{self, DiskInfo};
+use crate::snapshot::{self, SnapshotInfo};
+use serde::Serialize;
+use std::path::Path;
+
+const DAY_MS: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+/// Snapshots of parent folders need their tree loaded, so only the newest are used
+const MAX_LOADED_SNAPSHOTS: usize = 12;
+/// History shorter than this says nothing about a trend
+const MIN_SPAN_DAYS: f64 = 1.0;
+/// Disks expected to fill sooner than this get a warning in the disk overview
+pub const WARNING_DAYS: f64 = 60.0;
+
+/// Two-sided 95% Student's t values for 1 to 30 degrees of freedom
+const T_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SizePoint {
+    /// Milliseconds since the Unix epoch
+    pub at: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Forecast {
+    pub path: String,
+    /// Sizes from stored snapshots, oldest first
+    pub points: Vec<SizePoint>,
+    /// Fitted growth; `None` until there are two snapshots at least a day apart
+    pub growth_per_day: Option<f64>,
+    /// 95% bounds on the growth; needs three snapshots
+    pub growth_low: Option<f64>,
+    pub growth_high: Option<f64>,
+    pub available_space: Option<u64>,
+    /// Days from now until the volume is full at the fitted rate; `None` when not growing
+    pub days_until_full: Option<f64>,
+    /// At the fastest plausible rate
+    pub days_until_full_earliest: Option<f64>,
+    /// At the slowest plausible rate; `None` if that rate is not growth
+    pub days_until_full_latest: Option<f64>,
+}
+
+/// Least-squares line through the points: (slope per day, 95% half-width of the slope)
+fn fit(points: &[SizePoint]) -> Option<(f64, Option<f64>)> {
+    let (first, last) = (points.first()?, points.last()?);
+    if ((last.at - first.at) as f64) / DAY_MS < MIN_SPAN_DAYS {
+        return None;
+    }
+
+    let n = points.len() as f64;
+    let xs: Vec<f64> = points
+        .iter()
+        .map(|point| (point.at - first.at) as f64 / DAY_MS)
+        .collect();
+    let ys: Vec<f64> = points.iter().map(|point| point.bytes as f64).collect();
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = ys.iter().sum::<f64>() / n;
+
+    let sxx: f64 = xs.iter().map(|x| (x - mean_x).powi(2)).sum();
+    let sxy: f64 = xs
+        .iter()
+        .zip(&ys)
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let slope = sxy / sxx;
+
+    let degrees = points.len().checked_sub(2).filter(|&d| d > 0);
+    let half_width = degrees.map(|degrees| {
+        let intercept = mean_y - slope * mean_x;
+        let residuals: f64 = xs
+            .iter()
+            .zip(&ys)
+            .map(|(x, y)| (y - (intercept + slope * x)).powi(2))
+            .sum();
+        let standard_error = (residuals / degrees as f64 / sxx).sqrt();
+        let t = T_95.get(degrees - 1).copied().unwrap_or(1.96);
+        t * standard_error
+    });
+
+    Some((slope, half_width))
+}
+
+fn days_until_full(available: Option<u64>, growth: Option<f64>) -> Option<f64> {
+    let growth = growth.filter(|&growth| growth > 0.0)?;
+    Some(available? as f64 / growth)
+}
+
+/// Size of `path` in each stored snapshot that covers it, oldest first
+fn size_history(snapshots: &Path, infos: Vec<SnapshotInfo>, path: &Path) -> Vec<SizePoint> {
+    let mut loaded = 0;
+
+    let mut points: Vec<SizePoint> = infos
+        .into_iter()
+        .filter_map(|info| {
+            if Path::new(&info.root) == path {
+                return Some(SizePoint {
+                    at: info.created_at,
+                    bytes: info.total_size,
+                });
+            }
+
+            if !path.starts_with(&info.root) || loaded >= MAX_LOADED_SNAPSHOTS {
+                return None;
+            }
+            loaded += 1;
+
+            let tree = snapshot::load(snapshots, &info.id)
+                .inspect_err(|err| log::warn!("Skipping snapshot {}: {}", info.id, err))
+                .ok()?
+                .tree;
+            let node = tree.find(path)?;
+
+            Some(SizePoint {
+                at: info.created_at,
+                bytes: tree.nodes[node as usize].size,
+            })
+        })
+        .collect();
+
+    points.sort_by_key(|point| point.at);
+    points
+}
+
+fn forecast_from(path: &Path, points: Vec<SizePoint>, available: Option<u64>) -> Forecast {
+    let fitted = fit(&points);
+    let growth = fitted.map(|(slope, _)| slope);
+    let bounds = fitted.and_then(|(slope, half_width)| {
+        half_width.map(|half_width| (slope - half_width, slope + half_width))
+    });
+
+    Forecast {
+        path: path.to_string_lossy().to_string(),
+        points,
+        growth_per_day: growth,
+        growth_low: bounds.map(|(low, _)| low),
+        growth_high: bounds.map(|(_, high)| high),
+        available_space: available,
+        days_until_full: days_until_full(available, growth),
+        days_until_full_earliest: days_until_full(available, bounds.map(|(_, high)| high)),
+        days_until_full_latest: days_until_full(available, bounds.map(|(low, _)| low)),
+    }
+}
+
+/// Fit the growth of a folder or disk across stored snapshots and project when its volume fills
+pub fn forecast(path: &str, snapshots: &Path) -> Result<Forecast, DuneError> {
+    let target = Path::new(path);
+    if !target.exists() {
+        return Err(DuneError::NotFound { path: path.into() });
+    }
+
+    let points = size_history(snapshots, snapshot::list(snapshots)?, target);
+    let forecast = forecast_from(
+        target,
+        points,
+        scanner::disk_for(target).map(|disk| disk.available_space),
+    );
+
+    log::info!(
+        "Forecast for {} from {} snapshots: {:?} bytes/day",
+        path,
+        forecast.points.len(),
+        forecast.growth_per_day
+    );
+
+    Ok(forecast)
+}
+
+/// Fill in `full_in_days` for disks expected to fill within `WARNING_DAYS`.
+/// Uses only whole-disk snapshots, whose sizes are in the snapshot headers.
+pub fn mark_filling_disks(snapshots: &Path, disks: &mut [DiskInfo]) {
+    let infos = match snapshot::list(snapshots) {
+        Ok(infos) => infos,
+        Err(err) => {
+            log::warn!("Could not list snapshots for disk forecasts: {}", err);
+            return;
+        }
+    };
+
+    for disk in disks {
+        let root = Path::new(&disk.path);
+        let mut points: Vec<SizePoint> = infos
+            .iter()
+            .filter(|info| Path::new(&info.root) == root)
+            .map(|info| SizePoint {
+                at: info.created_at,
+                bytes: info.total_size,
+            })
+            .collect();
+        points.sort_by_key(|point| point.at);
+
+        let forecast = forecast_from(root, points, Some(disk.available_space));
+        disk.full_in_days = forecast
+            .days_until_full
+            .filter(|&days| days <= WARNING_DAYS);
+    }
+}
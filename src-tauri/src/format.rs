@@ -0,0 +1,166 @@
+use crate::scan::ScanTree;
+use crate::settings::Settings;
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+
+/// How sizes are counted and labelled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    /// Powers of 1024: KiB, MiB, GiB
+    #[default]
+    Binary,
+    /// Powers of 1000: KB, MB, GB, as drive makers and macOS count
+    Decimal,
+}
+
+const BINARY_UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+const DECIMAL_UNITS: [&str; 5] = ["KB", "MB", "GB", "TB", "PB"];
+
+/// Decimal mark and thousands separator of a locale
+struct NumberStyle {
+    decimal: char,
+    group: char,
+}
+
+/// Covers the common locales; anything else gets the English style
+fn number_style(locale: &str) -> NumberStyle {
+    let locale = locale.to_ascii_lowercase();
+    let mut parts = locale.split(['-', '_', '.', '@']);
+    let language = parts.next().unwrap_or_default();
+    let region = parts.next().unwrap_or_default();
+
+    let (decimal, group) = match (language, region) {
+        ("de" | "it" | "fr" | "rm", "ch" | "li") => ('.', '\''),
+        (
+            "de" | "nl" | "da" | "it" | "es" | "pt" | "id" | "tr" | "el" | "ro" | "hr" | "sl"
+            | "sr" | "vi" | "is",
+            _,
+        ) => (',', '.'),
+        (
+            "fr" | "ru" | "uk" | "be" | "pl" | "cs" | "sk" | "sv" | "nb" | "nn" | "no" | "fi"
+            | "hu" | "bg" | "et" | "lv" | "lt" | "kk",
+            _,
+        ) => (',', '\u{a0}'),
+        _ => ('.', ','),
+    };
+
+    NumberStyle { decimal, group }
+}
+
+fn group_digits(digits: &str, group: char) -> String {
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3 * 2);
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index) % 3 == 0 {
+            grouped.push(group);
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// `1234567` as `1,234,567` or `1.234.567`, by locale
+pub fn format_count(count: u64, locale: &str) -> String {
+    group_digits(&count.to_string(), number_style(locale).group)
+}
+
+/// Bytes with one decimal in the largest unit that keeps the value at 1 or more,
+/// like `12.3 GiB`, `12,3 GB` or `512 B`
+pub fn format_size(bytes: u64, units: Units, locale: &str) -> String {
+    let (base, labels) = match units {
+        Units::Binary => (1024.0, BINARY_UNITS),
+        Units::Decimal => (1000.0, DECIMAL_UNITS),
+    };
+
+    let mut value = bytes as f64;
+    let mut label = None;
+    for next in labels {
+        if value < base {
+            break;
+        }
+        value /= base;
+        label = Some(next);
+    }
+    let Some(label) = label else {
+        return format!("{} B", bytes);
+    };
+
+    let style = number_style(locale);
+    let fixed = format!("{:.1}", value);
+    let (whole, fraction) = fixed.split_once('.').unwrap_or((&fixed, "0"));
+
+    format!(
+        "{}{}{} {}",
+        group_digits(whole, style.group),
+        style.decimal,
+        fraction,
+        label
+    )
+}
+
+/// The locale numbers are written in when `Settings.locale` is empty
+fn system_locale() -> String {
+    ["LC_ALL", "LC_NUMERIC", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty() && value != "C" && value != "POSIX")
+        .unwrap_or_else(|| "en-US".into())
+}
+
+struct Formatter {
+    units: Units,
+    locale: String,
+}
+
+/// What `size` and `count` use, set from the settings at startup and on every save
+static FORMATTER: RwLock<Formatter> = RwLock::new(Formatter {
+    units: Units::Binary,
+    locale: String::new(),
+});
+
+pub fn configure(settings: &Settings) {
+    let locale = if settings.locale.trim().is_empty() {
+        system_locale()
+    } else {
+        settings.locale.trim().to_string()
+    };
+
+    let mut formatter = FORMATTER.write().unwrap();
+    formatter.units = settings.units;
+    formatter.locale = locale;
+}
+
+/// A size in the configured units and locale
+pub fn size(bytes: u64) -> String {
+    let formatter = FORMATTER.read().unwrap();
+    format_size(bytes, formatter.units, &formatter.locale)
+}
+
+/// A count in the configured locale
+pub fn count(count: u64) -> String {
+    format_count(count, &FORMATTER.read().unwrap().locale)
+}
+
+/// Totals of a scanned tree, written out so the UI shows exactly what Rust would
+#[derive(Debug, Clone, Serialize)]
+pub struct TreeSummary {
+    pub total_size_text: String,
+    pub file_count_text: String,
+    /// Space taken with clones counted once, when the scan looked it up
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unique_allocated_text: Option<String>,
+}
+
+impl TreeSummary {
+    pub fn of(tree: &ScanTree) -> Self {
+        let root = tree.nodes.first();
+
+        Self {
+            total_size_text: size(root.map_or(0, |node| node.size)),
+            file_count_text: count(root.map_or(0, |node| node.file_count)),
+            unique_allocated_text: tree
+                .allocation
+                .map(|allocation| size(allocation.unique_allocated)),
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use crate::error::DuneError;
+use crate::scan::ScanHandle;
+use crate::scanner::{self, DirEnumerator, EntryKind};
+use rayon::prelude::*;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Instant;
+
+/// A NUL byte this early marks a file as binary, as git and grep do
+const BINARY_SNIFF_BYTES: usize = 8_000;
+/// Lines are cut to this many characters so minified files stay readable
+const MAX_LINE_CHARS: usize = 400;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GrepOptions {
+    /// Treat the pattern as a regular expression instead of literal text
+    pub regex: bool,
+    pub case_sensitive: bool,
+    /// Lines shown before and after each match
+    pub context_lines: usize,
+    pub include_hidden: bool,
+    /// Larger files are skipped
+    pub max_file_mb: u64,
+    /// Stop after this many matches in total
+    pub max_matches: usize,
+    pub max_matches_per_file: usize,
+}
+
+impl Default for GrepOptions {
+    fn default() -> Self {
+        Self {
+            regex: false,
+            case_sensitive: false,
+            context_lines: 2,
+            include_hidden: false,
+            max_file_mb: 32,
+            max_matches: 1_000,
+            max_matches_per_file: 100,
+        }
+    }
+}
+
+/// Payload of `grep://started`; cancel with `cancel_scan(grep_id)`
+#[derive(Debug, Clone, Serialize)]
+pub struct GrepStarted {
+    pub grep_id: u64,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GrepMatch {
+    /// 1-based
+    pub line_number: u64,
+    pub line: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+/// Payload of `grep://results`: the matches in one file
+#[derive(Debug, Clone, Serialize)]
+pub struct GrepFile {
+    pub grep_id: u64,
+    pub path: String,
+    pub size: u64,
+    pub matches: Vec<GrepMatch>,
+    /// Hit `max_matches_per_file`
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GrepSummary {
+    pub grep_id: u64,
+    pub files_searched: u64,
+    pub files_matched: u64,
+    pub matches: u64,
+    pub skipped_binary: u64,
+    pub skipped_large: u64,
+    pub unreadable: u64,
+    /// Stopped at `max_matches`
+    pub truncated: bool,
+    /// False if some folders could not be read
+    pub complete: bool,
+    pub elapsed_ms: u64,
+}
+
+struct FileInfo {
+    path: PathBuf,
+    len: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    searched: AtomicU64,
+    matched: AtomicU64,
+    binary: AtomicU64,
+    large: AtomicU64,
+    unreadable: AtomicU64,
+}
+
+fn collect_files(
+    enumerator: &dyn DirEnumerator,
+    path: &Path,
+    include_hidden: bool,
+    files: &mut Vec<FileInfo>,
+) -> bool {
+    let Ok(listing) = enumerator.read_dir(path) else {
+        return false;
+    };
+    let mut complete = listing.complete;
+
+    for entry in listing.entries {
+        if !include_hidden && scanner::is_hidden_name(&entry.name) {
+            continue;
+        }
+
+        match entry.kind {
+            EntryKind::File => files.push(FileInfo {
+                path: path.join(&entry.name),
+                len: entry.len,
+            }),
+            EntryKind::Dir => {
+                complete &=
+                    collect_files(enumerator, &path.join(&entry.name), include_hidden, files)
+            }
+            _ => {}
+        }
+    }
+
+    complete
+}
+
+fn shorten(line: &str) -> String {
+    match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+enum Searched {
+    Matches(Vec<GrepMatch>, bool),
+    Binary,
+    Unreadable,
+}
+
+fn search_file(path: &Path, regex: &Regex, options: &GrepOptions, budget: usize) -> Searched {
+    let mut data = Vec::new();
+    if let Err(err) = File::open(path).and_then(|mut file| file.read_to_end(&mut data)) {
+        log::debug!("Could not read {}: {}", path.display(), err);
+        return Searched::Unreadable;
+    }
+
+    if data[..data.len().min(BINARY_SNIFF_BYTES)].contains(&0) {
+        return Searched::Binary;
+    }
+
+    let text = String::from_utf8_lossy(&data);
+    let lines: Vec<&str> = text.lines().collect();
+    let limit = options.max_matches_per_file.min(budget);
+    let context = options.context_lines;
+    let mut matches = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        if !regex.is_match(line) {
+            continue;
+        }
+        if matches.len() == limit {
+            return Searched::Matches(matches, true);
+        }
+
+        matches.push(GrepMatch {
+            line_number: index as u64 + 1,
+            line: shorten(line),
+            before: lines[index.saturating_sub(context)..index]
+                .iter()
+                .map(|line| shorten(line))
+                .collect(),
+            after: lines[index + 1..(index + 1 + context).min(lines.len())]
+                .iter()
+                .map(|line| shorten(line))
+                .collect(),
+        });
+    }
+
+    Searched::Matches(matches, false)
+}
+
+fn build_regex(pattern: &str, options: &GrepOptions) -> Result<Regex, DuneError> {
+    let pattern = if options.regex {
+        pattern.to_string()
+    } else {
+        regex::escape(pattern)
+    };
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .build()
+        .map_err(|err| DuneError::InvalidInput(err.to_string()))
+}
+
+/// Grep the text files under the handle's root, handing over each file's matches as it finishes
+pub fn grep(
+    handle: &ScanHandle,
+    pattern: &str,
+    options: &GrepOptions,
+    on_file: &(dyn Fn(GrepFile) + Sync),
+) -> Result<GrepSummary, DuneError> {
+    let started = Instant::now();
+    let root = Path::new(&handle.root);
+    if pattern.is_empty() {
+        return Err(DuneError::InvalidInput("Empty search pattern".into()));
+    }
+    let regex = build_regex(pattern, options)?;
+
+    let mut files = Vec::new();
+    let mut complete = true;
+    if root.is_file() {
+        let len = root.metadata().map_err(|e| DuneError::io(root, e))?.len();
+        files.push(FileInfo {
+            path: root.to_path_buf(),
+            len,
+        });
+    } else if root.is_dir() {
+        complete = collect_files(
+            scanner::default_enumerator(),
+            root,
+            options.include_hidden,
+            &mut files,
+        );
+    } else {
+        return Err(DuneError::NotFound {
+            path: handle.root.clone(),
+        });
+    }
+
+    let max_bytes = options.max_file_mb.saturating_mul(1024 * 1024);
+    let counters = Counters::default();
+    let matches = AtomicUsize::new(0);
+
+    files.par_iter().for_each(|file| {
+        let found = matches.load(Ordering::Relaxed);
+        if handle.is_cancelled() || found >= options.max_matches {
+            return;
+        }
+        if file.len > max_bytes {
+            counters.large.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        counters.searched.fetch_add(1, Ordering::Relaxed);
+        match search_file(&file.path, &regex, options, options.max_matches - found) {
+            Searched::Matches(mut hits, truncated) if !hits.is_empty() => {
+                let before = matches.fetch_add(hits.len(), Ordering::Relaxed);
+                hits.truncate(options.max_matches.saturating_sub(before));
+                if hits.is_empty() {
+                    return;
+                }
+
+                counters.matched.fetch_add(1, Ordering::Relaxed);
+                on_file(GrepFile {
+                    grep_id: handle.id,
+                    path: file.path.to_string_lossy().to_string(),
+                    size: file.len,
+                    matches: hits,
+                    truncated,
+                });
+            }
+            Searched::Matches(..) => {}
+            Searched::Binary => {
+                counters.binary.fetch_add(1, Ordering::Relaxed);
+            }
+            Searched::Unreadable => {
+                counters.unreadable.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    });
+
+    if handle.is_cancelled() {
+        log::info!("Grep {} of {} cancelled", handle.id, handle.root);
+        return Err(DuneError::Cancelled);
+    }
+
+    let found = matches.load(Ordering::Relaxed);
+    let summary = GrepSummary {
+        grep_id: handle.id,
+        files_searched: counters.searched.into_inner(),
+        files_matched: counters.matched.into_inner(),
+        matches: found.min(options.max_matches) as u64,
+        skipped_binary: counters.binary.into_inner(),
+        skipped_large: counters.large.into_inner(),
+        unreadable: counters.unreadable.into_inner(),
+        truncated: found >= options.max_matches,
+        complete,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    };
+
+    log::info!(
+        "Grep {} for {:?} under {}: {} matches in {} files",
+        handle.id,
+        pattern,
+        handle.root,
+        summary.matches,
+        summary.files_matched
+    );
+
+    Ok(summary)
+}
@@ -0,0 +1,240 @@
+use crate::error::DuneError;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Windows paths this long or longer break Explorer and most older tools
+pub const MAX_PATH: usize = 260;
+/// Longest name NTFS, APFS and ext4 all take; NTFS counts UTF-16 units, the others bytes
+pub const MAX_NAME: usize = 255;
+/// Problems kept before the report stops listing more
+const MAX_PROBLEMS: usize = 10_000;
+/// Names Windows keeps for devices, with or without an extension
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+/// Characters Windows refuses in names, on top of control characters
+const RESERVED_CHARACTERS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Problem {
+    /// The full path is `length` UTF-16 units, past `MAX_PATH`
+    LongPath { length: usize },
+    /// The name alone is longer than `MAX_NAME`
+    LongName { length: usize },
+    /// `CON`, `NUL`, `COM1` and the like, which Windows will not create
+    ReservedName,
+    /// Characters Windows refuses, such as `:` or `?`
+    ReservedCharacter { character: char },
+    /// Windows drops a trailing dot or space, so the copy gets another name
+    TrailingDotOrSpace,
+    /// Differs from `other` only by case, so one overwrites the other on macOS and Windows
+    CaseCollision { other: String },
+    /// The app could not read it
+    Unreadable,
+    /// Nothing to name it with, or only `.` or `..`
+    Empty,
+    /// A sibling has exactly this name already
+    AlreadyExists,
+    /// Anyone can write to it; folders without the sticky bit only
+    #[cfg(unix)]
+    WorldWritable,
+    /// Its owner has no read permission
+    #[cfg(unix)]
+    OwnerCannotRead,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PathProblem {
+    pub path: String,
+    #[serde(flatten)]
+    pub problem: Problem,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub path: String,
+    /// Files and folders looked at
+    pub checked: u64,
+    pub problems: Vec<PathProblem>,
+    /// Stopped listing problems after `MAX_PROBLEMS`
+    pub truncated: bool,
+}
+
+/// Ways `name` will not survive a copy to another platform, whatever platform it is on
+pub fn name_problems(name: &str) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    let length = name.len().max(name.encode_utf16().count());
+    if length > MAX_NAME {
+        problems.push(Problem::LongName { length });
+    }
+    problems.extend(windows_name_problems(name));
+
+    problems
+}
+
+/// Reserved names and characters, and the trailing dots and spaces Windows drops
+pub fn windows_name_problems(name: &str) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        problems.push(Problem::ReservedName);
+    }
+
+    if let Some(character) = name
+        .chars()
+        .find(|&c| RESERVED_CHARACTERS.contains(&c) || c.is_control())
+    {
+        problems.push(Problem::ReservedCharacter { character });
+    }
+
+    if name.ends_with('.') || name.ends_with(' ') {
+        problems.push(Problem::TrailingDotOrSpace);
+    }
+
+    problems
+}
+
+#[cfg(unix)]
+fn permission_problems(metadata: &fs::Metadata) -> Vec<Problem> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = metadata.permissions().mode();
+    let mut problems = Vec::new();
+    if !metadata.file_type().is_symlink() {
+        let sticky = mode & 0o1000 != 0;
+        if mode & 0o002 != 0 && !(metadata.is_dir() && sticky) {
+            problems.push(Problem::WorldWritable);
+        }
+        if mode & 0o400 == 0 {
+            problems.push(Problem::OwnerCannotRead);
+        }
+    }
+
+    problems
+}
+
+/// Windows ACLs have no single owner or world bit to check
+#[cfg(not(unix))]
+fn permission_problems(_metadata: &fs::Metadata) -> Vec<Problem> {
+    Vec::new()
+}
+
+struct Report {
+    checked: u64,
+    problems: Vec<PathProblem>,
+    truncated: bool,
+}
+
+impl Report {
+    fn add(&mut self, path: &Path, problem: Problem) {
+        if self.problems.len() == MAX_PROBLEMS {
+            self.truncated = true;
+            return;
+        }
+        self.problems.push(PathProblem {
+            path: path.to_string_lossy().into_owned(),
+            problem,
+        });
+    }
+
+    fn check(&mut self, path: &Path, metadata: &fs::Metadata) {
+        self.checked += 1;
+
+        let length = path.as_os_str().to_string_lossy().encode_utf16().count();
+        if length >= MAX_PATH {
+            self.add(path, Problem::LongPath { length });
+        }
+        if let Some(name) = path.file_name() {
+            for problem in name_problems(&name.to_string_lossy()) {
+                self.add(path, problem);
+            }
+        }
+        for problem in permission_problems(metadata) {
+            self.add(path, problem);
+        }
+    }
+
+    fn walk(&mut self, dir: &Path) {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                log::debug!("Could not read {}: {}", dir.display(), err);
+                self.add(dir, Problem::Unreadable);
+                return;
+            }
+        };
+
+        // Lowercased name to the first sibling seen with it
+        let mut folded: HashMap<String, String> = HashMap::new();
+        let mut folders = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            match folded.get(&name.to_lowercase()) {
+                Some(other) => self.add(
+                    &path,
+                    Problem::CaseCollision {
+                        other: other.clone(),
+                    },
+                ),
+                None => {
+                    folded.insert(name.to_lowercase(), name);
+                }
+            }
+
+            match fs::symlink_metadata(&path) {
+                Ok(metadata) => {
+                    self.check(&path, &metadata);
+                    if metadata.is_dir() {
+                        folders.push(path);
+                    }
+                }
+                Err(_) => self.add(&path, Problem::Unreadable),
+            }
+        }
+
+        for folder in folders {
+            self.walk(&folder);
+        }
+    }
+}
+
+/// Everything under `path` that will break common tools or a copy to another platform:
+/// long paths, unportable names, case collisions and odd permissions
+pub fn health_report(path: &str) -> Result<HealthReport, DuneError> {
+    let root = Path::new(path);
+    let metadata = fs::symlink_metadata(root).map_err(|e| DuneError::io(root, e))?;
+    if !metadata.is_dir() {
+        return Err(DuneError::NotADirectory { path: path.into() });
+    }
+
+    let mut report = Report {
+        checked: 0,
+        problems: Vec::new(),
+        truncated: false,
+    };
+    report.walk(root);
+
+    log::info!(
+        "Health report of {}: {} problems in {} entries",
+        path,
+        report.problems.len(),
+        report.checked
+    );
+
+    Ok(HealthReport {
+        path: path.to_string(),
+        checked: report.checked,
+        problems: report.problems,
+        truncated: report.truncated,
+    })
+}
@@ -0,0 +1,136 @@
+use crate::settings::Settings;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+/// Language strings fall back to when the chosen one lacks them
+const FALLBACK: &str = "en";
+
+/// Catalogs built into the binary: language code, its own name, and `key: template` JSON
+const CATALOGS: &[(&str, &str, &str)] = &[
+    ("en", "English", include_str!("../locales/en.json")),
+    ("de", "Deutsch", include_str!("../locales/de.json")),
+    ("es", "Español", include_str!("../locales/es.json")),
+    ("fr", "Français", include_str!("../locales/fr.json")),
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Language {
+    pub code: String,
+    pub name: String,
+}
+
+type Catalog = HashMap<String, String>;
+
+fn catalogs() -> &'static HashMap<&'static str, Catalog> {
+    static PARSED: OnceLock<HashMap<&'static str, Catalog>> = OnceLock::new();
+    PARSED.get_or_init(|| {
+        CATALOGS
+            .iter()
+            .filter_map(|&(code, _, json)| match serde_json::from_str(json) {
+                Ok(catalog) => Some((code, catalog)),
+                Err(err) => {
+                    log::error!("Locale catalog {} is not valid: {}", code, err);
+                    None
+                }
+            })
+            .collect()
+    })
+}
+
+/// `de` from `de-DE`, `de_AT.UTF-8` or `DE`, when there is a catalog for it
+fn supported(tag: &str) -> Option<&'static str> {
+    let language = tag
+        .split(['-', '_', '.', '@'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    CATALOGS
+        .iter()
+        .map(|&(code, _, _)| code)
+        .find(|&code| code == language)
+}
+
+/// The language messages are written in when `Settings.language` is empty: the one of
+/// `Settings.locale`, then the system's
+fn system_language(settings: &Settings) -> &'static str {
+    let environment = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty() && value != "C" && value != "POSIX");
+    [Some(settings.locale.trim().to_string()), environment]
+        .into_iter()
+        .flatten()
+        .find_map(|tag| supported(&tag))
+        .unwrap_or(FALLBACK)
+}
+
+/// What `tr` uses, set from the settings at startup and on every save
+static LANGUAGE: RwLock<&'static str> = RwLock::new(FALLBACK);
+
+pub fn configure(settings: &Settings) {
+    let language = supported(settings.language.trim()).unwrap_or_else(|| {
+        if !settings.language.trim().is_empty() {
+            log::warn!("No strings for language {}", settings.language);
+        }
+        system_language(settings)
+    });
+    *LANGUAGE.write().unwrap() = language;
+}
+
+/// The language `tr` writes in
+pub fn current() -> &'static str {
+    *LANGUAGE.read().unwrap()
+}
+
+/// Languages with a built-in catalog, for the settings
+pub fn languages() -> Vec<Language> {
+    CATALOGS
+        .iter()
+        .map(|&(code, name, _)| Language {
+            code: code.into(),
+            name: name.into(),
+        })
+        .collect()
+}
+
+/// The message `key` in the configured language, with each `{name}` in it replaced by
+/// the matching value of `args`; English when the language lacks it, the key when
+/// English does too
+pub fn tr(key: &str, args: &[(&str, &str)]) -> String {
+    let language = *LANGUAGE.read().unwrap();
+    let catalogs = catalogs();
+    let Some(template) = [language, FALLBACK]
+        .iter()
+        .find_map(|code| catalogs.get(code).and_then(|catalog| catalog.get(key)))
+    else {
+        log::debug!("No string for {}", key);
+        return key.to_string();
+    };
+
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template.as_str();
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after.find('}').and_then(|end| {
+            let name = &after[..end];
+            args.iter()
+                .find(|(arg, _)| *arg == name)
+                .map(|(_, value)| (*value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                out.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+
+    out
+}
@@ -0,0 +1,296 @@
+use crate::error::DuneError;
+use crate::scan::{NameInterner, ScanTree, TreeNode};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Reports from other tools that `import_scan` can turn into a scan tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportFormat {
+    /// `du -ab` output: byte size, tab, path, for every file and folder
+    Du,
+    /// WinDirStat's CSV export, with full paths in the name column
+    Windirstat,
+}
+
+/// One line of a report
+struct Row {
+    path: String,
+    size: u64,
+    /// The report says it is a folder even if nothing under it was listed
+    is_dir: bool,
+}
+
+/// `du -ab` lines; `du --time` adds a column between size and path, which is ignored
+fn parse_du(text: &str) -> Result<Vec<Row>, DuneError> {
+    let mut rows = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (size, rest) = line.split_once('\t').ok_or_else(|| {
+            DuneError::InvalidInput(format!("Line {} is not `size<TAB>path`", number + 1))
+        })?;
+        let size = size.trim().parse().map_err(|_| {
+            DuneError::InvalidInput(format!(
+                "Line {} has no byte size ({:?}); export with `du -ab`",
+                number + 1,
+                size
+            ))
+        })?;
+        let path = match rest.split_once('\t') {
+            Some((_time, path)) => path,
+            None => rest,
+        };
+
+        rows.push(Row {
+            path: path.to_string(),
+            size,
+            is_dir: false,
+        });
+    }
+
+    Ok(rows)
+}
+
+/// Split one CSV record, honouring quotes and doubled quotes inside them
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().unwrap();
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => field.push(c),
+        }
+    }
+
+    fields
+}
+
+/// Sizes may carry thousands separators of any locale
+fn parse_grouped_number(value: &str) -> Option<u64> {
+    let digits: String = value
+        .chars()
+        .filter(|c| !matches!(c, ',' | '.' | ' ' | '\u{a0}' | '\''))
+        .collect();
+    digits.parse().ok()
+}
+
+fn parse_windirstat(text: &str) -> Result<Vec<Row>, DuneError> {
+    let mut lines = text.trim_start_matches('\u{feff}').lines();
+    let header = lines
+        .next()
+        .map(csv_fields)
+        .ok_or_else(|| DuneError::InvalidInput("Empty WinDirStat report".into()))?;
+    let header: Vec<String> = header.iter().map(|name| name.to_lowercase()).collect();
+
+    let column = |matches: &dyn Fn(&str) -> bool| header.iter().position(|name| matches(name));
+    let name_column = column(&|name| name == "name" || name == "path")
+        .ok_or_else(|| DuneError::InvalidInput("WinDirStat report has no Name column".into()))?;
+    // Logical sizes match what the rest of the app shows; physical ones come second
+    let size_column = column(&|name| name.contains("size") && name.contains("logical"))
+        .or_else(|| column(&|name| name.contains("size")))
+        .ok_or_else(|| DuneError::InvalidInput("WinDirStat report has no size column".into()))?;
+
+    let mut rows = Vec::new();
+    for line in lines.filter(|line| !line.trim().is_empty()) {
+        let fields = csv_fields(line);
+        let (Some(path), Some(size)) = (fields.get(name_column), fields.get(size_column)) else {
+            continue;
+        };
+        let path = path.trim();
+        let leaf = path.rsplit('\\').next().unwrap_or(path);
+        // `<Files>`, `<Free Space>` and `<Unknown>` are WinDirStat's own grouping items
+        if leaf.starts_with('<') && leaf.ends_with('>') {
+            continue;
+        }
+        let Some(size) = parse_grouped_number(size) else {
+            continue;
+        };
+
+        rows.push(Row {
+            path: path.to_string(),
+            size,
+            is_dir: path.ends_with('\\'),
+        });
+    }
+
+    Ok(rows)
+}
+
+/// Drop trailing separators, except from a root such as `/` or `C:\`
+fn trim_separator(path: &str, separator: char) -> &str {
+    let trimmed = path.trim_end_matches(separator);
+    if trimmed.len() == path.len() {
+        trimmed
+    } else if trimmed.is_empty() || trimmed.ends_with(':') {
+        &path[..trimmed.len() + 1]
+    } else {
+        trimmed
+    }
+}
+
+/// Build a tree from rows keyed by full path. Folders keep the larger of their
+/// reported total and the sum of what was listed under them, so reports that skip
+/// small files still add up.
+fn build_tree(scan_id: u64, rows: &[Row], separator: char) -> ScanTree {
+    // The root is the shortest path; du prints it last, WinDirStat first
+    let root = rows
+        .iter()
+        .map(|row| trim_separator(&row.path, separator))
+        .min_by_key(|path| path.len())
+        .unwrap_or_default()
+        .to_string();
+    let prefix = if root.ends_with(separator) {
+        root.clone()
+    } else {
+        format!("{}{}", root, separator)
+    };
+
+    let mut relative: Vec<(Vec<&str>, &Row)> = Vec::with_capacity(rows.len());
+    let mut outside = 0;
+    for row in rows {
+        let path = trim_separator(&row.path, separator);
+        if path == root {
+            relative.push((Vec::new(), row));
+        } else if let Some(rest) = path.strip_prefix(&prefix) {
+            let parts = rest.split(separator).filter(|part| !part.is_empty());
+            relative.push((parts.collect(), row));
+        } else {
+            outside += 1;
+        }
+    }
+    if outside > 0 {
+        log::warn!("Skipped {} imported entries outside {}", outside, root);
+    }
+    // Parents before children, as the tree requires
+    relative.sort_by_key(|(parts, _)| parts.len());
+
+    let root_name = root
+        .trim_end_matches(separator)
+        .rsplit(separator)
+        .next()
+        .filter(|name| !name.is_empty())
+        .unwrap_or(&root);
+    let mut nodes = vec![TreeNode {
+        parent: None,
+        name: Arc::from(root_name),
+        is_dir: true,
+        size: 0,
+        file_count: 0,
+        size_complete: true,
+        modified: None,
+    }];
+    let mut reported = vec![0];
+    let mut names = NameInterner::default();
+    let mut indices: HashMap<&[&str], u32> = HashMap::from([(&[][..], 0)]);
+
+    for (parts, row) in &relative {
+        if parts.is_empty() {
+            reported[0] = reported[0].max(row.size);
+            continue;
+        }
+        if indices.contains_key(&parts[..]) {
+            continue;
+        }
+
+        // Folders the report left out are filled in, sized by their contents
+        let mut parent = 0;
+        for depth in 1..=parts.len() {
+            let is_leaf = depth == parts.len();
+            if !is_leaf {
+                if let Some(&index) = indices.get(&parts[..depth]) {
+                    parent = index;
+                    continue;
+                }
+            }
+
+            nodes[parent as usize].is_dir = true;
+            let index = nodes.len() as u32;
+            nodes.push(TreeNode {
+                parent: Some(parent),
+                name: names.intern(parts[depth - 1]),
+                is_dir: !is_leaf || row.is_dir,
+                size: 0,
+                file_count: 0,
+                size_complete: true,
+                modified: None,
+            });
+            reported.push(if is_leaf { row.size } else { 0 });
+            indices.insert(&parts[..depth], index);
+            parent = index;
+        }
+    }
+
+    for index in (0..nodes.len()).rev() {
+        let node = &mut nodes[index];
+        if node.is_dir {
+            node.size = node.size.max(reported[index]);
+        } else {
+            node.size = reported[index];
+            node.file_count = 1;
+        }
+
+        let (size, file_count) = (node.size, node.file_count);
+        if let Some(parent) = node.parent {
+            let parent = &mut nodes[parent as usize];
+            parent.size = parent.size.saturating_add(size);
+            parent.file_count += file_count;
+        }
+    }
+
+    ScanTree {
+        scan_id,
+        root,
+        nodes,
+        allocation: None,
+        subvolumes: Vec::new(),
+        ignored: Vec::new(),
+        small_items: Vec::new(),
+        skipped: Vec::new(),
+        stats: None,
+        elevated: false,
+    }
+}
+
+/// Load a report written by another tool as a finished scan tree
+pub fn import_scan(scan_id: u64, path: &Path, format: ImportFormat) -> Result<ScanTree, DuneError> {
+    let bytes = fs::read(path).map_err(|e| DuneError::io(path, e))?;
+    let text = String::from_utf8_lossy(&bytes);
+    let (rows, separator) = match format {
+        ImportFormat::Du => (parse_du(&text)?, '/'),
+        ImportFormat::Windirstat => (parse_windirstat(&text)?, '\\'),
+    };
+    if rows.is_empty() {
+        return Err(DuneError::InvalidInput(format!(
+            "No entries found in {}",
+            path.display()
+        )));
+    }
+
+    let tree = build_tree(scan_id, &rows, separator);
+    log::info!(
+        "Imported {} from {}: {} nodes, {} bytes",
+        path.display(),
+        match format {
+            ImportFormat::Du => "du",
+            ImportFormat::Windirstat => "WinDirStat",
+        },
+        tree.nodes.len(),
+        tree.nodes[0].size
+    );
+
+    Ok(tree)
+}
@@ -0,0 +1,140 @@
+use crate::error::DuneError;
+use crate::scanner::{self, DirEntryInfo, EntryKind, Hidden, Visitor};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::path::Path;
+
+/// Folders holding at least this many entries directly are reported as dense
+const DENSE_DIR_MIN_ENTRIES: u64 = 1_000;
+const MAX_DENSE_DIRS: usize = 50;
+
+/// Entry count for one child of the requested folder
+#[derive(Debug, Clone, Serialize)]
+pub struct InodeEntry {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    /// Files, folders, and links inside, each taking an inode; hard links count once per name
+    pub entries: u64,
+    pub complete: bool,
+}
+
+/// A folder with a pathological number of direct entries, e.g. a maildir or cache shard
+#[derive(Debug, Clone, Serialize)]
+pub struct DenseDir {
+    pub path: String,
+    pub entries: u64,
+    /// Entries in the whole subtree, this folder's own included
+    pub subtree_entries: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InodeReport {
+    pub path: String,
+    pub total_entries: u64,
+    /// Children, most entries first
+    pub children: Vec<InodeEntry>,
+    /// Most entries first
+    pub dense_dirs: Vec<DenseDir>,
+    pub total_inodes: Option<u64>,
+    pub free_inodes: Option<u64>,
+    pub complete: bool,
+}
+
+#[derive(Default)]
+struct Walk {
+    entries: u64,
+    dense: Vec<DenseDir>,
+    complete: bool,
+    /// Per folder being walked, its direct entries so far and `entries` when it was entered
+    open: Vec<(u64, u64)>,
+}
+
+/// Counts every entry; hidden ones too, since they use inodes all the same
+impl Visitor for Walk {
+    fn entry(&mut self, _path: &Path, _entry: &DirEntryInfo) -> bool {
+        self.entries += 1;
+        if let Some((direct, _)) = self.open.last_mut() {
+            *direct += 1;
+        }
+        true
+    }
+
+    fn enter(&mut self, _dir: &Path) {
+        self.open.push((0, self.entries));
+    }
+
+    fn leave(&mut self, dir: &Path) {
+        let Some((direct, entered_at)) = self.open.pop() else {
+            return;
+        };
+        if direct >= DENSE_DIR_MIN_ENTRIES {
+            self.dense.push(DenseDir {
+                path: dir.to_string_lossy().to_string(),
+                entries: direct,
+                subtree_entries: self.entries - entered_at,
+            });
+        }
+    }
+}
+
+/// Where the inodes under `path` go, and which folders hold an unusual number of entries
+pub fn inode_report(path: &str) -> Result<InodeReport, DuneError> {
+    let dir_path = scanner::require_dir(path)?;
+    let enumerator = scanner::default_enumerator();
+    let listing = enumerator
+        .read_dir(dir_path)
+        .map_err(|e| DuneError::io(dir_path, e))?;
+    let direct = listing.entries.len() as u64;
+
+    let walked: Vec<(InodeEntry, Vec<DenseDir>)> = listing
+        .entries
+        .into_par_iter()
+        .map(|entry| {
+            let entry_path = dir_path.join(&entry.name);
+            let is_dir = entry.kind == EntryKind::Dir;
+            let mut walked = Walk::default();
+            walked.complete =
+                !is_dir || scanner::walk(enumerator, &entry_path, Hidden::Include, &mut walked);
+
+            (
+                InodeEntry {
+                    name: entry.name.to_string_lossy().to_string(),
+                    path: entry_path.to_string_lossy().to_string(),
+                    is_dir,
+                    entries: walked.entries,
+                    complete: walked.complete,
+                },
+                walked.dense,
+            )
+        })
+        .collect();
+
+    let (mut children, dense): (Vec<InodeEntry>, Vec<Vec<DenseDir>>) = walked.into_iter().unzip();
+    let total_entries = direct + children.iter().map(|child| child.entries).sum::<u64>();
+
+    let mut dense_dirs: Vec<DenseDir> = dense.into_iter().flatten().collect();
+    if direct >= DENSE_DIR_MIN_ENTRIES {
+        dense_dirs.push(DenseDir {
+            path: path.to_string(),
+            entries: direct,
+            subtree_entries: total_entries,
+        });
+    }
+    dense_dirs.sort_by_key(|dir| std::cmp::Reverse(dir.entries));
+    dense_dirs.truncate(MAX_DENSE_DIRS);
+
+    children.sort_by_key(|child| std::cmp::Reverse(child.entries));
+
+    let disk = scanner::disk_for(dir_path);
+
+    Ok(InodeReport {
+        path: path.to_string(),
+        total_entries,
+        complete: listing.complete && children.iter().all(|child| child.complete),
+        children,
+        dense_dirs,
+        total_inodes: disk.as_ref().and_then(|disk| disk.total_inodes),
+        free_inodes: disk.as_ref().and_then(|disk| disk.free_inodes),
+    })
+}
@@ -0,0 +1,17 @@
+use crate::error::DuneError;
+use serde::Serialize;
+use tauri::ipc::Response;
+
+/// Encode a command result as JSON, or as MessagePack when the caller asked for `binary`
+///
+/// MessagePack keeps field names (`to_vec_named`) so the decoded value has the same
+/// shape as the JSON one, and arrives in the webview as an `ArrayBuffer`.
+pub fn encode<T: Serialize>(value: &T, binary: bool) -> Result<Response, DuneError> {
+    if binary {
+        let bytes = rmp_serde::to_vec_named(value).map_err(DuneError::internal)?;
+        Ok(Response::new(bytes))
+    } else {
+        let json = serde_json::to_string(value).map_err(DuneError::internal)?;
+        Ok(Response::new(json))
+    }
+}
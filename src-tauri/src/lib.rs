@@ -1,147 +1,1806 @@
+mod accessibility;
+mod age;
+mod allocation;
+mod anonymize;
+mod apfs;
+mod api;
+mod app_data;
+mod apps;
+mod audit;
+#[cfg(desktop)]
+mod background;
+mod benchmark;
+mod browsers;
+mod budgets;
+mod catalogs;
+mod category;
+mod cleanup;
+mod clipboard;
+mod colors;
+mod compare;
+mod compression;
+mod console;
+mod containers;
+mod crash;
+mod debug_bundle;
+mod dialogs;
+mod disk_map;
+mod dither;
+mod eject;
+mod elevated;
+mod error;
+mod favorites;
+mod file_ops;
+mod filetype;
+mod fingerprint;
+mod forecast;
+mod format;
+mod grep;
+mod health;
+mod i18n;
+mod import;
+mod inodes;
+mod ipc;
+mod logging;
+mod logs;
+mod memory;
+mod messaging;
+mod metrics;
+mod mtp;
+mod multi;
+mod names;
+mod notes;
+mod onboarding;
+mod origin;
+mod os_search;
+mod owner;
+mod packages;
+mod power;
+mod profile;
+mod project;
+mod query;
+mod recency;
+mod reconcile;
+mod remote;
+mod removed;
+mod resolve;
+mod ring;
+mod s3;
+mod saved_searches;
+mod scan;
 mod scanner;
+mod search;
+mod service;
+mod settings;
+mod shell_integration;
+mod similar_images;
+mod smart;
+mod snapshot;
+mod sort;
+mod streams;
+mod subvolumes;
+mod system_space;
+mod tags;
+mod timemachine;
+mod tree_text;
+mod validity;
+mod verify;
+mod webhooks;
+mod wsl;
 
-use scanner::{DiskInfo, FileEntry};
-use serde::{Deserialize, Serialize};
+use api::ApiServer;
+use audit::{OperationFilter, OperationRecord};
+use budgets::BudgetStatus;
+use cleanup::{CleanupRule, Refreshed, RulePreview, Suggestion};
+use dialogs::FileFilter;
+use dither::DitherAssets;
+use error::DuneError;
+use i18n::Language;
+use import::ImportFormat;
+use notes::{Note, PathNote};
+use onboarding::OnboardingState;
+use os_search::OsSearchResult;
+use project::{OpenedProject, Project};
+use ring::{RingLevel, RingStyle, SegmentTransition};
+use saved_searches::{SavedSearch, SavedSearchInput};
+use scan::{ScanOptions, ScanStarted};
+use scanner::DiskInfo;
+use search::{SearchOptions, SearchStarted, SearchSummary};
+use service::ScannerService;
+use settings::Settings;
+use snapshot::SnapshotInfo;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Mutex;
+use tags::TaggedPath;
+use tauri::ipc::Response;
+use tauri::{Emitter, Manager};
+use tree_text::TextFormat;
+use webhooks::{Notice, Webhook, WebhookEvent};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Settings {
-    pub pixel_size: f32,
-    pub dither_strength: f32,
-    pub gloom: f32,
-    pub contrast: f32,
-}
-
-impl Default for Settings {
-    fn default() -> Self {
-        Self {
-            pixel_size: 3.0,
-            dither_strength: 0.9,
-            gloom: 0.15,
-            contrast: 0.75,
+/// Folder handed to the app from the OS context menu, waiting for the frontend to pick it up
+struct LaunchPath(Mutex<Option<String>>);
+
+#[tauri::command]
+fn load_settings() -> Settings {
+    settings::load()
+}
+
+/// Languages messages, notifications and reports can be written in
+#[tauri::command]
+fn get_languages() -> Vec<Language> {
+    i18n::languages()
+}
+
+/// Dither threshold textures and the theme palette for the shader, from unsaved `settings`
+/// as the user edits them
+#[tauri::command]
+async fn generate_dither_assets(settings: Settings) -> Result<DitherAssets, DuneError> {
+    run_blocking(move || dither::generate(&settings)).await
+}
+
+#[tauri::command]
+fn save_settings(app: tauri::AppHandle, mut settings: Settings) -> Result<(), DuneError> {
+    settings.performance.validate()?;
+    cleanup::validate(&settings.cleanup_rules)?;
+    api::ensure_token(&mut settings.api, &settings::load().api)?;
+    settings::save(&settings).inspect_err(|err| log::error!("Saving settings failed: {}", err))?;
+    log::info!("Settings saved");
+
+    format::configure(&settings);
+    #[cfg(desktop)]
+    let language = i18n::current();
+    i18n::configure(&settings);
+    elevated::configure(&settings);
+    app.state::<ScannerService>().apply_settings(&settings);
+    app.state::<ApiServer>().apply(&app, &settings.api)?;
+
+    #[cfg(desktop)]
+    {
+        background::set_launch_at_login(&app, settings.background.launch_at_login)?;
+        if i18n::current() != language {
+            background::refresh_tray(&app).map_err(DuneError::internal)?;
         }
     }
+
+    Ok(())
 }
 
-fn get_settings_path() -> Option<PathBuf> {
-    dirs::config_dir().map(|p| p.join("spaceringscene").join("settings.json"))
+/// Replace the HTTP API token, restarting the server if it runs; returns the new token
+#[tauri::command]
+fn regenerate_api_token(app: tauri::AppHandle) -> Result<String, DuneError> {
+    let mut settings = settings::load();
+    settings.api.token = api::generate_token()?;
+    settings::save(&settings)?;
+    app.state::<ApiServer>().apply(&app, &settings.api)?;
+
+    Ok(settings.api.token)
 }
 
+/// Post a sample notice to a webhook so its URL and template can be checked from the settings
 #[tauri::command]
-fn load_settings() -> Settings {
-    let Some(path) = get_settings_path() else {
-        return Settings::default();
+async fn test_webhook(webhook: Webhook) -> Result<(), DuneError> {
+    run_blocking(move || {
+        webhooks::send(
+            &webhook,
+            &Notice {
+                event: WebhookEvent::ScanCompleted,
+                message: i18n::tr("webhook.test", &[]),
+                fields: vec![
+                    ("name", "Example".into()),
+                    ("path", "/example".into()),
+                    ("size_bytes", "0".into()),
+                ],
+            },
+        )
+    })
+    .await
+}
+
+/// Run blocking filesystem work on the blocking pool so the IPC thread stays free
+async fn run_blocking<T, F>(work: F) -> Result<T, DuneError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, DuneError> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(work)
+        .await
+        .map_err(DuneError::internal)?
+}
+
+#[tauri::command]
+async fn get_disks(app: tauri::AppHandle) -> Result<Vec<DiskInfo>, DuneError> {
+    let snapshots = snapshot::snapshot_dir(&app)?;
+
+    run_blocking(move || {
+        let mut disks = scanner::overview_disks();
+        forecast::mark_filling_disks(&snapshots, &mut disks);
+        Ok(disks)
+    })
+    .await
+}
+
+/// `last_opened: true` adds when each file was last opened; `unopened_days` also keeps
+/// only folders and files not opened for that long. `binary: true` returns MessagePack
+/// bytes instead of JSON, see `ipc::encode`
+#[tauri::command]
+async fn list_directory(
+    app: tauri::AppHandle,
+    path: String,
+    sort: Option<sort::SortOptions>,
+    last_opened: Option<bool>,
+    unopened_days: Option<u32>,
+    attributes: Option<bool>,
+    binary: Option<bool>,
+) -> Result<Response, DuneError> {
+    let store = tags::store_path(&app)?;
+    let notes = notes::store_path(&app)?;
+
+    run_blocking(move || {
+        let mut entries = app
+            .state::<ScannerService>()
+            .list_directory(&path)
+            .inspect_err(|err| log::warn!("list_directory({}) failed: {}", path, err))?;
+        if attributes.unwrap_or(false) {
+            scanner::apply_attributes(&mut entries);
+        }
+        // Applied after the listing cache, so tagging never needs to invalidate it
+        tags::apply(&store, &mut entries)?;
+        notes::apply(&notes, &mut entries)?;
+        if last_opened.unwrap_or(false) || unopened_days.is_some() {
+            recency::apply(&mut entries);
+        }
+        if let Some(days) = unopened_days {
+            recency::retain_unopened(&mut entries, days);
+        }
+        if let Some(options) = sort {
+            sort::sort_entries(&mut entries, &options);
+        }
+
+        ipc::encode(&entries, binary.unwrap_or(false))
+    })
+    .await
+}
+
+/// Canonical path of `path`, the links on the way there and the volume holding the bytes
+#[tauri::command]
+async fn resolve_path(path: String) -> Result<resolve::ResolvedPath, DuneError> {
+    run_blocking(move || resolve::resolve(&path)).await
+}
+
+/// Sizes of one file or folder for the detail view, and where a downloaded file came
+/// from; `include_streams` also lists its extended attributes or alternate data streams
+#[tauri::command]
+async fn file_details(
+    path: String,
+    include_streams: Option<bool>,
+) -> Result<streams::FileDetails, DuneError> {
+    run_blocking(move || streams::file_details(&path, include_streams.unwrap_or(false))).await
+}
+
+#[tauri::command]
+async fn age_histogram(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<age::AgeHistogram, DuneError> {
+    run_blocking(move || app.state::<ScannerService>().age_histogram(&path)).await
+}
+
+#[tauri::command]
+async fn category_stats(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<category::CategoryStats, DuneError> {
+    run_blocking(move || app.state::<ScannerService>().category_stats(&path)).await
+}
+
+#[tauri::command]
+async fn health_report(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<health::HealthReport, DuneError> {
+    run_blocking(move || app.state::<ScannerService>().health_report(&path)).await
+}
+
+#[tauri::command]
+async fn owner_stats(app: tauri::AppHandle, path: String) -> Result<owner::OwnerStats, DuneError> {
+    run_blocking(move || app.state::<ScannerService>().owner_stats(&path)).await
+}
+
+#[tauri::command]
+async fn installed_apps_report(app: tauri::AppHandle) -> Result<apps::AppsReport, DuneError> {
+    run_blocking(move || Ok(app.state::<ScannerService>().installed_apps_report())).await
+}
+
+#[tauri::command]
+async fn browser_report(app: tauri::AppHandle) -> Result<browsers::BrowserReport, DuneError> {
+    run_blocking(move || Ok(app.state::<ScannerService>().browser_report())).await
+}
+
+#[tauri::command]
+async fn package_report(app: tauri::AppHandle) -> Result<packages::PackageReport, DuneError> {
+    run_blocking(move || Ok(app.state::<ScannerService>().package_report())).await
+}
+
+#[tauri::command]
+async fn messaging_report(app: tauri::AppHandle) -> Result<messaging::MessagingReport, DuneError> {
+    run_blocking(move || Ok(app.state::<ScannerService>().messaging_report())).await
+}
+
+/// Local Time Machine snapshots; `unsupported` outside macOS
+#[tauri::command]
+async fn snapshot_report() -> Result<timemachine::SnapshotReport, DuneError> {
+    run_blocking(timemachine::snapshot_report).await
+}
+
+/// How much memory scan trees, indexes and caches hold
+#[tauri::command]
+async fn memory_stats(app: tauri::AppHandle) -> Result<memory::MemoryStats, DuneError> {
+    run_blocking(move || Ok(app.state::<ScannerService>().memory_stats())).await
+}
+
+/// Drop caches and older finished scans, returning what is left
+#[tauri::command]
+async fn trim_memory(app: tauri::AppHandle) -> Result<memory::MemoryStats, DuneError> {
+    run_blocking(move || Ok(app.state::<ScannerService>().trim_memory())).await
+}
+
+/// Processes with files open on a volume, which would keep it from ejecting
+#[tauri::command]
+async fn disk_open_files(disk: String) -> Result<Vec<eject::OpenFile>, DuneError> {
+    run_blocking(move || Ok(eject::open_files(&disk))).await
+}
+
+/// Processes holding a file, or anything in a folder, open; what to close when a delete
+/// or move to the trash fails because the file is in use
+#[tauri::command]
+async fn who_has_open(path: String) -> Result<Vec<eject::OpenFile>, DuneError> {
+    run_blocking(move || eject::who_has_open(&path)).await
+}
+
+/// Stop the app's own scans and watchers on a volume, then unmount and eject it
+#[tauri::command]
+async fn eject_disk(app: tauri::AppHandle, path: String) -> Result<eject::EjectOutcome, DuneError> {
+    run_blocking(move || {
+        app.state::<ScannerService>()
+            .release_volume(Path::new(&path));
+        eject::eject(&path)
+    })
+    .await
+}
+
+/// SMART health, temperature and lifetime writes of the drive behind a disk
+#[tauri::command]
+async fn disk_health(disk: String) -> Result<smart::DiskHealth, DuneError> {
+    run_blocking(move || smart::disk_health(&disk)).await
+}
+
+/// Page file, hibernation file and shadow copies on a Windows volume, reconciled with
+/// the last scan of its root
+#[tauri::command]
+async fn system_space_report(
+    app: tauri::AppHandle,
+    volume: String,
+) -> Result<system_space::SystemSpaceReport, DuneError> {
+    run_blocking(move || {
+        let scanned = app
+            .state::<ScannerService>()
+            .last_scan(&volume)
+            .filter(|scan| scan.complete)
+            .map(|scan| scan.bytes);
+        system_space::report(&volume, scanned)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn estimate_compression(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<compression::CompressionEstimate, DuneError> {
+    run_blocking(move || app.state::<ScannerService>().estimate_compression(&path)).await
+}
+
+/// `max_distance` is how many of the 64 hash bits may differ, 6 by default
+#[tauri::command]
+async fn similar_images(
+    app: tauri::AppHandle,
+    path: String,
+    max_distance: Option<u32>,
+) -> Result<similar_images::SimilarImagesReport, DuneError> {
+    run_blocking(move || {
+        app.state::<ScannerService>()
+            .similar_images(&path, max_distance)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn inode_report(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<inodes::InodeReport, DuneError> {
+    run_blocking(move || app.state::<ScannerService>().inode_report(&path)).await
+}
+
+#[tauri::command]
+async fn log_report(app: tauri::AppHandle, path: String) -> Result<logs::LogReport, DuneError> {
+    let snapshots = snapshot::snapshot_dir(&app)?;
+
+    run_blocking(move || app.state::<ScannerService>().log_report(&path, &snapshots)).await
+}
+
+#[tauri::command]
+async fn containers_report(
+    app: tauri::AppHandle,
+) -> Result<containers::ContainersReport, DuneError> {
+    run_blocking(move || Ok(app.state::<ScannerService>().containers_report())).await
+}
+
+/// The tree comes with the `notes` on its nodes. Without `options`, the named `profile`
+/// or the one chosen for `path` applies. `binary: true` returns MessagePack bytes
+/// instead of JSON, see `ipc::encode`
+#[tauri::command]
+async fn scan_tree(
+    app: tauri::AppHandle,
+    path: String,
+    options: Option<ScanOptions>,
+    profile: Option<String>,
+    binary: Option<bool>,
+) -> Result<Response, DuneError> {
+    let notes = notes::store_path(&app)?;
+    let catalogs = catalogs::catalog_dir(&app)?;
+    let options = match options {
+        Some(options) => options,
+        None => settings::load()
+            .scan
+            .options_for(&path, profile.as_deref())?,
     };
+    let handle = app.state::<ScannerService>().register_scan(path.clone());
+    let _ = app.emit(
+        "scan://started",
+        ScanStarted {
+            scan_id: handle.id,
+            path,
+        },
+    );
 
-    match fs::read_to_string(&path) {
-        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
-        Err(_) => Settings::default(),
-    }
+    run_blocking(move || {
+        let emit_progress = |progress| {
+            let _ = app.emit("scan://progress", progress);
+        };
+        let tree = app
+            .state::<ScannerService>()
+            .run_scan(&handle, &options, &emit_progress)?;
+        if let Err(err) = catalogs::record(&catalogs, &tree) {
+            log::warn!("Could not catalog {}: {}", tree.root, err);
+        }
+        let mut annotated = notes::annotate(&notes, &tree)?;
+        annotated.reconciliation = reconcile::for_volume(&tree);
+
+        ipc::encode(&annotated, binary.unwrap_or(false))
+    })
+    .await
 }
 
+/// Scan a folder only an administrator can read, such as `/var/log` or
+/// `C:\Windows\Installer`, after the system's password prompt; `Cancelled` when the
+/// prompt is dismissed. The tree comes back with `elevated: true`, and deleting or moving
+/// files in it stays off unless `Settings.scan.elevated_file_operations` is set. Options
+/// and `binary` as in `scan_tree`
 #[tauri::command]
-fn save_settings(settings: Settings) -> Result<(), String> {
-    let Some(path) = get_settings_path() else {
-        return Err("Could not determine config directory".into());
+async fn scan_elevated(
+    app: tauri::AppHandle,
+    path: String,
+    options: Option<ScanOptions>,
+    profile: Option<String>,
+    binary: Option<bool>,
+) -> Result<Response, DuneError> {
+    let work_dir = elevated::work_dir(&app)?;
+    let options = match options {
+        Some(options) => options,
+        None => settings::load()
+            .scan
+            .options_for(&path, profile.as_deref())?,
     };
 
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    run_blocking(move || {
+        let tree = app
+            .state::<ScannerService>()
+            .elevated_scan(&work_dir, &path, &options)?;
+        ipc::encode(&*tree, binary.unwrap_or(false))
+    })
+    .await
+}
+
+/// Scan each of `paths` in turn, each announced on `scan://started`, and return them joined
+/// under one synthetic root. Roots inside another one are scanned only once. Without
+/// `options`, each root gets its own profile; `binary` as in `scan_tree`
+#[tauri::command]
+async fn scan_multi(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+    options: Option<ScanOptions>,
+    binary: Option<bool>,
+) -> Result<Response, DuneError> {
+    let notes = notes::store_path(&app)?;
+    let roots = multi::distinct_roots(&paths);
+    if roots.is_empty() {
+        return Err(DuneError::InvalidInput("No folders to scan".into()));
     }
+    let scan_settings = settings::load().scan;
 
-    let json = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
-    fs::write(&path, json).map_err(|e| e.to_string())?;
+    run_blocking(move || {
+        let service = app.state::<ScannerService>();
+        let emit_progress = |progress| {
+            let _ = app.emit("scan://progress", progress);
+        };
 
-    Ok(())
+        let mut trees = Vec::new();
+        for root in roots {
+            let root_options = match &options {
+                Some(options) => options.clone(),
+                None => scan_settings.options_for(&root, None)?,
+            };
+            let handle = service.register_scan(root.clone());
+            let _ = app.emit(
+                "scan://started",
+                ScanStarted {
+                    scan_id: handle.id,
+                    path: root,
+                },
+            );
+            trees.push(service.run_scan(&handle, &root_options, &emit_progress)?);
+        }
+
+        let tree = service.combine_scans(&trees);
+        let annotated = notes::annotate(&notes, &tree)?;
+        ipc::encode(&annotated, binary.unwrap_or(false))
+    })
+    .await
 }
 
+/// Load a `du -ab` or WinDirStat report as a finished scan; `binary` as in `scan_tree`
 #[tauri::command]
-fn get_disks() -> Vec<DiskInfo> {
-    scanner::get_disks()
+async fn import_scan(
+    app: tauri::AppHandle,
+    path: String,
+    format: ImportFormat,
+    binary: Option<bool>,
+) -> Result<Response, DuneError> {
+    run_blocking(move || {
+        let tree = app.state::<ScannerService>().import_scan(&path, format)?;
+        ipc::encode(&*tree, binary.unwrap_or(false))
+    })
+    .await
 }
 
+/// Ring chart segments `depth` levels under `focus_path` in a finished scan, with children
+/// narrower than `min_angle` radians merged into "other" buckets; `style` picks colors from
+/// the theme palette and places labels
 #[tauri::command]
-fn list_directory(path: String) -> Result<Vec<FileEntry>, String> {
-    scanner::list_directory(&path)
+async fn get_ring_level(
+    app: tauri::AppHandle,
+    tree_id: u64,
+    focus_path: String,
+    depth: u32,
+    min_angle: f64,
+    style: Option<RingStyle>,
+) -> Result<RingLevel, DuneError> {
+    run_blocking(move || {
+        app.state::<ScannerService>().ring_level(
+            tree_id,
+            &focus_path,
+            depth,
+            min_angle,
+            &style.unwrap_or_default(),
+        )
+    })
+    .await
 }
 
+/// A ring level as vertex buffer arrays, see `ring::buffers`; the focus defaults to the
+/// scan root, radii come from `style.labels` or its defaults
 #[tauri::command]
-fn open_folder(path: String) -> Result<(), String> {
-    let folder_path = PathBuf::from(&path);
+async fn layout_buffers(
+    app: tauri::AppHandle,
+    tree_id: u64,
+    focus_path: Option<String>,
+    depth: Option<u32>,
+    min_angle: Option<f64>,
+    style: Option<RingStyle>,
+) -> Result<Response, DuneError> {
+    run_blocking(move || {
+        let service = app.state::<ScannerService>();
+        let focus_path = match focus_path {
+            Some(path) => path,
+            None => service.finished_scan(tree_id)?.root.clone(),
+        };
+        let mut style = style.unwrap_or_default();
+        let geometry = style.labels.take().unwrap_or_default();
+        let level = service.ring_level(
+            tree_id,
+            &focus_path,
+            depth.unwrap_or(ring::DEFAULT_DEPTH),
+            min_angle.unwrap_or(ring::DEFAULT_MIN_ANGLE),
+            &style,
+        )?;
 
-    if !folder_path.exists() {
-        return Err(format!("Path does not exist: {}", path));
-    }
+        Ok(Response::new(ring::buffers(&level, &geometry)))
+    })
+    .await
+}
 
-    if !folder_path.is_dir() {
-        return Err(format!("Path is not a folder: {}", path));
+/// Hilbert-curve block map of a finished scan under `focus_path` (the root by default),
+/// `2^order` cells per side; `binary` as in `scan_tree`
+#[tauri::command]
+async fn get_disk_map(
+    app: tauri::AppHandle,
+    tree_id: u64,
+    focus_path: Option<String>,
+    order: Option<u32>,
+    style: Option<RingStyle>,
+    binary: Option<bool>,
+) -> Result<Response, DuneError> {
+    run_blocking(move || {
+        let map = app.state::<ScannerService>().disk_map(
+            tree_id,
+            focus_path.as_deref(),
+            order.unwrap_or(disk_map::DEFAULT_ORDER),
+            &style.unwrap_or_default(),
+        )?;
+
+        ipc::encode(&map, binary.unwrap_or(false))
+    })
+    .await
+}
+
+/// Per-segment from and to angles between two `get_ring_level` results, matched by path
+#[tauri::command]
+fn layout_diff(old_layout: RingLevel, new_layout: RingLevel) -> Vec<SegmentTransition> {
+    ring::diff(&old_layout, &new_layout)
+}
+
+/// Top extensions, newest and oldest file, deepest path and average file size under
+/// `path`, reusing a finished scan that covers it
+#[tauri::command]
+async fn folder_profile(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<profile::FolderProfile, DuneError> {
+    run_blocking(move || {
+        let (tree, index) = app.state::<ScannerService>().subtree(&path)?;
+        Ok(profile::profile(&tree, index))
+    })
+    .await
+}
+
+/// Name, size, share of the parent and rank among siblings of `path` as text for screen
+/// readers, reusing a finished scan that covers it
+#[tauri::command]
+async fn describe_segment(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<accessibility::SegmentDescription, DuneError> {
+    run_blocking(move || {
+        let (tree, index) = app.state::<ScannerService>().subtree(&path)?;
+        Ok(accessibility::describe(&tree, index))
+    })
+    .await
+}
+
+/// Put `depth` levels under `path` on the clipboard as an indented tree or a Markdown
+/// table, reusing a finished scan that covers it; returns the copied text
+#[tauri::command]
+async fn copy_tree_as_text(
+    app: tauri::AppHandle,
+    path: String,
+    depth: u32,
+    format: TextFormat,
+) -> Result<String, DuneError> {
+    run_blocking(move || {
+        let (tree, index) = app.state::<ScannerService>().subtree(&path)?;
+        let text = tree_text::render(&tree, index, depth, format);
+        clipboard::set_text(&text)?;
+
+        Ok(text)
+    })
+    .await
+}
+
+/// Match names in stored snapshots, streaming hits as `search://results`;
+/// a new search makes the previous one return early
+#[tauri::command]
+async fn search_index(
+    app: tauri::AppHandle,
+    query: String,
+    options: Option<SearchOptions>,
+) -> Result<SearchSummary, DuneError> {
+    let snapshots = snapshot::snapshot_dir(&app)?;
+    let search_id = app.state::<ScannerService>().begin_search();
+    let _ = app.emit(
+        "search://started",
+        SearchStarted {
+            search_id,
+            query: query.clone(),
+        },
+    );
+
+    run_blocking(move || {
+        let emit_batch = |batch| {
+            let _ = app.emit("search://results", batch);
+        };
+        app.state::<ScannerService>().search_index(
+            &snapshots,
+            search_id,
+            &query,
+            &options.unwrap_or_default(),
+            &emit_batch,
+        )
+    })
+    .await
+}
+
+/// Drives scanned before, with the folder scanned on each and where it is mounted now
+#[tauri::command]
+async fn list_catalogs(app: tauri::AppHandle) -> Result<Vec<catalogs::Catalog>, DuneError> {
+    let dir = catalogs::catalog_dir(&app)?;
+    run_blocking(move || catalogs::list(&dir)).await
+}
+
+/// Match `query` in the catalogs of every drive, plugged in or not; `options.roots`
+/// takes volume ids here
+#[tauri::command]
+async fn search_catalogs(
+    app: tauri::AppHandle,
+    query: String,
+    options: Option<SearchOptions>,
+) -> Result<catalogs::CatalogSearch, DuneError> {
+    let dir = catalogs::catalog_dir(&app)?;
+    run_blocking(move || catalogs::search(&dir, &query, &options.unwrap_or_default())).await
+}
+
+/// Files added, removed and changed under `path` since the catalog `catalog_id` was
+/// taken, reusing a finished scan that covers `path`
+#[tauri::command]
+async fn compare_with_catalog(
+    app: tauri::AppHandle,
+    path: String,
+    catalog_id: String,
+) -> Result<catalogs::CatalogComparison, DuneError> {
+    let dir = catalogs::catalog_dir(&app)?;
+    run_blocking(move || {
+        let (tree, index) = app.state::<ScannerService>().subtree(&path)?;
+        catalogs::compare(&dir, &tree, index, &catalog_id)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn delete_catalog(app: tauri::AppHandle, id: String) -> Result<(), DuneError> {
+    let dir = catalogs::catalog_dir(&app)?;
+    run_blocking(move || catalogs::delete(&dir, &id)).await
+}
+
+/// Every match of `query` as one tree for the ring view; like `search_index`, a newer
+/// search makes it return early. `binary: true` returns MessagePack, see `ipc::encode`
+#[tauri::command]
+async fn search_tree(
+    app: tauri::AppHandle,
+    query: String,
+    options: Option<SearchOptions>,
+    binary: Option<bool>,
+) -> Result<Response, DuneError> {
+    let snapshots = snapshot::snapshot_dir(&app)?;
+    let search_id = app.state::<ScannerService>().begin_search();
+
+    run_blocking(move || {
+        let result = app.state::<ScannerService>().search_tree(
+            &snapshots,
+            search_id,
+            &query,
+            &options.unwrap_or_default(),
+        )?;
+
+        ipc::encode(&result, binary.unwrap_or(false))
+    })
+    .await
+}
+
+/// Like `search_index`, but also asks Spotlight or Windows Search so files that were
+/// never scanned show up; returns every hit at once instead of streaming
+#[tauri::command]
+async fn os_search(
+    app: tauri::AppHandle,
+    query: String,
+    options: Option<SearchOptions>,
+) -> Result<OsSearchResult, DuneError> {
+    let snapshots = snapshot::snapshot_dir(&app)?;
+    let search_id = app.state::<ScannerService>().begin_search();
+
+    run_blocking(move || {
+        app.state::<ScannerService>().os_search(
+            &snapshots,
+            search_id,
+            &query,
+            &options.unwrap_or_default(),
+        )
+    })
+    .await
+}
+
+#[tauri::command]
+async fn list_saved_searches(app: tauri::AppHandle) -> Result<Vec<SavedSearch>, DuneError> {
+    let path = saved_searches::store_path(&app)?;
+
+    run_blocking(move || saved_searches::list(&path)).await
+}
+
+/// Creates a saved search, or updates the one with `search.id`
+#[tauri::command]
+async fn save_search(
+    app: tauri::AppHandle,
+    search: SavedSearchInput,
+) -> Result<SavedSearch, DuneError> {
+    let path = saved_searches::store_path(&app)?;
+
+    run_blocking(move || saved_searches::save(&path, search)).await
+}
+
+#[tauri::command]
+async fn delete_saved_search(app: tauri::AppHandle, id: String) -> Result<(), DuneError> {
+    let path = saved_searches::store_path(&app)?;
+
+    run_blocking(move || saved_searches::delete(&path, &id)).await
+}
+
+/// Same result as `search_tree`; the tree's `scan_id` works with `save_snapshot` and
+/// `export_scan_json`
+#[tauri::command]
+async fn run_saved_search(
+    app: tauri::AppHandle,
+    id: String,
+    binary: Option<bool>,
+) -> Result<Response, DuneError> {
+    let path = saved_searches::store_path(&app)?;
+    let snapshots = snapshot::snapshot_dir(&app)?;
+    let search_id = app.state::<ScannerService>().begin_search();
+
+    run_blocking(move || {
+        let saved = saved_searches::get(&path, &id)?;
+        let result = app.state::<ScannerService>().search_tree(
+            &snapshots,
+            search_id,
+            &saved.query,
+            &saved.options,
+        )?;
+        if !result.summary.superseded {
+            saved_searches::record_run(&path, &id, &result)?;
+        }
+
+        ipc::encode(&result, binary.unwrap_or(false))
+    })
+    .await
+}
+
+#[tauri::command]
+async fn tag_paths(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+    tags: Vec<String>,
+) -> Result<(), DuneError> {
+    let store = tags::store_path(&app)?;
+
+    run_blocking(move || tags::tag_paths(&store, &paths, &tags)).await
+}
+
+/// Without `tags`, removes every custom tag from the paths; Finder tags are left alone
+#[tauri::command]
+async fn untag_paths(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+    tags: Option<Vec<String>>,
+) -> Result<(), DuneError> {
+    let store = tags::store_path(&app)?;
+
+    run_blocking(move || tags::untag_paths(&store, &paths, tags.as_deref())).await
+}
+
+#[tauri::command]
+async fn find_by_tag(app: tauri::AppHandle, tag: String) -> Result<Vec<TaggedPath>, DuneError> {
+    let store = tags::store_path(&app)?;
+
+    run_blocking(move || tags::find_by_tag(&store, &tag)).await
+}
+
+/// Where the first-run wizard stands and which disclaimers were accepted
+#[tauri::command]
+async fn get_onboarding(app: tauri::AppHandle) -> Result<OnboardingState, DuneError> {
+    let store = onboarding::store_path(&app)?;
+
+    run_blocking(move || onboarding::read(&store)).await
+}
+
+#[tauri::command]
+async fn complete_onboarding_step(
+    app: tauri::AppHandle,
+    step: String,
+) -> Result<OnboardingState, DuneError> {
+    let store = onboarding::store_path(&app)?;
+
+    run_blocking(move || onboarding::complete_step(&store, &step)).await
+}
+
+#[tauri::command]
+async fn accept_disclaimer(
+    app: tauri::AppHandle,
+    id: String,
+) -> Result<OnboardingState, DuneError> {
+    let store = onboarding::store_path(&app)?;
+
+    run_blocking(move || onboarding::accept_disclaimer(&store, &id)).await
+}
+
+#[tauri::command]
+async fn finish_onboarding(app: tauri::AppHandle) -> Result<OnboardingState, DuneError> {
+    let store = onboarding::store_path(&app)?;
+
+    run_blocking(move || onboarding::finish(&store)).await
+}
+
+/// Show the wizard again on the next launch
+#[tauri::command]
+async fn reset_onboarding(app: tauri::AppHandle) -> Result<OnboardingState, DuneError> {
+    let store = onboarding::store_path(&app)?;
+
+    run_blocking(move || onboarding::reset(&store)).await
+}
+
+/// Full Disk Access on macOS and whether the usual scan roots can be listed
+#[tauri::command]
+async fn check_permissions() -> Result<onboarding::PermissionReport, DuneError> {
+    run_blocking(|| Ok(onboarding::check_permissions())).await
+}
+
+/// Blank `text` removes the note
+#[tauri::command]
+async fn set_note(
+    app: tauri::AppHandle,
+    path: String,
+    text: String,
+) -> Result<Option<Note>, DuneError> {
+    let store = notes::store_path(&app)?;
+
+    run_blocking(move || notes::set_note(&store, &path, &text)).await
+}
+
+/// Notes on `root` and every path below it
+#[tauri::command]
+async fn get_notes(app: tauri::AppHandle, root: String) -> Result<Vec<PathNote>, DuneError> {
+    let store = notes::store_path(&app)?;
+
+    run_blocking(move || notes::get_notes(&store, &root)).await
+}
+
+/// Look inside the text files under `path`, streaming each file's matches as
+/// `grep://results`; cancel with `cancel_scan` and the id from `grep://started`
+#[tauri::command]
+async fn grep(
+    app: tauri::AppHandle,
+    path: String,
+    pattern: String,
+    options: Option<grep::GrepOptions>,
+) -> Result<grep::GrepSummary, DuneError> {
+    let handle = app.state::<ScannerService>().register_scan(path.clone());
+    let _ = app.emit(
+        "grep://started",
+        grep::GrepStarted {
+            grep_id: handle.id,
+            path,
+        },
+    );
+
+    run_blocking(move || {
+        let emit_file = |file| {
+            let _ = app.emit("grep://results", file);
+        };
+        app.state::<ScannerService>().run_grep(
+            &handle,
+            &pattern,
+            &options.unwrap_or_default(),
+            &emit_file,
+        )
+    })
+    .await
+}
+
+/// Walk `a` and `b` side by side, e.g. to check that a backup matches its source; cancel
+/// with `cancel_scan` and the id from `compare://started`. `binary: true` returns
+/// MessagePack, see `ipc::encode`
+#[tauri::command]
+async fn compare_dirs(
+    app: tauri::AppHandle,
+    a: String,
+    b: String,
+    options: Option<compare::CompareOptions>,
+    binary: Option<bool>,
+) -> Result<Response, DuneError> {
+    let handle = app.state::<ScannerService>().register_scan(a.clone());
+    let _ = app.emit(
+        "compare://started",
+        compare::CompareStarted {
+            compare_id: handle.id,
+            a,
+            b: b.clone(),
+        },
+    );
+
+    run_blocking(move || {
+        let diff = app.state::<ScannerService>().compare_dirs(
+            &handle,
+            &b,
+            &options.unwrap_or_default(),
+        )?;
+
+        ipc::encode(&diff, binary.unwrap_or(false))
+    })
+    .await
+}
+
+/// Check every file of `source` against `backup`, streaming `verify://progress`; cancel
+/// with `cancel_scan` and the id from `verify://started`
+#[tauri::command]
+async fn verify_backup(
+    app: tauri::AppHandle,
+    source: String,
+    backup: String,
+    options: Option<verify::VerifyOptions>,
+) -> Result<verify::VerifySummary, DuneError> {
+    let handle = app.state::<ScannerService>().register_scan(source.clone());
+    let _ = app.emit(
+        "verify://started",
+        verify::VerifyStarted {
+            verify_id: handle.id,
+            source,
+            backup: backup.clone(),
+        },
+    );
+
+    run_blocking(move || {
+        let emit_progress = |progress| {
+            let _ = app.emit("verify://progress", progress);
+        };
+        app.state::<ScannerService>().verify_backup(
+            &handle,
+            &backup,
+            &options.unwrap_or_default(),
+            &emit_progress,
+        )
+    })
+    .await
+}
+
+/// Storage-class breakdown of an `s3://bucket/prefix` location; scan the same location
+/// with `scan_tree` for the ring view
+#[tauri::command]
+async fn bucket_report(location: String) -> Result<s3::BucketReport, DuneError> {
+    run_blocking(move || s3::bucket_report(&location)).await
+}
+
+/// Developer tool: time the scanner across thread counts and enumeration backends
+#[tauri::command]
+async fn benchmark_scan(path: String) -> Result<benchmark::BenchmarkReport, DuneError> {
+    run_blocking(move || benchmark::benchmark_scan(&path)).await
+}
+
+#[tauri::command]
+fn cancel_scan(service: tauri::State<ScannerService>, scan_id: u64) -> bool {
+    service.cancel_scan(scan_id)
+}
+
+/// Leave `path` unscanned in a running scan, such as the folder `scan://stalled` named;
+/// false when the scan is over or `path` is outside it
+#[tauri::command]
+fn skip_path(service: tauri::State<ScannerService>, scan_id: u64, path: String) -> bool {
+    service.skip_path(scan_id, &path)
+}
+
+/// Called on navigation so a running scan reads the opened folder first
+#[tauri::command]
+fn boost_priority(service: tauri::State<ScannerService>, path: String) -> bool {
+    service.boost_priority(&path)
+}
+
+/// `path` may be a folder or a disk mount point
+#[tauri::command]
+async fn forecast(app: tauri::AppHandle, path: String) -> Result<forecast::Forecast, DuneError> {
+    let snapshots = snapshot::snapshot_dir(&app)?;
+
+    run_blocking(move || forecast::forecast(&path, &snapshots)).await
+}
+
+/// Every folder in `Settings.monitoring.budgets` against its budget, reusing finished scans
+#[tauri::command]
+async fn get_budget_status(app: tauri::AppHandle) -> Result<Vec<BudgetStatus>, DuneError> {
+    run_blocking(move || {
+        let budgets = settings::load().monitoring.budgets;
+        Ok(budgets::check(
+            &app.state::<ScannerService>(),
+            &budgets,
+            false,
+        ))
+    })
+    .await
+}
+
+#[tauri::command]
+async fn save_snapshot(app: tauri::AppHandle, scan_id: u64) -> Result<SnapshotInfo, DuneError> {
+    let dir = snapshot::snapshot_dir(&app)?;
+
+    run_blocking(move || {
+        let tree = app.state::<ScannerService>().finished_scan(scan_id)?;
+        snapshot::save(&dir, &tree)
+    })
+    .await
+}
+
+/// Write a finished scan or search result to JSON without storing it as a snapshot
+#[tauri::command]
+async fn export_scan_json(
+    app: tauri::AppHandle,
+    scan_id: u64,
+    dest: String,
+) -> Result<(), DuneError> {
+    run_blocking(move || {
+        let tree = app.state::<ScannerService>().finished_scan(scan_id)?;
+        snapshot::export_tree_json(&tree, Path::new(&dest))
+    })
+    .await
+}
+
+/// System folder picker, opening where the last one for `purpose` (such as `scan` or
+/// `export`) ended; `None` when cancelled
+#[tauri::command]
+async fn pick_folder(
+    app: tauri::AppHandle,
+    purpose: Option<String>,
+) -> Result<Option<String>, DuneError> {
+    let store = dialogs::store_path(&app)?;
+
+    run_blocking(move || dialogs::pick_folder(&store, purpose.as_deref())).await
+}
+
+/// System save picker suggesting `default_name`, remembering its folder per `purpose`
+#[tauri::command]
+async fn pick_save_path(
+    app: tauri::AppHandle,
+    default_name: String,
+    filters: Option<Vec<FileFilter>>,
+    purpose: Option<String>,
+) -> Result<Option<String>, DuneError> {
+    let store = dialogs::store_path(&app)?;
+
+    run_blocking(move || {
+        dialogs::pick_save_path(
+            &store,
+            &default_name,
+            &filters.unwrap_or_default(),
+            purpose.as_deref(),
+        )
+    })
+    .await
+}
+
+/// Drop cached listings touched by `records`, whether or not each operation succeeded
+fn forget_listings(app: &tauri::AppHandle, records: &[OperationRecord]) {
+    let service = app.state::<ScannerService>();
+    for record in records {
+        service.invalidate_listings(Path::new(&record.path));
+        if let Some(destination) = &record.destination {
+            service.invalidate_listings(Path::new(destination));
+        }
     }
+}
+
+/// Delete `paths` for good; one record per path, failures included
+#[tauri::command]
+async fn delete_paths(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+) -> Result<Vec<OperationRecord>, DuneError> {
+    let store = audit::store_path(&app)?;
+    let records = run_blocking(move || Ok(file_ops::delete(&store, &paths))).await?;
+    forget_listings(&app, &records);
+
+    Ok(records)
+}
+
+/// Move `paths` to the system trash; one record per path, failures included
+#[tauri::command]
+async fn trash_paths(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+) -> Result<Vec<OperationRecord>, DuneError> {
+    let store = audit::store_path(&app)?;
+    let records = run_blocking(move || Ok(file_ops::move_to_trash(&store, &paths))).await?;
+    forget_listings(&app, &records);
+
+    Ok(records)
+}
+
+/// Move `paths` into the app's "Recently removed" folder, restorable until they expire;
+/// one record per path, failures included
+#[tauri::command]
+async fn soft_delete_paths(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+) -> Result<Vec<OperationRecord>, DuneError> {
+    let store = audit::store_path(&app)?;
+    let folder = removed::folder_path(&app)?;
+    let records = run_blocking(move || removed::soft_delete(&store, &folder, &paths)).await?;
+    forget_listings(&app, &records);
+
+    Ok(records)
+}
+
+/// Put recently removed `paths` back where they were; one record per path
+#[tauri::command]
+async fn restore_paths(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+) -> Result<Vec<OperationRecord>, DuneError> {
+    let store = audit::store_path(&app)?;
+    let folder = removed::folder_path(&app)?;
+    let records = run_blocking(move || removed::restore(&store, &folder, &paths)).await?;
+    forget_listings(&app, &records);
+
+    Ok(records)
+}
+
+/// What can still be restored, most recently removed first
+#[tauri::command]
+async fn get_recently_removed(
+    app: tauri::AppHandle,
+) -> Result<Vec<removed::RemovedItem>, DuneError> {
+    let store = audit::store_path(&app)?;
+    let folder = removed::folder_path(&app)?;
+    run_blocking(move || {
+        let retention_days = settings::load().recently_removed.retention_days;
+        removed::purge(&store, &folder, retention_days)?;
+        removed::list(&folder, retention_days)
+    })
+    .await
+}
+
+/// Delete everything recently removed for good
+#[tauri::command]
+async fn empty_recently_removed(app: tauri::AppHandle) -> Result<(), DuneError> {
+    let store = audit::store_path(&app)?;
+    let folder = removed::folder_path(&app)?;
+    run_blocking(move || removed::empty(&store, &folder)).await
+}
+
+/// What the unsaved `rule` would suggest, without keeping any of it
+#[tauri::command]
+async fn preview_cleanup_rule(
+    app: tauri::AppHandle,
+    rule: CleanupRule,
+) -> Result<RulePreview, DuneError> {
+    run_blocking(move || cleanup::preview(&app.state::<ScannerService>(), &rule)).await
+}
+
+/// Evaluate the saved cleanup rules now and return the suggestions waiting for approval
+#[tauri::command]
+async fn run_cleanup_rules(app: tauri::AppHandle) -> Result<Refreshed, DuneError> {
+    let store = cleanup::store_path(&app)?;
+    run_blocking(move || {
+        let rules = settings::load().cleanup_rules;
+        cleanup::run(&app.state::<ScannerService>(), &store, &rules)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn get_cleanup_suggestions(app: tauri::AppHandle) -> Result<Vec<Suggestion>, DuneError> {
+    let store = cleanup::store_path(&app)?;
+    run_blocking(move || cleanup::pending(&store)).await
+}
+
+/// Carry out the approved suggestions for `paths`; one record per path, failures included
+#[tauri::command]
+async fn apply_cleanup_suggestions(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+) -> Result<Vec<OperationRecord>, DuneError> {
+    let audit = audit::store_path(&app)?;
+    let folder = removed::folder_path(&app)?;
+    let store = cleanup::store_path(&app)?;
+    let records = run_blocking(move || cleanup::apply(&audit, &folder, &store, &paths)).await?;
+    forget_listings(&app, &records);
+
+    Ok(records)
+}
+
+/// Stop suggesting `paths` until they change; returns the remaining suggestions
+#[tauri::command]
+async fn dismiss_cleanup_suggestions(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+) -> Result<Vec<Suggestion>, DuneError> {
+    let store = cleanup::store_path(&app)?;
+    run_blocking(move || cleanup::dismiss(&store, &paths)).await
+}
+
+/// Move `paths` into the folder `dest`; one record per path, failures included
+#[tauri::command]
+async fn move_paths(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+    dest: String,
+) -> Result<Vec<OperationRecord>, DuneError> {
+    let store = audit::store_path(&app)?;
+    let records = run_blocking(move || Ok(file_ops::move_into(&store, &paths, &dest))).await?;
+    forget_listings(&app, &records);
+
+    Ok(records)
+}
+
+#[tauri::command]
+async fn rename_path(
+    app: tauri::AppHandle,
+    path: String,
+    new_name: String,
+) -> Result<OperationRecord, DuneError> {
+    let store = audit::store_path(&app)?;
+    let record = run_blocking(move || Ok(file_ops::rename(&store, &path, &new_name))).await?;
+    forget_listings(&app, std::slice::from_ref(&record));
+
+    Ok(record)
+}
+
+/// Whether `name` can be created or renamed to in `folder`, and what stops it if not
+#[tauri::command]
+async fn check_name(
+    folder: String,
+    name: String,
+    replacing: Option<String>,
+) -> Result<validity::NameCheck, DuneError> {
+    run_blocking(move || validity::check_name(&folder, &name, replacing.as_deref())).await
+}
+
+/// Past file operations, newest first
+#[tauri::command]
+async fn get_operation_log(
+    app: tauri::AppHandle,
+    filter: Option<OperationFilter>,
+) -> Result<Vec<OperationRecord>, DuneError> {
+    let store = audit::store_path(&app)?;
+
+    run_blocking(move || audit::read(&store, &filter.unwrap_or_default())).await
+}
+
+/// Save the scanned root, snapshot, camera, theme and look to a project file at `dest`
+#[tauri::command]
+async fn save_project(dest: String, project: Project) -> Result<Project, DuneError> {
+    run_blocking(move || project::save(Path::new(&dest), project)).await
+}
+
+/// Read a project saved by `save_project`; `snapshot` is `None` when it has to be rescanned
+#[tauri::command]
+async fn open_project(app: tauri::AppHandle, path: String) -> Result<OpenedProject, DuneError> {
+    let dir = snapshot::snapshot_dir(&app)?;
+
+    run_blocking(move || project::open(&dir, Path::new(&path))).await
+}
+
+#[tauri::command]
+async fn list_snapshots(app: tauri::AppHandle) -> Result<Vec<SnapshotInfo>, DuneError> {
+    let dir = snapshot::snapshot_dir(&app)?;
 
+    run_blocking(move || snapshot::list(&dir)).await
+}
+
+#[tauri::command]
+async fn delete_snapshot(app: tauri::AppHandle, id: String) -> Result<(), DuneError> {
+    let dir = snapshot::snapshot_dir(&app)?;
+
+    run_blocking(move || snapshot::delete(&dir, &id)).await
+}
+
+#[tauri::command]
+async fn export_snapshot_json(
+    app: tauri::AppHandle,
+    id: String,
+    dest: String,
+) -> Result<(), DuneError> {
+    let dir = snapshot::snapshot_dir(&app)?;
+
+    run_blocking(move || snapshot::export_json(&dir, &id, Path::new(&dest))).await
+}
+
+#[tauri::command]
+async fn import_snapshot_json(
+    app: tauri::AppHandle,
+    source: String,
+) -> Result<SnapshotInfo, DuneError> {
+    let dir = snapshot::snapshot_dir(&app)?;
+
+    run_blocking(move || snapshot::import_json(&dir, Path::new(&source))).await
+}
+
+#[tauri::command]
+fn watch_path(
+    app: tauri::AppHandle,
+    service: tauri::State<ScannerService>,
+    path: String,
+) -> Result<(), DuneError> {
+    service.watch(app, &path)
+}
+
+/// Watch a favorite folder and emit `favorites://updated` with fresh totals as it changes
+#[tauri::command]
+fn watch_favorite(
+    app: tauri::AppHandle,
+    service: tauri::State<ScannerService>,
+    path: String,
+) -> Result<(), DuneError> {
+    service.watch_favorite(app, &path)
+}
+
+#[tauri::command]
+fn unwatch_path(service: tauri::State<ScannerService>, path: String) {
+    service.unwatch(&path);
+}
+
+fn spawn_file_manager(folder_path: &Path) -> Result<(), DuneError> {
     #[cfg(target_os = "macos")]
     let mut command = {
         let mut command = Command::new("open");
-        command.arg(&folder_path);
+        command.arg(folder_path);
         command
     };
 
     #[cfg(target_os = "windows")]
     let mut command = {
         let mut command = Command::new("explorer");
-        command.arg(&folder_path);
+        command.arg(folder_path);
         command
     };
 
     #[cfg(all(unix, not(target_os = "macos")))]
     let mut command = {
         let mut command = Command::new("xdg-open");
-        command.arg(&folder_path);
+        command.arg(folder_path);
         command
     };
 
-    command.spawn().map_err(|e| e.to_string())?;
+    command.spawn().map_err(|e| DuneError::io(folder_path, e))?;
 
     Ok(())
 }
 
 #[tauri::command]
-fn save_screenshot(png_base64: String) -> Result<String, String> {
+async fn open_folder(path: String) -> Result<(), DuneError> {
+    run_blocking(move || open_folder_blocking(path)).await
+}
+
+fn open_folder_blocking(path: String) -> Result<(), DuneError> {
+    let folder_path = PathBuf::from(&path);
+
+    if !folder_path.exists() {
+        return Err(DuneError::NotFound { path });
+    }
+
+    if !folder_path.is_dir() {
+        return Err(DuneError::NotADirectory { path });
+    }
+
+    log::info!("Opening {} in the file manager", path);
+    spawn_file_manager(&folder_path)
+}
+
+#[tauri::command]
+async fn save_screenshot(png_base64: String) -> Result<String, DuneError> {
+    run_blocking(move || save_screenshot_blocking(&png_base64)).await
+}
+
+fn save_screenshot_blocking(png_base64: &str) -> Result<String, DuneError> {
     let Some(pictures_dir) = dirs::picture_dir() else {
-        return Err("Could not determine pictures directory".into());
+        return Err(DuneError::Internal(
+            "Could not determine pictures directory".into(),
+        ));
     };
 
     let screenshots_dir = pictures_dir.join("SpaceRingScene");
-    fs::create_dir_all(&screenshots_dir).map_err(|e| e.to_string())?;
+    fs::create_dir_all(&screenshots_dir).map_err(|e| DuneError::io(&screenshots_dir, e))?;
 
     let timestamp = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
-        .map_err(|e| e.to_string())?
+        .map_err(DuneError::internal)?
         .as_secs();
 
     let filename = format!("screenshot_{}.png", timestamp);
     let path = screenshots_dir.join(&filename);
 
-    let data = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &png_base64)
-        .map_err(|e| e.to_string())?;
+    let data = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, png_base64)
+        .map_err(DuneError::internal)?;
 
-    fs::write(&path, data).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| DuneError::io(&path, e))?;
+    log::info!("Saved screenshot to {}", path.display());
 
     Ok(path.to_string_lossy().to_string())
 }
 
+#[tauri::command]
+async fn get_recent_logs(app: tauri::AppHandle, n: usize) -> Result<Vec<String>, DuneError> {
+    run_blocking(move || logging::recent_logs(&app, n)).await
+}
+
+#[tauri::command]
+async fn open_log_folder(app: tauri::AppHandle) -> Result<(), DuneError> {
+    let dir = logging::log_dir(&app)?;
+
+    run_blocking(move || {
+        fs::create_dir_all(&dir).map_err(|e| DuneError::io(&dir, e))?;
+        spawn_file_manager(&dir)
+    })
+    .await
+}
+
+fn crash_dir(app: &tauri::AppHandle) -> Result<PathBuf, DuneError> {
+    app_data::path(app, "crashes")
+}
+
+#[tauri::command]
+async fn get_crash_reports(app: tauri::AppHandle) -> Result<Vec<crash::CrashReport>, DuneError> {
+    let dir = crash_dir(&app)?;
+
+    run_blocking(move || crash::crash_reports(&dir)).await
+}
+
+/// Zip logs, crash reports, settings without paths, the structure of scan `scan_id` (the
+/// most recent one by default) and machine details to `dest`, for bug reports
+#[tauri::command]
+async fn export_debug_bundle(
+    app: tauri::AppHandle,
+    dest: String,
+    scan_id: Option<u64>,
+) -> Result<debug_bundle::DebugBundle, DuneError> {
+    let sources = debug_bundle::Sources {
+        log_dir: logging::log_dir(&app)?,
+        crash_dir: crash_dir(&app)?,
+        app_version: app.package_info().version.to_string(),
+    };
+
+    run_blocking(move || {
+        let service = app.state::<ScannerService>();
+        let tree = match scan_id {
+            Some(scan_id) => Some(service.finished_scan(scan_id)?),
+            None => service.latest_scan(),
+        };
+        debug_bundle::export(
+            Path::new(&dest),
+            &sources,
+            &settings::load(),
+            tree.as_deref(),
+        )
+    })
+    .await
+}
+
+#[tauri::command]
+fn take_launch_path(launch_path: tauri::State<LaunchPath>) -> Option<String> {
+    launch_path.0.lock().ok().and_then(|mut path| path.take())
+}
+
+#[tauri::command]
+fn install_shell_integration() -> Result<(), DuneError> {
+    log::info!("Installing shell integration");
+    shell_integration::install()
+        .inspect_err(|err| log::error!("Shell integration install failed: {}", err))
+}
+
+#[tauri::command]
+fn uninstall_shell_integration() -> Result<(), DuneError> {
+    log::info!("Removing shell integration");
+    shell_integration::uninstall()
+        .inspect_err(|err| log::error!("Shell integration removal failed: {}", err))
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    tauri::Builder::default()
-        .plugin(tauri_plugin_log::Builder::default().build())
-        .invoke_handler(tauri::generate_handler![
-            load_settings,
-            save_settings,
-            save_screenshot,
-            get_disks,
-            list_directory,
-            open_folder
-        ])
+    if let Some(code) = elevated::run_helper(&std::env::args().collect::<Vec<_>>()) {
+        std::process::exit(code);
+    }
+
+    let builder = tauri::Builder::default();
+
+    // Must be the first plugin so a second launch exits before doing any work
+    #[cfg(desktop)]
+    let builder = builder.plugin(tauri_plugin_single_instance::init(|app, args, _cwd| {
+        // Also brings back a window hidden in the tray or never shown after --background
+        background::show_main_window(app);
+
+        if let Some(path) = shell_integration::folder_from_args(&args) {
+            log::info!("Second launch asked to open {}", path);
+            let _ = app.emit("shell://open-folder", path);
+        }
+    }));
+
+    // The login item relaunches with --background so the app starts in the tray
+    #[cfg(desktop)]
+    let builder = builder.plugin(tauri_plugin_autostart::init(
+        tauri_plugin_autostart::MacosLauncher::LaunchAgent,
+        Some(vec![background::BACKGROUND_ARG]),
+    ));
+
+    let handler: fn(tauri::ipc::Invoke) -> bool = tauri::generate_handler![
+        load_settings,
+        save_settings,
+        get_languages,
+        generate_dither_assets,
+        regenerate_api_token,
+        test_webhook,
+        save_screenshot,
+        get_disks,
+        list_directory,
+        file_details,
+        resolve_path,
+        scan_tree,
+        scan_elevated,
+        scan_multi,
+        import_scan,
+        copy_tree_as_text,
+        folder_profile,
+        describe_segment,
+        get_ring_level,
+        layout_diff,
+        layout_buffers,
+        get_disk_map,
+        age_histogram,
+        category_stats,
+        owner_stats,
+        health_report,
+        installed_apps_report,
+        estimate_compression,
+        similar_images,
+        inode_report,
+        log_report,
+        containers_report,
+        browser_report,
+        package_report,
+        messaging_report,
+        snapshot_report,
+        system_space_report,
+        disk_health,
+        disk_open_files,
+        who_has_open,
+        eject_disk,
+        memory_stats,
+        trim_memory,
+        cancel_scan,
+        skip_path,
+        benchmark_scan,
+        boost_priority,
+        save_snapshot,
+        list_snapshots,
+        pick_folder,
+        pick_save_path,
+        delete_paths,
+        trash_paths,
+        soft_delete_paths,
+        restore_paths,
+        get_recently_removed,
+        empty_recently_removed,
+        preview_cleanup_rule,
+        run_cleanup_rules,
+        get_cleanup_suggestions,
+        apply_cleanup_suggestions,
+        dismiss_cleanup_suggestions,
+        move_paths,
+        rename_path,
+        check_name,
+        get_operation_log,
+        save_project,
+        open_project,
+        delete_snapshot,
+        export_snapshot_json,
+        import_snapshot_json,
+        export_scan_json,
+        search_index,
+        list_catalogs,
+        search_catalogs,
+        compare_with_catalog,
+        delete_catalog,
+        search_tree,
+        os_search,
+        grep,
+        compare_dirs,
+        verify_backup,
+        bucket_report,
+        list_saved_searches,
+        save_search,
+        delete_saved_search,
+        run_saved_search,
+        tag_paths,
+        untag_paths,
+        find_by_tag,
+        set_note,
+        get_onboarding,
+        complete_onboarding_step,
+        accept_disclaimer,
+        finish_onboarding,
+        reset_onboarding,
+        check_permissions,
+        get_notes,
+        forecast,
+        get_budget_status,
+        watch_path,
+        watch_favorite,
+        unwatch_path,
+        open_folder,
+        get_recent_logs,
+        get_crash_reports,
+        export_debug_bundle,
+        open_log_folder,
+        take_launch_path,
+        install_shell_integration,
+        uninstall_shell_integration
+    ];
+
+    builder
+        .plugin(logging::plugin())
+        .plugin(tauri_plugin_notification::init())
+        .manage(LaunchPath(Mutex::new(shell_integration::folder_from_args(
+            &std::env::args().collect::<Vec<_>>(),
+        ))))
+        .setup(|app| {
+            crash::install_panic_hook(
+                crash_dir(app.handle())?,
+                app.package_info().version.to_string(),
+            );
+
+            let settings = settings::load();
+            format::configure(&settings);
+            i18n::configure(&settings);
+            elevated::configure(&settings);
+            app.manage(ScannerService::new(&settings)?);
+
+            // A taken port must not keep the app from starting
+            app.manage(ApiServer::default());
+            if let Err(err) = app.state::<ApiServer>().apply(app.handle(), &settings.api) {
+                log::warn!("HTTP API not started: {}", err);
+            }
+
+            #[cfg(desktop)]
+            {
+                background::setup_tray(app.handle())?;
+                app.manage(background::spawn_scheduler(app.handle().clone()));
+                background::spawn_volume_watch(app.handle().clone());
+                background::spawn_scan_watchdog(app.handle().clone());
+
+                // The window is created hidden so background launches never flash it
+                if !background::launched_in_background() {
+                    background::show_main_window(app.handle());
+                }
+            }
+
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            #[cfg(desktop)]
+            if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+                if settings::load().background.enabled {
+                    api.prevent_close();
+                    let _ = window.hide();
+                }
+            }
+        })
+        .invoke_handler(move |invoke| {
+            crash::record_command(invoke.message.command());
+            handler(invoke)
+        })
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -0,0 +1,54 @@
+use crate::error::DuneError;
+use std::fs;
+use std::path::PathBuf;
+use tauri::plugin::TauriPlugin;
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_plugin_log::{RotationStrategy, Target, TargetKind};
+
+const LOG_FILE_NAME: &str = "dunefiles";
+const MAX_LOG_FILE_SIZE: u128 = 5 * 1024 * 1024;
+const KEEP_LOG_FILES: usize = 5;
+const MAX_RECENT_LOG_LINES: usize = 5_000;
+
+/// Log plugin writing to stdout and a rotating file in the app log dir
+pub fn plugin<R: Runtime>() -> TauriPlugin<R> {
+    let level = if cfg!(debug_assertions) {
+        log::LevelFilter::Debug
+    } else {
+        log::LevelFilter::Info
+    };
+
+    tauri_plugin_log::Builder::default()
+        .clear_targets()
+        .targets([
+            Target::new(TargetKind::Stdout),
+            Target::new(TargetKind::LogDir {
+                file_name: Some(LOG_FILE_NAME.into()),
+            }),
+        ])
+        .level(level)
+        .max_file_size(MAX_LOG_FILE_SIZE)
+        .rotation_strategy(RotationStrategy::KeepSome(KEEP_LOG_FILES))
+        .build()
+}
+
+pub fn log_dir<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, DuneError> {
+    app.path().app_log_dir().map_err(DuneError::internal)
+}
+
+/// Last `count` lines of the current log file, oldest first
+pub fn recent_logs<R: Runtime>(app: &AppHandle<R>, count: usize) -> Result<Vec<String>, DuneError> {
+    let path = log_dir(app)?.join(format!("{}.log", LOG_FILE_NAME));
+
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(DuneError::io(&path, err)),
+    };
+
+    let lines: Vec<&str> = content.lines().collect();
+    let count = count.min(MAX_RECENT_LOG_LINES);
+    let start = lines.len().saturating_sub(count);
+
+    Ok(lines[start..].iter().map(|line| line.to_string()).collect())
+}
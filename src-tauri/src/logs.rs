@@ -0,0 +1,253 @@
+use crate::error::DuneError;
+use crate::scanner::{self, DirEntryInfo, DirEnumerator, EntryKind, Hidden};
+use crate::snapshot;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+/// Logs smaller than this are only reported when they grow
+const MIN_LOG_BYTES: u64 = 1024 * 1024;
+const MAX_LOGS: usize = 200;
+/// Newest snapshots of the folder consulted for growth history
+const MAX_HISTORY_SNAPSHOTS: usize = 6;
+/// Snapshots younger than this are too close to now for a meaningful rate
+const MIN_HISTORY_AGE: Duration = Duration::from_secs(60 * 60);
+
+const LOG_EXTENSIONS: &[&str] = &["log", "out", "err", "journal", "trace"];
+/// Log files that usually have no extension
+const LOG_NAMES: &[&str] = &[
+    "syslog", "messages", "kern", "auth", "daemon", "dmesg", "wtmp", "btmp", "lastlog",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogFile {
+    pub path: String,
+    pub bytes: u64,
+    /// Days since the file was last written
+    pub modified_days: Option<u64>,
+    /// Net change per day since the oldest snapshot that has this file; negative after rotation
+    pub growth_per_day: Option<i64>,
+    /// How many days of history the growth rate covers
+    pub history_days: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogReport {
+    pub path: String,
+    /// Fastest growing first, then largest
+    pub logs: Vec<LogFile>,
+    pub total_bytes: u64,
+    /// Net growth of all reported logs per day
+    pub growth_per_day: i64,
+    /// Free space on the volume holding `path`
+    pub available_space: Option<u64>,
+    /// When the logs alone would fill the volume at their current rate
+    pub days_until_full: Option<f64>,
+    /// Snapshots the growth rates were taken from
+    pub snapshots_used: usize,
+    pub complete: bool,
+}
+
+/// Whether a file name looks like a log, rotated and compressed ones included
+fn is_log_name(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+
+    if name.contains(".log.") || LOG_NAMES.contains(&name.as_str()) {
+        return true;
+    }
+
+    // syslog.1, messages.2.gz
+    let stem = name.split('.').next().unwrap_or_default();
+    if name.len() > stem.len() && LOG_NAMES.contains(&stem) {
+        return true;
+    }
+
+    Path::new(&name)
+        .extension()
+        .is_some_and(|extension| LOG_EXTENSIONS.contains(&&*extension.to_string_lossy()))
+}
+
+struct Found {
+    path: PathBuf,
+    bytes: u64,
+    modified: Option<SystemTime>,
+}
+
+#[derive(Default)]
+struct Walk {
+    logs: Vec<Found>,
+    complete: bool,
+}
+
+/// `entry`, found at `path`, if it is a log, and every log under it; hidden folders are
+/// included since logs often live there
+fn add_entry(enumerator: &dyn DirEnumerator, path: &Path, entry: &DirEntryInfo, walked: &mut Walk) {
+    let complete = scanner::visit(
+        enumerator,
+        path,
+        entry,
+        Hidden::Include,
+        &mut |path: &Path, entry: &DirEntryInfo| {
+            if entry.kind == EntryKind::File && is_log_name(&entry.name.to_string_lossy()) {
+                walked.logs.push(Found {
+                    path: path.to_path_buf(),
+                    bytes: entry.len,
+                    modified: entry.modified,
+                });
+            }
+            true
+        },
+    );
+    walked.complete &= complete;
+}
+
+/// Log sizes recorded in one snapshot, with the snapshot's age
+struct History {
+    age: Duration,
+    sizes: HashMap<PathBuf, u64>,
+}
+
+/// Log sizes under `root` from the newest snapshots that cover it, oldest first
+fn load_history(snapshots: &Path, root: &Path, now: SystemTime) -> Vec<History> {
+    let infos = match snapshot::list(snapshots) {
+        Ok(infos) => infos,
+        Err(err) => {
+            log::warn!("Could not list snapshots for log history: {}", err);
+            return Vec::new();
+        }
+    };
+    let now_ms = now
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+
+    let mut history: Vec<History> = infos
+        .into_iter()
+        .filter(|info| {
+            let snapshot_root = Path::new(&info.root);
+            root.starts_with(snapshot_root) || snapshot_root.starts_with(root)
+        })
+        .map(|info| {
+            (
+                Duration::from_millis(now_ms.saturating_sub(info.created_at)),
+                info,
+            )
+        })
+        .filter(|(age, _)| *age >= MIN_HISTORY_AGE)
+        .take(MAX_HISTORY_SNAPSHOTS)
+        .filter_map(|(age, info)| {
+            let loaded = snapshot::load(snapshots, &info.id)
+                .inspect_err(|err| log::warn!("Skipping snapshot {}: {}", info.id, err))
+                .ok()?;
+            let tree = &loaded.tree;
+
+            let sizes = tree
+                .nodes
+                .iter()
+                .enumerate()
+                .filter(|(_, node)| !node.is_dir && is_log_name(&node.name))
+                .map(|(index, node)| (tree.path(index as u32), node.size))
+                .filter(|(path, _)| path.starts_with(root))
+                .collect();
+
+            Some(History { age, sizes })
+        })
+        .collect();
+
+    history.sort_by_key(|history| std::cmp::Reverse(history.age));
+    history
+}
+
+/// Large and growing log files under `path`, with growth rates from stored snapshots
+pub fn log_report(path: &str, snapshots: &Path) -> Result<LogReport, DuneError> {
+    let dir_path = scanner::require_dir(path)?;
+    let enumerator = scanner::default_enumerator();
+    let listing = enumerator
+        .read_dir(dir_path)
+        .map_err(|e| DuneError::io(dir_path, e))?;
+    let now = SystemTime::now();
+
+    let walked = listing
+        .entries
+        .into_par_iter()
+        .map(|entry| {
+            let mut walked = Walk {
+                complete: true,
+                ..Walk::default()
+            };
+            add_entry(enumerator, &dir_path.join(&entry.name), &entry, &mut walked);
+            walked
+        })
+        .reduce(
+            || Walk {
+                complete: true,
+                ..Walk::default()
+            },
+            |mut a, b| {
+                a.logs.extend(b.logs);
+                a.complete &= b.complete;
+                a
+            },
+        );
+
+    let history = load_history(snapshots, dir_path, now);
+
+    let mut logs: Vec<LogFile> = walked
+        .logs
+        .into_iter()
+        .map(|found| {
+            let oldest = history
+                .iter()
+                .find_map(|history| Some((history.age, *history.sizes.get(&found.path)?)));
+            let growth = oldest.map(|(age, then)| {
+                let days = age.as_secs_f64() / DAY.as_secs_f64();
+                ((found.bytes as f64 - then as f64) / days, days)
+            });
+
+            LogFile {
+                path: found.path.to_string_lossy().to_string(),
+                bytes: found.bytes,
+                modified_days: found.modified.map(|modified| {
+                    now.duration_since(modified).unwrap_or_default().as_secs() / DAY.as_secs()
+                }),
+                growth_per_day: growth.map(|(rate, _)| rate.round() as i64),
+                history_days: growth.map(|(_, days)| days),
+            }
+        })
+        .filter(|log| log.bytes >= MIN_LOG_BYTES || log.growth_per_day.is_some_and(|g| g > 0))
+        .collect();
+
+    logs.sort_by_key(|log| {
+        (
+            std::cmp::Reverse(log.growth_per_day.unwrap_or(i64::MIN)),
+            std::cmp::Reverse(log.bytes),
+        )
+    });
+    logs.truncate(MAX_LOGS);
+
+    let growth_per_day: i64 = logs.iter().filter_map(|log| log.growth_per_day).sum();
+    let available_space = scanner::disk_for(dir_path).map(|disk| disk.available_space);
+
+    log::info!(
+        "Found {} logs under {} growing {} bytes/day",
+        logs.len(),
+        path,
+        growth_per_day
+    );
+
+    Ok(LogReport {
+        path: path.to_string(),
+        total_bytes: logs.iter().map(|log| log.bytes).sum(),
+        growth_per_day,
+        days_until_full: available_space
+            .filter(|_| growth_per_day > 0)
+            .map(|available| available as f64 / growth_per_day as f64),
+        available_space,
+        snapshots_used: history.len(),
+        logs,
+        complete: listing.complete && walked.complete,
+    })
+}
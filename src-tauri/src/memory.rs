@@ -0,0 +1,72 @@
+use serde::Serialize;
+
+/// A cache and the rough heap size of what it holds
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct CacheMemory {
+    pub entries: usize,
+    pub bytes: u64,
+}
+
+/// A scan tree held in memory, finished or still growing
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanMemory {
+    pub scan_id: u64,
+    pub root: String,
+    pub nodes: u64,
+    pub bytes: u64,
+    pub running: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryStats {
+    /// Resident size of the whole app as the OS sees it, webview processes aside
+    pub resident_bytes: Option<u64>,
+    /// Running scans first, then finished ones newest first
+    pub scans: Vec<ScanMemory>,
+    pub child_indexes: CacheMemory,
+    pub listings: CacheMemory,
+    /// Snapshot trees loaded for search
+    pub search_index: CacheMemory,
+    /// Sum of the estimates above; the rest of `resident_bytes` is code, the allocator's
+    /// free lists and everything else
+    pub tracked_bytes: u64,
+}
+
+impl MemoryStats {
+    pub fn new(
+        scans: Vec<ScanMemory>,
+        child_indexes: CacheMemory,
+        listings: CacheMemory,
+        search_index: CacheMemory,
+    ) -> Self {
+        let tracked_bytes = scans.iter().map(|scan| scan.bytes).sum::<u64>()
+            + child_indexes.bytes
+            + listings.bytes
+            + search_index.bytes;
+
+        Self {
+            resident_bytes: resident_bytes(),
+            scans,
+            child_indexes,
+            listings,
+            search_index,
+            tracked_bytes,
+        }
+    }
+}
+
+fn resident_bytes() -> Option<u64> {
+    let pid = sysinfo::get_current_pid().ok()?;
+    let mut system = sysinfo::System::new();
+    system.refresh_processes(sysinfo::ProcessesToUpdate::Some(&[pid]), false);
+    system.process(pid).map(|process| process.memory())
+}
+
+/// Hand memory freed by dropped caches back to the OS; glibc keeps it otherwise, while
+/// the macOS and Windows allocators return it on their own
+pub fn release_freed() {
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    unsafe {
+        libc::malloc_trim(0);
+    }
+}
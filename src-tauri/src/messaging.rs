@@ -0,0 +1,405 @@
+use crate::scanner;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Cache folders Electron chat apps keep inside their data folder
+const ELECTRON_CACHES: &[&str] = &[
+    "Cache",
+    "Code Cache",
+    "GPUCache",
+    "DawnCache",
+    "Service Worker/CacheStorage",
+];
+/// Folder name mail clients use for attachments stored beside messages
+const ATTACHMENTS_DIR: &str = "Attachments";
+/// How deep to look for attachment folders inside a mail store
+const ATTACHMENT_SEARCH_DEPTH: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PartKind {
+    /// Mailboxes, OST/PST files, chat history
+    Messages,
+    Attachments,
+    Cache,
+    /// Everything else the app keeps
+    Data,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MessagingPart {
+    pub kind: PartKind,
+    pub path: String,
+    /// Excludes the parts carved out of this folder and listed on their own
+    pub bytes: u64,
+    /// Caches and copies of opened attachments; quit the app before clearing
+    pub safe_to_clear: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MessagingApp {
+    pub name: String,
+    /// Largest first
+    pub parts: Vec<MessagingPart>,
+    pub messages_bytes: u64,
+    pub attachments_bytes: u64,
+    pub cache_bytes: u64,
+    pub data_bytes: u64,
+    pub total_bytes: u64,
+    pub complete: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MessagingReport {
+    /// Apps, largest first
+    pub apps: Vec<MessagingApp>,
+    pub total_bytes: u64,
+    /// Bytes in parts flagged safe to clear
+    pub reclaimable_bytes: u64,
+}
+
+struct Location {
+    kind: PartKind,
+    path: PathBuf,
+    safe_to_clear: bool,
+}
+
+/// Where a mail or chat app keeps its data on this platform
+struct Layout {
+    name: &'static str,
+    locations: Vec<Location>,
+}
+
+fn messages(path: PathBuf) -> Location {
+    Location {
+        kind: PartKind::Messages,
+        path,
+        safe_to_clear: false,
+    }
+}
+
+fn data(path: PathBuf) -> Location {
+    Location {
+        kind: PartKind::Data,
+        path,
+        safe_to_clear: false,
+    }
+}
+
+fn cache(path: PathBuf) -> Location {
+    Location {
+        kind: PartKind::Cache,
+        path,
+        safe_to_clear: true,
+    }
+}
+
+/// Copies made when attachments are opened or saved from the app
+#[cfg(any(target_os = "macos", windows))]
+fn opened_attachments(path: PathBuf) -> Location {
+    Location {
+        kind: PartKind::Attachments,
+        path,
+        safe_to_clear: true,
+    }
+}
+
+/// `Attachments` folders inside a mail store
+fn find_attachments(dir: &Path, depth: usize, found: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        if !entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            continue;
+        }
+
+        let path = entry.path();
+        if entry.file_name() == ATTACHMENTS_DIR {
+            found.push(path);
+        } else if depth > 0 {
+            find_attachments(&path, depth - 1, found);
+        }
+    }
+}
+
+/// Parts stored inside `location` that are reported on their own
+fn carved_out(location: &Location) -> Vec<Location> {
+    match location.kind {
+        PartKind::Messages => {
+            let mut found = Vec::new();
+            find_attachments(&location.path, ATTACHMENT_SEARCH_DEPTH, &mut found);
+
+            // Stored beside the messages; some may exist nowhere else, e.g. POP accounts
+            found
+                .into_iter()
+                .map(|path| Location {
+                    kind: PartKind::Attachments,
+                    path,
+                    safe_to_clear: false,
+                })
+                .collect()
+        }
+        PartKind::Data => ELECTRON_CACHES
+            .iter()
+            .map(|name| location.path.join(name))
+            .filter(|path| path.exists())
+            .map(cache)
+            .collect(),
+        PartKind::Attachments | PartKind::Cache => Vec::new(),
+    }
+}
+
+fn part(location: &Location, bytes: u64) -> MessagingPart {
+    MessagingPart {
+        kind: location.kind,
+        path: location.path.to_string_lossy().to_string(),
+        bytes,
+        safe_to_clear: location.safe_to_clear,
+    }
+}
+
+fn measure(layout: Layout) -> MessagingApp {
+    let mut complete = true;
+    let mut parts = Vec::new();
+
+    for location in layout.locations.iter().filter(|l| l.path.exists()) {
+        let size = scanner::path_size(&location.path);
+        complete &= size.complete;
+        let mut carved_bytes = 0u64;
+
+        for inner in carved_out(location) {
+            let inner_size = scanner::path_size(&inner.path);
+            complete &= inner_size.complete;
+            carved_bytes = carved_bytes.saturating_add(inner_size.bytes);
+            parts.push(part(&inner, inner_size.bytes));
+        }
+
+        parts.push(part(location, size.bytes.saturating_sub(carved_bytes)));
+    }
+
+    parts.retain(|part| part.bytes > 0);
+    parts.sort_by_key(|part| std::cmp::Reverse(part.bytes));
+
+    let bytes_of = |kind| {
+        parts
+            .iter()
+            .filter(|part| part.kind == kind)
+            .map(|part| part.bytes)
+            .sum::<u64>()
+    };
+    let messages_bytes = bytes_of(PartKind::Messages);
+    let attachments_bytes = bytes_of(PartKind::Attachments);
+    let cache_bytes = bytes_of(PartKind::Cache);
+    let data_bytes = bytes_of(PartKind::Data);
+
+    MessagingApp {
+        name: layout.name.to_string(),
+        parts,
+        messages_bytes,
+        attachments_bytes,
+        cache_bytes,
+        data_bytes,
+        total_bytes: messages_bytes + attachments_bytes + cache_bytes + data_bytes,
+        complete,
+    }
+}
+
+/// Mail stores and chat app caches found in their default locations
+pub fn messaging_report() -> MessagingReport {
+    let mut apps: Vec<MessagingApp> = platform::layouts()
+        .into_iter()
+        .filter(|layout| layout.locations.iter().any(|l| l.path.exists()))
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(measure)
+        .filter(|app| app.total_bytes > 0)
+        .collect();
+
+    apps.sort_by_key(|app| std::cmp::Reverse(app.total_bytes));
+    log::info!("Measured {} mail and chat apps", apps.len());
+
+    MessagingReport {
+        total_bytes: apps.iter().map(|app| app.total_bytes).sum(),
+        reclaimable_bytes: apps
+            .iter()
+            .flat_map(|app| &app.parts)
+            .filter(|part| part.safe_to_clear)
+            .map(|part| part.bytes)
+            .sum(),
+        apps,
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::*;
+
+    pub fn layouts() -> Vec<Layout> {
+        let Some(home) = dirs::home_dir() else {
+            return Vec::new();
+        };
+        let library = home.join("Library");
+        let support = library.join("Application Support");
+        let containers = library.join("Containers");
+
+        vec![
+            // Reading ~/Library/Mail needs Full Disk Access; without it the size comes back incomplete
+            Layout {
+                name: "Mail",
+                locations: vec![
+                    messages(library.join("Mail")),
+                    opened_attachments(
+                        containers.join("com.apple.mail/Data/Library/Mail Downloads"),
+                    ),
+                    cache(containers.join("com.apple.mail/Data/Library/Caches")),
+                ],
+            },
+            Layout {
+                name: "Outlook",
+                locations: vec![
+                    messages(
+                        library
+                            .join("Group Containers/UBF8T346G9.Office/Outlook/Outlook 15 Profiles"),
+                    ),
+                    cache(containers.join("com.microsoft.Outlook/Data/Library/Caches")),
+                ],
+            },
+            Layout {
+                name: "Thunderbird",
+                locations: vec![
+                    messages(library.join("Thunderbird/Profiles")),
+                    cache(library.join("Caches/Thunderbird")),
+                ],
+            },
+            Layout {
+                name: "Slack",
+                locations: vec![
+                    data(support.join("Slack")),
+                    data(
+                        containers.join(
+                            "com.tinyspeck.slackmacgap/Data/Library/Application Support/Slack",
+                        ),
+                    ),
+                ],
+            },
+            Layout {
+                name: "Discord",
+                locations: vec![data(support.join("discord"))],
+            },
+            Layout {
+                name: "Teams",
+                locations: vec![
+                    data(support.join("Microsoft/Teams")),
+                    data(containers.join("com.microsoft.teams2/Data/Library/Application Support")),
+                    cache(containers.join("com.microsoft.teams2/Data/Library/Caches")),
+                ],
+            },
+        ]
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::*;
+
+    pub fn layouts() -> Vec<Layout> {
+        let (Some(roaming), Some(local)) = (dirs::data_dir(), dirs::data_local_dir()) else {
+            return Vec::new();
+        };
+        let documents = dirs::document_dir();
+
+        let mut outlook = vec![
+            // OST caches of Exchange and IMAP accounts
+            messages(local.join("Microsoft\\Outlook")),
+            opened_attachments(local.join("Microsoft\\Windows\\INetCache\\Content.Outlook")),
+        ];
+        if let Some(documents) = documents {
+            outlook.push(messages(documents.join("Outlook Files")));
+        }
+
+        vec![
+            Layout {
+                name: "Outlook",
+                locations: outlook,
+            },
+            Layout {
+                name: "Thunderbird",
+                locations: vec![
+                    messages(roaming.join("Thunderbird\\Profiles")),
+                    cache(local.join("Thunderbird\\Profiles")),
+                ],
+            },
+            Layout {
+                name: "Slack",
+                locations: vec![data(roaming.join("Slack"))],
+            },
+            Layout {
+                name: "Discord",
+                locations: vec![data(roaming.join("discord"))],
+            },
+            Layout {
+                name: "Teams",
+                locations: vec![
+                    data(roaming.join("Microsoft\\Teams")),
+                    data(local.join("Packages\\MSTeams_8wekyb3d8bbwe\\LocalCache")),
+                ],
+            },
+        ]
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::*;
+
+    pub fn layouts() -> Vec<Layout> {
+        let (Some(home), Some(config), Some(cache_dir)) =
+            (dirs::home_dir(), dirs::config_dir(), dirs::cache_dir())
+        else {
+            return Vec::new();
+        };
+        let data_dir = dirs::data_dir().unwrap_or_else(|| home.join(".local/share"));
+
+        vec![
+            Layout {
+                name: "Thunderbird",
+                locations: vec![
+                    messages(home.join(".thunderbird")),
+                    cache(cache_dir.join("thunderbird")),
+                ],
+            },
+            Layout {
+                name: "Evolution",
+                locations: vec![
+                    messages(data_dir.join("evolution/mail")),
+                    cache(cache_dir.join("evolution")),
+                ],
+            },
+            Layout {
+                name: "Slack",
+                locations: vec![data(config.join("Slack"))],
+            },
+            Layout {
+                name: "Discord",
+                locations: vec![data(config.join("discord"))],
+            },
+            Layout {
+                name: "Teams",
+                locations: vec![data(config.join("Microsoft/Microsoft Teams"))],
+            },
+        ]
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows, target_os = "linux")))]
+mod platform {
+    use super::Layout;
+
+    pub fn layouts() -> Vec<Layout> {
+        Vec::new()
+    }
+}
@@ -0,0 +1,126 @@
+use crate::scanner::DiskInfo;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Content type of the Prometheus text exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Outcome of the latest scan of a root, kept for `/metrics`
+#[derive(Debug, Clone)]
+pub struct ScanRecord {
+    pub bytes: u64,
+    /// Unknown for scheduled scans, which only sum sizes
+    pub files: Option<u64>,
+    pub duration: Duration,
+    pub complete: bool,
+    /// Milliseconds since the Unix epoch
+    pub finished_at: u64,
+}
+
+/// Quote a label value: backslash, double quote and newline are escaped
+fn label(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str(r"\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str(r"\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Metric name, help text, and the value it takes from each subject
+type Gauge<T, V> = (&'static str, &'static str, fn(&T) -> Option<V>);
+
+struct Family<'a> {
+    out: &'a mut String,
+}
+
+impl<'a> Family<'a> {
+    fn gauge(out: &'a mut String, name: &str, help: &str) -> Self {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        Self { out }
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, label(value)))
+            .collect();
+        let _ = writeln!(self.out, "{}{{{}}} {}", name, labels.join(","), value);
+    }
+}
+
+/// Disk space and the latest scan of each watched root, in the Prometheus text format.
+/// Roots that have not been scanned yet are left out.
+pub fn render(disks: &[DiskInfo], roots: &[(String, ScanRecord)]) -> String {
+    let mut out = String::new();
+
+    let disk_gauges: [Gauge<DiskInfo, u64>; 3] = [
+        ("dunefiles_disk_total_bytes", "Size of the disk", |disk| {
+            Some(disk.total_space)
+        }),
+        (
+            "dunefiles_disk_free_bytes",
+            "Space available to the user on the disk",
+            |disk| Some(disk.available_space),
+        ),
+        (
+            "dunefiles_disk_free_inodes",
+            "Inodes left on the disk, where the filesystem has a fixed number",
+            |disk| disk.free_inodes,
+        ),
+    ];
+    for (name, help, value) in disk_gauges {
+        let mut family = Family::gauge(&mut out, name, help);
+        for disk in disks {
+            if let Some(value) = value(disk) {
+                let labels = [("disk", disk.name.as_str()), ("mount", disk.path.as_str())];
+                family.sample(name, &labels, value as f64);
+            }
+        }
+    }
+
+    let scan_gauges: [Gauge<ScanRecord, f64>; 5] = [
+        (
+            "dunefiles_scan_size_bytes",
+            "Total size of the root at its latest scan",
+            |record| Some(record.bytes as f64),
+        ),
+        (
+            "dunefiles_scan_files",
+            "Files under the root at its latest scan",
+            |record| record.files.map(|files| files as f64),
+        ),
+        (
+            "dunefiles_scan_duration_seconds",
+            "How long the latest scan of the root took",
+            |record| Some(record.duration.as_secs_f64()),
+        ),
+        (
+            "dunefiles_scan_complete",
+            "1 if the latest scan read every folder under the root",
+            |record| Some(f64::from(u8::from(record.complete))),
+        ),
+        (
+            "dunefiles_scan_timestamp_seconds",
+            "When the latest scan of the root finished, as a Unix time",
+            |record| Some(record.finished_at as f64 / 1000.0),
+        ),
+    ];
+    for (name, help, value) in scan_gauges {
+        let mut family = Family::gauge(&mut out, name, help);
+        for (root, record) in roots {
+            if let Some(value) = value(record) {
+                family.sample(name, &[("root", root)], value);
+            }
+        }
+    }
+
+    out
+}
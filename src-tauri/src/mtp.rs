@@ -0,0 +1,400 @@
+use crate::error::DuneError;
+use crate::scan::{ScanHandle, ScanOptions, ScanProgress, ScanTree};
+use crate::scanner::DiskInfo;
+
+/// Scheme of phones reached through Windows Portable Devices, `mtp://Device/Storage/path`
+pub const SCHEME: &str = "mtp://";
+
+/// Marks gvfs FUSE paths to phones and cameras; every read is a USB round trip
+const SLOW_GVFS_BACKENDS: [&str; 2] = ["/gvfs/mtp:host=", "/gvfs/gphoto2:host="];
+
+/// MTP answers one request at a time, so parallel reads only queue up on the device
+pub const DEVICE_SCAN_THREADS: usize = 1;
+
+/// Paths on phones and cameras, where scans should not fan out
+pub fn is_slow_device(path: &str) -> bool {
+    path.starts_with(SCHEME)
+        || SLOW_GVFS_BACKENDS
+            .iter()
+            .any(|backend| path.contains(backend))
+}
+
+#[cfg_attr(not(any(target_os = "linux", target_os = "windows")), allow(dead_code))]
+fn device_disk(name: String, path: String, total_space: u64, available_space: u64) -> DiskInfo {
+    DiskInfo {
+        name,
+        path,
+        total_space,
+        available_space,
+        purgeable_space: 0,
+        full_in_days: None,
+        total_inodes: None,
+        free_inodes: None,
+        mtp: true,
+        ram_disk: false,
+        original_name: None,
+        default_folder: None,
+        container: None,
+        virtual_disk: None,
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::*;
+    use std::ffi::CString;
+    use std::fs;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+
+    fn capacity(path: &Path) -> (u64, u64) {
+        let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+            return (0, 0);
+        };
+        let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 {
+            return (0, 0);
+        }
+
+        #[allow(clippy::unnecessary_cast)] // The field types differ between platforms
+        let (blocks, available, fragment) = (
+            stats.f_blocks as u64,
+            stats.f_bavail as u64,
+            stats.f_frsize as u64,
+        );
+        (blocks * fragment, available * fragment)
+    }
+
+    /// `mtp:host=SAMSUNG_Galaxy_S21_R5CR10XYZ` to `SAMSUNG Galaxy S21`
+    fn device_name(mount: &str) -> String {
+        let host = mount.split_once("host=").map_or(mount, |(_, host)| host);
+        let mut words: Vec<&str> = host.split('_').collect();
+        // The last word is usually the serial number
+        if words.len() > 2 {
+            words.pop();
+        }
+        words.join(" ")
+    }
+
+    /// Storages of phones that gvfs has mounted under `/run/user/<uid>/gvfs`
+    pub fn mtp_disks() -> Vec<DiskInfo> {
+        let gvfs = PathBuf::from(format!("/run/user/{}/gvfs", unsafe { libc::getuid() }));
+        let Ok(mounts) = fs::read_dir(&gvfs) else {
+            return Vec::new();
+        };
+
+        let mut disks = Vec::new();
+        for mount in mounts.flatten() {
+            let mount_name = mount.file_name().to_string_lossy().to_string();
+            if !mount_name.starts_with("mtp:") {
+                continue;
+            }
+            let Ok(storages) = fs::read_dir(mount.path()) else {
+                continue;
+            };
+
+            for storage in storages.flatten() {
+                let path = storage.path();
+                let (total, available) = capacity(&path);
+                disks.push(device_disk(
+                    format!(
+                        "{} ({})",
+                        device_name(&mount_name),
+                        storage.file_name().to_string_lossy()
+                    ),
+                    path.to_string_lossy().to_string(),
+                    total,
+                    available,
+                ));
+            }
+        }
+
+        disks
+    }
+
+    pub fn scan_tree(
+        handle: &ScanHandle,
+        _options: &ScanOptions,
+        _on_progress: &(dyn Fn(ScanProgress) + Sync),
+    ) -> Result<ScanTree, DuneError> {
+        Err(DuneError::Unsupported(format!(
+            "{} locations only exist on Windows; gvfs mounts the phone as a folder instead",
+            handle.root
+        )))
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::*;
+    use crate::console;
+    use crate::scan::{self, NameInterner, ProgressCoalescer, TreeNode};
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read};
+    use std::path::Path;
+    use std::process::{Command, Stdio};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    /// Disk lists are read on every protection check; PowerShell is too slow to start each time
+    const CACHE_TTL: Duration = Duration::from_secs(30);
+
+    static CACHE: Mutex<Option<(Instant, Vec<DiskInfo>)>> = Mutex::new(None);
+
+    /// Storages of portable devices under This PC, one `device, storage, capacity, free` row each
+    const LIST_SCRIPT: &str = r#"
+        $ErrorActionPreference = 'Stop'
+        [Console]::OutputEncoding = [Text.Encoding]::UTF8
+        $shell = New-Object -ComObject Shell.Application
+        foreach ($device in $shell.NameSpace(17).Items()) {
+            if ($device.IsFileSystem -or -not $device.IsFolder) { continue }
+            foreach ($storage in $device.GetFolder.Items()) {
+                if (-not $storage.IsFolder) { continue }
+                $capacity = $storage.ExtendedProperty('System.Capacity')
+                $free = $storage.ExtendedProperty('System.FreeSpace')
+                "$($device.Name)`t$($storage.Name)`t$capacity`t$free"
+            }
+        }
+    "#;
+
+    /// Walks the folder named by `DUNE_MTP_PATH` (`Device/Storage/sub`), writing
+    /// NUL-terminated `type, size, relative path` records
+    const WALK_SCRIPT: &str = r#"
+        $ErrorActionPreference = 'Stop'
+        [Console]::OutputEncoding = [Text.Encoding]::UTF8
+        $shell = New-Object -ComObject Shell.Application
+        $folder = $shell.NameSpace(17)
+        foreach ($part in ($env:DUNE_MTP_PATH -split '/' | Where-Object { $_ })) {
+            $item = $folder.Items() | Where-Object { $_.Name -eq $part } | Select-Object -First 1
+            if (-not $item) { [Console]::Error.WriteLine("Not found: $part"); exit 2 }
+            $folder = $item.GetFolder
+        }
+        function Walk($folder, $prefix) {
+            foreach ($item in $folder.Items()) {
+                $path = if ($prefix) { "$prefix/$($item.Name)" } else { $item.Name }
+                if ($item.IsFolder) {
+                    [Console]::Out.Write("d`t0`t$path`0")
+                    Walk $item.GetFolder $path
+                } else {
+                    $size = $item.ExtendedProperty('System.Size')
+                    if (-not $size) { $size = 0 }
+                    [Console]::Out.Write("f`t$size`t$path`0")
+                }
+            }
+        }
+        Walk $folder ''
+    "#;
+
+    fn powershell(script: &str) -> Command {
+        let mut command = console::powershell(script);
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        command
+    }
+
+    fn device_disks() -> Vec<DiskInfo> {
+        let output = match powershell(LIST_SCRIPT).output() {
+            Ok(output) if output.status.success() => output,
+            Ok(output) => {
+                log::warn!(
+                    "Listing portable devices failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+                return Vec::new();
+            }
+            Err(err) => {
+                log::warn!("Could not run PowerShell: {}", err);
+                return Vec::new();
+            }
+        };
+
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|row| {
+                let mut fields = row.trim_end().split('\t');
+                let (device, storage) = (fields.next()?, fields.next()?);
+                let total = fields.next().and_then(|value| value.parse().ok());
+                let free = fields.next().and_then(|value| value.parse().ok());
+
+                Some(device_disk(
+                    format!("{} ({})", device, storage),
+                    format!("{}{}/{}", SCHEME, device, storage),
+                    total.unwrap_or(0),
+                    free.unwrap_or(0),
+                ))
+            })
+            .collect()
+    }
+
+    pub fn mtp_disks() -> Vec<DiskInfo> {
+        let mut cache = CACHE.lock().unwrap();
+        if let Some((read_at, disks)) = cache.as_ref() {
+            if read_at.elapsed() < CACHE_TTL {
+                return disks.clone();
+            }
+        }
+
+        let disks = device_disks();
+        *cache = Some((Instant::now(), disks.clone()));
+        disks
+    }
+
+    /// Walk a portable device through the Shell namespace; far slower than a disk, but the
+    /// only access Windows offers without a driver
+    pub fn scan_tree(
+        handle: &ScanHandle,
+        options: &ScanOptions,
+        on_progress: &(dyn Fn(ScanProgress) + Sync),
+    ) -> Result<ScanTree, DuneError> {
+        let started = Instant::now();
+        let device_path = handle.root.strip_prefix(SCHEME).unwrap_or(&handle.root);
+        let mut child = powershell(WALK_SCRIPT)
+            .env("DUNE_MTP_PATH", device_path)
+            .spawn()
+            .map_err(DuneError::internal)?;
+        let stderr = child.stderr.take().map(|mut stderr| {
+            thread::spawn(move || {
+                let mut text = String::new();
+                let _ = stderr.read_to_string(&mut text);
+                text
+            })
+        });
+
+        let root_name = device_path
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or(device_path);
+        let mut nodes = vec![TreeNode {
+            parent: None,
+            name: Arc::from(root_name),
+            is_dir: true,
+            size: 0,
+            file_count: 0,
+            size_complete: true,
+            modified: None,
+        }];
+        let mut names = NameInterner::default();
+        let mut dirs: HashMap<String, u32> = HashMap::from([(String::new(), 0)]);
+        let progress =
+            ProgressCoalescer::new(handle.id, options.progress_events_per_second, on_progress);
+        let current = Path::new(&handle.root);
+
+        if let Some(stdout) = child.stdout.take() {
+            for record in BufReader::new(stdout).split(0).map_while(Result::ok) {
+                if handle.is_cancelled() {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    log::info!("Device scan {} of {} cancelled", handle.id, handle.root);
+                    return Err(DuneError::Cancelled);
+                }
+
+                let record = String::from_utf8_lossy(&record);
+                let mut fields = record.splitn(3, '\t');
+                let (Some(kind), Some(size), Some(path)) =
+                    (fields.next(), fields.next(), fields.next())
+                else {
+                    continue;
+                };
+                let (parent_path, name) = path.rsplit_once('/').unwrap_or(("", path));
+                if !options.include_hidden && name.starts_with('.') {
+                    continue;
+                }
+                let Some(&parent) = dirs.get(parent_path) else {
+                    continue;
+                };
+
+                let is_dir = kind == "d";
+                let size = if is_dir { 0 } else { size.parse().unwrap_or(0) };
+                if is_dir {
+                    dirs.insert(path.to_string(), nodes.len() as u32);
+                    progress.record(0, 0, 1, current);
+                } else {
+                    progress.record(size, 1, 0, current);
+                }
+                nodes.push(TreeNode {
+                    parent: Some(parent),
+                    name: names.intern(name),
+                    is_dir,
+                    size,
+                    file_count: u64::from(!is_dir),
+                    size_complete: true,
+                    modified: None,
+                });
+            }
+        }
+
+        let status = child.wait().map_err(DuneError::internal)?;
+        if !status.success() {
+            let stderr = stderr
+                .and_then(|reader| reader.join().ok())
+                .unwrap_or_default();
+            return Err(DuneError::Io {
+                path: Some(handle.root.clone()),
+                message: format!("Device scan failed: {}", stderr.trim()),
+            });
+        }
+
+        progress.flush(current);
+        scan::aggregate(&mut nodes);
+
+        log::info!(
+            "Device scan {} of {}: {} nodes in {:?}",
+            handle.id,
+            handle.root,
+            nodes.len(),
+            started.elapsed()
+        );
+
+        Ok(ScanTree {
+            scan_id: handle.id,
+            root: handle.root.clone(),
+            nodes,
+            allocation: None,
+            subvolumes: Vec::new(),
+            ignored: Vec::new(),
+            small_items: Vec::new(),
+            skipped: Vec::new(),
+            stats: None,
+            elevated: false,
+        })
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+mod platform {
+    use super::*;
+
+    /// macOS has no system MTP support; phones show up only through vendor apps
+    pub fn mtp_disks() -> Vec<DiskInfo> {
+        Vec::new()
+    }
+
+    pub fn scan_tree(
+        handle: &ScanHandle,
+        _options: &ScanOptions,
+        _on_progress: &(dyn Fn(ScanProgress) + Sync),
+    ) -> Result<ScanTree, DuneError> {
+        Err(DuneError::Unsupported(format!(
+            "{} locations only exist on Windows",
+            handle.root
+        )))
+    }
+}
+
+/// Storages of connected phones and cameras, as disks
+pub fn mtp_disks() -> Vec<DiskInfo> {
+    platform::mtp_disks()
+}
+
+/// Scan an `mtp://` location; Windows only, elsewhere devices are mounted as folders
+pub fn scan_tree(
+    handle: &ScanHandle,
+    options: &ScanOptions,
+    on_progress: &(dyn Fn(ScanProgress) + Sync),
+) -> Result<ScanTree, DuneError> {
+    platform::scan_tree(handle, options, on_progress)
+}
@@ -0,0 +1,82 @@
+use crate::scan::{ScanTree, TreeNode};
+use crate::subvolumes::Subvolume;
+use std::path::Path;
+use std::sync::Arc;
+
+/// `paths` without repeats and without any that lie inside another one, whose bytes
+/// would otherwise count twice
+pub fn distinct_roots(paths: &[String]) -> Vec<String> {
+    let mut roots: Vec<&String> = Vec::new();
+    for path in paths {
+        if roots
+            .iter()
+            .any(|root| Path::new(path).starts_with(root.as_str()))
+        {
+            log::info!("Skipping {}, already covered by another root", path);
+            continue;
+        }
+        roots.retain(|root| !Path::new(root.as_str()).starts_with(path));
+        roots.push(path);
+    }
+
+    roots.into_iter().cloned().collect()
+}
+
+/// One tree holding every tree in `trees` under a synthetic root; each scanned root
+/// becomes a child named by its full path, so `ScanTree::path` still gives real paths
+pub fn combine(scan_id: u64, trees: &[Arc<ScanTree>]) -> ScanTree {
+    let mut nodes = vec![TreeNode {
+        parent: None,
+        name: Arc::from(format!("{} locations", trees.len())),
+        is_dir: true,
+        size: 0,
+        file_count: 0,
+        size_complete: true,
+        modified: None,
+    }];
+    let mut subvolumes = Vec::new();
+    let mut ignored = Vec::new();
+    let mut small_items = Vec::new();
+    let mut skipped = Vec::new();
+
+    for tree in trees {
+        let offset = nodes.len() as u32;
+        let Some(root) = tree.nodes.first() else {
+            continue;
+        };
+        nodes[0].size = nodes[0].size.saturating_add(root.size);
+        nodes[0].file_count += root.file_count;
+        nodes[0].size_complete &= root.size_complete;
+
+        // Totals are already rolled up, so the nodes only need their parents moved
+        nodes.extend(tree.nodes.iter().enumerate().map(|(index, node)| TreeNode {
+            parent: Some(node.parent.map_or(0, |parent| parent + offset)),
+            name: if index == 0 {
+                Arc::from(tree.root.as_str())
+            } else {
+                node.name.clone()
+            },
+            ..node.clone()
+        }));
+        subvolumes.extend(tree.subvolumes.iter().map(|subvolume| Subvolume {
+            node: subvolume.node + offset,
+            ..subvolume.clone()
+        }));
+        ignored.extend(tree.ignored.iter().cloned());
+        small_items.extend(tree.small_items.iter().map(|&node| node + offset));
+        skipped.extend(tree.skipped.iter().cloned());
+    }
+
+    ScanTree {
+        scan_id,
+        root: String::new(),
+        nodes,
+        allocation: None,
+        subvolumes,
+        ignored,
+        small_items,
+        skipped,
+        stats: None,
+        elevated: trees.iter().any(|tree| tree.elevated),
+    }
+}
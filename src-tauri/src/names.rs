@@ -0,0 +1,44 @@
+use icu_normalizer::ComposingNormalizerBorrowed;
+use std::borrow::Cow;
+use std::ffi::OsStr;
+
+/// Composed (NFC) form; macOS stores names decomposed while typed text is composed
+pub fn nfc(text: &str) -> Cow<'_, str> {
+    if text.is_ascii() {
+        return Cow::Borrowed(text);
+    }
+    ComposingNormalizerBorrowed::new_nfc().normalize(text)
+}
+
+/// What names and query words are compared as: composed and lowercased
+pub fn fold(text: &str) -> String {
+    nfc(&text.to_lowercase()).into_owned()
+}
+
+/// Characters that would break a line, or reorder the text around them, if shown as is
+fn visible(c: char) -> char {
+    match c {
+        // Control pictures, so `a\nb` shows as `a␊b`
+        '\0'..='\u{1f}' => char::from_u32(0x2400 + c as u32).unwrap_or('\u{fffd}'),
+        '\u{7f}' => '\u{2421}',
+        '\u{80}'..='\u{9f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}' => '\u{fffd}',
+        c => c,
+    }
+}
+
+/// A name as the UI shows it: valid UTF-8, composed, without control characters
+pub fn display(name: &OsStr) -> String {
+    let lossy = name.to_string_lossy();
+    let composed = nfc(&lossy);
+    if composed.chars().all(|c| visible(c) == c) {
+        return composed.into_owned();
+    }
+    composed.chars().map(visible).collect()
+}
+
+/// The exact bytes of a name whose display form differs from it, so it can still be
+/// matched or shown faithfully; WTF-8 on Windows
+pub fn raw_bytes(name: &OsStr, display: &str) -> Option<Vec<u8>> {
+    let bytes = name.as_encoded_bytes();
+    (bytes != display.as_bytes()).then(|| bytes.to_vec())
+}
@@ -0,0 +1,143 @@
+use crate::app_data;
+use crate::error::DuneError;
+use crate::format::TreeSummary;
+use crate::reconcile::Reconciliation;
+use crate::scan::{self, ScanTree};
+use crate::scanner::FileEntry;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Runtime};
+
+const FILE_NAME: &str = "notes.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Note {
+    pub text: String,
+    /// Milliseconds since the Unix epoch
+    pub updated_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PathNote {
+    pub path: String,
+    #[serde(flatten)]
+    pub note: Note,
+}
+
+/// A note on a node of a scan tree
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeNote {
+    pub index: u32,
+    #[serde(flatten)]
+    pub note: Note,
+}
+
+/// A scan tree sent with the notes inside it, keeping the tree's own fields at the top level
+#[derive(Serialize)]
+pub struct AnnotatedTree<'a> {
+    #[serde(flatten)]
+    pub tree: &'a ScanTree,
+    pub notes: Vec<NodeNote>,
+    #[serde(flatten)]
+    pub summary: TreeSummary,
+    /// Set for scans of a whole volume, see `reconcile`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reconciliation: Option<Reconciliation>,
+}
+
+pub fn store_path<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, DuneError> {
+    app_data::path(app, FILE_NAME)
+}
+
+/// Notes by path
+fn read(path: &Path) -> Result<BTreeMap<String, Note>, DuneError> {
+    match fs::read(path) {
+        Ok(json) => serde_json::from_slice(&json).map_err(|e| DuneError::Io {
+            path: Some(path.to_string_lossy().to_string()),
+            message: e.to_string(),
+        }),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(err) => Err(DuneError::io(path, err)),
+    }
+}
+
+fn write(path: &Path, notes: &BTreeMap<String, Note>) -> Result<(), DuneError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| DuneError::io(parent, e))?;
+    }
+
+    let json = serde_json::to_vec_pretty(notes).map_err(DuneError::internal)?;
+    fs::write(path, json).map_err(|e| DuneError::io(path, e))
+}
+
+/// Set the note on `path`; blank text removes it
+pub fn set_note(store: &Path, path: &str, text: &str) -> Result<Option<Note>, DuneError> {
+    let mut notes = read(store)?;
+    let text = text.trim();
+
+    let note = if text.is_empty() {
+        notes.remove(path);
+        None
+    } else {
+        let note = Note {
+            text: text.to_string(),
+            updated_at: scan::now_millis(),
+        };
+        notes.insert(path.to_string(), note.clone());
+        Some(note)
+    };
+
+    write(store, &notes)?;
+    log::info!(
+        "{} note on {}",
+        if note.is_some() { "Saved" } else { "Removed" },
+        path
+    );
+
+    Ok(note)
+}
+
+/// Notes on `root` and everything below it, sorted by path
+pub fn get_notes(store: &Path, root: &str) -> Result<Vec<PathNote>, DuneError> {
+    let root = Path::new(root);
+
+    Ok(read(store)?
+        .into_iter()
+        .filter(|(path, _)| Path::new(path).starts_with(root))
+        .map(|(path, note)| PathNote { path, note })
+        .collect())
+}
+
+/// Fill in `note` from the store
+pub fn apply(store: &Path, entries: &mut [FileEntry]) -> Result<(), DuneError> {
+    let mut notes = read(store)?;
+    if notes.is_empty() {
+        return Ok(());
+    }
+
+    for entry in entries {
+        entry.note = notes.remove(&entry.path).map(|note| note.text);
+    }
+
+    Ok(())
+}
+
+/// Notes on the nodes of `tree`; notes on paths the scan did not reach are left out
+pub fn annotate<'a>(store: &Path, tree: &'a ScanTree) -> Result<AnnotatedTree<'a>, DuneError> {
+    let notes = get_notes(store, &tree.root)?
+        .into_iter()
+        .filter_map(|PathNote { path, note }| {
+            let index = tree.find(Path::new(&path))?;
+            Some(NodeNote { index, note })
+        })
+        .collect();
+
+    Ok(AnnotatedTree {
+        tree,
+        notes,
+        summary: TreeSummary::of(tree),
+        reconciliation: None,
+    })
+}
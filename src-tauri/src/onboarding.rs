@@ -0,0 +1,198 @@
+use crate::app_data;
+use crate::error::DuneError;
+use crate::scan;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tauri::{AppHandle, Runtime};
+
+const FILE_NAME: &str = "onboarding.json";
+/// Bumped when the wizard gains steps everyone should see again
+pub const WIZARD_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OnboardingState {
+    /// Wizard version last finished, 0 before the first run
+    pub completed_version: u32,
+    /// Milliseconds since the Unix epoch
+    pub completed_at: Option<u64>,
+    /// Steps done so far, kept so an interrupted wizard resumes where it stopped
+    pub steps: Vec<String>,
+    /// When each disclaimer was accepted, in milliseconds since the Unix epoch
+    pub accepted_disclaimers: BTreeMap<String, u64>,
+    /// The wizard should run: never finished, or finished before it gained steps
+    #[serde(skip_deserializing)]
+    pub needed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Access {
+    Readable,
+    Denied,
+    Missing,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PathAccess {
+    /// `home`, `desktop`, `documents` and the like
+    pub label: &'static str,
+    pub path: String,
+    pub access: Access,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PermissionReport {
+    /// macOS only: `Some(false)` means scans will miss Mail, Safari, Messages and more
+    /// until the app is given Full Disk Access in System Settings
+    pub full_disk_access: Option<bool>,
+    pub paths: Vec<PathAccess>,
+}
+
+pub fn store_path<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, DuneError> {
+    app_data::path(app, FILE_NAME)
+}
+
+pub fn read(store: &Path) -> Result<OnboardingState, DuneError> {
+    let mut state = stored(store)?;
+    state.needed = state.completed_version < WIZARD_VERSION;
+    Ok(state)
+}
+
+fn stored(store: &Path) -> Result<OnboardingState, DuneError> {
+    match fs::read(store) {
+        Ok(json) => serde_json::from_slice(&json).map_err(|e| DuneError::Io {
+            path: Some(store.to_string_lossy().to_string()),
+            message: e.to_string(),
+        }),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(OnboardingState::default()),
+        Err(err) => Err(DuneError::io(store, err)),
+    }
+}
+
+fn write(store: &Path, state: &OnboardingState) -> Result<(), DuneError> {
+    if let Some(parent) = store.parent() {
+        fs::create_dir_all(parent).map_err(|e| DuneError::io(parent, e))?;
+    }
+
+    let json = serde_json::to_vec_pretty(state).map_err(DuneError::internal)?;
+    fs::write(store, json).map_err(|e| DuneError::io(store, e))
+}
+
+fn update(
+    store: &Path,
+    change: impl FnOnce(&mut OnboardingState, u64),
+) -> Result<OnboardingState, DuneError> {
+    let mut state = stored(store)?;
+    change(
+        &mut state,
+        scan::millis_since_epoch(SystemTime::now()).unwrap_or(0),
+    );
+    write(store, &state)?;
+    state.needed = state.completed_version < WIZARD_VERSION;
+    Ok(state)
+}
+
+/// Record that the user accepted the disclaimer `id`; accepting again keeps the first date
+pub fn accept_disclaimer(store: &Path, id: &str) -> Result<OnboardingState, DuneError> {
+    if id.trim().is_empty() {
+        return Err(DuneError::InvalidInput("Disclaimer id is empty".into()));
+    }
+    log::info!("Disclaimer {} accepted", id);
+    update(store, |state, now| {
+        state
+            .accepted_disclaimers
+            .entry(id.to_string())
+            .or_insert(now);
+    })
+}
+
+pub fn complete_step(store: &Path, step: &str) -> Result<OnboardingState, DuneError> {
+    update(store, |state, _| {
+        if !state.steps.iter().any(|done| done == step) {
+            state.steps.push(step.to_string());
+        }
+    })
+}
+
+pub fn finish(store: &Path) -> Result<OnboardingState, DuneError> {
+    log::info!("Onboarding finished");
+    update(store, |state, now| {
+        state.completed_version = WIZARD_VERSION;
+        state.completed_at = Some(now);
+    })
+}
+
+/// Start the wizard over on the next launch, keeping accepted disclaimers
+pub fn reset(store: &Path) -> Result<OnboardingState, DuneError> {
+    update(store, |state, _| {
+        state.completed_version = 0;
+        state.completed_at = None;
+        state.steps.clear();
+    })
+}
+
+fn access(path: &Path) -> Access {
+    // Listing is what privacy protection stops; `metadata` passes either way
+    match fs::read_dir(path) {
+        Ok(_) => Access::Readable,
+        Err(err) => match err.kind() {
+            io::ErrorKind::PermissionDenied => Access::Denied,
+            io::ErrorKind::NotFound => Access::Missing,
+            _ => Access::Failed,
+        },
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn full_disk_access() -> Option<bool> {
+    // Both stay unreadable without Full Disk Access, whatever else was allowed
+    let home = dirs::home_dir()?;
+    let protected = [
+        home.join("Library/Application Support/com.apple.TCC/TCC.db"),
+        home.join("Library/Safari"),
+    ];
+    protected
+        .iter()
+        .find_map(|path| match fs::File::open(path) {
+            Ok(_) => Some(true),
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => Some(false),
+            Err(_) => None,
+        })
+}
+
+#[cfg(not(target_os = "macos"))]
+fn full_disk_access() -> Option<bool> {
+    None
+}
+
+/// Whether the folders most scans start from can be listed, and on macOS whether the
+/// app has Full Disk Access
+pub fn check_permissions() -> PermissionReport {
+    let folders = [
+        ("home", dirs::home_dir()),
+        ("desktop", dirs::desktop_dir()),
+        ("documents", dirs::document_dir()),
+        ("downloads", dirs::download_dir()),
+        ("pictures", dirs::picture_dir()),
+    ];
+    let paths = folders
+        .into_iter()
+        .filter_map(|(label, path)| Some((label, path?)))
+        .map(|(label, path)| PathAccess {
+            label,
+            access: access(&path),
+            path: path.to_string_lossy().into_owned(),
+        })
+        .collect();
+
+    PermissionReport {
+        full_disk_access: full_disk_access(),
+        paths,
+    }
+}
@@ -0,0 +1,274 @@
+use serde::Serialize;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Where a downloaded file came from, as the browser or OS recorded it
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DownloadOrigin {
+    /// The file itself
+    pub url: Option<String>,
+    /// The page it was downloaded from
+    pub referrer: Option<String>,
+    /// App that downloaded it, e.g. `Safari`; macOS only
+    pub agent: Option<String>,
+    /// When it was downloaded; outside macOS, when the file was created
+    pub downloaded_at: Option<SystemTime>,
+    /// macOS Gatekeeper quarantine is still in place, or on Windows the file came from the
+    /// internet zone or a less trusted one
+    pub quarantined: bool,
+}
+
+/// Download metadata of `path`; `None` when there is none, as for files made locally
+pub fn read(path: &Path) -> Option<DownloadOrigin> {
+    let origin = platform::origin(path)?;
+    Some(DownloadOrigin {
+        downloaded_at: origin.downloaded_at.or_else(|| {
+            std::fs::symlink_metadata(path)
+                .and_then(|m| m.created())
+                .ok()
+        }),
+        ..origin
+    })
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod xattr {
+    use std::ffi::{CStr, CString};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    #[cfg(target_os = "linux")]
+    unsafe fn get(path: &CStr, name: &CStr, buffer: *mut libc::c_void, size: usize) -> isize {
+        libc::lgetxattr(path.as_ptr(), name.as_ptr(), buffer, size)
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn get(path: &CStr, name: &CStr, buffer: *mut libc::c_void, size: usize) -> isize {
+        libc::getxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            buffer,
+            size,
+            0,
+            libc::XATTR_NOFOLLOW,
+        )
+    }
+
+    /// Value of the extended attribute `name`, `None` when it is missing or unreadable
+    pub fn value(path: &Path, name: &str) -> Option<Vec<u8>> {
+        let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+        let name = CString::new(name).ok()?;
+
+        let size = unsafe { get(&path, &name, std::ptr::null_mut(), 0) };
+        if size < 0 {
+            return None;
+        }
+        let mut buffer = vec![0u8; size as usize];
+        let read = unsafe { get(&path, &name, buffer.as_mut_ptr().cast(), buffer.len()) };
+        if read < 0 {
+            return None;
+        }
+        buffer.truncate(read as usize);
+
+        Some(buffer)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{xattr, DownloadOrigin};
+    use std::path::Path;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// Set by Gatekeeper once the user allowed the file to open
+    const QUARANTINE_APPROVED: u32 = 0x40;
+
+    pub fn origin(path: &Path) -> Option<DownloadOrigin> {
+        let quarantine = xattr::value(path, "com.apple.quarantine");
+        let where_froms = xattr::value(path, "com.apple.metadata:kMDItemWhereFroms")
+            .and_then(|plist| strings(&plist))
+            .unwrap_or_default();
+        if quarantine.is_none() && where_froms.is_empty() {
+            return None;
+        }
+
+        // `0083;5f2b1c3a;Safari;UUID`: flags and seconds since 1970, both in hex
+        let quarantine = quarantine.map(|value| String::from_utf8_lossy(&value).into_owned());
+        let fields: Vec<&str> = quarantine.as_deref().map_or(Vec::new(), |value| {
+            value.trim_end_matches('\0').split(';').collect()
+        });
+        let flags = fields
+            .first()
+            .and_then(|flags| u32::from_str_radix(flags, 16).ok());
+        let downloaded_at = fields
+            .get(1)
+            .and_then(|seconds| u64::from_str_radix(seconds, 16).ok())
+            .map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds));
+        let agent = fields
+            .get(2)
+            .filter(|agent| !agent.is_empty())
+            .map(|agent| agent.to_string());
+
+        let mut where_froms = where_froms.into_iter();
+        Some(DownloadOrigin {
+            url: where_froms.next(),
+            referrer: where_froms.next(),
+            agent,
+            downloaded_at,
+            quarantined: flags.is_some_and(|flags| flags & QUARANTINE_APPROVED == 0),
+        })
+    }
+
+    /// Big-endian integer of `width` bytes at `at`
+    fn number(data: &[u8], at: usize, width: usize) -> Option<usize> {
+        let bytes = data.get(at..at.checked_add(width)?)?;
+        Some(
+            bytes
+                .iter()
+                .fold(0usize, |value, &byte| (value << 8) | usize::from(byte)),
+        )
+    }
+
+    /// Length of the object with `marker` at `at`, and where its contents start
+    fn length(data: &[u8], marker: u8, at: usize) -> Option<(usize, usize)> {
+        if marker & 0x0f != 0x0f {
+            return Some((usize::from(marker & 0x0f), at + 1));
+        }
+        // Longer lengths follow as an integer object
+        let int_marker = *data.get(at + 1)?;
+        if int_marker & 0xf0 != 0x10 {
+            return None;
+        }
+        let width = 1usize << (int_marker & 0x0f);
+        Some((number(data, at + 2, width)?, at + 2 + width))
+    }
+
+    /// The array of strings in a `bplist00` binary property list, which is all
+    /// `kMDItemWhereFroms` holds
+    fn strings(data: &[u8]) -> Option<Vec<String>> {
+        if !data.starts_with(b"bplist00") || data.len() < 40 {
+            return None;
+        }
+        let trailer = &data[data.len() - 32..];
+        let offset_width = usize::from(trailer[6]);
+        let ref_width = usize::from(trailer[7]);
+        let objects = number(trailer, 8, 8)?;
+        let top = number(trailer, 16, 8)?;
+        let table = number(trailer, 24, 8)?;
+        let offset_of = |object: usize| {
+            (object < objects)
+                .then(|| number(data, table + object * offset_width, offset_width))
+                .flatten()
+        };
+
+        let string_at = |at: usize| -> Option<String> {
+            let marker = *data.get(at)?;
+            let (count, start) = length(data, marker, at)?;
+            match marker >> 4 {
+                0x5 => Some(String::from_utf8_lossy(data.get(start..start + count)?).into_owned()),
+                0x6 => {
+                    let units: Vec<u16> = data
+                        .get(start..start + count * 2)?
+                        .chunks_exact(2)
+                        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                        .collect();
+                    Some(String::from_utf16_lossy(&units))
+                }
+                _ => None,
+            }
+        };
+
+        let at = offset_of(top)?;
+        let marker = *data.get(at)?;
+        if marker >> 4 != 0xa {
+            return None;
+        }
+        let (count, start) = length(data, marker, at)?;
+
+        Some(
+            (0..count)
+                .filter_map(|item| number(data, start + item * ref_width, ref_width))
+                .filter_map(offset_of)
+                .filter_map(string_at)
+                .filter(|string| !string.is_empty())
+                .collect(),
+        )
+    }
+}
+
+/// Chrome and Firefox on Linux keep the URLs in `user.xdg` attributes
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{xattr, DownloadOrigin};
+    use std::path::Path;
+
+    fn text(path: &Path, name: &str) -> Option<String> {
+        xattr::value(path, name)
+            .map(|value| {
+                String::from_utf8_lossy(&value)
+                    .trim_end_matches('\0')
+                    .to_string()
+            })
+            .filter(|value| !value.is_empty())
+    }
+
+    pub fn origin(path: &Path) -> Option<DownloadOrigin> {
+        let url = text(path, "user.xdg.origin.url");
+        let referrer = text(path, "user.xdg.referrer.url");
+        if url.is_none() && referrer.is_none() {
+            return None;
+        }
+
+        Some(DownloadOrigin {
+            url,
+            referrer,
+            ..DownloadOrigin::default()
+        })
+    }
+}
+
+/// The `Zone.Identifier` stream browsers and Explorer attach to downloads
+#[cfg(windows)]
+mod platform {
+    use super::DownloadOrigin;
+    use std::path::Path;
+
+    /// `URLZONE_INTERNET`; restricted sites are 4
+    const INTERNET_ZONE: u32 = 3;
+
+    pub fn origin(path: &Path) -> Option<DownloadOrigin> {
+        let mut stream = path.as_os_str().to_owned();
+        stream.push(":Zone.Identifier");
+        let identifier = std::fs::read_to_string(stream).ok()?;
+
+        // `[ZoneTransfer]` with `ZoneId=3`, `ReferrerUrl=...` and `HostUrl=...`
+        let mut origin = DownloadOrigin::default();
+        for line in identifier.lines() {
+            let Some((key, value)) = line.trim().split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "ZoneId" => {
+                    origin.quarantined =
+                        value.parse::<u32>().is_ok_and(|zone| zone >= INTERNET_ZONE)
+                }
+                "HostUrl" if value != "about:internet" => origin.url = Some(value.into()),
+                "ReferrerUrl" => origin.referrer = Some(value.into()),
+                _ => {}
+            }
+        }
+
+        Some(origin)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+mod platform {
+    use super::DownloadOrigin;
+    use std::path::Path;
+
+    pub fn origin(_path: &Path) -> Option<DownloadOrigin> {
+        None
+    }
+}
@@ -0,0 +1,297 @@
+use crate::error::DuneError;
+use crate::query::{Query, Subject};
+use crate::scan::{self, ScanTree, TreeNode};
+use crate::search::{SearchHit, SearchOptions, SearchSummary};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Paths taken from the OS index per search
+const OS_LIMIT: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HitSource {
+    /// Only in stored snapshots
+    Index,
+    /// Only in the OS index, so not scanned yet
+    Os,
+    Both,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OsSearchHit {
+    /// Set when the path is in a stored snapshot
+    pub snapshot_id: Option<String>,
+    pub path: String,
+    pub name: String,
+    pub is_dir: bool,
+    /// From the snapshot when there is one; folders only found by the OS report 0
+    pub size: u64,
+    pub score: u32,
+    pub source: HitSource,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OsSearchResult {
+    pub index: SearchSummary,
+    /// Spotlight or Windows Search; `None` where the OS has no index to ask
+    pub provider: Option<String>,
+    /// Why the OS index could not be asked; the index hits are still returned
+    pub os_error: Option<String>,
+    pub os_matches: u64,
+    /// Index hits by score, then paths only the OS knows, largest first
+    pub hits: Vec<OsSearchHit>,
+    /// Stopped at `limit`
+    pub truncated: bool,
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+
+    pub const PROVIDER: Option<&str> = Some("Spotlight");
+
+    /// `mdfind -name` takes one word, so ask for the longest and let the query filter the rest
+    pub fn find(words: &[String], limit: usize) -> Result<Vec<PathBuf>, DuneError> {
+        let Some(word) = words.iter().max_by_key(|word| word.chars().count()) else {
+            return Ok(Vec::new());
+        };
+
+        let mut child = Command::new("mdfind")
+            .args(["-0", "-name"])
+            .arg(word)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(DuneError::internal)?;
+
+        let paths = match child.stdout.take() {
+            Some(stdout) => BufReader::new(stdout)
+                .split(0)
+                .map_while(Result::ok)
+                .take(limit)
+                .map(|path| PathBuf::from(String::from_utf8_lossy(&path).to_string()))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        // Common words match far more than `limit`; there is no need to read the rest
+        let _ = child.kill();
+        let _ = child.wait();
+
+        Ok(paths)
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::*;
+    use crate::console;
+
+    pub const PROVIDER: Option<&str> = Some("Windows Search");
+
+    /// The SQL arrives through `DUNE_QUERY`, so nothing in it is parsed by PowerShell
+    const SCRIPT: &str = "$ErrorActionPreference = 'Stop'; \
+        [Console]::OutputEncoding = [Text.Encoding]::UTF8; \
+        $connection = New-Object -ComObject ADODB.Connection; \
+        $connection.Open(\"Provider=Search.CollatorDSO;Extended Properties='Application=Windows';\"); \
+        $records = $connection.Execute($env:DUNE_QUERY); \
+        while (-not $records.EOF) { \
+            $records.Fields.Item('System.ItemPathDisplay').Value; $records.MoveNext() \
+        }";
+
+    /// Quote a word for a Windows Search `LIKE` pattern
+    fn like_escape(word: &str) -> String {
+        word.chars()
+            .map(|c| match c {
+                '\'' => "''".to_string(),
+                '[' | '%' | '_' => format!("[{}]", c),
+                c => c.to_string(),
+            })
+            .collect()
+    }
+
+    pub fn find(words: &[String], limit: usize) -> Result<Vec<PathBuf>, DuneError> {
+        if words.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let filters: Vec<String> = words
+            .iter()
+            .map(|word| format!("System.FileName LIKE '%{}%'", like_escape(word)))
+            .collect();
+        let sql = format!(
+            "SELECT TOP {} System.ItemPathDisplay FROM SYSTEMINDEX WHERE SCOPE='file:' AND {}",
+            limit,
+            filters.join(" AND ")
+        );
+
+        let output = console::powershell(SCRIPT)
+            .env("DUNE_QUERY", sql)
+            .output()
+            .map_err(DuneError::internal)?;
+
+        if !output.status.success() {
+            return Err(DuneError::Internal(format!(
+                "Windows Search query failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(PathBuf::from)
+            .collect())
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod platform {
+    use super::*;
+
+    pub const PROVIDER: Option<&str> = None;
+
+    pub fn find(_words: &[String], _limit: usize) -> Result<Vec<PathBuf>, DuneError> {
+        Err(DuneError::Unsupported(
+            "OS search needs Spotlight or Windows Search".into(),
+        ))
+    }
+}
+
+/// A one-node tree, so the query can check a path the app never scanned
+fn stat_tree(path: &Path) -> Option<ScanTree> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    let is_dir = metadata.is_dir();
+    let name = path.file_name()?.to_string_lossy();
+
+    Some(ScanTree {
+        scan_id: 0,
+        root: path.to_string_lossy().to_string(),
+        nodes: vec![TreeNode {
+            parent: None,
+            name: Arc::from(name),
+            is_dir,
+            size: if is_dir { 0 } else { metadata.len() },
+            file_count: if is_dir { 0 } else { 1 },
+            size_complete: !is_dir,
+            modified: (!is_dir)
+                .then(|| metadata.modified().ok())
+                .flatten()
+                .and_then(scan::millis_since_epoch),
+        }],
+        allocation: None,
+        subvolumes: Vec::new(),
+        ignored: Vec::new(),
+        small_items: Vec::new(),
+        skipped: Vec::new(),
+        stats: None,
+        elevated: false,
+    })
+}
+
+/// Ask the OS index for the query's plain words, keeping the paths the whole query accepts.
+/// Queries made only of filters are not sent, as the OS would have to list every file.
+pub fn os_hits(query: &Query, options: &SearchOptions) -> Result<Vec<OsSearchHit>, DuneError> {
+    let words = query.required_words();
+    if words.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let hits = platform::find(&words, OS_LIMIT)?
+        .into_iter()
+        .filter(|path| {
+            options.roots.is_empty() || options.roots.iter().any(|root| path.starts_with(root))
+        })
+        .filter_map(|path| {
+            // The OS index can lag behind deletions
+            let tree = stat_tree(&path)?;
+            let score = query.score(&Subject::new(&tree, 0))?;
+            let node = &tree.nodes[0];
+            if (node.is_dir && !options.include_dirs) || node.size < options.min_size {
+                return None;
+            }
+
+            Some(OsSearchHit {
+                snapshot_id: None,
+                path: tree.root.clone(),
+                name: node.name.to_string(),
+                is_dir: node.is_dir,
+                size: node.size,
+                score,
+                source: HitSource::Os,
+            })
+        })
+        .collect();
+
+    Ok(hits)
+}
+
+/// Merge index and OS hits by path
+pub fn merge(
+    index: SearchSummary,
+    index_hits: Vec<SearchHit>,
+    os: Result<Vec<OsSearchHit>, DuneError>,
+    options: &SearchOptions,
+) -> OsSearchResult {
+    let (os, os_error) = match os {
+        Ok(os) => (os, None),
+        Err(err) => (Vec::new(), Some(err.to_string())),
+    };
+    let os_matches = os.len() as u64;
+    let mut os_only: HashMap<PathBuf, OsSearchHit> = os
+        .into_iter()
+        .map(|hit| (PathBuf::from(&hit.path), hit))
+        .collect();
+
+    let mut hits: Vec<OsSearchHit> = index_hits
+        .into_iter()
+        .map(|hit| {
+            let in_os = os_only.remove(Path::new(&hit.path)).is_some();
+            OsSearchHit {
+                snapshot_id: Some(hit.snapshot_id),
+                path: hit.path,
+                name: hit.name,
+                is_dir: hit.is_dir,
+                size: hit.size,
+                score: hit.score,
+                source: if in_os {
+                    HitSource::Both
+                } else {
+                    HitSource::Index
+                },
+            }
+        })
+        .collect();
+    hits.sort_by_key(|hit| std::cmp::Reverse(hit.score));
+
+    let mut os_only: Vec<OsSearchHit> = os_only.into_values().collect();
+    os_only.sort_by_key(|hit| std::cmp::Reverse(hit.size));
+    hits.extend(os_only);
+
+    let truncated = index.truncated || hits.len() > options.limit;
+    hits.truncate(options.limit);
+
+    log::info!(
+        "OS search {} merged {} index and {} OS matches into {} hits",
+        index.search_id,
+        index.matches,
+        os_matches,
+        hits.len()
+    );
+
+    OsSearchResult {
+        index,
+        provider: platform::PROVIDER.map(str::to_string),
+        os_error,
+        os_matches,
+        hits,
+        truncated,
+    }
+}
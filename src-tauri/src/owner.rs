@@ -0,0 +1,311 @@
+use crate::error::DuneError;
+use crate::scanner::{self, DirEntryInfo, DirEnumerator, EntryKind, Hidden};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OwnerTotal {
+    /// `uid:<n>` on Unix, the SID string on Windows
+    pub id: String,
+    /// Account name, when the system can resolve it
+    pub name: Option<String>,
+    pub bytes: u64,
+    pub files: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OwnerStats {
+    pub path: String,
+    /// Owners, largest first
+    pub owners: Vec<OwnerTotal>,
+    pub complete: bool,
+}
+
+struct Tally {
+    owners: HashMap<platform::OwnerKey, (u64, u64)>,
+    complete: bool,
+}
+
+impl Default for Tally {
+    fn default() -> Self {
+        Self {
+            owners: HashMap::new(),
+            complete: true,
+        }
+    }
+}
+
+impl Tally {
+    fn add(&mut self, owner: platform::OwnerKey, bytes: u64) {
+        let total = self.owners.entry(owner).or_default();
+        total.0 = total.0.saturating_add(bytes);
+        total.1 += 1;
+    }
+
+    fn merge(mut self, other: Tally) -> Tally {
+        for (owner, (bytes, files)) in other.owners {
+            let total = self.owners.entry(owner).or_default();
+            total.0 = total.0.saturating_add(bytes);
+            total.1 += files;
+        }
+        self.complete &= other.complete;
+        self
+    }
+}
+
+/// Tally `entry`, found at `path`, and everything under it; owners are per inode, so
+/// links are never followed
+fn add_entry(enumerator: &dyn DirEnumerator, path: &Path, entry: &DirEntryInfo, tally: &mut Tally) {
+    let complete = scanner::visit(
+        enumerator,
+        path,
+        entry,
+        Hidden::Include,
+        &mut |path: &Path, entry: &DirEntryInfo| {
+            if entry.kind == EntryKind::File {
+                match fs::symlink_metadata(path)
+                    .ok()
+                    .and_then(|metadata| platform::owner(path, &metadata))
+                {
+                    Some(owner) => tally.add(owner, entry.len),
+                    None => tally.complete = false,
+                }
+            }
+            true
+        },
+    );
+    tally.complete &= complete;
+}
+
+/// Bytes under `path` per file owner; hidden files count too, since they use the volume all the same
+pub fn owner_stats(path: &str) -> Result<OwnerStats, DuneError> {
+    let dir_path = scanner::require_dir(path)?;
+    let enumerator = scanner::default_enumerator();
+    let listing = enumerator
+        .read_dir(dir_path)
+        .map_err(|e| DuneError::io(dir_path, e))?;
+
+    let tally = listing
+        .entries
+        .into_par_iter()
+        .map(|entry| {
+            let mut tally = Tally::default();
+            add_entry(enumerator, &dir_path.join(&entry.name), &entry, &mut tally);
+            tally
+        })
+        .reduce(Tally::default, Tally::merge);
+
+    let mut owners: Vec<OwnerTotal> = tally
+        .owners
+        .into_iter()
+        .map(|(owner, (bytes, files))| {
+            let (id, name) = platform::describe(&owner);
+            OwnerTotal {
+                id,
+                name,
+                bytes,
+                files,
+            }
+        })
+        .collect();
+
+    owners.sort_by_key(|owner| std::cmp::Reverse(owner.bytes));
+
+    Ok(OwnerStats {
+        path: path.to_string(),
+        owners,
+        complete: listing.complete && tally.complete,
+    })
+}
+
+/// Owner of one file or folder as in `OwnerTotal.id`, without resolving the account name
+pub fn owner_id(path: &Path) -> Option<String> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    platform::owner(path, &metadata).map(|owner| platform::id(&owner))
+}
+
+#[cfg(unix)]
+mod platform {
+    use std::ffi::CStr;
+    use std::fs;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::Path;
+
+    pub type OwnerKey = u32;
+
+    pub fn owner(_path: &Path, metadata: &fs::Metadata) -> Option<OwnerKey> {
+        Some(metadata.uid())
+    }
+
+    pub fn id(uid: &OwnerKey) -> String {
+        format!("uid:{}", uid)
+    }
+
+    pub fn describe(uid: &OwnerKey) -> (String, Option<String>) {
+        (id(uid), user_name(*uid))
+    }
+
+    fn user_name(uid: u32) -> Option<String> {
+        let mut buffer = vec![0 as libc::c_char; 4096];
+        let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut result = std::ptr::null_mut();
+
+        let status = unsafe {
+            libc::getpwuid_r(
+                uid,
+                &mut passwd,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        };
+
+        if status != 0 || result.is_null() {
+            return None;
+        }
+
+        Some(
+            unsafe { CStr::from_ptr(passwd.pw_name) }
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::ffi::OsString;
+    use std::fs;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use std::path::Path;
+    use windows_sys::Win32::Foundation::{LocalFree, ERROR_SUCCESS};
+    use windows_sys::Win32::Security::Authorization::{
+        ConvertSidToStringSidW, ConvertStringSidToSidW, GetNamedSecurityInfoW, SE_FILE_OBJECT,
+    };
+    use windows_sys::Win32::Security::{
+        LookupAccountSidW, OWNER_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR, PSID,
+    };
+
+    /// SID in string form, e.g. `S-1-5-21-...`
+    pub type OwnerKey = String;
+
+    fn wide(value: &std::ffi::OsStr) -> Vec<u16> {
+        value.encode_wide().chain(std::iter::once(0)).collect()
+    }
+
+    fn from_wide(buffer: &[u16]) -> String {
+        let length = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+        OsString::from_wide(&buffer[..length])
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    pub fn owner(path: &Path, _metadata: &fs::Metadata) -> Option<OwnerKey> {
+        let path = wide(path.as_os_str());
+        let mut owner: PSID = std::ptr::null_mut();
+        let mut descriptor: PSECURITY_DESCRIPTOR = std::ptr::null_mut();
+
+        let status = unsafe {
+            GetNamedSecurityInfoW(
+                path.as_ptr(),
+                SE_FILE_OBJECT,
+                OWNER_SECURITY_INFORMATION,
+                &mut owner,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                &mut descriptor,
+            )
+        };
+
+        if status != ERROR_SUCCESS {
+            return None;
+        }
+
+        let mut sid_string = std::ptr::null_mut();
+        let converted = unsafe { ConvertSidToStringSidW(owner, &mut sid_string) } != 0;
+        let key = converted.then(|| {
+            let length = (0..)
+                .take_while(|&i| unsafe { *sid_string.add(i) } != 0)
+                .count();
+            let key = from_wide(unsafe { std::slice::from_raw_parts(sid_string, length) });
+            unsafe { LocalFree(sid_string as _) };
+            key
+        });
+
+        unsafe { LocalFree(descriptor as _) };
+
+        key
+    }
+
+    pub fn id(sid: &OwnerKey) -> String {
+        sid.clone()
+    }
+
+    pub fn describe(sid: &OwnerKey) -> (String, Option<String>) {
+        (id(sid), account_name(sid))
+    }
+
+    fn account_name(sid: &str) -> Option<String> {
+        let sid_string = wide(std::ffi::OsStr::new(sid));
+        let mut sid_value: PSID = std::ptr::null_mut();
+
+        if unsafe { ConvertStringSidToSidW(sid_string.as_ptr(), &mut sid_value) } == 0 {
+            return None;
+        }
+
+        let mut name = [0u16; 256];
+        let mut domain = [0u16; 256];
+        let mut name_length = name.len() as u32;
+        let mut domain_length = domain.len() as u32;
+        let mut use_kind = 0;
+
+        let found = unsafe {
+            LookupAccountSidW(
+                std::ptr::null(),
+                sid_value,
+                name.as_mut_ptr(),
+                &mut name_length,
+                domain.as_mut_ptr(),
+                &mut domain_length,
+                &mut use_kind,
+            )
+        } != 0;
+
+        unsafe { LocalFree(sid_value as _) };
+
+        if !found {
+            return None;
+        }
+
+        let (domain, name) = (from_wide(&domain), from_wide(&name));
+        Some(if domain.is_empty() {
+            name
+        } else {
+            format!("{}\\{}", domain, name)
+        })
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod platform {
+    use std::fs;
+    use std::path::Path;
+
+    pub type OwnerKey = ();
+
+    pub fn owner(_path: &Path, _metadata: &fs::Metadata) -> Option<OwnerKey> {
+        Some(())
+    }
+
+    pub fn id(_owner: &OwnerKey) -> String {
+        "unknown".into()
+    }
+
+    pub fn describe(owner: &OwnerKey) -> (String, Option<String>) {
+        (id(owner), None)
+    }
+}
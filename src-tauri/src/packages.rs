@@ -0,0 +1,396 @@
+use crate::scanner;
+use rayon::prelude::*;
+use serde::Serialize;
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+use std::fs;
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+use std::path::Path;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(not(any(target_os = "macos", target_os = "linux")), allow(dead_code))]
+pub enum PartKind {
+    /// Packages in use: Homebrew kegs and casks, flatpak apps, current snap revisions
+    Installed,
+    /// Shared flatpak runtimes and their extensions
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    Runtime,
+    /// Superseded Homebrew versions and disabled snap revisions, kept for rollback
+    OldVersion,
+    /// Downloaded packages, fetched again when needed
+    Cache,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PackagePart {
+    pub kind: PartKind,
+    /// Formula, snap or runtime the part belongs to, when it is one
+    pub name: Option<String>,
+    pub path: String,
+    /// Excludes the parts listed inside it
+    pub bytes: u64,
+    pub safe_to_clear: bool,
+    /// Command that removes just this part the package manager's own way
+    pub cleanup: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ManagerUsage {
+    pub name: String,
+    /// Largest first
+    pub parts: Vec<PackagePart>,
+    pub total_bytes: u64,
+    /// Bytes in parts flagged safe to clear
+    pub reclaimable_bytes: u64,
+    /// Command that frees the reclaimable parts in one go
+    pub cleanup: Option<&'static str>,
+    pub complete: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageReport {
+    /// Package managers, largest first
+    pub managers: Vec<ManagerUsage>,
+    pub total_bytes: u64,
+    pub reclaimable_bytes: u64,
+}
+
+/// A part before it is measured
+#[cfg_attr(not(any(target_os = "macos", target_os = "linux")), allow(dead_code))]
+struct Candidate {
+    kind: PartKind,
+    name: Option<String>,
+    path: PathBuf,
+    cleanup: Option<String>,
+}
+
+impl Candidate {
+    #[cfg_attr(not(any(target_os = "macos", target_os = "linux")), allow(dead_code))]
+    fn new(kind: PartKind, path: PathBuf) -> Self {
+        Self {
+            kind,
+            name: None,
+            path,
+            cleanup: None,
+        }
+    }
+}
+
+/// Where a package manager keeps its packages on this platform
+#[cfg_attr(not(any(target_os = "macos", target_os = "linux")), allow(dead_code))]
+struct Layout {
+    name: &'static str,
+    candidates: Vec<Candidate>,
+    cleanup: Option<&'static str>,
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn child_dirs(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut dirs: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Prefixes are tried in order and the first with a `Cellar` wins; `HOMEBREW_PREFIX`
+/// and `HOMEBREW_CACHE` override the usual places
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn homebrew(prefixes: &[PathBuf], cache: Option<PathBuf>) -> Layout {
+    let prefix = std::env::var_os("HOMEBREW_PREFIX")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(prefixes.iter().cloned())
+        .find(|prefix| prefix.join("Cellar").is_dir());
+    let mut candidates = Vec::new();
+
+    if let Some(prefix) = prefix {
+        let cellar = prefix.join("Cellar");
+        for formula in child_dirs(&cellar) {
+            let name = file_name(&formula);
+            // `opt/<formula>` links the version in use, keg-only formulae included
+            let Some(linked) = fs::read_link(prefix.join("opt").join(&name))
+                .ok()
+                .map(|target| file_name(&target))
+            else {
+                continue;
+            };
+            candidates.extend(
+                child_dirs(&formula)
+                    .into_iter()
+                    .filter(|version| file_name(version) != linked)
+                    .map(|version| Candidate {
+                        kind: PartKind::OldVersion,
+                        name: Some(format!("{} {}", name, file_name(&version))),
+                        path: version,
+                        cleanup: Some(format!("brew cleanup {}", name)),
+                    }),
+            );
+        }
+        candidates.push(Candidate::new(PartKind::Installed, cellar));
+        candidates.push(Candidate::new(PartKind::Installed, prefix.join("Caskroom")));
+    }
+
+    if let Some(cache) = std::env::var_os("HOMEBREW_CACHE")
+        .map(PathBuf::from)
+        .or(cache)
+    {
+        candidates.push(Candidate {
+            cleanup: Some("brew cleanup --prune=all".to_string()),
+            ..Candidate::new(PartKind::Cache, cache)
+        });
+    }
+
+    Layout {
+        name: "Homebrew",
+        candidates,
+        cleanup: Some("brew cleanup"),
+    }
+}
+
+fn measure(layout: Layout) -> ManagerUsage {
+    let measured: Vec<(Candidate, scanner::FolderSize)> = layout
+        .candidates
+        .into_par_iter()
+        .filter(|candidate| candidate.path.exists())
+        .map(|candidate| {
+            let size = scanner::path_size(&candidate.path);
+            (candidate, size)
+        })
+        .collect();
+    let complete = measured.iter().all(|(_, size)| size.complete);
+
+    let mut parts: Vec<PackagePart> = measured
+        .iter()
+        .map(|(candidate, size)| {
+            let inside: u64 = measured
+                .iter()
+                .filter(|(other, _)| {
+                    other.path != candidate.path && other.path.starts_with(&candidate.path)
+                })
+                .map(|(_, size)| size.bytes)
+                .sum();
+
+            PackagePart {
+                kind: candidate.kind,
+                name: candidate.name.clone(),
+                path: candidate.path.to_string_lossy().into_owned(),
+                bytes: size.bytes.saturating_sub(inside),
+                safe_to_clear: matches!(candidate.kind, PartKind::OldVersion | PartKind::Cache),
+                cleanup: candidate.cleanup.clone(),
+            }
+        })
+        .filter(|part| part.bytes > 0)
+        .collect();
+    parts.sort_by_key(|part| std::cmp::Reverse(part.bytes));
+
+    ManagerUsage {
+        name: layout.name.to_string(),
+        total_bytes: parts.iter().map(|part| part.bytes).sum(),
+        reclaimable_bytes: parts
+            .iter()
+            .filter(|part| part.safe_to_clear)
+            .map(|part| part.bytes)
+            .sum(),
+        parts,
+        cleanup: layout.cleanup,
+        complete,
+    }
+}
+
+/// Space taken by Homebrew, flatpak, snap, apt and dnf, with what each can safely give
+/// back: superseded versions, disabled revisions and download caches
+pub fn package_report() -> PackageReport {
+    let mut managers: Vec<ManagerUsage> = platform::layouts()
+        .into_par_iter()
+        .map(measure)
+        .filter(|manager| manager.total_bytes > 0)
+        .collect();
+
+    managers.sort_by_key(|manager| std::cmp::Reverse(manager.total_bytes));
+    log::info!("Measured {} package managers", managers.len());
+
+    PackageReport {
+        total_bytes: managers.iter().map(|manager| manager.total_bytes).sum(),
+        reclaimable_bytes: managers
+            .iter()
+            .map(|manager| manager.reclaimable_bytes)
+            .sum(),
+        managers,
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{homebrew, Layout};
+    use std::path::PathBuf;
+
+    pub fn layouts() -> Vec<Layout> {
+        let cache = dirs::home_dir().map(|home| home.join("Library/Caches/Homebrew"));
+
+        // Apple silicon installs under /opt/homebrew, Intel under /usr/local
+        vec![homebrew(
+            &[PathBuf::from("/opt/homebrew"), PathBuf::from("/usr/local")],
+            cache,
+        )]
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{child_dirs, file_name, homebrew, Candidate, Layout, PartKind};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    /// Every kept revision of a snap is a `<snap>_<revision>.snap` image here
+    const SNAP_IMAGES: &str = "/var/lib/snapd/snaps";
+
+    /// Apps and runtimes of the system and the user installation. Runtime deploys share
+    /// files through hard links, which count once per runtime here. Telling unused
+    /// runtimes apart needs `flatpak` itself, so none are flagged safe to clear.
+    fn flatpak(home: &Path) -> Layout {
+        let mut candidates = Vec::new();
+        for installation in [
+            PathBuf::from("/var/lib/flatpak"),
+            home.join(".local/share/flatpak"),
+        ] {
+            candidates.push(Candidate::new(
+                PartKind::Installed,
+                installation.join("app"),
+            ));
+            // runtime/<id>/<arch>/<branch>
+            for runtime in child_dirs(&installation.join("runtime")) {
+                let id = file_name(&runtime);
+                for arch in child_dirs(&runtime) {
+                    for branch in child_dirs(&arch) {
+                        candidates.push(Candidate {
+                            name: Some(format!(
+                                "{}/{}/{}",
+                                id,
+                                file_name(&arch),
+                                file_name(&branch)
+                            )),
+                            ..Candidate::new(PartKind::Runtime, branch)
+                        });
+                    }
+                }
+            }
+        }
+
+        Layout {
+            name: "Flatpak",
+            candidates,
+            cleanup: Some("flatpak uninstall --unused"),
+        }
+    }
+
+    /// Revisions other than the one `/snap/<snap>/current` links are disabled, kept only
+    /// for `snap revert`
+    fn snap() -> Layout {
+        let images: Vec<PathBuf> = fs::read_dir(SNAP_IMAGES)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "snap"))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let candidates = images
+            .into_iter()
+            .filter_map(|image| {
+                let stem = image.file_stem()?.to_string_lossy().into_owned();
+                let (snap, revision) = stem.rsplit_once('_')?;
+                let current = fs::read_link(Path::new("/snap").join(snap).join("current"))
+                    .ok()
+                    .map(|target| file_name(&target));
+                // Without a readable link every revision counts as in use
+                let old = current.is_some_and(|current| current != revision);
+
+                Some(if old {
+                    Candidate {
+                        kind: PartKind::OldVersion,
+                        name: Some(format!("{} {}", snap, revision)),
+                        cleanup: Some(format!("sudo snap remove {} --revision={}", snap, revision)),
+                        path: image,
+                    }
+                } else {
+                    Candidate {
+                        name: Some(snap.to_string()),
+                        ..Candidate::new(PartKind::Installed, image)
+                    }
+                })
+            })
+            .collect();
+
+        Layout {
+            name: "Snap",
+            candidates,
+            cleanup: None,
+        }
+    }
+
+    fn cache(name: &'static str, paths: &[&str], cleanup: &'static str) -> Layout {
+        Layout {
+            name,
+            candidates: paths
+                .iter()
+                .map(|path| Candidate {
+                    cleanup: Some(cleanup.to_string()),
+                    ..Candidate::new(PartKind::Cache, PathBuf::from(path))
+                })
+                .collect(),
+            cleanup: Some(cleanup),
+        }
+    }
+
+    pub fn layouts() -> Vec<Layout> {
+        let Some(home) = dirs::home_dir() else {
+            return Vec::new();
+        };
+        let brew_cache = dirs::cache_dir().map(|cache| cache.join("Homebrew"));
+
+        vec![
+            homebrew(
+                &[
+                    PathBuf::from("/home/linuxbrew/.linuxbrew"),
+                    home.join(".linuxbrew"),
+                ],
+                brew_cache,
+            ),
+            flatpak(&home),
+            snap(),
+            cache("apt", &["/var/cache/apt/archives"], "sudo apt-get clean"),
+            // dnf5 moved the cache to libdnf5
+            cache(
+                "dnf",
+                &["/var/cache/dnf", "/var/cache/libdnf5"],
+                "sudo dnf clean all",
+            ),
+        ]
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+mod platform {
+    use super::Layout;
+
+    pub fn layouts() -> Vec<Layout> {
+        Vec::new()
+    }
+}
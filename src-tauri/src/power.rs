@@ -0,0 +1,245 @@
+use crate::scanner::{DirEnumerator, DirListing};
+use crate::settings::PowerMode;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+/// Threads used for scans while saving power
+const SAVER_THREADS: usize = 2;
+/// Pause after every directory read while saving power, to keep the disk and CPU idle more
+const SAVER_IO_PAUSE: Duration = Duration::from_millis(2);
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PowerState {
+    pub on_battery: bool,
+    pub thermal_pressure: bool,
+}
+
+/// How hard a scan may push the machine
+#[derive(Debug, Clone, Copy)]
+pub struct ScanBudget {
+    pub threads: usize,
+    pub io_pause: Option<Duration>,
+}
+
+impl ScanBudget {
+    pub fn throttled(&self) -> bool {
+        self.io_pause.is_some()
+    }
+}
+
+/// Budget for `mode`; `Auto` throttles on battery or under thermal pressure
+pub fn scan_budget(mode: PowerMode, max_threads: usize) -> ScanBudget {
+    let save_power = match mode {
+        PowerMode::Performance => false,
+        PowerMode::Saver => true,
+        PowerMode::Auto => {
+            let state = current();
+            if state.on_battery || state.thermal_pressure {
+                log::info!("Throttling scans: {:?}", state);
+            }
+            state.on_battery || state.thermal_pressure
+        }
+    };
+
+    if save_power {
+        ScanBudget {
+            threads: max_threads.clamp(1, SAVER_THREADS),
+            io_pause: Some(SAVER_IO_PAUSE),
+        }
+    } else {
+        ScanBudget {
+            threads: max_threads.max(1),
+            io_pause: None,
+        }
+    }
+}
+
+/// Directories open at once, shared by every scan so together they stay under the
+/// process's file descriptor limit
+pub struct OpenFileLimit {
+    open: Mutex<usize>,
+    max: AtomicUsize,
+    freed: Condvar,
+}
+
+impl OpenFileLimit {
+    pub fn new(max: usize) -> Self {
+        Self {
+            open: Mutex::new(0),
+            max: AtomicUsize::new(max.max(1)),
+            freed: Condvar::new(),
+        }
+    }
+
+    /// Reads already past the new limit finish; later ones wait
+    pub fn set_max(&self, max: usize) {
+        self.max.store(max.max(1), Ordering::Relaxed);
+        self.freed.notify_all();
+    }
+
+    fn acquire(&self) -> OpenFileGuard<'_> {
+        let mut open = self.open.lock().unwrap();
+        while *open >= self.max.load(Ordering::Relaxed) {
+            open = self.freed.wait(open).unwrap();
+        }
+        *open += 1;
+        OpenFileGuard(self)
+    }
+}
+
+struct OpenFileGuard<'a>(&'a OpenFileLimit);
+
+impl Drop for OpenFileGuard<'_> {
+    fn drop(&mut self) {
+        *self.0.open.lock().unwrap() -= 1;
+        self.0.freed.notify_one();
+    }
+}
+
+/// Sleeps after each directory read of the wrapped backend, and waits for a free slot
+/// under an `OpenFileLimit` before one when given
+pub struct ThrottledEnumerator<'a> {
+    inner: &'a dyn DirEnumerator,
+    pause: Option<Duration>,
+    limit: Option<&'a OpenFileLimit>,
+}
+
+impl<'a> ThrottledEnumerator<'a> {
+    pub fn new(inner: &'a dyn DirEnumerator, budget: &ScanBudget) -> Self {
+        Self {
+            inner,
+            pause: budget.io_pause,
+            limit: None,
+        }
+    }
+
+    pub fn with_limit(mut self, limit: &'a OpenFileLimit) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    fn throttled(&self, read: impl FnOnce() -> io::Result<DirListing>) -> io::Result<DirListing> {
+        let listing = {
+            let _slot = self.limit.map(OpenFileLimit::acquire);
+            read()
+        };
+
+        if let Some(pause) = self.pause {
+            std::thread::sleep(pause);
+        }
+
+        listing
+    }
+}
+
+impl DirEnumerator for ThrottledEnumerator<'_> {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<DirListing> {
+        self.throttled(|| self.inner.read_dir(path))
+    }
+
+    fn read_dir_or_give_up(
+        &self,
+        path: &Path,
+        give_up: &dyn Fn() -> bool,
+    ) -> io::Result<DirListing> {
+        self.throttled(|| self.inner.read_dir_or_give_up(path, give_up))
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn current() -> PowerState {
+    use std::fs;
+
+    let read = |path: &Path| {
+        fs::read_to_string(path)
+            .map(|value| value.trim().to_string())
+            .unwrap_or_default()
+    };
+
+    let on_battery = fs::read_dir("/sys/class/power_supply")
+        .map(|supplies| {
+            supplies.flatten().any(|supply| {
+                let path = supply.path();
+                read(&path.join("type")) == "Battery" && read(&path.join("status")) == "Discharging"
+            })
+        })
+        .unwrap_or(false);
+
+    // A zone is under pressure once it reaches its passive (throttling) trip point
+    let thermal_pressure = fs::read_dir("/sys/class/thermal")
+        .map(|zones| {
+            zones.flatten().any(|zone| {
+                let path = zone.path();
+                let Ok(temp) = read(&path.join("temp")).parse::<i64>() else {
+                    return false;
+                };
+
+                (0..16).any(|trip| {
+                    read(&path.join(format!("trip_point_{}_type", trip))) == "passive"
+                        && read(&path.join(format!("trip_point_{}_temp", trip)))
+                            .parse::<i64>()
+                            .is_ok_and(|limit| limit > 0 && temp >= limit)
+                })
+            })
+        })
+        .unwrap_or(false);
+
+    PowerState {
+        on_battery,
+        thermal_pressure,
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn current() -> PowerState {
+    use std::process::Command;
+
+    let pmset = |arg: &str| {
+        Command::new("pmset")
+            .args(["-g", arg])
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
+            .unwrap_or_default()
+    };
+
+    let on_battery = pmset("batt").contains("'Battery Power'");
+
+    // "CPU_Speed_Limit = 80" means the CPU is being held below full speed
+    let thermal_pressure = pmset("therm").lines().any(|line| {
+        line.split_once('=').is_some_and(|(key, value)| {
+            key.trim() == "CPU_Speed_Limit"
+                && value.trim().parse::<u32>().is_ok_and(|limit| limit < 100)
+        })
+    });
+
+    PowerState {
+        on_battery,
+        thermal_pressure,
+    }
+}
+
+#[cfg(windows)]
+pub fn current() -> PowerState {
+    use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    let mut status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+    let on_battery = unsafe { GetSystemPowerStatus(&mut status) } != 0 && status.ACLineStatus == 0;
+
+    // Windows has no public thermal pressure signal for desktop apps
+    PowerState {
+        on_battery,
+        thermal_pressure: false,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn current() -> PowerState {
+    PowerState::default()
+}
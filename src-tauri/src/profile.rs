@@ -0,0 +1,134 @@
+use crate::scan::ScanTree;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Extensions listed in a folder profile
+const TOP_EXTENSIONS: usize = 5;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtensionTotal {
+    /// Lowercase without the dot, empty for files without one
+    pub extension: String,
+    pub bytes: u64,
+    pub files: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileFile {
+    pub path: String,
+    pub size: u64,
+    /// Milliseconds since the Unix epoch
+    pub modified: u64,
+}
+
+/// A glance at a folder for the side panel
+#[derive(Debug, Clone, Serialize)]
+pub struct FolderProfile {
+    pub path: String,
+    pub total_bytes: u64,
+    pub files: u64,
+    pub folders: u64,
+    /// By bytes, largest first
+    pub top_extensions: Vec<ExtensionTotal>,
+    pub newest: Option<ProfileFile>,
+    pub oldest: Option<ProfileFile>,
+    /// Most deeply nested file or folder, and how many levels down it is
+    pub deepest_path: Option<String>,
+    pub depth: u32,
+    pub average_file_size: u64,
+    pub complete: bool,
+}
+
+fn file_at(tree: &ScanTree, index: u32, modified: u64) -> ProfileFile {
+    ProfileFile {
+        path: tree.path(index).to_string_lossy().into_owned(),
+        size: tree.nodes[index as usize].size,
+        modified,
+    }
+}
+
+/// Profile of `tree.nodes[focus]` in one pass over its subtree; parents come before their
+/// children, so each node's depth follows from its parent's
+pub fn profile(tree: &ScanTree, focus: u32) -> FolderProfile {
+    let start = focus as usize;
+    // Depth below the focus by node, `None` for nodes outside the subtree
+    let mut depths: Vec<Option<u32>> = vec![None; tree.nodes.len() - start];
+    depths[0] = Some(0);
+
+    let mut extensions: HashMap<String, (u64, u64)> = HashMap::new();
+    let (mut files, mut folders, mut file_bytes) = (0u64, 0u64, 0u64);
+    let mut newest: Option<(u32, u64)> = None;
+    let mut oldest: Option<(u32, u64)> = None;
+    let mut deepest = (focus, 0u32);
+
+    for (offset, node) in tree.nodes[start..].iter().enumerate().skip(1) {
+        let Some(parent_depth) = node
+            .parent
+            .and_then(|parent| (parent as usize).checked_sub(start))
+            .and_then(|parent| depths[parent])
+        else {
+            continue;
+        };
+        let depth = parent_depth + 1;
+        depths[offset] = Some(depth);
+        let index = (start + offset) as u32;
+        if depth > deepest.1 {
+            deepest = (index, depth);
+        }
+
+        if node.is_dir {
+            folders += 1;
+            continue;
+        }
+
+        files += 1;
+        file_bytes = file_bytes.saturating_add(node.size);
+        let extension = Path::new(&*node.name)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let total = extensions.entry(extension).or_default();
+        total.0 = total.0.saturating_add(node.size);
+        total.1 += 1;
+
+        if let Some(modified) = node.modified {
+            if newest.map_or(true, |(_, newest)| modified > newest) {
+                newest = Some((index, modified));
+            }
+            if oldest.map_or(true, |(_, oldest)| modified < oldest) {
+                oldest = Some((index, modified));
+            }
+        }
+    }
+
+    let mut top_extensions: Vec<ExtensionTotal> = extensions
+        .into_iter()
+        .map(|(extension, (bytes, files))| ExtensionTotal {
+            extension,
+            bytes,
+            files,
+        })
+        .collect();
+    top_extensions.sort_by(|a, b| {
+        b.bytes
+            .cmp(&a.bytes)
+            .then_with(|| a.extension.cmp(&b.extension))
+    });
+    top_extensions.truncate(TOP_EXTENSIONS);
+
+    let root = &tree.nodes[start];
+    FolderProfile {
+        path: tree.path(focus).to_string_lossy().into_owned(),
+        total_bytes: root.size,
+        files,
+        folders,
+        top_extensions,
+        newest: newest.map(|(index, modified)| file_at(tree, index, modified)),
+        oldest: oldest.map(|(index, modified)| file_at(tree, index, modified)),
+        deepest_path: (deepest.1 > 0).then(|| tree.path(deepest.0).to_string_lossy().into_owned()),
+        depth: deepest.1,
+        average_file_size: file_bytes.checked_div(files).unwrap_or(0),
+        complete: root.size_complete,
+    }
+}
@@ -0,0 +1,137 @@
+use crate::colors::Theme;
+use crate::error::DuneError;
+use crate::settings::{DitherSettings, Settings};
+use crate::snapshot::{self, SnapshotInfo};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Bumped when a field changes meaning; older files still open
+const PROJECT_VERSION: u32 = 1;
+const FORMAT: &str = "dunefiles-project";
+
+/// Where the 3D camera was looking
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraState {
+    pub position: [f64; 3],
+    pub target: [f64; 3],
+    pub zoom: f64,
+}
+
+impl Default for CameraState {
+    fn default() -> Self {
+        Self {
+            position: [0.0, 10.0, 10.0],
+            target: [0.0, 0.0, 0.0],
+            zoom: 1.0,
+        }
+    }
+}
+
+/// The look of the scene, as in `Settings`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VisualSettings {
+    pub pixel_size: f32,
+    pub dither_strength: f32,
+    pub gloom: f32,
+    pub contrast: f32,
+    pub dither: DitherSettings,
+}
+
+impl Default for VisualSettings {
+    fn default() -> Self {
+        Self::from(&Settings::default())
+    }
+}
+
+impl From<&Settings> for VisualSettings {
+    fn from(settings: &Settings) -> Self {
+        Self {
+            pixel_size: settings.pixel_size,
+            dither_strength: settings.dither_strength,
+            gloom: settings.gloom,
+            contrast: settings.contrast,
+            dither: settings.dither.clone(),
+        }
+    }
+}
+
+/// An exploration saved to reopen where it was left
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    /// Always `dunefiles-project`, so other JSON files are refused
+    #[serde(default)]
+    pub format: String,
+    #[serde(default)]
+    pub version: u32,
+    /// Scanned folder, rescanned on open when the snapshot is gone
+    pub root: String,
+    /// Stored snapshot of `root` to show instead of rescanning
+    #[serde(default)]
+    pub snapshot_id: Option<String>,
+    /// Folder the ring was zoomed into, `root` if unset
+    #[serde(default)]
+    pub focus_path: Option<String>,
+    #[serde(default)]
+    pub camera: CameraState,
+    #[serde(default)]
+    pub theme: Theme,
+    #[serde(default)]
+    pub visual: VisualSettings,
+    /// Milliseconds since the Unix epoch, set on save
+    #[serde(default)]
+    pub saved_at: u64,
+}
+
+/// A project as opened, with its snapshot if that is still stored
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenedProject {
+    pub project: Project,
+    pub snapshot: Option<SnapshotInfo>,
+}
+
+/// Write `project` to `dest` as JSON
+pub fn save(dest: &Path, mut project: Project) -> Result<Project, DuneError> {
+    project.format = FORMAT.into();
+    project.version = PROJECT_VERSION;
+    project.saved_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(DuneError::internal)?
+        .as_millis() as u64;
+
+    let json = serde_json::to_vec_pretty(&project).map_err(DuneError::internal)?;
+    fs::write(dest, json).map_err(|e| DuneError::io(dest, e))?;
+    log::info!("Saved project of {} to {}", project.root, dest.display());
+
+    Ok(project)
+}
+
+/// Read a project file, looking up its snapshot in `snapshots`
+pub fn open(snapshots: &Path, path: &Path) -> Result<OpenedProject, DuneError> {
+    let json = fs::read(path).map_err(|e| DuneError::io(path, e))?;
+    let project: Project = serde_json::from_slice(&json)
+        .map_err(|e| DuneError::InvalidInput(format!("Not a project file: {}", e)))?;
+
+    if project.format != FORMAT {
+        return Err(DuneError::InvalidInput(format!(
+            "{} is not a dunefiles project",
+            path.display()
+        )));
+    }
+    if project.version > PROJECT_VERSION {
+        return Err(DuneError::InvalidInput(format!(
+            "Project format {} is newer than this app",
+            project.version
+        )));
+    }
+
+    let snapshot = project.snapshot_id.as_deref().and_then(|id| {
+        snapshot::info(snapshots, id)
+            .inspect_err(|err| log::warn!("Project snapshot {} is unavailable: {}", id, err))
+            .ok()
+    });
+
+    Ok(OpenedProject { project, snapshot })
+}
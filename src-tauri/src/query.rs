@@ -0,0 +1,917 @@
+use crate::error::DuneError;
+use crate::names;
+use crate::scan::{ScanTree, TreeNode};
+use regex::{Regex, RegexBuilder};
+use std::cell::OnceCell;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Longer names rank lower, but only by up to this much
+const MAX_LENGTH_PENALTY: u32 = 200;
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// How bare words in a query match names
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextMode {
+    pub fuzzy: bool,
+    /// Bare words are case-insensitive regular expressions
+    pub regex: bool,
+}
+
+/// One bare query word, folded once up front
+struct Term {
+    lower: String,
+    chars: Vec<char>,
+}
+
+impl Term {
+    fn new(term: &str) -> Self {
+        let lower = names::fold(term);
+        Self {
+            chars: lower.chars().collect(),
+            lower,
+        }
+    }
+
+    /// Case- and normalization-insensitive substring match, best for whole-name and
+    /// prefix matches
+    fn substring_score(&self, name: &str) -> Option<u32> {
+        let folded;
+        let (position, name) = if name.is_ascii() && self.lower.is_ascii() {
+            let needle = self.lower.as_bytes();
+            let position = name
+                .as_bytes()
+                .windows(needle.len())
+                .position(|window| window.eq_ignore_ascii_case(needle))?;
+            (position, name)
+        } else {
+            folded = names::fold(name);
+            (folded.find(&self.lower)?, folded.as_str())
+        };
+
+        let extra = name.chars().count().saturating_sub(self.chars.len()) as u32;
+        let bonus = match (position, extra) {
+            (_, 0) => 600,
+            (0, _) => 300,
+            _ => 0,
+        };
+
+        Some(1_000 + bonus - extra.min(MAX_LENGTH_PENALTY))
+    }
+
+    /// Characters of the term in order, rewarding runs and word starts
+    fn fuzzy_score(&self, name: &str) -> Option<u32> {
+        let mut wanted = self.chars.iter().peekable();
+        let mut score = 0u32;
+        let mut previous: Option<char> = None;
+        let mut matched_previous = false;
+        let mut skipped = 0u32;
+
+        for c in names::nfc(name).chars() {
+            let Some(&&next) = wanted.peek() else {
+                break;
+            };
+            let lower = c.to_lowercase().next().unwrap_or(c);
+
+            if lower == next {
+                let word_start = previous.map_or(true, |p| {
+                    !p.is_alphanumeric() || (p.is_lowercase() && c.is_uppercase())
+                });
+                score +=
+                    10 + if matched_previous { 15 } else { 0 } + if word_start { 10 } else { 0 };
+                matched_previous = true;
+                wanted.next();
+            } else {
+                matched_previous = false;
+                skipped += 1;
+            }
+            previous = Some(c);
+        }
+
+        if wanted.peek().is_some() {
+            return None;
+        }
+        Some(score.saturating_sub(skipped.min(score / 2)))
+    }
+}
+
+fn regex_score(regex: &Regex, text: &str) -> Option<u32> {
+    let text = names::nfc(text);
+    regex
+        .is_match(&text)
+        .then(|| 1_000 - (text.chars().count() as u32).min(MAX_LENGTH_PENALTY))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// `~`, regular expression match
+    Matches,
+}
+
+impl Cmp {
+    fn holds<T: PartialOrd>(self, left: T, right: T) -> bool {
+        match self {
+            Self::Eq | Self::Matches => left == right,
+            Self::Ne => left != right,
+            Self::Lt => left < right,
+            Self::Le => left <= right,
+            Self::Gt => left > right,
+            Self::Ge => left >= right,
+        }
+    }
+
+    /// The same comparison with the operands swapped
+    fn flipped(self) -> Self {
+        match self {
+            Self::Lt => Self::Gt,
+            Self::Le => Self::Ge,
+            Self::Gt => Self::Lt,
+            Self::Ge => Self::Le,
+            other => other,
+        }
+    }
+}
+
+enum Text {
+    Term(Term),
+    Regex(Regex),
+}
+
+enum Expr {
+    Name {
+        text: Text,
+        fuzzy: bool,
+    },
+    Path(Text),
+    Extensions(Vec<String>),
+    Size(Cmp, u64),
+    /// Milliseconds since the Unix epoch; folders never match
+    Modified(Cmp, u64),
+    IsDir(bool),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+}
+
+/// A node being matched, with its full path built only if a filter needs it
+pub struct Subject<'a> {
+    pub tree: &'a ScanTree,
+    pub index: u32,
+    path: OnceCell<String>,
+}
+
+impl<'a> Subject<'a> {
+    pub fn new(tree: &'a ScanTree, index: u32) -> Self {
+        Self {
+            tree,
+            index,
+            path: OnceCell::new(),
+        }
+    }
+
+    fn node(&self) -> &TreeNode {
+        &self.tree.nodes[self.index as usize]
+    }
+
+    fn path(&self) -> &str {
+        self.path
+            .get_or_init(|| self.tree.path(self.index).to_string_lossy().to_string())
+    }
+}
+
+fn extension(name: &str) -> Option<String> {
+    let (stem, extension) = name.rsplit_once('.')?;
+    (!stem.is_empty()).then(|| names::fold(extension))
+}
+
+impl Expr {
+    /// Match score, `None` when the node does not match; filters add nothing to the score
+    fn score(&self, subject: &Subject) -> Option<u32> {
+        let node = subject.node();
+        let matched = |holds: bool| holds.then_some(0);
+
+        match self {
+            Self::Name {
+                text: Text::Term(term),
+                fuzzy,
+            } => term
+                .substring_score(&node.name)
+                .or_else(|| fuzzy.then(|| term.fuzzy_score(&node.name)).flatten()),
+            Self::Name {
+                text: Text::Regex(regex),
+                ..
+            } => regex_score(regex, &node.name),
+            Self::Path(Text::Term(term)) => term.substring_score(subject.path()),
+            Self::Path(Text::Regex(regex)) => regex_score(regex, subject.path()),
+            Self::Extensions(extensions) => matched(
+                extension(&node.name).is_some_and(|extension| extensions.contains(&extension)),
+            ),
+            Self::Size(cmp, bytes) => matched(cmp.holds(node.size, *bytes)),
+            Self::Modified(cmp, at) => matched(
+                node.modified
+                    .is_some_and(|modified| cmp.holds(modified, *at)),
+            ),
+            Self::IsDir(is_dir) => matched(node.is_dir == *is_dir),
+            Self::And(parts) => parts.iter().map(|part| part.score(subject)).sum(),
+            Self::Or(parts) => parts.iter().filter_map(|part| part.score(subject)).max(),
+            Self::Not(inner) => matched(inner.score(subject).is_none()),
+        }
+    }
+
+    fn required_words(&self, words: &mut Vec<String>) {
+        match self {
+            Self::Name {
+                text: Text::Term(term),
+                ..
+            } => words.push(term.lower.clone()),
+            Self::And(parts) => parts.iter().for_each(|part| part.required_words(words)),
+            _ => {}
+        }
+    }
+}
+
+/// Parsed search query: bare words, `field op value` filters, `AND`, `OR`, `NOT`, `-` and parentheses.
+///
+/// Fields are `name`, `path`, `ext`, `size`, `modified`, `age` and `type`, e.g.
+/// `size > 1GB AND ext in [mov,mkv] AND modified < 2023`.
+pub struct Query {
+    expr: Option<Expr>,
+}
+
+impl Query {
+    pub fn parse(query: &str, mode: TextMode) -> Result<Self, DuneError> {
+        // Composed like the names it is matched against, also inside regular expressions
+        let tokens = tokenize(&names::nfc(query))?;
+        if tokens.is_empty() {
+            return Ok(Self { expr: None });
+        }
+
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            mode,
+            now: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(DuneError::internal)?
+                .as_millis() as u64,
+        };
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            return Err(invalid(&format!("Unexpected {}", token.describe())));
+        }
+
+        Ok(Self { expr: Some(expr) })
+    }
+
+    /// Score of the node, `None` if it does not match; an empty query matches nothing
+    pub fn score(&self, subject: &Subject) -> Option<u32> {
+        self.expr.as_ref()?.score(subject)
+    }
+
+    /// Plain words every matching name contains, lowercased; what an OS search index can look up
+    pub fn required_words(&self) -> Vec<String> {
+        let mut words = Vec::new();
+        if let Some(expr) = &self.expr {
+            expr.required_words(&mut words);
+        }
+        words
+    }
+}
+
+fn invalid(message: &str) -> DuneError {
+    DuneError::InvalidInput(message.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    /// Quoted text, never a keyword or field
+    Quoted(String),
+    Op(Cmp),
+    Minus,
+    Open,
+    Close,
+    OpenList,
+    CloseList,
+    Comma,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Self::Word(word) | Self::Quoted(word) => format!("\"{}\"", word),
+            Self::Op(_) => "comparison".into(),
+            Self::Minus => "\"-\"".into(),
+            Self::Open => "\"(\"".into(),
+            Self::Close => "\")\"".into(),
+            Self::OpenList => "\"[\"".into(),
+            Self::CloseList => "\"]\"".into(),
+            Self::Comma => "\",\"".into(),
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    !c.is_whitespace() && !"()[],<>=!:~\"".contains(c)
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, DuneError> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        chars.next();
+        let token = match c {
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '[' => Token::OpenList,
+            ']' => Token::CloseList,
+            ',' => Token::Comma,
+            ':' => Token::Op(Cmp::Eq),
+            '~' => Token::Op(Cmp::Matches),
+            '=' => {
+                chars.next_if_eq(&'=');
+                Token::Op(Cmp::Eq)
+            }
+            '!' if chars.next_if_eq(&'=').is_some() => Token::Op(Cmp::Ne),
+            '<' if chars.next_if_eq(&'=').is_some() => Token::Op(Cmp::Le),
+            '>' if chars.next_if_eq(&'=').is_some() => Token::Op(Cmp::Ge),
+            '<' => Token::Op(Cmp::Lt),
+            '>' => Token::Op(Cmp::Gt),
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => text.extend(chars.next()),
+                        Some(c) => text.push(c),
+                        None => return Err(invalid("Unclosed quote")),
+                    }
+                }
+                Token::Quoted(text)
+            }
+            // Only a leading dash negates, so `my-file` stays one word
+            '-' if chars.peek().is_some_and(|&next| !next.is_whitespace()) => Token::Minus,
+            c if is_word_char(c) => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|&c| is_word_char(c)) {
+                    word.push(c);
+                }
+                Token::Word(word)
+            }
+            c => return Err(invalid(&format!("Unexpected \"{}\"", c))),
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    mode: TextMode,
+    /// Reference point for `age` filters
+    now: u64,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.peek() == Some(token);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        self.eat(&Token::Word(keyword.to_string()))
+    }
+
+    fn or(&mut self) -> Result<Expr, DuneError> {
+        let mut parts = vec![self.and()?];
+        while self.eat_keyword("OR") {
+            parts.push(self.and()?);
+        }
+
+        Ok(if parts.len() == 1 {
+            parts.remove(0)
+        } else {
+            Expr::Or(parts)
+        })
+    }
+
+    /// Terms next to each other must all match, with or without `AND` between them
+    fn and(&mut self) -> Result<Expr, DuneError> {
+        let mut parts = vec![self.unary()?];
+        loop {
+            match self.peek() {
+                None | Some(Token::Close) => break,
+                Some(Token::Word(word)) if word == "OR" => break,
+                _ => {}
+            }
+            self.eat_keyword("AND");
+            parts.push(self.unary()?);
+        }
+
+        Ok(if parts.len() == 1 {
+            parts.remove(0)
+        } else {
+            Expr::And(parts)
+        })
+    }
+
+    fn unary(&mut self) -> Result<Expr, DuneError> {
+        if self.eat_keyword("NOT") || self.eat(&Token::Minus) {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, DuneError> {
+        match self.next() {
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err(invalid("Missing \")\"")),
+                }
+            }
+            Some(Token::Word(word)) => {
+                if let Some(Token::Op(cmp)) = self.peek().cloned() {
+                    self.position += 1;
+                    return self.filter(&word, cmp);
+                }
+                if FIELDS.contains(&word.as_str()) && self.eat_keyword("in") {
+                    return self.list_filter(&word);
+                }
+                self.bare_word(&word)
+            }
+            Some(Token::Quoted(word)) => self.bare_word(&word),
+            Some(token) => Err(invalid(&format!("Unexpected {}", token.describe()))),
+            None => Err(invalid("Query ends too early")),
+        }
+    }
+
+    /// A bare word, matched against the name in the configured text mode
+    fn bare_word(&self, word: &str) -> Result<Expr, DuneError> {
+        Ok(Expr::Name {
+            text: self.text(word, self.mode.regex)?,
+            fuzzy: self.mode.fuzzy,
+        })
+    }
+
+    fn value(&mut self) -> Result<String, DuneError> {
+        match self.next() {
+            Some(Token::Word(value)) | Some(Token::Quoted(value)) => Ok(value),
+            Some(token) => Err(invalid(&format!(
+                "Expected a value, found {}",
+                token.describe()
+            ))),
+            None => Err(invalid("Query ends too early")),
+        }
+    }
+
+    fn text(&self, text: &str, regex: bool) -> Result<Text, DuneError> {
+        if text.is_empty() {
+            return Err(invalid("Empty search term"));
+        }
+        if !regex {
+            return Ok(Text::Term(Term::new(text)));
+        }
+
+        RegexBuilder::new(text)
+            .case_insensitive(true)
+            .build()
+            .map(Text::Regex)
+            .map_err(|err| invalid(&err.to_string()))
+    }
+
+    /// `ext in [mov, mkv]`
+    fn list_filter(&mut self, field: &str) -> Result<Expr, DuneError> {
+        if field != "ext" {
+            return Err(invalid(&format!(
+                "\"in\" only works with ext, not {}",
+                field
+            )));
+        }
+        if self.next() != Some(Token::OpenList) {
+            return Err(invalid("Expected \"[\" after \"in\""));
+        }
+
+        let mut extensions = vec![normalize_extension(&self.value()?)];
+        loop {
+            match self.next() {
+                Some(Token::Comma) => extensions.push(normalize_extension(&self.value()?)),
+                Some(Token::CloseList) => break,
+                _ => return Err(invalid("Missing \"]\"")),
+            }
+        }
+
+        Ok(Expr::Extensions(extensions))
+    }
+
+    fn filter(&mut self, field: &str, cmp: Cmp) -> Result<Expr, DuneError> {
+        let value = self.value()?;
+        let negate = |expr: Expr| {
+            if cmp == Cmp::Ne {
+                Expr::Not(Box::new(expr))
+            } else {
+                expr
+            }
+        };
+        let equality_only = || {
+            if matches!(cmp, Cmp::Eq | Cmp::Ne | Cmp::Matches) {
+                Ok(())
+            } else {
+                Err(invalid(&format!(
+                    "{} can only be compared with = or !=",
+                    field
+                )))
+            }
+        };
+        let ordered_only = || {
+            if cmp == Cmp::Matches {
+                Err(invalid(&format!("{} cannot be matched with ~", field)))
+            } else {
+                Ok(())
+            }
+        };
+
+        match field {
+            "name" => {
+                equality_only()?;
+                let regex = cmp == Cmp::Matches || self.mode.regex;
+                Ok(negate(Expr::Name {
+                    text: self.text(&value, regex)?,
+                    fuzzy: false,
+                }))
+            }
+            "path" => {
+                equality_only()?;
+                let regex = cmp == Cmp::Matches || self.mode.regex;
+                Ok(negate(Expr::Path(self.text(&value, regex)?)))
+            }
+            "ext" => {
+                equality_only()?;
+                Ok(negate(Expr::Extensions(vec![normalize_extension(&value)])))
+            }
+            "type" => {
+                equality_only()?;
+                let is_dir = match value.to_lowercase().as_str() {
+                    "dir" | "folder" => true,
+                    "file" => false,
+                    _ => return Err(invalid("type is file or dir")),
+                };
+                Ok(negate(Expr::IsDir(is_dir)))
+            }
+            "size" => {
+                ordered_only()?;
+                Ok(Expr::Size(cmp, parse_size(&value)?))
+            }
+            "modified" => {
+                ordered_only()?;
+                let (start, end) = parse_date(&value)?;
+                // A date stands for the whole year, month or day it names
+                Ok(match cmp {
+                    Cmp::Lt => Expr::Modified(Cmp::Lt, start),
+                    Cmp::Ge => Expr::Modified(Cmp::Ge, start),
+                    Cmp::Gt => Expr::Modified(Cmp::Ge, end),
+                    Cmp::Le => Expr::Modified(Cmp::Lt, end),
+                    Cmp::Eq | Cmp::Ne | Cmp::Matches => negate(Expr::And(vec![
+                        Expr::Modified(Cmp::Ge, start),
+                        Expr::Modified(Cmp::Lt, end),
+                    ])),
+                })
+            }
+            "age" => {
+                if !matches!(cmp, Cmp::Lt | Cmp::Le | Cmp::Gt | Cmp::Ge) {
+                    return Err(invalid("age can only be compared with <, <=, > or >="));
+                }
+                // Older than a duration means modified before that long ago
+                let at = self.now.saturating_sub(parse_duration(&value)?);
+                Ok(Expr::Modified(cmp.flipped(), at))
+            }
+            _ => Err(invalid(&format!("Unknown field \"{}\"", field))),
+        }
+    }
+}
+
+const FIELDS: &[&str] = &["name", "path", "ext", "size", "modified", "age", "type"];
+
+fn normalize_extension(extension: &str) -> String {
+    names::fold(extension.trim_start_matches('.'))
+}
+
+/// `500`, `10KB`, `1.5GB`; units are powers of 1024
+fn parse_size(value: &str) -> Result<u64, DuneError> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| invalid(&format!("Not a size: {}", value)))?;
+
+    let multiplier: u64 = match unit.to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        _ => return Err(invalid(&format!("Unknown size unit: {}", unit))),
+    };
+
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// `12h`, `30d`, `2w`, `6mo`, `1y`, in milliseconds
+fn parse_duration(value: &str) -> Result<u64, DuneError> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| invalid(&format!("Not a duration: {}", value)))?;
+
+    let unit_ms = match unit.to_ascii_lowercase().as_str() {
+        "h" => DAY_MS as f64 / 24.0,
+        "d" => DAY_MS as f64,
+        "w" => 7.0 * DAY_MS as f64,
+        "mo" => 30.44 * DAY_MS as f64,
+        "y" => 365.25 * DAY_MS as f64,
+        _ => {
+            return Err(invalid(&format!(
+                "Duration needs a unit (h, d, w, mo, y): {}",
+                value
+            )))
+        }
+    };
+
+    Ok((number * unit_ms) as u64)
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// `2023`, `2023-06` or `2023-06-15` as the UTC span [start, end) in milliseconds
+fn parse_date(value: &str) -> Result<(u64, u64), DuneError> {
+    let bad = || {
+        invalid(&format!(
+            "Not a date (YYYY, YYYY-MM or YYYY-MM-DD): {}",
+            value
+        ))
+    };
+    let parts: Vec<&str> = value.split('-').collect();
+    let numbers: Vec<u32> = parts
+        .iter()
+        .map(|part| part.parse().map_err(|_| bad()))
+        .collect::<Result<_, _>>()?;
+
+    let days = |year: u32, month, day| days_from_civil(i64::from(year), month, day);
+    let next_year = |year: u32| year.checked_add(1).ok_or_else(bad);
+    let (start, end) = match numbers[..] {
+        [year] => (days(year, 1, 1), days(next_year(year)?, 1, 1)),
+        [year, 12] => (days(year, 12, 1), days(next_year(year)?, 1, 1)),
+        [year, month] if (1..12).contains(&month) => {
+            (days(year, month, 1), days(year, month + 1, 1))
+        }
+        [year, month, day] if (1..=12).contains(&month) && (1..=31).contains(&day) => {
+            let start = days(year, month, day);
+            (start, start + 1)
+        }
+        _ => return Err(bad()),
+    };
+
+    let to_ms = |days: i64| (days.max(0) as u64).saturating_mul(DAY_MS);
+    Ok((to_ms(start), to_ms(end)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(names: &[(&str, bool, u64)]) -> ScanTree {
+        let nodes: Vec<serde_json::Value> = std::iter::once(("root", true, 0))
+            .chain(names.iter().copied())
+            .enumerate()
+            .map(|(index, (name, is_dir, size))| {
+                serde_json::json!({
+                    "parent": (index > 0).then_some(0),
+                    "name": name,
+                    "is_dir": is_dir,
+                    "size": size,
+                    "file_count": u64::from(!is_dir),
+                    "size_complete": true,
+                    "modified": (!is_dir).then_some(1_700_000_000_000u64),
+                })
+            })
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "scan_id": 1,
+            "root": "/data",
+            "nodes": nodes,
+        }))
+        .unwrap()
+    }
+
+    fn matching(query: &str, mode: TextMode) -> Vec<String> {
+        let tree = tree(&[
+            ("report.pdf", false, 2_000),
+            ("Report Final.PDF", false, 5 << 30),
+            ("movie.mkv", false, 3 << 30),
+            ("photos", true, 0),
+        ]);
+        let query = Query::parse(query, mode).unwrap();
+        (1..tree.nodes.len() as u32)
+            .filter(|&index| query.score(&Subject::new(&tree, index)).is_some())
+            .map(|index| tree.nodes[index as usize].name.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn tokenizes_operators_and_quotes() {
+        assert_eq!(
+            tokenize(r#"size>=1GB -name:"a \"b\"" [x,y]"#).unwrap(),
+            vec![
+                Token::Word("size".into()),
+                Token::Op(Cmp::Ge),
+                Token::Word("1GB".into()),
+                Token::Minus,
+                Token::Word("name".into()),
+                Token::Op(Cmp::Eq),
+                Token::Quoted(r#"a "b""#.into()),
+                Token::OpenList,
+                Token::Word("x".into()),
+                Token::Comma,
+                Token::Word("y".into()),
+                Token::CloseList,
+            ]
+        );
+        assert_eq!(
+            tokenize("my-file").unwrap(),
+            vec![Token::Word("my-file".into())]
+        );
+        assert!(tokenize("\"open").is_err());
+    }
+
+    #[test]
+    fn rejects_empty_terms() {
+        for query in [r#""""#, r#"name:"""#, r#"path="""#, r#"name~"""#] {
+            assert!(
+                Query::parse(query, TextMode::default()).is_err(),
+                "{}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_queries() {
+        for query in [
+            "(report",
+            "size >",
+            "ext in mov",
+            "size in [1]",
+            "type:link",
+            "colour:red",
+            "size ~ 1GB",
+            "age = 1d",
+            "age < 3",
+            "name < a",
+        ] {
+            assert!(
+                Query::parse(query, TextMode::default()).is_err(),
+                "{}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn empty_query_matches_nothing() {
+        assert!(matching("", TextMode::default()).is_empty());
+        assert!(matching("   ", TextMode::default()).is_empty());
+    }
+
+    #[test]
+    fn matches_words_and_filters() {
+        let mode = TextMode::default();
+        assert_eq!(matching("report", mode), ["report.pdf", "Report Final.PDF"]);
+        assert_eq!(matching("report size > 1GB", mode), ["Report Final.PDF"]);
+        assert_eq!(
+            matching("ext in [.MKV, pdf] AND NOT report", mode),
+            ["movie.mkv"]
+        );
+        assert_eq!(matching("(report OR movie) -ext:pdf", mode), ["movie.mkv"]);
+        // AND binds tighter than OR
+        assert_eq!(matching("report OR movie -ext:mkv", mode).len(), 2);
+        assert_eq!(matching("type:dir", mode), ["photos"]);
+        assert_eq!(matching("path:data/PHOTOS", mode), ["photos"]);
+        assert_eq!(matching("modified < 2023", mode), Vec::<String>::new());
+        assert_eq!(matching("modified = 2023-11", mode).len(), 3);
+        assert_eq!(
+            matching("name~^rep.*pdf$", mode),
+            ["report.pdf", "Report Final.PDF"]
+        );
+    }
+
+    #[test]
+    fn fuzzy_and_regex_modes() {
+        let fuzzy = TextMode {
+            fuzzy: true,
+            regex: false,
+        };
+        assert_eq!(matching("mvmkv", fuzzy), ["movie.mkv"]);
+        assert!(matching("mvmkv", TextMode::default()).is_empty());
+
+        let regex = TextMode {
+            fuzzy: false,
+            regex: true,
+        };
+        assert_eq!(matching(r#""\.mkv$""#, regex), ["movie.mkv"]);
+        assert!(Query::parse("(unclosed", regex).is_err());
+    }
+
+    #[test]
+    fn scores_whole_and_prefix_matches_higher() {
+        let term = Term::new("report");
+        let whole = term.substring_score("Report").unwrap();
+        let prefix = term.substring_score("report.pdf").unwrap();
+        let inside = term.substring_score("old report.pdf").unwrap();
+        assert!(whole > prefix && prefix > inside);
+        assert_eq!(term.substring_score("rep"), None);
+        // Folded like names, so composed and decomposed accents match
+        assert!(Term::new("cafe\u{301}")
+            .substring_score("Café.txt")
+            .is_some());
+
+        let fuzzy = Term::new("rf");
+        assert!(fuzzy.fuzzy_score("Report Final").unwrap() > fuzzy.fuzzy_score("rxxxf").unwrap());
+        assert_eq!(fuzzy.fuzzy_score("fr"), None);
+    }
+
+    #[test]
+    fn parses_sizes_durations_and_dates() {
+        assert_eq!(parse_size("500").unwrap(), 500);
+        assert_eq!(parse_size("1.5KB").unwrap(), 1536);
+        assert_eq!(parse_size("2g").unwrap(), 2 << 30);
+        assert!(parse_size("1PB").is_err());
+        assert_eq!(parse_duration("2d").unwrap(), 2 * DAY_MS);
+        assert!(parse_duration("2").is_err());
+
+        let day_2023 = days_from_civil(2023, 1, 1) as u64 * DAY_MS;
+        assert_eq!(
+            parse_date("2023").unwrap(),
+            (day_2023, days_from_civil(2024, 1, 1) as u64 * DAY_MS)
+        );
+        assert_eq!(
+            parse_date("2023-12").unwrap().1,
+            days_from_civil(2024, 1, 1) as u64 * DAY_MS
+        );
+        assert_eq!(
+            parse_date("2023-01-01").unwrap(),
+            (day_2023, day_2023 + DAY_MS)
+        );
+        for date in [
+            "2023-13",
+            "2023-00",
+            "2023-02-32",
+            "june",
+            "4294967295",
+            "4294967295-12",
+        ] {
+            assert!(parse_date(date).is_err(), "{}", date);
+        }
+        assert!(Query::parse("modified < 4294967295", TextMode::default()).is_err());
+    }
+}
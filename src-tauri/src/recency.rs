@@ -0,0 +1,95 @@
+use crate::scan;
+use crate::scanner::FileEntry;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+const MILLIS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    /// What Finder shows as "Last opened", a `timespec` set by LaunchServices
+    const LAST_USED_DATE: &[u8] = b"com.apple.lastuseddate#PS\0";
+
+    /// `kMDItemLastUsedDate` without asking Spotlight, which may not have indexed the file
+    pub fn last_used(path: &Path) -> Option<SystemTime> {
+        let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+        let mut value = [0u8; 16];
+        let read = unsafe {
+            libc::getxattr(
+                path.as_ptr(),
+                LAST_USED_DATE.as_ptr().cast(),
+                value.as_mut_ptr().cast(),
+                value.len(),
+                0,
+                libc::XATTR_NOFOLLOW,
+            )
+        };
+        if read < 8 {
+            return None;
+        }
+
+        let seconds = i64::from_le_bytes(value[..8].try_into().ok()?);
+        let nanos = if read >= 16 {
+            i64::from_le_bytes(value[8..].try_into().ok()?)
+        } else {
+            0
+        };
+        Some(
+            UNIX_EPOCH
+                + Duration::from_secs(u64::try_from(seconds).ok()?)
+                + Duration::from_nanos(u64::try_from(nanos).unwrap_or(0)),
+        )
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod platform {
+    use std::path::Path;
+    use std::time::SystemTime;
+
+    pub fn last_used(_path: &Path) -> Option<SystemTime> {
+        None
+    }
+}
+
+/// When `path` was last opened, in milliseconds since the Unix epoch: the date Finder
+/// shows on macOS, else the access time, which `noatime` mounts never update and
+/// `relatime` ones only about daily
+pub fn last_opened(path: &Path) -> Option<u64> {
+    platform::last_used(path)
+        .or_else(|| {
+            fs::symlink_metadata(path)
+                .and_then(|metadata| metadata.accessed())
+                .ok()
+        })
+        .and_then(scan::millis_since_epoch)
+}
+
+/// Fill in `last_opened` of the files in a listing
+pub fn apply(entries: &mut [FileEntry]) {
+    for entry in entries.iter_mut().filter(|entry| !entry.is_dir) {
+        entry.last_opened = last_opened(Path::new(&entry.path));
+    }
+}
+
+/// Keep folders and the files not opened in the last `days`; files with no known date
+/// are dropped, since nothing says they went unused
+pub fn retain_unopened(entries: &mut Vec<FileEntry>, days: u32) {
+    let cutoff = SystemTime::now()
+        .checked_sub(Duration::from_millis(u64::from(days) * MILLIS_PER_DAY))
+        .and_then(scan::millis_since_epoch)
+        .unwrap_or(0);
+
+    entries.retain(|entry| {
+        entry.is_dir
+            || entry
+                .last_opened
+                .is_some_and(|last_opened| last_opened < cutoff)
+    });
+}
@@ -0,0 +1,203 @@
+use crate::scan::ScanTree;
+use crate::scanner::{self, DiskInfo};
+use crate::system_space;
+use serde::Serialize;
+use std::path::Path;
+
+/// Cluster size assumed for estimates when the scan did not look up allocations
+const CLUSTER: u64 = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cause {
+    /// Local snapshots, purgeable caches, shadow copies and btrfs/ZFS snapshots
+    Snapshots,
+    /// Page, hibernation and swap files the scan cannot open
+    SystemFiles,
+    /// Partly used clusters and folder entries
+    Metadata,
+    /// Folders the scan was not allowed to read
+    NoPermission,
+    /// Files changed since the scan, hidden files the scan skipped, and the like
+    Unexplained,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Attribution {
+    pub cause: Cause,
+    pub bytes: u64,
+    pub detail: String,
+}
+
+/// How a full-volume scan's total compares to the volume's used space
+#[derive(Debug, Clone, Serialize)]
+pub struct Reconciliation {
+    pub volume: String,
+    pub used_bytes: u64,
+    pub scanned_bytes: u64,
+    /// Used space the scan did not see, split into `attributions`, largest cause first
+    pub missing_bytes: u64,
+    /// Scanned more than is used: hard links, clones and compressed files counted in full
+    pub excess_bytes: u64,
+    pub attributions: Vec<Attribution>,
+}
+
+/// Unreadable folders: incomplete ones whose subfolders are all complete
+fn unreadable_folders(tree: &ScanTree) -> usize {
+    let mut incomplete_child = vec![false; tree.nodes.len()];
+    for node in &tree.nodes {
+        if let Some(parent) = node.parent {
+            if node.is_dir && !node.size_complete {
+                incomplete_child[parent as usize] = true;
+            }
+        }
+    }
+
+    tree.nodes
+        .iter()
+        .zip(&incomplete_child)
+        .filter(|(node, &child)| node.is_dir && !node.size_complete && !child)
+        .count()
+}
+
+/// Space held by snapshots, and how it was found
+fn snapshots(tree: &ScanTree, disk: &DiskInfo, shadow_bytes: u64) -> (u64, String) {
+    let subvolumes: u64 = tree
+        .subvolumes
+        .iter()
+        .filter(|subvolume| subvolume.snapshot)
+        .filter_map(|subvolume| subvolume.used)
+        .sum();
+
+    if disk.purgeable_space > 0 {
+        let detail = "Local snapshots and purgeable caches macOS frees on demand";
+        (disk.purgeable_space, detail.into())
+    } else if shadow_bytes > 0 {
+        (shadow_bytes, "Volume Shadow Copy storage".into())
+    } else {
+        (
+            subvolumes,
+            "btrfs and ZFS snapshots not descended into".into(),
+        )
+    }
+}
+
+fn metadata(tree: &ScanTree) -> (u64, String) {
+    let (files, folders) = tree
+        .nodes
+        .iter()
+        .fold((0u64, 0u64), |(files, folders), node| {
+            if node.is_dir {
+                (files, folders + 1)
+            } else {
+                (files + 1, folders)
+            }
+        });
+    let logical = tree.nodes.first().map_or(0, |root| root.size);
+
+    match tree.allocation {
+        Some(allocation) => (
+            allocation.unique_allocated.saturating_sub(logical) + folders * CLUSTER,
+            format!(
+                "Unused ends of clusters across {} files, and {} folder entries",
+                crate::format::count(files),
+                crate::format::count(folders)
+            ),
+        ),
+        None => (
+            files * CLUSTER / 2 + folders * CLUSTER,
+            format!(
+                "Estimated: half a cluster per file for {} files and a cluster per folder for {}",
+                crate::format::count(files),
+                crate::format::count(folders)
+            ),
+        ),
+    }
+}
+
+/// Split the difference between used space and what a scan of the whole volume found
+pub fn reconcile(tree: &ScanTree, disk: &DiskInfo) -> Reconciliation {
+    let used_bytes = disk.total_space.saturating_sub(disk.available_space);
+    let scanned_bytes = tree.nodes.first().map_or(0, |root| root.size);
+    let mut remaining = used_bytes.saturating_sub(scanned_bytes);
+    let missing_bytes = remaining;
+
+    let hidden = system_space::report(&disk.path, None).ok();
+    let shadow_bytes = hidden
+        .as_ref()
+        .and_then(|report| report.shadow_storage.as_ref())
+        .map_or(0, |storage| storage.allocated_bytes);
+    let (system_bytes, system_detail) = hidden.as_ref().map_or((0, String::new()), |report| {
+        let names: Vec<&str> = report.files.iter().map(|file| file.name.as_str()).collect();
+        (
+            report.files.iter().map(|file| file.bytes).sum(),
+            names.join(", "),
+        )
+    });
+    let unreadable = unreadable_folders(tree);
+
+    let (snapshot_bytes, snapshot_detail) = snapshots(tree, disk, shadow_bytes);
+    let (metadata_bytes, metadata_detail) = metadata(tree);
+    let candidates = [
+        (Cause::Snapshots, snapshot_bytes, snapshot_detail),
+        (Cause::SystemFiles, system_bytes, system_detail),
+        (Cause::Metadata, metadata_bytes, metadata_detail),
+        // Unreadable folders can hold anything, so they take what is left
+        (
+            Cause::NoPermission,
+            if unreadable > 0 { u64::MAX } else { 0 },
+            format!(
+                "{} folders could not be read",
+                crate::format::count(unreadable as u64)
+            ),
+        ),
+        (
+            Cause::Unexplained,
+            u64::MAX,
+            "Files changed since the scan, hidden files it skipped, or space the volume shares"
+                .into(),
+        ),
+    ];
+
+    let mut attributions = Vec::new();
+    for (cause, bytes, detail) in candidates {
+        let bytes = bytes.min(remaining);
+        if bytes == 0 {
+            continue;
+        }
+        remaining -= bytes;
+        attributions.push(Attribution {
+            cause,
+            bytes,
+            detail,
+        });
+    }
+    attributions.sort_by_key(|attribution| std::cmp::Reverse(attribution.bytes));
+
+    Reconciliation {
+        volume: disk.path.clone(),
+        used_bytes,
+        scanned_bytes,
+        missing_bytes,
+        excess_bytes: scanned_bytes.saturating_sub(used_bytes),
+        attributions,
+    }
+}
+
+/// The reconciliation of a scan whose root is a mounted volume, `None` for any other folder
+pub fn for_volume(tree: &ScanTree) -> Option<Reconciliation> {
+    let disk = scanner::get_disks()
+        .into_iter()
+        .find(|disk| Path::new(&disk.path) == Path::new(&tree.root))?;
+    let reconciliation = reconcile(tree, &disk);
+
+    log::info!(
+        "{}: {} bytes used, {} scanned, {} unaccounted for",
+        reconciliation.volume,
+        reconciliation.used_bytes,
+        reconciliation.scanned_bytes,
+        reconciliation.missing_bytes
+    );
+
+    Some(reconciliation)
+}
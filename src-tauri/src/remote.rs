@@ -0,0 +1,277 @@
+use crate::console;
+use crate::error::DuneError;
+use crate::scan::{
+    self, NameInterner, ProgressCoalescer, ScanHandle, ScanOptions, ScanProgress, ScanTree,
+    TreeNode,
+};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+const SCHEME: &str = "ssh://";
+/// What is kept of the remote's stderr for error messages
+const MAX_STDERR: usize = 4096;
+/// Exit status of the remote command when its `find` has no `-printf`, as on BSD,
+/// macOS and busybox
+const NO_PRINTF: i32 = 97;
+
+/// A location like `ssh://user@host:2222/var`
+#[derive(Debug, Clone)]
+pub struct RemoteLocation {
+    /// `user@host` as passed to ssh
+    pub destination: String,
+    pub port: Option<u16>,
+    /// Path on the remote; the login directory when the location has none
+    pub path: String,
+}
+
+pub fn is_remote(location: &str) -> bool {
+    location.starts_with(SCHEME)
+}
+
+impl RemoteLocation {
+    pub fn parse(location: &str) -> Result<Self, DuneError> {
+        let invalid =
+            || DuneError::InvalidInput(format!("Not an ssh://host/path location: {}", location));
+        let rest = location.strip_prefix(SCHEME).ok_or_else(invalid)?;
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash..]),
+            None => (rest, "."),
+        };
+        let (user, host_port) = match authority.rsplit_once('@') {
+            Some((user, host_port)) => (Some(user), host_port),
+            None => (None, authority),
+        };
+
+        // `[::1]:22` keeps the port outside the brackets
+        let (host, port) = match host_port.strip_prefix('[') {
+            Some(bracketed) => {
+                let (host, after) = bracketed.split_once(']').ok_or_else(invalid)?;
+                (host, after.strip_prefix(':'))
+            }
+            None => match host_port.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (host_port, None),
+            },
+        };
+        if host.is_empty() || host.starts_with('-') {
+            return Err(invalid());
+        }
+        let port = port
+            .map(|port| port.parse::<u16>().map_err(|_| invalid()))
+            .transpose()?;
+
+        Ok(Self {
+            destination: match user {
+                Some(user) => format!("{}@{}", user, host),
+                None => host.to_string(),
+            },
+            port,
+            path: path.to_string(),
+        })
+    }
+}
+
+/// Quote for the remote POSIX shell
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// GNU find listing type, size, mtime and relative path, NUL-terminated; the root itself
+/// is left out. A `find` without `-printf` exits with `NO_PRINTF` before listing anything.
+fn find_command(location: &RemoteLocation, options: &ScanOptions) -> String {
+    let mut command = format!(
+        "find / -prune -printf '' >/dev/null 2>&1 || exit {}; find {} {} -mindepth 1",
+        NO_PRINTF,
+        if options.follow_symlinks { "-L" } else { "-P" },
+        shell_quote(&location.path)
+    );
+    if options.same_filesystem {
+        command.push_str(" -xdev");
+    }
+    if let Some(max_depth) = options.max_depth {
+        command.push_str(&format!(" -maxdepth {}", max_depth + 1));
+    }
+    if !options.include_hidden {
+        command.push_str(" -name '.*' -prune -o");
+    }
+    command.push_str(r" -printf '%y\t%s\t%T@\t%P\0'");
+    command
+}
+
+fn ssh_command(location: &RemoteLocation, remote_command: &str) -> Command {
+    let mut command = console::command("ssh");
+    // There is no terminal to type a password into, so only keys and agents work
+    command.args(["-o", "BatchMode=yes", "-o", "ConnectTimeout=15"]);
+    if let Some(port) = location.port {
+        command.arg("-p").arg(port.to_string());
+    }
+    command
+        .arg("--")
+        .arg(&location.destination)
+        .arg(remote_command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    command
+}
+
+/// One `find -printf` record: type, size, mtime in fractional seconds, relative path
+fn parse_record(record: &str) -> Option<(char, u64, Option<u64>, &str)> {
+    let mut fields = record.splitn(4, '\t');
+    let kind = fields.next()?.chars().next()?;
+    let size = fields.next()?.parse().ok()?;
+    let modified = fields
+        .next()?
+        .parse::<f64>()
+        .ok()
+        .filter(|seconds| *seconds >= 0.0)
+        .map(|seconds| (seconds * 1000.0) as u64);
+    let path = fields.next()?;
+
+    Some((kind, size, modified, path))
+}
+
+/// Scan a remote location by running `find` over ssh and building the tree locally
+pub fn scan_tree(
+    handle: &ScanHandle,
+    options: &ScanOptions,
+    on_progress: &(dyn Fn(ScanProgress) + Sync),
+) -> Result<ScanTree, DuneError> {
+    let started = Instant::now();
+    let location = RemoteLocation::parse(&handle.root)?;
+    let mut child = ssh_command(&location, &find_command(&location, options))
+        .spawn()
+        .map_err(|err| DuneError::Unsupported(format!("Could not run ssh: {}", err)))?;
+
+    // Drained on its own thread, so a flood of permission errors can never block the listing
+    let stderr = child.stderr.take().map(|mut stderr| {
+        thread::spawn(move || {
+            let mut text = String::new();
+            let _ = stderr.read_to_string(&mut text);
+            text
+        })
+    });
+
+    let root_name = Path::new(&location.path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| location.destination.clone());
+    let mut nodes = vec![TreeNode {
+        parent: None,
+        name: Arc::from(root_name),
+        is_dir: true,
+        size: 0,
+        file_count: 0,
+        size_complete: true,
+        modified: None,
+    }];
+    let mut names = NameInterner::default();
+    let mut dirs: HashMap<String, u32> = HashMap::from([(String::new(), 0)]);
+    let leaf_depth = options.max_depth.map(|max_depth| max_depth as usize + 1);
+    let progress =
+        ProgressCoalescer::new(handle.id, options.progress_events_per_second, on_progress);
+    let current = Path::new(&handle.root);
+
+    if let Some(stdout) = child.stdout.take() {
+        for record in BufReader::new(stdout).split(0).map_while(Result::ok) {
+            if handle.is_cancelled() {
+                let _ = child.kill();
+                let _ = child.wait();
+                log::info!("Remote scan {} of {} cancelled", handle.id, handle.root);
+                return Err(DuneError::Cancelled);
+            }
+
+            let record = String::from_utf8_lossy(&record);
+            let Some((kind, size, modified, path)) = parse_record(&record) else {
+                continue;
+            };
+            let is_dir = match kind {
+                'd' => true,
+                'f' => false,
+                _ => continue,
+            };
+            // find lists every folder before its contents
+            let (parent_path, name) = path.rsplit_once('/').unwrap_or(("", path));
+            let Some(&parent) = dirs.get(parent_path) else {
+                continue;
+            };
+
+            let index = nodes.len() as u32;
+            let depth = path.split('/').count();
+            nodes.push(TreeNode {
+                parent: Some(parent),
+                name: names.intern(name),
+                is_dir,
+                size: if is_dir { 0 } else { size },
+                file_count: u64::from(!is_dir),
+                // Folders at the depth limit were not looked into
+                size_complete: !(is_dir && leaf_depth == Some(depth)),
+                modified: if is_dir { None } else { modified },
+            });
+            if is_dir {
+                dirs.insert(path.to_string(), index);
+                progress.record(0, 0, 1, current);
+            } else {
+                progress.record(size, 1, 0, current);
+            }
+        }
+    }
+
+    let status = child.wait().map_err(DuneError::internal)?;
+    let stderr = stderr
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+    let stderr = stderr.trim();
+
+    match status.code() {
+        Some(0) => {}
+        // find exits with 1 after unreadable folders but still lists the rest
+        Some(1) if nodes.len() > 1 => nodes[0].size_complete = false,
+        Some(NO_PRINTF) => {
+            return Err(DuneError::Unsupported(format!(
+                "Remote scans need GNU find with -printf on {}; install findutils there",
+                location.destination
+            )))
+        }
+        _ => {
+            let message = stderr
+                .get(stderr.len().saturating_sub(MAX_STDERR)..)
+                .unwrap_or(stderr);
+            return Err(DuneError::Io {
+                path: Some(handle.root.clone()),
+                message: format!("Remote scan failed ({}): {}", status, message),
+            });
+        }
+    }
+
+    progress.flush(current);
+    scan::aggregate(&mut nodes);
+
+    log::info!(
+        "Remote scan {} of {}: {} nodes, {} bytes in {:?}",
+        handle.id,
+        handle.root,
+        nodes.len(),
+        nodes[0].size,
+        started.elapsed()
+    );
+
+    Ok(ScanTree {
+        scan_id: handle.id,
+        root: handle.root.clone(),
+        nodes,
+        allocation: None,
+        subvolumes: Vec::new(),
+        ignored: Vec::new(),
+        small_items: Vec::new(),
+        skipped: Vec::new(),
+        stats: None,
+        elevated: false,
+    })
+}
@@ -0,0 +1,239 @@
+use crate::app_data;
+use crate::audit::{Operation, OperationRecord};
+use crate::error::DuneError;
+use crate::file_ops;
+use crate::scan;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Runtime};
+
+const FOLDER: &str = "Recently removed";
+/// Inside `FOLDER`, next to one folder per removed item
+const INDEX: &str = "index.json";
+const DAY_MILLIS: u64 = 24 * 60 * 60 * 1000;
+
+/// Held while the index is read, changed and written back
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemovedItem {
+    /// Folder under `FOLDER` the item was moved into
+    pub id: String,
+    pub original_path: String,
+    /// Milliseconds since the Unix epoch
+    pub removed_at: u64,
+    /// When it is deleted for good under the current `Settings.recently_removed`
+    #[serde(skip_deserializing)]
+    pub expires_at: u64,
+    pub bytes: u64,
+}
+
+impl RemovedItem {
+    fn staged(&self, folder: &Path) -> PathBuf {
+        let name = Path::new(&self.original_path)
+            .file_name()
+            .unwrap_or_default();
+        folder.join(&self.id).join(name)
+    }
+}
+
+pub fn folder_path<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, DuneError> {
+    app_data::path(app, FOLDER)
+}
+
+fn read(folder: &Path) -> Result<Vec<RemovedItem>, DuneError> {
+    let path = folder.join(INDEX);
+    match fs::read(&path) {
+        Ok(json) => serde_json::from_slice(&json).map_err(|e| DuneError::Io {
+            path: Some(path.to_string_lossy().to_string()),
+            message: e.to_string(),
+        }),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(DuneError::io(&path, err)),
+    }
+}
+
+fn write(folder: &Path, items: &[RemovedItem]) -> Result<(), DuneError> {
+    fs::create_dir_all(folder).map_err(|e| DuneError::io(folder, e))?;
+
+    let path = folder.join(INDEX);
+    let json = serde_json::to_vec_pretty(items).map_err(DuneError::internal)?;
+    fs::write(&path, json).map_err(|e| DuneError::io(&path, e))
+}
+
+/// Delete `items` for good, keeping the ones that could not be deleted
+fn delete_items(audit: &Path, folder: &Path, items: Vec<RemovedItem>) -> Vec<RemovedItem> {
+    let mut kept = Vec::new();
+    for item in items {
+        let staged = item.staged(folder);
+        let record = file_ops::perform(audit, Operation::Delete, &staged, None, || {
+            match fs::symlink_metadata(&staged) {
+                Ok(_) => file_ops::remove(&staged),
+                // Already gone, so only the index entry is left to drop
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(err) => Err(DuneError::io(&staged, err)),
+            }
+        });
+        if record.ok {
+            let _ = fs::remove_dir(folder.join(&item.id));
+        } else {
+            kept.push(item);
+        }
+    }
+
+    kept
+}
+
+/// Delete for good what has been in the folder longer than `retention_days`
+pub fn purge(audit: &Path, folder: &Path, retention_days: u32) -> Result<(), DuneError> {
+    let _lock = INDEX_LOCK.lock().unwrap();
+    let items = read(folder)?;
+    let cutoff = scan::now_millis().saturating_sub(u64::from(retention_days) * DAY_MILLIS);
+    let (expired, mut kept): (Vec<_>, Vec<_>) = items
+        .into_iter()
+        .partition(|item| item.removed_at <= cutoff);
+    if expired.is_empty() {
+        return Ok(());
+    }
+
+    log::info!("Deleting {} expired recently removed items", expired.len());
+    kept.extend(delete_items(audit, folder, expired));
+    write(folder, &kept)
+}
+
+/// Everything restorable, most recently removed first
+pub fn list(folder: &Path, retention_days: u32) -> Result<Vec<RemovedItem>, DuneError> {
+    let mut items = read(folder)?;
+    for item in &mut items {
+        item.expires_at = item.removed_at + u64::from(retention_days) * DAY_MILLIS;
+    }
+    items.sort_by_key(|item| std::cmp::Reverse(item.removed_at));
+
+    Ok(items)
+}
+
+/// Move `paths` into the app's "Recently removed" folder instead of deleting them, so
+/// they can be restored until they expire; works where the OS has no trash, such as
+/// network shares. Fails as a whole only when the index cannot be read
+pub fn soft_delete(
+    audit: &Path,
+    folder: &Path,
+    paths: &[String],
+) -> Result<Vec<OperationRecord>, DuneError> {
+    let _lock = INDEX_LOCK.lock().unwrap();
+    let mut items = read(folder)?;
+
+    let removed_at = scan::now_millis();
+    let records = paths
+        .iter()
+        .enumerate()
+        .map(|(position, path)| {
+            let source = Path::new(path);
+            let id = format!("{}-{}", removed_at, position);
+            let target = source.file_name().map(|name| folder.join(&id).join(name));
+            let record =
+                file_ops::perform(audit, Operation::SoftDelete, source, target.clone(), || {
+                    let Some(target) = &target else {
+                        return Err(DuneError::InvalidInput(format!(
+                            "{} has no name to keep",
+                            source.display()
+                        )));
+                    };
+                    if source.starts_with(folder) {
+                        return Err(DuneError::InvalidInput(format!(
+                            "{} is already recently removed",
+                            source.display()
+                        )));
+                    }
+
+                    let staging = folder.join(&id);
+                    fs::create_dir_all(&staging).map_err(|e| DuneError::io(&staging, e))?;
+                    file_ops::relocate(source, target).inspect_err(|_| {
+                        let _ = fs::remove_dir(&staging);
+                    })
+                });
+
+            if record.ok {
+                items.push(RemovedItem {
+                    id,
+                    original_path: path.clone(),
+                    removed_at,
+                    expires_at: 0,
+                    bytes: record.bytes,
+                });
+            }
+            record
+        })
+        .collect();
+
+    // The items are moved already, so a failed write must not hide that from the caller
+    if let Err(err) = write(folder, &items) {
+        log::error!("Could not write the recently removed index: {}", err);
+    }
+
+    Ok(records)
+}
+
+/// Move the most recent removal of each of `paths` back where it was; a path that has
+/// been taken again in the meantime is left alone
+pub fn restore(
+    audit: &Path,
+    folder: &Path,
+    paths: &[String],
+) -> Result<Vec<OperationRecord>, DuneError> {
+    let _lock = INDEX_LOCK.lock().unwrap();
+    let mut items = read(folder)?;
+
+    let records = paths
+        .iter()
+        .map(|path| {
+            let original = Path::new(path);
+            let Some(position) = items
+                .iter()
+                .enumerate()
+                .filter(|(_, item)| item.original_path == *path)
+                .max_by_key(|(_, item)| item.removed_at)
+                .map(|(position, _)| position)
+            else {
+                return file_ops::perform(audit, Operation::Restore, original, None, || {
+                    Err(DuneError::NotFound { path: path.clone() })
+                });
+            };
+
+            let staged = items[position].staged(folder);
+            let record = file_ops::perform(
+                audit,
+                Operation::Restore,
+                &staged,
+                Some(original.to_path_buf()),
+                || {
+                    if let Some(parent) = original.parent() {
+                        fs::create_dir_all(parent).map_err(|e| DuneError::io(parent, e))?;
+                    }
+                    file_ops::relocate(&staged, original)
+                },
+            );
+            if record.ok {
+                let item = items.remove(position);
+                let _ = fs::remove_dir(folder.join(item.id));
+            }
+            record
+        })
+        .collect();
+
+    // The items are moved already, so a failed write must not hide that from the caller
+    if let Err(err) = write(folder, &items) {
+        log::error!("Could not write the recently removed index: {}", err);
+    }
+
+    Ok(records)
+}
+
+/// Delete everything in the folder for good, without waiting for it to expire
+pub fn empty(audit: &Path, folder: &Path) -> Result<(), DuneError> {
+    let _lock = INDEX_LOCK.lock().unwrap();
+    let kept = delete_items(audit, folder, read(folder)?);
+    write(folder, &kept)
+}
@@ -0,0 +1,137 @@
+use crate::error::DuneError;
+use crate::scanner::{self, DiskInfo};
+use serde::Serialize;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Links followed before giving up, as `ELOOP` does on Linux
+const MAX_LINKS: usize = 40;
+
+/// One link passed on the way to the real location
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkHop {
+    pub link: String,
+    /// As stored in the link, possibly relative
+    pub target: String,
+    /// `target` made absolute against the link's folder
+    pub resolved: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedPath {
+    pub path: String,
+    pub canonical: String,
+    /// Every link traversed in order, including links to folders along the way
+    pub links: Vec<LinkHop>,
+    /// Volume the bytes live on
+    pub volume: Option<DiskInfo>,
+    /// The target is on another volume than the path itself
+    pub other_volume: bool,
+}
+
+/// `C:\...` instead of the `\\?\C:\...` that `canonicalize` returns on Windows, so
+/// paths compare with the rest of the app; network paths keep their form
+pub(crate) fn plain(path: PathBuf) -> PathBuf {
+    let text = path.to_string_lossy();
+    match text.strip_prefix(r"\\?\") {
+        Some(rest) if !rest.starts_with("UNC\\") => PathBuf::from(rest),
+        _ => path,
+    }
+}
+
+/// Follow `path` one component at a time, collecting every link it passes through
+fn walk(path: &Path) -> Result<Vec<LinkHop>, DuneError> {
+    let mut pending: Vec<PathBuf> = path
+        .components()
+        .rev()
+        .map(|component| PathBuf::from(component.as_os_str()))
+        .collect();
+    let mut current = PathBuf::new();
+    let mut links = Vec::new();
+
+    while let Some(part) = pending.pop() {
+        match part.components().next() {
+            Some(Component::Normal(name)) => {
+                let candidate = current.join(name);
+                let metadata =
+                    fs::symlink_metadata(&candidate).map_err(|e| DuneError::io(&candidate, e))?;
+                if !metadata.is_symlink() {
+                    current = candidate;
+                    continue;
+                }
+                if links.len() == MAX_LINKS {
+                    return Err(DuneError::InvalidInput(format!(
+                        "More than {} links from {}",
+                        MAX_LINKS,
+                        path.display()
+                    )));
+                }
+
+                let target = fs::read_link(&candidate).map_err(|e| DuneError::io(&candidate, e))?;
+                let resolved = current.join(&target);
+                links.push(LinkHop {
+                    link: candidate.to_string_lossy().into_owned(),
+                    target: target.to_string_lossy().into_owned(),
+                    resolved: resolved.to_string_lossy().into_owned(),
+                });
+                // The target goes on from the link's folder, or from scratch when absolute
+                if target.is_absolute() {
+                    current = PathBuf::new();
+                }
+                pending.extend(
+                    target
+                        .components()
+                        .rev()
+                        .map(|component| PathBuf::from(component.as_os_str())),
+                );
+            }
+            Some(Component::ParentDir) => {
+                current.pop();
+            }
+            Some(Component::CurDir) | None => {}
+            Some(Component::RootDir) | Some(Component::Prefix(_)) => current.push(&part),
+        }
+    }
+
+    Ok(links)
+}
+
+/// Where the bytes of `path` really are: its canonical path, the links on the way there
+/// and the volume holding them
+pub fn resolve(path: &str) -> Result<ResolvedPath, DuneError> {
+    let original = Path::new(path);
+    if !original.is_absolute() {
+        return Err(DuneError::InvalidInput(format!(
+            "{} is not an absolute path",
+            path
+        )));
+    }
+
+    let links = walk(original)?;
+    let canonical = plain(fs::canonicalize(original).map_err(|e| DuneError::io(original, e))?);
+    let disks = scanner::get_disks();
+    let disk_for = |path: &Path| {
+        disks
+            .iter()
+            .filter(|disk| path.starts_with(&disk.path))
+            .max_by_key(|disk| disk.path.len())
+    };
+    let volume = disk_for(&canonical).cloned();
+    // Where the link itself is stored, not where it leads
+    let link_volume = original
+        .parent()
+        .and_then(|parent| fs::canonicalize(parent).ok())
+        .and_then(|parent| disk_for(&plain(parent)).map(|disk| disk.path.clone()));
+    let other_volume = match (&volume, &link_volume) {
+        (Some(volume), Some(link_volume)) => &volume.path != link_volume,
+        _ => false,
+    };
+
+    Ok(ResolvedPath {
+        path: path.to_string(),
+        canonical: canonical.to_string_lossy().into_owned(),
+        links,
+        volume,
+        other_volume,
+    })
+}
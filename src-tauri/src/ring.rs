@@ -0,0 +1,546 @@
+use crate::anonymize::{Anonymizer, Redaction};
+use crate::colors::{self, ColorBy, Theme};
+use crate::error::DuneError;
+use crate::scan::ScanTree;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+use std::path::Path;
+
+/// Rings and narrowest segment `layout_buffers` uses when not told
+pub const DEFAULT_DEPTH: u32 = 6;
+pub const DEFAULT_MIN_ANGLE: f64 = 0.005;
+/// Lower limit for `min_angle`, so a zero cannot ask for every node of the tree
+const SMALLEST_ANGLE: f64 = 0.0005;
+
+/// Children of every node, largest first, in one flat list
+pub struct ChildIndex {
+    /// `children[offsets[i]..offsets[i + 1]]` are the children of node `i`
+    offsets: Vec<u32>,
+    children: Vec<u32>,
+    /// See `colors::newest_modified`
+    newest: Vec<Option<u64>>,
+}
+
+impl ChildIndex {
+    pub fn heap_bytes(&self) -> u64 {
+        ((self.offsets.capacity() + self.children.capacity()) * std::mem::size_of::<u32>()
+            + self.newest.capacity() * std::mem::size_of::<Option<u64>>()) as u64
+    }
+
+    pub fn new(tree: &ScanTree) -> Self {
+        let mut offsets = vec![0u32; tree.nodes.len() + 1];
+        for node in &tree.nodes {
+            if let Some(parent) = node.parent {
+                offsets[parent as usize + 1] += 1;
+            }
+        }
+        for index in 1..offsets.len() {
+            offsets[index] += offsets[index - 1];
+        }
+
+        let mut next = offsets.clone();
+        let mut children = vec![0u32; tree.nodes.len().saturating_sub(1)];
+        for (index, node) in tree.nodes.iter().enumerate() {
+            if let Some(parent) = node.parent {
+                children[next[parent as usize] as usize] = index as u32;
+                next[parent as usize] += 1;
+            }
+        }
+        for window in offsets.windows(2) {
+            children[window[0] as usize..window[1] as usize]
+                .sort_by_key(|&child| std::cmp::Reverse(tree.nodes[child as usize].size));
+        }
+
+        Self {
+            offsets,
+            children,
+            newest: colors::newest_modified(tree),
+        }
+    }
+
+    pub fn of(&self, index: u32) -> &[u32] {
+        let start = self.offsets[index as usize] as usize;
+        let end = self.offsets[index as usize + 1] as usize;
+        &self.children[start..end]
+    }
+
+    /// See `colors::newest_modified`
+    pub fn newest(&self) -> &[Option<u64>] {
+        &self.newest
+    }
+
+    /// Like `ScanTree::find`, following child lists instead of scanning every node
+    pub fn find(&self, tree: &ScanTree, path: &Path) -> Option<u32> {
+        let (mut current, relative) = tree.start_of(path, self.of(0).iter().copied())?;
+
+        for component in relative.components() {
+            let name = component.as_os_str().to_str()?;
+            current = *self
+                .of(current)
+                .iter()
+                .find(|&&child| &*tree.nodes[child as usize].name == name)?;
+        }
+
+        Some(current)
+    }
+}
+
+/// One arc of the ring chart; angles are in radians, clockwise from the top
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RingSegment {
+    /// Tree node, `None` for an "other" bucket
+    pub node: Option<u32>,
+    /// Node of the segment this one sits on, `None` for the focus
+    pub parent: Option<u32>,
+    pub name: String,
+    /// Rings out from the focus, which is 0
+    pub depth: u32,
+    pub start_angle: f64,
+    pub sweep_angle: f64,
+    pub size: u64,
+    pub is_dir: bool,
+    pub size_complete: bool,
+    /// Children left out for being past `depth`; zooming in shows them
+    pub has_hidden_children: bool,
+    /// Items merged into an "other" bucket, 0 for a node
+    pub merged: u64,
+    /// Index into `RingLevel.palette`
+    #[serde(default)]
+    pub color: u8,
+}
+
+/// Tags `layout_buffers` output
+const BUFFER_MAGIC: &[u8; 4] = b"DRNG";
+const BUFFER_VERSION: u32 = 1;
+
+/// Sizes of the drawn ring, in the frontend's units, for placing labels and vertex buffers
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct LabelOptions {
+    /// Radius of the focus disc; ring `n` spans `center_radius + (n - 1) * ring_width`
+    /// to `center_radius + n * ring_width`
+    pub center_radius: f64,
+    pub ring_width: f64,
+    /// Average advance of one character
+    pub char_width: f64,
+    pub line_height: f64,
+    /// Segments that fit fewer characters, ellipsis included, get no label
+    pub min_chars: usize,
+}
+
+impl Default for LabelOptions {
+    fn default() -> Self {
+        Self {
+            center_radius: 60.0,
+            ring_width: 40.0,
+            char_width: 7.0,
+            line_height: 12.0,
+            min_chars: 4,
+        }
+    }
+}
+
+/// How `get_ring_level` colors and labels segments
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct RingStyle {
+    pub color_by: ColorBy,
+    pub theme: Theme,
+    /// No labels are placed without these
+    pub labels: Option<LabelOptions>,
+    /// Replace names, and the focus path, for screenshots that must not show them;
+    /// `layout_diff` only matches such layouts across levels when hashed
+    pub redact: Option<Redaction>,
+}
+
+/// Text for one segment, written along the middle of its arc; labels in the same ring
+/// never overlap, since each stays within its own segment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Label {
+    /// Index into `RingLevel.segments`
+    pub segment: usize,
+    pub text: String,
+    pub truncated: bool,
+    /// Anchor at the middle of the text, with `y` up and the origin at the center
+    pub x: f64,
+    pub y: f64,
+    /// Angle of the anchor, clockwise from the top; text runs tangent to it
+    pub angle: f64,
+}
+
+/// The segments visible around `focus`, for `get_ring_level`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RingLevel {
+    pub scan_id: u64,
+    pub focus: u32,
+    pub path: String,
+    pub segments: Vec<RingSegment>,
+    pub color_by: ColorBy,
+    /// `0xRRGGBB` colors of the theme, see `colors::palette`
+    pub palette: Vec<u32>,
+    /// Only placed when `RingStyle.labels` is set
+    #[serde(default)]
+    pub labels: Vec<Label>,
+}
+
+struct Builder<'a> {
+    tree: &'a ScanTree,
+    index: &'a ChildIndex,
+    max_depth: u32,
+    min_angle: f64,
+    segments: Vec<RingSegment>,
+}
+
+impl Builder<'_> {
+    /// Rings under `node`, which has been given `start..start + sweep`
+    fn children(&mut self, node: u32, depth: u32, start: f64, sweep: f64) {
+        let size = self.tree.nodes[node as usize].size;
+        if size == 0 {
+            return;
+        }
+
+        let per_byte = sweep / size as f64;
+        let mut angle = start;
+        let children = self.index.of(node);
+        // Largest first, so everything after the first narrow child is narrow too
+        let shown = children
+            .iter()
+            .position(|&child| {
+                self.tree.nodes[child as usize].size as f64 * per_byte < self.min_angle
+            })
+            .unwrap_or(children.len());
+
+        for &child in &children[..shown] {
+            let entry = &self.tree.nodes[child as usize];
+            let child_sweep = entry.size as f64 * per_byte;
+            let grandchildren = !self.index.of(child).is_empty();
+            self.segments.push(RingSegment {
+                node: Some(child),
+                parent: Some(node),
+                name: entry.name.to_string(),
+                depth,
+                start_angle: angle,
+                sweep_angle: child_sweep,
+                size: entry.size,
+                is_dir: entry.is_dir,
+                size_complete: entry.size_complete,
+                has_hidden_children: grandchildren && depth >= self.max_depth,
+                merged: 0,
+                color: 0,
+            });
+            if depth < self.max_depth {
+                self.children(child, depth + 1, angle, child_sweep);
+            }
+            angle += child_sweep;
+        }
+
+        let rest = &children[shown..];
+        let rest_size: u64 = rest
+            .iter()
+            .map(|&child| self.tree.nodes[child as usize].size)
+            .sum();
+        if rest_size > 0 {
+            self.segments.push(RingSegment {
+                node: None,
+                parent: Some(node),
+                name: format!("{} other items", crate::format::count(rest.len() as u64)),
+                depth,
+                start_angle: angle,
+                sweep_angle: rest_size as f64 * per_byte,
+                size: rest_size,
+                is_dir: false,
+                size_complete: rest
+                    .iter()
+                    .all(|&child| self.tree.nodes[child as usize].size_complete),
+                has_hidden_children: false,
+                merged: rest.len() as u64,
+                color: colors::OTHER,
+            });
+        }
+    }
+}
+
+/// `text`, cut to `max_chars` with an ellipsis
+fn truncate(text: &str, max_chars: usize) -> (String, bool) {
+    if text.chars().count() <= max_chars {
+        return (text.to_string(), false);
+    }
+
+    let mut cut: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    cut.push('\u{2026}');
+    (cut, true)
+}
+
+/// Labels for the segments with room for at least `min_chars` characters
+fn labels(segments: &[RingSegment], options: &LabelOptions) -> Vec<Label> {
+    if options.char_width <= 0.0 || options.line_height > options.ring_width {
+        return Vec::new();
+    }
+
+    segments
+        .iter()
+        .enumerate()
+        .filter_map(|(position, segment)| {
+            let (radius, room) = if segment.depth == 0 {
+                (0.0, 2.0 * options.center_radius)
+            } else {
+                let radius =
+                    options.center_radius + (f64::from(segment.depth) - 0.5) * options.ring_width;
+                (radius, segment.sweep_angle * radius)
+            };
+            let max_chars = (room / options.char_width) as usize;
+            if max_chars < options.min_chars.max(1) {
+                return None;
+            }
+
+            let (text, truncated) = truncate(&segment.name, max_chars);
+            let angle = segment.start_angle + segment.sweep_angle / 2.0;
+            Some(Label {
+                segment: position,
+                text,
+                truncated,
+                x: radius * angle.sin(),
+                y: radius * angle.cos(),
+                angle,
+            })
+        })
+        .collect()
+}
+
+/// Segments `depth` rings out from `focus_path`, with children narrower than
+/// `min_angle` merged into one "other" bucket per parent, colored and labeled by `style`
+pub fn level(
+    tree: &ScanTree,
+    index: &ChildIndex,
+    focus_path: &str,
+    depth: u32,
+    min_angle: f64,
+    style: &RingStyle,
+) -> Result<RingLevel, DuneError> {
+    let focus = index
+        .find(tree, Path::new(focus_path))
+        .ok_or_else(|| DuneError::NotFound {
+            path: focus_path.into(),
+        })?;
+    let node = &tree.nodes[focus as usize];
+
+    let mut builder = Builder {
+        tree,
+        index,
+        max_depth: depth,
+        min_angle: min_angle.max(SMALLEST_ANGLE),
+        segments: vec![RingSegment {
+            node: Some(focus),
+            parent: None,
+            name: node.name.to_string(),
+            depth: 0,
+            start_angle: 0.0,
+            sweep_angle: TAU,
+            size: node.size,
+            is_dir: node.is_dir,
+            size_complete: node.size_complete,
+            has_hidden_children: depth == 0 && !index.of(focus).is_empty(),
+            merged: 0,
+            color: 0,
+        }],
+    };
+    if depth > 0 {
+        builder.children(focus, 1, 0.0, TAU);
+    }
+    let mut segments = builder.segments;
+    colors::stamp(tree, &index.newest, focus, &mut segments, style.color_by);
+    let mut path = tree.path(focus).to_string_lossy().into_owned();
+    if let Some(mode) = style.redact {
+        let mut anonymizer = Anonymizer::new(mode);
+        for segment in segments.iter_mut().filter(|segment| segment.node.is_some()) {
+            segment.name = anonymizer.name(&segment.name, segment.is_dir);
+        }
+        path = segments[0].name.clone();
+    }
+    let labels = style
+        .labels
+        .map_or_else(Vec::new, |options| labels(&segments, &options));
+
+    Ok(RingLevel {
+        scan_id: tree.scan_id,
+        focus,
+        path,
+        segments,
+        color_by: style.color_by,
+        palette: colors::palette(style.theme).to_vec(),
+        labels,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    Kept,
+    /// Only in the new layout; grows from no width at its new start
+    Added,
+    /// Only in the old layout; shrinks to no width at its old start
+    Removed,
+}
+
+/// Where a segment is in one layout
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SegmentState {
+    pub depth: u32,
+    pub start_angle: f64,
+    pub sweep_angle: f64,
+    pub size: u64,
+    pub color: u8,
+}
+
+impl SegmentState {
+    fn of(segment: &RingSegment) -> Self {
+        Self {
+            depth: segment.depth,
+            start_angle: segment.start_angle,
+            sweep_angle: segment.sweep_angle,
+            size: segment.size,
+            color: segment.color,
+        }
+    }
+
+    fn collapsed(self) -> Self {
+        Self {
+            sweep_angle: 0.0,
+            size: 0,
+            ..self
+        }
+    }
+}
+
+/// One segment's move between two layouts, for tweening the ring after a rescan
+#[derive(Debug, Clone, Serialize)]
+pub struct SegmentTransition {
+    /// For an "other" bucket, the path of the folder it belongs to
+    pub path: String,
+    pub name: String,
+    pub other: bool,
+    pub change: Change,
+    pub from: SegmentState,
+    pub to: SegmentState,
+}
+
+/// Segments of a layout by path, each with its name and whether it is an "other" bucket
+fn keyed(layout: &RingLevel) -> Vec<((String, bool), &RingSegment)> {
+    // Parents are listed before their children
+    let mut paths: HashMap<u32, String> = HashMap::new();
+    let mut keys = Vec::with_capacity(layout.segments.len());
+
+    for segment in &layout.segments {
+        let parent = segment
+            .parent
+            .and_then(|parent| paths.get(&parent))
+            .cloned();
+        let key = match (segment.node, parent) {
+            (Some(node), Some(parent)) => {
+                let path = Path::new(&parent)
+                    .join(&segment.name)
+                    .to_string_lossy()
+                    .into_owned();
+                paths.insert(node, path.clone());
+                (path, false)
+            }
+            (Some(node), None) => {
+                paths.insert(node, layout.path.clone());
+                (layout.path.clone(), false)
+            }
+            (None, parent) => (parent.unwrap_or_else(|| layout.path.clone()), true),
+        };
+        keys.push((key, segment));
+    }
+
+    keys
+}
+
+/// Match the segments of two layouts by path: those in both move from their old angles to
+/// their new ones, the rest grow in or shrink away. New layout order, removed ones last
+pub fn diff(old_layout: &RingLevel, new_layout: &RingLevel) -> Vec<SegmentTransition> {
+    let mut old: HashMap<(String, bool), &RingSegment> = keyed(old_layout).into_iter().collect();
+    let mut transitions = Vec::with_capacity(new_layout.segments.len());
+
+    for (key, segment) in keyed(new_layout) {
+        let to = SegmentState::of(segment);
+        let (change, from) = match old.remove(&key) {
+            Some(previous) => (Change::Kept, SegmentState::of(previous)),
+            None => (Change::Added, to.collapsed()),
+        };
+        transitions.push(SegmentTransition {
+            path: key.0,
+            name: segment.name.clone(),
+            other: key.1,
+            change,
+            from,
+            to,
+        });
+    }
+
+    for (key, segment) in keyed(old_layout) {
+        if old.remove(&key).is_none() {
+            continue;
+        }
+        let from = SegmentState::of(segment);
+        transitions.push(SegmentTransition {
+            path: key.0,
+            name: segment.name.clone(),
+            other: key.1,
+            change: Change::Removed,
+            from,
+            to: from.collapsed(),
+        });
+    }
+
+    transitions
+}
+
+/// A level as flat little-endian arrays to upload as vertex buffers.
+///
+/// A 16-byte header (`DRNG`, version, segment count `n`, focus node as `u32`), then
+/// `n` of each: `f32` start angles, `f32` sweep angles, `f32` inner radii, `f32` outer
+/// radii, `u32` `0xRRGGBB` colors and `u32` node ids, `u32::MAX` for "other" buckets.
+/// Every array starts 4-byte aligned, so each can back a typed array view directly.
+pub fn buffers(level: &RingLevel, geometry: &LabelOptions) -> Vec<u8> {
+    let count = level.segments.len();
+    let mut bytes = Vec::with_capacity(16 + count * 6 * 4);
+    bytes.extend_from_slice(BUFFER_MAGIC);
+    for value in [BUFFER_VERSION, count as u32, level.focus] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let radii = |segment: &RingSegment| match segment.depth {
+        0 => (0.0, geometry.center_radius),
+        depth => {
+            let inner = geometry.center_radius + f64::from(depth - 1) * geometry.ring_width;
+            (inner, inner + geometry.ring_width)
+        }
+    };
+    let floats: [&dyn Fn(&RingSegment) -> f64; 4] = [
+        &|segment| segment.start_angle,
+        &|segment| segment.sweep_angle,
+        &|segment| radii(segment).0,
+        &|segment| radii(segment).1,
+    ];
+    for field in floats {
+        for segment in &level.segments {
+            bytes.extend_from_slice(&(field(segment) as f32).to_le_bytes());
+        }
+    }
+
+    for segment in &level.segments {
+        let color = level
+            .palette
+            .get(segment.color as usize)
+            .copied()
+            .unwrap_or(0);
+        bytes.extend_from_slice(&color.to_le_bytes());
+    }
+    for segment in &level.segments {
+        bytes.extend_from_slice(&segment.node.unwrap_or(u32::MAX).to_le_bytes());
+    }
+
+    bytes
+}
@@ -0,0 +1,428 @@
+use crate::console;
+use crate::error::DuneError;
+use crate::query;
+use crate::scan::{
+    self, NameInterner, ProgressCoalescer, ScanHandle, ScanOptions, ScanProgress, ScanTree,
+    TreeNode,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Instant;
+
+const SCHEME: &str = "s3://";
+/// Objects without a class in the listing are billed as Standard
+const DEFAULT_CLASS: &str = "STANDARD";
+/// Objects per CLI run; the CLI holds a whole run's JSON before printing it
+const PAGE_ITEMS: u32 = 10_000;
+
+/// A location like `s3://bucket/some/prefix`
+#[derive(Debug, Clone)]
+pub struct BucketLocation {
+    pub bucket: String,
+    /// Empty for the whole bucket, otherwise ends with `/`
+    pub prefix: String,
+}
+
+pub fn is_bucket(location: &str) -> bool {
+    location.starts_with(SCHEME)
+}
+
+impl BucketLocation {
+    pub fn parse(location: &str) -> Result<Self, DuneError> {
+        let rest = location.strip_prefix(SCHEME).unwrap_or(location);
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() || bucket.starts_with('-') {
+            return Err(DuneError::InvalidInput(format!(
+                "Not an s3://bucket/prefix location: {}",
+                location
+            )));
+        }
+
+        // `s3://bucket/photos` means the photos "folder", not every key starting with "photos"
+        let prefix = prefix.trim_start_matches('/');
+        let prefix = if prefix.is_empty() || prefix.ends_with('/') {
+            prefix.to_string()
+        } else {
+            format!("{}/", prefix)
+        };
+
+        Ok(Self {
+            bucket: bucket.to_string(),
+            prefix,
+        })
+    }
+}
+
+struct BucketObject {
+    key: String,
+    size: u64,
+    class: String,
+    /// Milliseconds since the Unix epoch
+    modified: Option<u64>,
+}
+
+/// An object as `list-objects-v2` prints it
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListedObject {
+    key: String,
+    #[serde(default)]
+    size: u64,
+    storage_class: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// One run of `list-objects-v2`, with the token to continue from when there is more
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListedPage {
+    #[serde(default)]
+    contents: Vec<ListedObject>,
+    next_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageClassUsage {
+    pub class: String,
+    pub bytes: u64,
+    pub objects: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PrefixUsage {
+    /// First path segment below the location, or the object name for top-level objects
+    pub prefix: String,
+    pub bytes: u64,
+    pub objects: u64,
+    pub classes: Vec<StorageClassUsage>,
+}
+
+/// What a bucket location holds per storage class, overall and per top-level prefix
+#[derive(Debug, Clone, Serialize)]
+pub struct BucketReport {
+    pub location: String,
+    pub bytes: u64,
+    pub objects: u64,
+    /// Largest first
+    pub classes: Vec<StorageClassUsage>,
+    /// Largest first
+    pub prefixes: Vec<PrefixUsage>,
+    pub elapsed_ms: u64,
+}
+
+/// `2024-05-01T09:30:00.000Z` or `2024-05-01T09:30:00+00:00` as milliseconds since the epoch
+fn parse_timestamp(value: &str) -> Option<u64> {
+    let (date, time) = value.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(str::parse::<u32>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    let mut time = time.get(..8)?.splitn(3, ':').map(str::parse::<u64>);
+    let (hours, minutes, seconds) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+
+    let days = query::days_from_civil(i64::from(year), month, day);
+    let seconds = u64::try_from(days).ok()? * 86_400 + hours * 3_600 + minutes * 60 + seconds;
+    Some(seconds * 1000)
+}
+
+impl From<ListedObject> for BucketObject {
+    fn from(object: ListedObject) -> Self {
+        Self {
+            key: object.key,
+            size: object.size,
+            class: object
+                .storage_class
+                .filter(|class| !class.is_empty())
+                .unwrap_or_else(|| DEFAULT_CLASS.to_string()),
+            modified: object.last_modified.as_deref().and_then(parse_timestamp),
+        }
+    }
+}
+
+/// One page of `aws s3api list-objects-v2` as JSON, which keeps keys with tabs and
+/// newlines whole. Credentials and region come from the usual AWS configuration,
+/// including `AWS_PROFILE`.
+fn list_page(
+    location: &BucketLocation,
+    display: &str,
+    token: Option<&str>,
+) -> Result<ListedPage, DuneError> {
+    let mut command = console::command("aws");
+    command
+        .args(["s3api", "list-objects-v2", "--bucket", &location.bucket])
+        .args(["--output", "json"])
+        .args(["--max-items", &PAGE_ITEMS.to_string()]);
+    if !location.prefix.is_empty() {
+        command.args(["--prefix", &location.prefix]);
+    }
+    if let Some(token) = token {
+        command.args(["--starting-token", token]);
+    }
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let output = command.output().map_err(|err| {
+        DuneError::Unsupported(format!(
+            "Bucket scans need the AWS CLI (aws) installed on the PATH and configured \
+             with `aws configure`: {}",
+            err
+        ))
+    })?;
+    if !output.status.success() {
+        return Err(DuneError::Io {
+            path: Some(display.to_string()),
+            message: format!(
+                "Listing failed ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        });
+    }
+
+    // An empty location prints nothing at all
+    if output.stdout.iter().all(u8::is_ascii_whitespace) {
+        return Ok(ListedPage {
+            contents: Vec::new(),
+            next_token: None,
+        });
+    }
+    serde_json::from_slice(&output.stdout).map_err(|err| DuneError::Io {
+        path: Some(display.to_string()),
+        message: format!("Unreadable listing: {}", err),
+    })
+}
+
+/// Hand every object under `location` to `on_object`, a page at a time; stops between
+/// pages when `cancelled` says so
+fn list_objects(
+    location: &BucketLocation,
+    display: &str,
+    cancelled: &dyn Fn() -> bool,
+    on_object: &mut dyn FnMut(BucketObject),
+) -> Result<(), DuneError> {
+    let mut token = None;
+    loop {
+        if cancelled() {
+            return Err(DuneError::Cancelled);
+        }
+
+        let page = list_page(location, display, token.as_deref())?;
+        page.contents
+            .into_iter()
+            .for_each(|object| on_object(object.into()));
+        match page.next_token {
+            Some(next) => token = Some(next),
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Build a tree of the location's objects, with key prefixes as folders
+pub fn scan_tree(
+    handle: &ScanHandle,
+    options: &ScanOptions,
+    on_progress: &(dyn Fn(ScanProgress) + Sync),
+) -> Result<ScanTree, DuneError> {
+    let started = Instant::now();
+    let location = BucketLocation::parse(&handle.root)?;
+    let root_name = location
+        .prefix
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .unwrap_or(&location.bucket)
+        .to_string();
+
+    let mut nodes = vec![TreeNode {
+        parent: None,
+        name: Arc::from(root_name),
+        is_dir: true,
+        size: 0,
+        file_count: 0,
+        size_complete: true,
+        modified: None,
+    }];
+    let mut names = NameInterner::default();
+    let mut dirs: HashMap<String, u32> = HashMap::from([(String::new(), 0)]);
+    let progress =
+        ProgressCoalescer::new(handle.id, options.progress_events_per_second, on_progress);
+    let current = Path::new(&handle.root);
+
+    let result = list_objects(
+        &location,
+        &handle.root,
+        &|| handle.is_cancelled(),
+        &mut |object| {
+            let relative = object
+                .key
+                .strip_prefix(&location.prefix)
+                .unwrap_or(&object.key);
+            let mut parent = 0u32;
+            let mut path = String::new();
+            let mut segments = relative.split('/').peekable();
+
+            while let Some(segment) = segments.next() {
+                let is_last = segments.peek().is_none();
+                if !is_last && segment.is_empty() {
+                    continue;
+                }
+                if !path.is_empty() {
+                    path.push('/');
+                }
+                path.push_str(segment);
+
+                if is_last {
+                    // Keys ending in `/` are folder placeholders made by consoles
+                    if !segment.is_empty() && (options.include_hidden || !segment.starts_with('.'))
+                    {
+                        nodes.push(TreeNode {
+                            parent: Some(parent),
+                            name: names.intern(segment),
+                            is_dir: false,
+                            size: object.size,
+                            file_count: 1,
+                            size_complete: true,
+                            modified: object.modified,
+                        });
+                        progress.record(object.size, 1, 0, current);
+                    }
+                    break;
+                }
+
+                if !options.include_hidden && segment.starts_with('.') {
+                    break;
+                }
+                parent = match dirs.get(&path) {
+                    Some(&index) => index,
+                    None => {
+                        let index = nodes.len() as u32;
+                        nodes.push(TreeNode {
+                            parent: Some(parent),
+                            name: names.intern(segment),
+                            is_dir: true,
+                            size: 0,
+                            file_count: 0,
+                            size_complete: true,
+                            modified: None,
+                        });
+                        dirs.insert(path.clone(), index);
+                        progress.record(0, 0, 1, current);
+                        index
+                    }
+                };
+            }
+        },
+    );
+
+    if let Err(err) = result {
+        if matches!(err, DuneError::Cancelled) {
+            log::info!("Bucket scan {} of {} cancelled", handle.id, handle.root);
+        }
+        return Err(err);
+    }
+
+    progress.flush(current);
+    scan::aggregate(&mut nodes);
+
+    log::info!(
+        "Bucket scan {} of {}: {} nodes, {} bytes in {:?}",
+        handle.id,
+        handle.root,
+        nodes.len(),
+        nodes[0].size,
+        started.elapsed()
+    );
+
+    Ok(ScanTree {
+        scan_id: handle.id,
+        root: handle.root.clone(),
+        nodes,
+        allocation: None,
+        subvolumes: Vec::new(),
+        ignored: Vec::new(),
+        small_items: Vec::new(),
+        skipped: Vec::new(),
+        stats: None,
+        elevated: false,
+    })
+}
+
+fn sorted_classes(classes: BTreeMap<String, (u64, u64)>) -> Vec<StorageClassUsage> {
+    let mut classes: Vec<StorageClassUsage> = classes
+        .into_iter()
+        .map(|(class, (bytes, objects))| StorageClassUsage {
+            class,
+            bytes,
+            objects,
+        })
+        .collect();
+    classes.sort_by_key(|usage| std::cmp::Reverse(usage.bytes));
+    classes
+}
+
+/// Bytes and objects per storage class under `location`, overall and per top-level prefix
+pub fn bucket_report(location: &str) -> Result<BucketReport, DuneError> {
+    let started = Instant::now();
+    let parsed = BucketLocation::parse(location)?;
+    let mut classes: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+    let mut prefixes: HashMap<String, BTreeMap<String, (u64, u64)>> = HashMap::new();
+
+    list_objects(&parsed, location, &|| false, &mut |object| {
+        // Folder placeholders hold nothing worth counting
+        if object.key.ends_with('/') && object.size == 0 {
+            return;
+        }
+        let relative = object
+            .key
+            .strip_prefix(&parsed.prefix)
+            .unwrap_or(&object.key);
+        let top = relative.split('/').next().unwrap_or(relative).to_string();
+
+        for usage in [
+            classes.entry(object.class.clone()).or_default(),
+            prefixes
+                .entry(top)
+                .or_default()
+                .entry(object.class)
+                .or_default(),
+        ] {
+            usage.0 += object.size;
+            usage.1 += 1;
+        }
+    })?;
+
+    let mut prefixes: Vec<PrefixUsage> = prefixes
+        .into_iter()
+        .map(|(prefix, classes)| PrefixUsage {
+            prefix,
+            bytes: classes.values().map(|usage| usage.0).sum(),
+            objects: classes.values().map(|usage| usage.1).sum(),
+            classes: sorted_classes(classes),
+        })
+        .collect();
+    prefixes.sort_by_key(|usage| std::cmp::Reverse(usage.bytes));
+
+    let classes = sorted_classes(classes);
+    let report = BucketReport {
+        location: location.to_string(),
+        bytes: classes.iter().map(|usage| usage.bytes).sum(),
+        objects: classes.iter().map(|usage| usage.objects).sum(),
+        classes,
+        prefixes,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    };
+
+    log::info!(
+        "Bucket report for {}: {} objects, {} bytes in {} classes",
+        location,
+        report.objects,
+        report.bytes,
+        report.classes.len()
+    );
+
+    Ok(report)
+}
@@ -0,0 +1,165 @@
+use crate::app_data;
+use crate::error::DuneError;
+use crate::query::Query;
+use crate::scan;
+use crate::search::{SearchOptions, SearchTree};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Runtime};
+
+const FILE_NAME: &str = "saved_searches.json";
+
+/// Outcome of the last run, so the start screen can show it without re-running
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LastRun {
+    /// Milliseconds since the Unix epoch
+    pub at: u64,
+    pub matches: u64,
+    pub total_bytes: u64,
+    pub total_files: u64,
+}
+
+/// A named query that can be re-run with `run_saved_search`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub id: String,
+    pub name: String,
+    pub query: String,
+    #[serde(default)]
+    pub options: SearchOptions,
+    /// Shown on the start screen
+    #[serde(default)]
+    pub pinned: bool,
+    pub created_at: u64,
+    #[serde(default)]
+    pub last_run: Option<LastRun>,
+}
+
+/// What the frontend sends to create a saved search, or to update one when `id` is set
+#[derive(Debug, Clone, Deserialize)]
+pub struct SavedSearchInput {
+    pub id: Option<String>,
+    pub name: String,
+    pub query: String,
+    #[serde(default)]
+    pub options: SearchOptions,
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+pub fn store_path<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, DuneError> {
+    app_data::path(app, FILE_NAME)
+}
+
+fn read(path: &Path) -> Result<Vec<SavedSearch>, DuneError> {
+    match fs::read(path) {
+        Ok(json) => serde_json::from_slice(&json).map_err(|e| DuneError::Io {
+            path: Some(path.to_string_lossy().to_string()),
+            message: e.to_string(),
+        }),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(DuneError::io(path, err)),
+    }
+}
+
+fn write(path: &Path, searches: &[SavedSearch]) -> Result<(), DuneError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| DuneError::io(parent, e))?;
+    }
+
+    let json = serde_json::to_vec_pretty(searches).map_err(DuneError::internal)?;
+    fs::write(path, json).map_err(|e| DuneError::io(path, e))
+}
+
+/// Saved searches, pinned first, then by name
+pub fn list(path: &Path) -> Result<Vec<SavedSearch>, DuneError> {
+    let mut searches = read(path)?;
+    searches.sort_by(|a, b| {
+        b.pinned
+            .cmp(&a.pinned)
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+
+    Ok(searches)
+}
+
+pub fn get(path: &Path, id: &str) -> Result<SavedSearch, DuneError> {
+    read(path)?
+        .into_iter()
+        .find(|search| search.id == id)
+        .ok_or_else(|| DuneError::NotFound { path: id.into() })
+}
+
+/// Create or update a saved search; the query must parse
+pub fn save(path: &Path, input: SavedSearchInput) -> Result<SavedSearch, DuneError> {
+    Query::parse(&input.query, input.options.text_mode())?;
+
+    let mut searches = read(path)?;
+    let saved = match input.id {
+        Some(id) => {
+            let search = searches
+                .iter_mut()
+                .find(|search| search.id == id)
+                .ok_or(DuneError::NotFound { path: id })?;
+
+            // A different query makes the old result stale
+            if search.query != input.query {
+                search.last_run = None;
+            }
+            search.name = input.name;
+            search.query = input.query;
+            search.options = input.options;
+            search.pinned = input.pinned;
+            search.clone()
+        }
+        None => {
+            let created_at = scan::now_millis();
+            let search = SavedSearch {
+                id: format!("search_{}", created_at),
+                name: input.name,
+                query: input.query,
+                options: input.options,
+                pinned: input.pinned,
+                created_at,
+                last_run: None,
+            };
+            searches.push(search.clone());
+            search
+        }
+    };
+
+    write(path, &searches)?;
+    log::info!("Saved search {} ({})", saved.id, saved.name);
+
+    Ok(saved)
+}
+
+pub fn delete(path: &Path, id: &str) -> Result<(), DuneError> {
+    let mut searches = read(path)?;
+    let before = searches.len();
+    searches.retain(|search| search.id != id);
+
+    if searches.len() == before {
+        return Err(DuneError::NotFound { path: id.into() });
+    }
+    write(path, &searches)
+}
+
+/// Remember the outcome of running a saved search
+pub fn record_run(path: &Path, id: &str, result: &SearchTree) -> Result<(), DuneError> {
+    let mut searches = read(path)?;
+    let Some(search) = searches.iter_mut().find(|search| search.id == id) else {
+        return Err(DuneError::NotFound { path: id.into() });
+    };
+
+    let root = result.tree.nodes.first();
+    search.last_run = Some(LastRun {
+        at: scan::now_millis(),
+        matches: result.summary.matches,
+        total_bytes: root.map_or(0, |node| node.size),
+        total_files: root.map_or(0, |node| node.file_count),
+    });
+
+    write(path, &searches)
+}
@@ -0,0 +1,1272 @@
+use crate::allocation::{self, AllocationSummary};
+use crate::error::DuneError;
+use crate::format;
+use crate::scanner::{self, DirEnumerator, EntryKind};
+use crate::subvolumes::{self, Boundary, Subvolume};
+use crate::wsl;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const IDLE_WORKER_POLL: Duration = Duration::from_millis(100);
+/// A folder read back within this most likely came from the OS metadata cache
+const CACHED_READ: Duration = Duration::from_millis(1);
+/// Only the most recent navigations are worth prioritizing
+const MAX_BOOSTS: usize = 8;
+/// Folders holding this file are left out the way backup tools skip them
+const IGNORE_MARKER: &str = ".dunefilesignore";
+/// Cache folder tag from the Cache Directory Tagging Specification
+const CACHEDIR_TAG: &str = "CACHEDIR.TAG";
+const CACHEDIR_SIGNATURE: &[u8] = b"Signature: 8a477f597d28d172789f06886806bc55";
+/// Name of the node files and folders under `ScanOptions.noise_floor` merge into
+pub const SMALL_ITEMS: &str = "Small items";
+
+/// What scans do with folders holding `.dunefilesignore`, or `CACHEDIR.TAG` if asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IgnoreMarkers {
+    /// Scan them like any other folder
+    Off,
+    /// Count their size and files, but show no contents
+    Size,
+    /// Leave them out of the tree and the totals
+    Exclude,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanOptions {
+    pub include_hidden: bool,
+    pub follow_symlinks: bool,
+    /// Do not cross into other mounted filesystems (Unix only)
+    pub same_filesystem: bool,
+    pub max_depth: Option<u32>,
+    /// Upper bound on `scan://progress` events, 0 to disable them
+    pub progress_events_per_second: u32,
+    /// Look up every file's allocation for `ScanTree.allocation`; one more call per file
+    pub allocation: bool,
+    /// Descend into btrfs snapshots and ZFS `.zfs` folders, counting their
+    /// copy-on-write copies as if they were data of their own
+    pub snapshots: bool,
+    pub ignore_markers: IgnoreMarkers,
+    /// Treat folders tagged with a valid `CACHEDIR.TAG` as marked too
+    pub cachedir_tags: bool,
+    /// Bytes below which files and folders merge into one `SMALL_ITEMS` node per folder;
+    /// 0 keeps every node
+    pub noise_floor: u64,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            include_hidden: false,
+            follow_symlinks: false,
+            same_filesystem: false,
+            max_depth: None,
+            progress_events_per_second: 10,
+            allocation: false,
+            snapshots: false,
+            ignore_markers: IgnoreMarkers::Size,
+            cachedir_tags: false,
+            noise_floor: 0,
+        }
+    }
+}
+
+/// One file or folder in a scanned tree; folders carry the totals of their subtree
+///
+/// Nodes store only their name; the full path is the root joined with the names
+/// along the parent chain.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreeNode {
+    /// Index of the parent node, `None` for the root
+    pub parent: Option<u32>,
+    /// Shared with every other node of the same name
+    pub name: Arc<str>,
+    pub is_dir: bool,
+    pub size: u64,
+    pub file_count: u64,
+    pub size_complete: bool,
+    /// Last write time of files in milliseconds since the Unix epoch; `None` for folders
+    #[serde(default)]
+    pub modified: Option<u64>,
+}
+
+/// Result of a full scan; `nodes[0]` is the root and parents always precede children
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanTree {
+    pub scan_id: u64,
+    /// Empty for several roots combined under one synthetic node, see `multi::combine`
+    pub root: String,
+    pub nodes: Vec<TreeNode>,
+    /// Only filled in when the scan asked for it, see `ScanOptions.allocation`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allocation: Option<AllocationSummary>,
+    /// btrfs subvolumes and ZFS datasets the scan crossed into
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subvolumes: Vec<Subvolume>,
+    /// Folders skipped for an ignore marker, see `ScanOptions.ignore_markers`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignored: Vec<String>,
+    /// Nodes standing for everything under `ScanOptions.noise_floor` in their folder
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub small_items: Vec<u32>,
+    /// Folders given up on mid-scan with `skip_path`, left unscanned
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<String>,
+    /// How the scan went; only local scans measure it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<ScanStats>,
+    /// Scanned as administrator, see `elevated::scan`; it may list files the app cannot
+    /// otherwise open
+    #[serde(default)]
+    pub elevated: bool,
+}
+
+/// Where a scan's time went, to tell a slow disk from a cold cache or a folder full of
+/// errors
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanStats {
+    pub duration_ms: u64,
+    /// Folders read, and files listed in them
+    pub dirs: u64,
+    pub files: u64,
+    pub bytes_per_second: u64,
+    /// Share of folder reads back within `CACHED_READ`, most likely served from the OS
+    /// metadata cache rather than the disk. An estimate: cold reads on a fast SSD can be
+    /// that quick too.
+    pub cache_hit_rate: f64,
+    /// Failed folder and entry reads by cause: `permission_denied`, `not_found` for ones
+    /// gone mid-scan, `timed_out`, `skipped`, `unreadable_entries` and `other`
+    pub errors: BTreeMap<String, u64>,
+    pub threads: usize,
+    /// The enumeration backend, see `DirEnumerator::name`
+    pub backend: String,
+}
+
+#[derive(Default)]
+struct StatCounters {
+    dirs: AtomicU64,
+    files: AtomicU64,
+    cached_reads: AtomicU64,
+    errors: Mutex<BTreeMap<String, u64>>,
+}
+
+impl StatCounters {
+    fn error(&self, kind: &str) {
+        *self
+            .errors
+            .lock()
+            .unwrap()
+            .entry(kind.to_string())
+            .or_default() += 1;
+    }
+
+    fn io_error(&self, err: &io::Error) {
+        self.error(match err.kind() {
+            io::ErrorKind::PermissionDenied => "permission_denied",
+            io::ErrorKind::NotFound => "not_found",
+            io::ErrorKind::TimedOut => "timed_out",
+            io::ErrorKind::Interrupted => "skipped",
+            _ => "other",
+        });
+    }
+}
+
+impl ScanTree {
+    /// Rough heap size: the nodes, plus each name split evenly between the nodes sharing it
+    pub fn heap_bytes(&self) -> u64 {
+        let names: f64 = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let bytes = node.name.len() + 2 * std::mem::size_of::<usize>();
+                bytes as f64 / Arc::strong_count(&node.name) as f64
+            })
+            .sum();
+        (self.nodes.capacity() * std::mem::size_of::<TreeNode>()) as u64 + names as u64
+    }
+
+    /// Rebuild the full path of `nodes[index]` from the parent chain
+    pub fn path(&self, index: u32) -> PathBuf {
+        let mut names = Vec::new();
+        let mut current = index;
+
+        while let Some(parent) = self.nodes[current as usize].parent {
+            names.push(&*self.nodes[current as usize].name);
+            current = parent;
+        }
+
+        let mut path = PathBuf::from(&self.root);
+        path.extend(names.iter().rev());
+        path
+    }
+
+    /// Merge every file and folder smaller than `floor` into a `SMALL_ITEMS` node of its
+    /// parent, dropping what was under merged folders; totals stay as they were
+    pub fn apply_noise_floor(&mut self, floor: u64) {
+        if floor == 0 || self.nodes.len() < 2 {
+            return;
+        }
+
+        let nodes = std::mem::take(&mut self.nodes);
+        let mut merged = vec![false; nodes.len()];
+        for &bucket in &self.small_items {
+            merged[bucket as usize] = true;
+        }
+        // New index of each old node, `None` once merged or under a merged folder
+        let mut moved: Vec<Option<u32>> = vec![None; nodes.len()];
+        // New index of each kept folder's bucket
+        let mut buckets: HashMap<u32, u32> = HashMap::new();
+        let mut kept = Vec::with_capacity(nodes.len());
+
+        for (index, mut node) in nodes.into_iter().enumerate() {
+            let Some(old_parent) = node.parent else {
+                moved[index] = Some(kept.len() as u32);
+                kept.push(node);
+                continue;
+            };
+            let Some(parent) = moved[old_parent as usize] else {
+                continue;
+            };
+
+            if merged[index] || node.size < floor {
+                match buckets.get(&parent) {
+                    Some(&bucket) => {
+                        let bucket = &mut kept[bucket as usize];
+                        bucket.size = bucket.size.saturating_add(node.size);
+                        bucket.file_count += node.file_count;
+                        bucket.size_complete &= node.size_complete;
+                    }
+                    None => {
+                        buckets.insert(parent, kept.len() as u32);
+                        kept.push(TreeNode {
+                            parent: Some(parent),
+                            name: Arc::from(SMALL_ITEMS),
+                            is_dir: false,
+                            modified: None,
+                            ..node
+                        });
+                    }
+                }
+                continue;
+            }
+
+            node.parent = Some(parent);
+            moved[index] = Some(kept.len() as u32);
+            kept.push(node);
+        }
+
+        log::debug!(
+            "Noise floor of {} bytes kept {} of {} nodes",
+            floor,
+            kept.len(),
+            moved.len()
+        );
+        self.nodes = kept;
+        self.small_items = buckets.into_values().collect();
+        self.small_items.sort_unstable();
+        self.subvolumes.retain_mut(|subvolume| {
+            moved[subvolume.node as usize].is_some_and(|node| {
+                subvolume.node = node;
+                true
+            })
+        });
+    }
+
+    pub fn is_combined(&self) -> bool {
+        self.root.is_empty()
+    }
+
+    /// The node a lookup of `path` starts from, and the rest of the path below it; in a
+    /// combined tree, that is whichever of the `roots` `path` lies under
+    pub fn start_of<'a>(
+        &self,
+        path: &'a Path,
+        roots: impl IntoIterator<Item = u32>,
+    ) -> Option<(u32, &'a Path)> {
+        if !self.is_combined() {
+            return Some((0, path.strip_prefix(&self.root).ok()?));
+        }
+        if path.as_os_str().is_empty() {
+            return Some((0, path));
+        }
+
+        // Roots are full paths and never nested, so at most one matches
+        roots.into_iter().find_map(|root| {
+            let relative = path.strip_prefix(&*self.nodes[root as usize].name).ok()?;
+            Some((root, relative))
+        })
+    }
+
+    /// Index of the node at `path`, if it lies inside this tree
+    pub fn find(&self, path: &Path) -> Option<u32> {
+        let roots = (1..self.nodes.len() as u32)
+            .filter(|&index| self.is_combined() && self.nodes[index as usize].parent == Some(0));
+        let (mut current, relative) = self.start_of(path, roots)?;
+
+        for component in relative.components() {
+            let name = component.as_os_str().to_str()?;
+            // Children always come after their parent
+            current = self.nodes[current as usize + 1..]
+                .iter()
+                .position(|node| node.parent == Some(current) && &*node.name == name)
+                .map(|offset| current + 1 + offset as u32)?;
+        }
+
+        (!self.nodes.is_empty()).then_some(current)
+    }
+}
+
+/// Payload of `scan://started`, so the frontend can cancel a scan it is awaiting
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanStarted {
+    pub scan_id: u64,
+    pub path: String,
+}
+
+/// Payload of `scan://progress`: running totals plus what changed since the last event
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanProgress {
+    pub scan_id: u64,
+    pub bytes: u64,
+    pub files: u64,
+    pub dirs: u64,
+    pub bytes_delta: u64,
+    pub files_delta: u64,
+    /// `bytes` and `files` as the UI shows them, see `format`
+    pub bytes_text: String,
+    pub files_text: String,
+    /// Last directory read before this event
+    pub current_path: String,
+}
+
+#[derive(Default)]
+struct ProgressTotals {
+    bytes: u64,
+    files: u64,
+    dirs: u64,
+    bytes_delta: u64,
+    files_delta: u64,
+    last_emit: Option<Instant>,
+}
+
+/// Batches per-directory progress into at most `progress_events_per_second` events
+pub struct ProgressCoalescer<'a> {
+    scan_id: u64,
+    interval: Option<Duration>,
+    emit: &'a (dyn Fn(ScanProgress) + Sync),
+    totals: Mutex<ProgressTotals>,
+}
+
+impl<'a> ProgressCoalescer<'a> {
+    pub fn new(
+        scan_id: u64,
+        events_per_second: u32,
+        emit: &'a (dyn Fn(ScanProgress) + Sync),
+    ) -> Self {
+        Self {
+            scan_id,
+            interval: (events_per_second > 0).then(|| Duration::from_secs(1) / events_per_second),
+            emit,
+            totals: Mutex::new(ProgressTotals::default()),
+        }
+    }
+
+    pub fn record(&self, bytes: u64, files: u64, dirs: u64, path: &Path) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+
+        let progress = {
+            let mut totals = self.totals.lock().unwrap();
+            totals.bytes = totals.bytes.saturating_add(bytes);
+            totals.files += files;
+            totals.dirs += dirs;
+            totals.bytes_delta = totals.bytes_delta.saturating_add(bytes);
+            totals.files_delta += files;
+
+            let now = Instant::now();
+            if totals
+                .last_emit
+                .is_some_and(|last_emit| now.duration_since(last_emit) < interval)
+            {
+                return;
+            }
+            totals.last_emit = Some(now);
+
+            self.take(&mut totals, path)
+        };
+
+        (self.emit)(progress);
+    }
+
+    /// Emit whatever is still pending so the last event matches the final totals
+    pub fn flush(&self, path: &Path) {
+        if self.interval.is_none() {
+            return;
+        }
+
+        let progress = {
+            let mut totals = self.totals.lock().unwrap();
+            if totals.bytes_delta == 0 && totals.files_delta == 0 {
+                return;
+            }
+            self.take(&mut totals, path)
+        };
+
+        (self.emit)(progress);
+    }
+
+    fn take(&self, totals: &mut ProgressTotals, path: &Path) -> ScanProgress {
+        let progress = ScanProgress {
+            scan_id: self.scan_id,
+            bytes: totals.bytes,
+            files: totals.files,
+            dirs: totals.dirs,
+            bytes_delta: totals.bytes_delta,
+            files_delta: totals.files_delta,
+            bytes_text: format::size(totals.bytes),
+            files_text: format::count(totals.files),
+            current_path: path.to_string_lossy().to_string(),
+        };
+        totals.bytes_delta = 0;
+        totals.files_delta = 0;
+
+        progress
+    }
+}
+
+/// A running scan as seen by the registry
+pub struct ScanHandle {
+    pub id: u64,
+    pub root: String,
+    cancelled: AtomicBool,
+    /// Boosted folders, most recent last
+    boosts: Mutex<Vec<PathBuf>>,
+    /// Bumped on every boost so workers know to re-prioritize pending jobs
+    boost_generation: AtomicU64,
+    /// Nodes in the tree so far, for `memory_stats`
+    nodes: AtomicU64,
+    /// Folders being read right now and since when, by read; the scan's heartbeat
+    reads: Mutex<HashMap<u64, (PathBuf, Instant)>>,
+    next_read: AtomicU64,
+    /// Folder `stalled` last reported, so each stall is reported once
+    reported_stall: Mutex<Option<PathBuf>>,
+    /// Folders given up on with `skip`
+    skipped: Mutex<Vec<PathBuf>>,
+    /// Set with the first skip, so reads need not lock `skipped` until then
+    any_skipped: AtomicBool,
+}
+
+/// A folder read in progress, dropped when the read is over
+pub struct ReadGuard<'a> {
+    handle: &'a ScanHandle,
+    read: u64,
+}
+
+impl Drop for ReadGuard<'_> {
+    fn drop(&mut self) {
+        self.handle.reads.lock().unwrap().remove(&self.read);
+    }
+}
+
+/// Payload of `scan://stalled`: a folder read that has not returned for
+/// `Settings.performance.stall_timeout_seconds`, such as on a hung network mount
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanStalled {
+    pub scan_id: u64,
+    pub root: String,
+    pub path: String,
+    pub stalled_for_ms: u64,
+}
+
+impl ScanHandle {
+    pub fn new(id: u64, root: String) -> Self {
+        Self {
+            id,
+            root,
+            cancelled: AtomicBool::new(false),
+            boosts: Mutex::new(Vec::new()),
+            boost_generation: AtomicU64::new(0),
+            nodes: AtomicU64::new(0),
+            reads: Mutex::new(HashMap::new()),
+            next_read: AtomicU64::new(0),
+            reported_stall: Mutex::new(None),
+            skipped: Mutex::new(Vec::new()),
+            any_skipped: AtomicBool::new(false),
+        }
+    }
+
+    /// Note that `path` is being read until the guard is dropped
+    pub fn begin_read(&self, path: &Path) -> ReadGuard<'_> {
+        let read = self.next_read.fetch_add(1, Ordering::Relaxed);
+        self.reads
+            .lock()
+            .unwrap()
+            .insert(read, (path.to_path_buf(), Instant::now()));
+        ReadGuard { handle: self, read }
+    }
+
+    /// The folder whose read has been going on longest, once it passes `timeout`; a
+    /// stall already reported is not reported again
+    pub fn stalled(&self, timeout: Duration) -> Option<ScanStalled> {
+        let (path, started) = self
+            .reads
+            .lock()
+            .unwrap()
+            .values()
+            .min_by_key(|(_, started)| *started)
+            .cloned()?;
+        let stalled_for = started.elapsed();
+        if stalled_for < timeout {
+            return None;
+        }
+
+        let mut reported = self.reported_stall.lock().unwrap();
+        if reported.as_ref() == Some(&path) {
+            return None;
+        }
+        *reported = Some(path.clone());
+
+        Some(ScanStalled {
+            scan_id: self.id,
+            root: self.root.clone(),
+            path: path.to_string_lossy().into_owned(),
+            stalled_for_ms: stalled_for.as_millis() as u64,
+        })
+    }
+
+    /// Read `path`'s subtree before anything else still pending; returns false if it is outside the scan
+    pub fn boost(&self, path: &Path) -> bool {
+        if !path.starts_with(&self.root) {
+            return false;
+        }
+
+        let mut boosts = self.boosts.lock().unwrap();
+        boosts.retain(|boosted| boosted != path);
+        boosts.push(path.to_path_buf());
+
+        if boosts.len() > MAX_BOOSTS {
+            boosts.remove(0);
+        }
+
+        self.boost_generation.fetch_add(1, Ordering::Relaxed);
+
+        true
+    }
+
+    /// Later boosts rank higher; 0 means the path is under no boosted folder
+    fn priority(&self, path: &Path) -> usize {
+        self.boosts
+            .lock()
+            .unwrap()
+            .iter()
+            .rposition(|boosted| path.starts_with(boosted))
+            .map_or(0, |index| index + 1)
+    }
+
+    /// Give up on `path`'s subtree: reads under it still pending are dropped, and one
+    /// hanging on a network share or removable drive is abandoned. The scan finishes
+    /// with the folder incomplete and listed in `ScanTree.skipped`. Returns false if
+    /// `path` is outside the scan.
+    pub fn skip(&self, path: &Path) -> bool {
+        if !path.starts_with(&self.root) {
+            return false;
+        }
+
+        let mut skipped = self.skipped.lock().unwrap();
+        if !skipped.iter().any(|folder| folder == path) {
+            skipped.push(path.to_path_buf());
+        }
+        self.any_skipped.store(true, Ordering::Relaxed);
+
+        true
+    }
+
+    pub fn is_skipped(&self, path: &Path) -> bool {
+        self.any_skipped.load(Ordering::Relaxed)
+            && self
+                .skipped
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|folder| path.starts_with(folder))
+    }
+
+    /// Nodes the scan has added to its tree so far
+    pub fn nodes(&self) -> u64 {
+        self.nodes.load(Ordering::Relaxed)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+struct DirJob {
+    node: u32,
+    path: PathBuf,
+    depth: u32,
+    /// Filesystem device, when the folder was looked up
+    device: Option<u64>,
+}
+
+/// A pending job ordered by boost priority, then first queued first
+struct QueuedJob {
+    priority: usize,
+    sequence: u64,
+    job: DirJob,
+}
+
+impl PartialEq for QueuedJob {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for QueuedJob {}
+
+impl PartialOrd for QueuedJob {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedJob {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+#[derive(Default)]
+struct WorkQueue {
+    pending: BinaryHeap<QueuedJob>,
+    next_sequence: u64,
+    /// Boost generation the pending priorities were computed for
+    generation: u64,
+    /// Jobs taken by a worker but not finished yet
+    active: usize,
+}
+
+impl WorkQueue {
+    fn push(&mut self, handle: &ScanHandle, job: DirJob) {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.pending.push(QueuedJob {
+            priority: handle.priority(&job.path),
+            sequence,
+            job,
+        });
+    }
+
+    /// Recompute priorities after the frontend boosted a folder
+    fn reprioritize(&mut self, handle: &ScanHandle) {
+        let generation = handle.boost_generation.load(Ordering::Relaxed);
+
+        if generation == self.generation {
+            return;
+        }
+
+        self.generation = generation;
+        self.pending = std::mem::take(&mut self.pending)
+            .into_iter()
+            .map(|mut queued| {
+                queued.priority = handle.priority(&queued.job.path);
+                queued
+            })
+            .collect();
+    }
+}
+
+/// Shares one allocation between repeated names like `node_modules` or `index.js`
+#[derive(Default)]
+pub struct NameInterner {
+    names: HashSet<Arc<str>>,
+}
+
+impl NameInterner {
+    pub fn intern(&mut self, name: &str) -> Arc<str> {
+        if let Some(interned) = self.names.get(name) {
+            return interned.clone();
+        }
+
+        let interned: Arc<str> = Arc::from(name);
+        self.names.insert(interned.clone());
+        interned
+    }
+}
+
+/// Nodes and their names, locked together since nodes are only created while interning
+#[derive(Default)]
+struct TreeArena {
+    nodes: Vec<TreeNode>,
+    names: NameInterner,
+    boundaries: Vec<(u32, Boundary)>,
+}
+
+struct ScanState<'a> {
+    handle: &'a ScanHandle,
+    options: &'a ScanOptions,
+    enumerator: &'a dyn DirEnumerator,
+    /// Reads go through a reader thread that can be abandoned, see `scanner::may_hang`
+    hand_off: bool,
+    /// Other filesystems inside a WSL distro, listed but not read, see `wsl::foreign_mounts`
+    foreign_mounts: Vec<PathBuf>,
+    root_path: PathBuf,
+    root_device: Option<u64>,
+    /// On btrfs and ZFS every folder is looked up for subvolume boundaries
+    cow: bool,
+    arena: Mutex<TreeArena>,
+    queue: Mutex<WorkQueue>,
+    ready: Condvar,
+    followed_links: Mutex<HashSet<PathBuf>>,
+    progress: ProgressCoalescer<'a>,
+    allocation: Mutex<allocation::Totals>,
+    ignored: Mutex<Vec<String>>,
+    stats: StatCounters,
+}
+
+struct Child {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    file_count: u64,
+    size_complete: bool,
+    modified: Option<SystemTime>,
+    descend: Option<PathBuf>,
+    device: Option<u64>,
+    boundary: Option<Boundary>,
+}
+
+pub fn millis_since_epoch(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|elapsed| elapsed.as_millis() as u64)
+}
+
+/// The current time in milliseconds since the Unix epoch; 0 for a clock set before it
+pub fn now_millis() -> u64 {
+    millis_since_epoch(SystemTime::now()).unwrap_or(0)
+}
+
+#[cfg(unix)]
+fn device_id(metadata: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device_id(_metadata: &fs::Metadata) -> Option<u64> {
+    None
+}
+
+/// Walk `root` with `threads` workers sharing one directory queue, reporting through `on_progress`
+pub fn scan_tree(
+    handle: &ScanHandle,
+    options: &ScanOptions,
+    threads: usize,
+    enumerator: &dyn DirEnumerator,
+    on_progress: &(dyn Fn(ScanProgress) + Sync),
+) -> Result<ScanTree, DuneError> {
+    let root_path = PathBuf::from(&handle.root);
+    let metadata = {
+        let _read = handle.begin_read(&root_path);
+        fs::metadata(&root_path).map_err(|e| DuneError::io(&root_path, e))?
+    };
+
+    if !metadata.is_dir() {
+        return Err(DuneError::NotADirectory {
+            path: handle.root.clone(),
+        });
+    }
+
+    let started = Instant::now();
+    let root = TreeNode {
+        parent: None,
+        name: root_path
+            .file_name()
+            .map(|name| Arc::from(name.to_string_lossy()))
+            .unwrap_or_else(|| Arc::from(handle.root.as_str())),
+        is_dir: true,
+        size: 0,
+        file_count: 0,
+        size_complete: true,
+        modified: None,
+    };
+
+    let state = ScanState {
+        handle,
+        options,
+        enumerator,
+        hand_off: scanner::may_hang(&root_path),
+        foreign_mounts: wsl::foreign_mounts(&root_path),
+        root_path: root_path.clone(),
+        root_device: device_id(&metadata),
+        cow: subvolumes::is_cow_filesystem(&root_path),
+        arena: Mutex::new(TreeArena {
+            nodes: vec![root],
+            names: NameInterner::default(),
+            boundaries: Vec::new(),
+        }),
+        queue: Mutex::new(WorkQueue::default()),
+        ready: Condvar::new(),
+        followed_links: Mutex::new(HashSet::new()),
+        progress: ProgressCoalescer::new(
+            handle.id,
+            options.progress_events_per_second,
+            on_progress,
+        ),
+        allocation: Mutex::default(),
+        ignored: Mutex::default(),
+        stats: StatCounters::default(),
+    };
+
+    state.queue.lock().unwrap().push(
+        handle,
+        DirJob {
+            node: 0,
+            path: root_path,
+            depth: 0,
+            device: device_id(&metadata),
+        },
+    );
+
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| run_worker(&state));
+        }
+    });
+
+    if handle.is_cancelled() {
+        log::info!("Scan {} of {} cancelled", handle.id, handle.root);
+        return Err(DuneError::Cancelled);
+    }
+
+    state.progress.flush(&state.root_path);
+
+    let TreeArena {
+        mut nodes,
+        boundaries,
+        ..
+    } = state.arena.into_inner().unwrap();
+    aggregate(&mut nodes);
+
+    let elapsed = started.elapsed();
+    log::info!(
+        "Scanned {}: {} nodes, {} bytes in {:?}",
+        handle.root,
+        nodes.len(),
+        nodes[0].size,
+        elapsed
+    );
+
+    let dirs = state.stats.dirs.into_inner();
+    let stats = ScanStats {
+        duration_ms: elapsed.as_millis() as u64,
+        dirs,
+        files: state.stats.files.into_inner(),
+        bytes_per_second: (nodes[0].size as f64 / elapsed.as_secs_f64().max(0.001)) as u64,
+        cache_hit_rate: if dirs == 0 {
+            0.0
+        } else {
+            state.stats.cached_reads.into_inner() as f64 / dirs as f64
+        },
+        errors: state.stats.errors.into_inner().unwrap(),
+        threads: threads.max(1),
+        backend: enumerator.name().to_string(),
+    };
+
+    Ok(ScanTree {
+        scan_id: handle.id,
+        root: handle.root.clone(),
+        nodes,
+        allocation: options
+            .allocation
+            .then(|| state.allocation.into_inner().unwrap().summary()),
+        subvolumes: subvolumes::describe(boundaries),
+        ignored: state.ignored.into_inner().unwrap(),
+        small_items: Vec::new(),
+        skipped: handle
+            .skipped
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|folder| folder.to_string_lossy().into_owned())
+            .collect(),
+        stats: Some(stats),
+        elevated: false,
+    })
+}
+
+fn run_worker(state: &ScanState) {
+    while let Some(job) = next_job(state) {
+        let (children, complete) = read_children(state, &job);
+        let mut jobs = Vec::new();
+        let (mut bytes, mut files) = (0, 0);
+
+        {
+            let mut arena = state.arena.lock().unwrap();
+            let TreeArena {
+                nodes,
+                names,
+                boundaries,
+            } = &mut *arena;
+
+            if !complete {
+                nodes[job.node as usize].size_complete = false;
+            }
+
+            for child in children {
+                // Only files and folders sized for a marker carry totals of their own
+                bytes += child.size;
+                files += child.file_count;
+
+                let index = nodes.len() as u32;
+                nodes.push(TreeNode {
+                    parent: Some(job.node),
+                    name: names.intern(&child.name),
+                    is_dir: child.is_dir,
+                    size: child.size,
+                    file_count: child.file_count,
+                    size_complete: child.size_complete,
+                    modified: child.modified.and_then(millis_since_epoch),
+                });
+
+                if let Some(boundary) = child.boundary {
+                    boundaries.push((index, boundary));
+                }
+                if let Some(path) = child.descend {
+                    jobs.push(DirJob {
+                        node: index,
+                        path,
+                        depth: job.depth + 1,
+                        device: child.device,
+                    });
+                }
+            }
+            state
+                .handle
+                .nodes
+                .store(nodes.len() as u64, Ordering::Relaxed);
+        }
+
+        state.progress.record(bytes, files, 1, &job.path);
+
+        let mut queue = state.queue.lock().unwrap();
+        for job in jobs {
+            queue.push(state.handle, job);
+        }
+        queue.active -= 1;
+        state.ready.notify_all();
+    }
+}
+
+/// Block until there is a directory to read, or return `None` once the scan is done
+fn next_job(state: &ScanState) -> Option<DirJob> {
+    let mut queue = state.queue.lock().unwrap();
+
+    loop {
+        if state.handle.is_cancelled() {
+            state.ready.notify_all();
+            return None;
+        }
+
+        queue.reprioritize(state.handle);
+
+        if let Some(queued) = queue.pending.pop() {
+            queue.active += 1;
+            return Some(queued.job);
+        }
+
+        if queue.active == 0 {
+            state.ready.notify_all();
+            return None;
+        }
+
+        // Time out regularly so idle workers notice cancellation
+        queue = state.ready.wait_timeout(queue, IDLE_WORKER_POLL).unwrap().0;
+    }
+}
+
+fn read_children(state: &ScanState, job: &DirJob) -> (Vec<Child>, bool) {
+    // Held over the stats of the entries too, which hang on a dead mount just the same
+    if state.handle.is_skipped(&job.path) {
+        state.stats.error("skipped");
+        return (Vec::new(), false);
+    }
+
+    let _read = state.handle.begin_read(&job.path);
+    let listing = if state.hand_off {
+        state.enumerator.read_dir_or_give_up(&job.path, &|| {
+            state.handle.is_cancelled() || state.handle.is_skipped(&job.path)
+        })
+    } else {
+        state.enumerator.read_dir(&job.path)
+    };
+    let listing = match listing {
+        Ok(listing) => listing,
+        Err(err) => {
+            log::debug!("Could not read {}: {}", job.path.display(), err);
+            state.stats.io_error(&err);
+            return (Vec::new(), false);
+        }
+    };
+
+    state.stats.dirs.fetch_add(1, Ordering::Relaxed);
+    if listing.elapsed < CACHED_READ {
+        state.stats.cached_reads.fetch_add(1, Ordering::Relaxed);
+    }
+    if !listing.complete {
+        state.stats.error("unreadable_entries");
+    }
+
+    let mut children = Vec::new();
+    let mut complete = listing.complete;
+    let mut allocated = allocation::Totals::default();
+    let can_descend = state
+        .options
+        .max_depth
+        .map_or(true, |max_depth| job.depth < max_depth);
+
+    for entry in listing.entries {
+        if state.handle.is_skipped(&job.path) {
+            state.stats.error("skipped");
+            return (Vec::new(), false);
+        }
+        if !state.options.include_hidden && scanner::is_hidden_name(&entry.name) {
+            continue;
+        }
+
+        let entry_path = job.path.join(&entry.name);
+        let is_symlink = entry.kind == EntryKind::Symlink;
+
+        // Only links, the device check and subvolume boundaries need a stat; the
+        // listing already has the rest
+        let mut boundary = None;
+        let (is_dir, len, device, modified) = match entry.kind {
+            EntryKind::File => (false, entry.len, None, entry.modified),
+            EntryKind::Dir if state.options.same_filesystem || state.cow => {
+                match fs::symlink_metadata(&entry_path) {
+                    Ok(metadata) => {
+                        if state.cow {
+                            boundary = subvolumes::boundary(
+                                &entry_path,
+                                &entry.name,
+                                &metadata,
+                                job.device,
+                            );
+                        }
+                        (true, 0, device_id(&metadata), None)
+                    }
+                    Err(err) => {
+                        state.stats.io_error(&err);
+                        complete = false;
+                        continue;
+                    }
+                }
+            }
+            EntryKind::Dir => (true, 0, None, None),
+            EntryKind::Symlink if state.options.follow_symlinks => {
+                match fs::metadata(&entry_path) {
+                    Ok(metadata) if metadata.is_dir() => (true, 0, device_id(&metadata), None),
+                    Ok(metadata) if metadata.is_file() => {
+                        (false, metadata.len(), None, metadata.modified().ok())
+                    }
+                    Ok(_) => continue,
+                    Err(err) => {
+                        state.stats.io_error(&err);
+                        complete = false;
+                        continue;
+                    }
+                }
+            }
+            EntryKind::Symlink | EntryKind::Other => continue,
+        };
+
+        if state.options.allocation && !is_dir {
+            if let Some(file) = allocation::of(&entry_path) {
+                allocated.add(&file);
+            }
+        }
+
+        // Snapshots hold the same data as what they were taken of, already counted there
+        let skipped_snapshot =
+            !state.options.snapshots && boundary.as_ref().is_some_and(|boundary| boundary.snapshot);
+        let descend = is_dir
+            && can_descend
+            && !skipped_snapshot
+            && !state.foreign_mounts.contains(&entry_path)
+            && (!is_symlink || should_follow(state, &entry_path))
+            && !(state.options.same_filesystem
+                && state.root_device.is_some()
+                && device != state.root_device);
+
+        let marked = descend
+            && state.options.ignore_markers != IgnoreMarkers::Off
+            && has_marker(&entry_path, state.options.cachedir_tags);
+        if marked {
+            state
+                .ignored
+                .lock()
+                .unwrap()
+                .push(entry_path.to_string_lossy().into_owned());
+            if state.options.ignore_markers == IgnoreMarkers::Exclude {
+                continue;
+            }
+
+            let totals = folder_totals(state, &entry_path, job.depth + 1, device);
+            children.push(Child {
+                name: entry.name.to_string_lossy().to_string(),
+                is_dir,
+                size: totals.size,
+                file_count: totals.files,
+                size_complete: totals.complete,
+                modified,
+                descend: None,
+                device,
+                boundary,
+            });
+            continue;
+        }
+
+        children.push(Child {
+            name: entry.name.to_string_lossy().to_string(),
+            is_dir,
+            size: len,
+            file_count: u64::from(!is_dir),
+            // Folders we do not descend into are lower bounds by definition
+            size_complete: !is_dir || descend || skipped_snapshot,
+            modified,
+            descend: descend.then_some(entry_path),
+            device,
+            boundary,
+        });
+    }
+
+    if state.options.allocation {
+        state.allocation.lock().unwrap().merge(allocated);
+    }
+    state.stats.files.fetch_add(
+        children.iter().filter(|child| !child.is_dir).count() as u64,
+        Ordering::Relaxed,
+    );
+
+    (children, complete)
+}
+
+/// `.dunefilesignore` of any content, or a `CACHEDIR.TAG` starting with the signature
+fn has_marker(folder: &Path, cachedir_tags: bool) -> bool {
+    use std::io::Read;
+
+    if fs::symlink_metadata(folder.join(IGNORE_MARKER)).is_ok() {
+        return true;
+    }
+
+    let mut signature = [0u8; CACHEDIR_SIGNATURE.len()];
+    cachedir_tags
+        && fs::File::open(folder.join(CACHEDIR_TAG))
+            .and_then(|mut tag| tag.read_exact(&mut signature))
+            .is_ok()
+        && signature == CACHEDIR_SIGNATURE
+}
+
+#[derive(Default)]
+struct FolderTotals {
+    size: u64,
+    files: u64,
+    complete: bool,
+}
+
+/// Everything under a marked folder, read like the rest of the scan so pacing, skips
+/// and stall reports apply, but summed instead of added to the tree
+fn folder_totals(
+    state: &ScanState,
+    folder: &Path,
+    depth: u32,
+    device: Option<u64>,
+) -> FolderTotals {
+    let mut totals = FolderTotals {
+        complete: true,
+        ..FolderTotals::default()
+    };
+    // The jobs never reach the tree, so they have no node of their own
+    let mut pending = vec![DirJob {
+        node: 0,
+        path: folder.to_path_buf(),
+        depth,
+        device,
+    }];
+
+    while let Some(job) = pending.pop() {
+        if state.handle.is_cancelled() {
+            totals.complete = false;
+            break;
+        }
+
+        let (children, complete) = read_children(state, &job);
+        totals.complete &= complete;
+        for child in children {
+            totals.size = totals.size.saturating_add(child.size);
+            totals.files += child.file_count;
+            totals.complete &= child.size_complete;
+            if let Some(path) = child.descend {
+                pending.push(DirJob {
+                    node: 0,
+                    path,
+                    depth: job.depth + 1,
+                    device: child.device,
+                });
+            }
+        }
+    }
+
+    totals
+}
+
+/// Follow a directory link once, and never into the scanned tree itself
+fn should_follow(state: &ScanState, link: &Path) -> bool {
+    let Ok(target) = fs::canonicalize(link) else {
+        return false;
+    };
+
+    if let Ok(root) = fs::canonicalize(&state.root_path) {
+        if target.starts_with(&root) || root.starts_with(&target) {
+            return false;
+        }
+    }
+
+    state.followed_links.lock().unwrap().insert(target)
+}
+
+/// Roll child totals up into their parents; relies on parents preceding children
+pub fn aggregate(nodes: &mut [TreeNode]) {
+    for index in (1..nodes.len()).rev() {
+        let Some(parent) = nodes[index].parent else {
+            continue;
+        };
+        let (size, file_count, complete) = {
+            let node = &nodes[index];
+            (node.size, node.file_count, node.size_complete)
+        };
+        let parent = &mut nodes[parent as usize];
+
+        parent.size = parent.size.saturating_add(size);
+        parent.file_count += file_count;
+        parent.size_complete &= complete;
+    }
+}
@@ -1,11 +1,24 @@
+use crate::allocation::{self, StorageFlags};
+use crate::apfs::{self, ApfsContainer};
+use crate::error::DuneError;
+use crate::wsl::VirtualDisk;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::ffi::{OsStr, OsString};
 use std::fs;
-use std::path::Path;
-use std::time::{Duration, Instant};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 use sysinfo::Disks;
 
 const MAX_DIRECTORY_LIST_SCAN_DURATION: Duration = Duration::from_millis(1_500);
 const MAX_FOLDER_SCAN_DURATION: Duration = Duration::from_millis(250);
+const MAX_BACKGROUND_SCAN_DURATION: Duration = Duration::from_secs(10 * 60);
+/// How often a read handed to a reader thread checks whether to give up on it
+const GIVE_UP_POLL: Duration = Duration::from_millis(100);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiskInfo {
@@ -13,21 +26,609 @@ pub struct DiskInfo {
     pub path: String,
     pub total_space: u64,
     pub available_space: u64,
+    /// Space the OS frees on demand, such as APFS snapshots and caches; already counted as used
+    pub purgeable_space: u64,
+    /// Set when snapshot history says the disk fills within `forecast::WARNING_DAYS`
+    pub full_in_days: Option<f64>,
+    /// File slots on the volume; a disk can be full with bytes to spare once these run out.
+    /// `None` where the filesystem has no fixed limit or the platform does not report one.
+    pub total_inodes: Option<u64>,
+    pub free_inodes: Option<u64>,
+    /// A phone or camera storage reached over MTP; scans of it run on one thread
+    pub mtp: bool,
+    /// tmpfs or a RAM disk: its space is memory, so the overview leaves it out unless
+    /// `include_ram_disks` is set
+    #[serde(default)]
+    pub ram_disk: bool,
+    /// The name the volume reports, when `name` is one the user chose
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_name: Option<String>,
+    /// Folder to open instead of the mount point, from the user's settings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_folder: Option<String>,
+    /// The APFS container the volume shares its space with, on macOS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<ApfsContainer>,
+    /// A WSL distro, whose files live in a virtual disk on another volume
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub virtual_disk: Option<VirtualDisk>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileEntry {
+    /// Composed and with control characters made visible, see `names::display`
     pub name: String,
+    /// The name as stored, when it differs from `name` or is not valid UTF-8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_name_bytes: Option<Vec<u8>>,
     pub path: String,
     pub is_dir: bool,
     pub size: u64,
     pub size_complete: bool,
+    /// Finder tags, macOS only
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Tags from the app's own store, see `tag_paths`
+    #[serde(default)]
+    pub custom_tags: Vec<String>,
+    /// See `set_note`
+    #[serde(default)]
+    pub note: Option<String>,
+    /// Clones, sparse and compressed files, where `size` overstates the space taken
+    #[serde(default, skip_serializing_if = "StorageFlags::is_plain")]
+    pub storage: StorageFlags,
+    /// Milliseconds since the Unix epoch, only when asked for, see `recency::last_opened`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_opened: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    Other,
+}
+
+/// A directory entry with just the metadata the scanner needs
+#[derive(Debug, Clone)]
+pub struct DirEntryInfo {
+    pub name: OsString,
+    pub kind: EntryKind,
+    /// Logical length, only meaningful for files
+    pub len: u64,
+    /// Last write time, only filled in for files
+    pub modified: Option<SystemTime>,
+}
+
+pub struct DirListing {
+    pub entries: Vec<DirEntryInfo>,
+    /// False when some entries could not be read
+    pub complete: bool,
+    /// Filesystem calls made: open and close, each batch read the backend issues, per-entry stats
+    pub syscalls: u64,
+    /// Time the backend spent reading, without any throttling around it
+    pub elapsed: Duration,
+}
+
+/// A way of reading a directory and its entries' sizes
+///
+/// Platform backends fetch names, types, and sizes in batches instead of one
+/// `stat` per entry.
+pub trait DirEnumerator: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn read_dir(&self, path: &Path) -> io::Result<DirListing>;
+
+    /// `read_dir`, but fails with `Interrupted` as soon as `give_up` returns true instead
+    /// of waiting on a read that may never return. Backends that cannot give up just read.
+    fn read_dir_or_give_up(
+        &self,
+        path: &Path,
+        _give_up: &dyn Fn() -> bool,
+    ) -> io::Result<DirListing> {
+        self.read_dir(path)
+    }
+}
+
+type ReadRequest = (
+    &'static dyn DirEnumerator,
+    PathBuf,
+    mpsc::Sender<io::Result<DirListing>>,
+);
+
+thread_local! {
+    /// The calling thread's reader, kept across reads and replaced once one is abandoned
+    static READER: RefCell<Option<mpsc::Sender<ReadRequest>>> = const { RefCell::new(None) };
+}
+
+fn spawn_reader() -> mpsc::Sender<ReadRequest> {
+    let (sender, requests) = mpsc::channel::<ReadRequest>();
+    let spawned = thread::Builder::new()
+        .name("dir-reader".to_string())
+        .spawn(move || {
+            for (enumerator, path, reply) in requests {
+                let _ = reply.send(enumerator.read_dir(&path));
+            }
+        });
+    if let Err(err) = spawned {
+        log::warn!("Could not start a reader thread: {}", err);
+    }
+
+    sender
+}
+
+/// Read `path` on a reader thread and wait for it, polling `give_up`. A read given up
+/// on is left to finish or hang on its own; the caller does not block on it again.
+fn read_on_reader(
+    enumerator: &'static dyn DirEnumerator,
+    path: &Path,
+    give_up: &dyn Fn() -> bool,
+) -> io::Result<DirListing> {
+    let (reply, result) = mpsc::channel();
+    let sent = READER.with(|reader| {
+        reader.borrow_mut().get_or_insert_with(spawn_reader).send((
+            enumerator,
+            path.to_path_buf(),
+            reply,
+        ))
+    });
+    if sent.is_err() {
+        READER.with(|reader| reader.borrow_mut().take());
+        return enumerator.read_dir(path);
+    }
+
+    loop {
+        match result.recv_timeout(GIVE_UP_POLL) {
+            Ok(listing) => return listing,
+            Err(RecvTimeoutError::Timeout) if give_up() => {
+                READER.with(|reader| reader.borrow_mut().take());
+                return Err(io::Error::new(
+                    io::ErrorKind::Interrupted,
+                    "gave up waiting for the read",
+                ));
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                READER.with(|reader| reader.borrow_mut().take());
+                return Err(io::Error::other("the reader thread stopped"));
+            }
+        }
+    }
+}
+
+/// Portable backend on top of `std::fs::read_dir`
+pub struct StdEnumerator;
+
+impl DirEnumerator for StdEnumerator {
+    fn name(&self) -> &'static str {
+        "std"
+    }
+
+    fn read_dir_or_give_up(
+        &self,
+        path: &Path,
+        give_up: &dyn Fn() -> bool,
+    ) -> io::Result<DirListing> {
+        read_on_reader(&StdEnumerator, path, give_up)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<DirListing> {
+        let started = Instant::now();
+        let mut entries = Vec::new();
+        let mut complete = true;
+        // std hides its getdents batches, so only open, close, and stats are counted
+        let mut syscalls = 2;
+
+        for entry in fs::read_dir(path)? {
+            let Ok(entry) = entry else {
+                complete = false;
+                continue;
+            };
+            let Ok(file_type) = entry.file_type() else {
+                complete = false;
+                continue;
+            };
+
+            let kind = if file_type.is_symlink() {
+                EntryKind::Symlink
+            } else if file_type.is_dir() {
+                EntryKind::Dir
+            } else if file_type.is_file() {
+                EntryKind::File
+            } else {
+                EntryKind::Other
+            };
+
+            let (len, modified) = if kind == EntryKind::File {
+                syscalls += 1;
+                match entry.metadata() {
+                    Ok(metadata) => (metadata.len(), metadata.modified().ok()),
+                    Err(_) => {
+                        complete = false;
+                        continue;
+                    }
+                }
+            } else {
+                (0, None)
+            };
+
+            entries.push(DirEntryInfo {
+                name: entry.file_name(),
+                kind,
+                len,
+                modified,
+            });
+        }
+
+        Ok(DirListing {
+            entries,
+            complete,
+            syscalls,
+            elapsed: started.elapsed(),
+        })
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub use bulk_attr::BulkAttrEnumerator;
+
+/// `getattrlistbulk` backend: one syscall returns names, types, and sizes for many entries
+#[cfg(target_os = "macos")]
+mod bulk_attr {
+    use super::{DirEntryInfo, DirEnumerator, DirListing, EntryKind, StdEnumerator};
+    use std::ffi::{CString, OsString};
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::path::Path;
+    use std::time::{Duration, Instant, SystemTime};
+
+    const BUFFER_SIZE: usize = 256 * 1024;
+
+    // `enum vtype` from <sys/vnode.h>
+    const VREG: u32 = 1;
+    const VDIR: u32 = 2;
+    const VLNK: u32 = 5;
+
+    pub struct BulkAttrEnumerator;
+
+    fn read_u32(buffer: &[u8], offset: usize) -> Option<u32> {
+        buffer
+            .get(offset..offset + 4)
+            .map(|bytes| u32::from_ne_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn read_u64(buffer: &[u8], offset: usize) -> Option<u64> {
+        buffer
+            .get(offset..offset + 8)
+            .map(|bytes| u64::from_ne_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// Parse one packed entry: length, returned attrs, name ref, object type,
+    /// modification `timespec`, data length
+    fn parse_entry(entry: &[u8]) -> Option<DirEntryInfo> {
+        let name_ref = 4 + std::mem::size_of::<libc::attribute_set_t>();
+        let name_offset = read_u32(entry, name_ref)? as i32;
+        let name_length = read_u32(entry, name_ref + 4)? as usize;
+        let object_type = read_u32(entry, name_ref + 8)?;
+        let modified_secs = read_u64(entry, name_ref + 12)? as i64;
+        let modified_nanos = read_u64(entry, name_ref + 20)? as u32;
+        let data_length = read_u64(entry, name_ref + 28)?;
+
+        let name_start = (name_ref as isize + name_offset as isize) as usize;
+        // The length includes the trailing NUL
+        let name = entry.get(name_start..name_start + name_length.saturating_sub(1))?;
+
+        let kind = match object_type {
+            VREG => EntryKind::File,
+            VDIR => EntryKind::Dir,
+            VLNK => EntryKind::Symlink,
+            _ => EntryKind::Other,
+        };
+
+        Some(DirEntryInfo {
+            name: OsString::from_vec(name.to_vec()),
+            kind,
+            len: if kind == EntryKind::File {
+                data_length
+            } else {
+                0
+            },
+            modified: (kind == EntryKind::File && modified_secs >= 0).then(|| {
+                SystemTime::UNIX_EPOCH + Duration::new(modified_secs as u64, modified_nanos)
+            }),
+        })
+    }
+
+    impl DirEnumerator for BulkAttrEnumerator {
+        fn name(&self) -> &'static str {
+            "getattrlistbulk"
+        }
+
+        fn read_dir_or_give_up(
+            &self,
+            path: &Path,
+            give_up: &dyn Fn() -> bool,
+        ) -> io::Result<DirListing> {
+            super::read_on_reader(&BulkAttrEnumerator, path, give_up)
+        }
+
+        fn read_dir(&self, path: &Path) -> io::Result<DirListing> {
+            let started = Instant::now();
+            let c_path = CString::new(path.as_os_str().as_bytes())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            let fd = unsafe {
+                libc::open(
+                    c_path.as_ptr(),
+                    libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC,
+                )
+            };
+
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+            let mut attributes = libc::attrlist {
+                bitmapcount: libc::ATTR_BIT_MAP_COUNT,
+                reserved: 0,
+                commonattr: libc::ATTR_CMN_RETURNED_ATTRS
+                    | libc::ATTR_CMN_NAME
+                    | libc::ATTR_CMN_OBJTYPE
+                    | libc::ATTR_CMN_MODTIME,
+                volattr: 0,
+                dirattr: 0,
+                fileattr: libc::ATTR_FILE_DATALENGTH,
+                forkattr: 0,
+            };
+            let mut buffer = vec![0u8; BUFFER_SIZE];
+            let mut entries = Vec::new();
+            let mut complete = true;
+            let mut syscalls = 2;
+
+            loop {
+                syscalls += 1;
+
+                // FSOPT_PACK_INVAL_ATTRS keeps every entry's layout identical, so
+                // directories carry a zeroed data length instead of omitting it
+                let count = unsafe {
+                    libc::getattrlistbulk(
+                        fd.as_raw_fd(),
+                        &mut attributes as *mut libc::attrlist as *mut libc::c_void,
+                        buffer.as_mut_ptr() as *mut libc::c_void,
+                        buffer.len(),
+                        u64::from(libc::FSOPT_PACK_INVAL_ATTRS),
+                    )
+                };
+
+                if count < 0 {
+                    let err = io::Error::last_os_error();
+
+                    // Some network and FUSE filesystems do not implement bulk attributes
+                    if entries.is_empty()
+                        && matches!(err.raw_os_error(), Some(libc::ENOTSUP | libc::EINVAL))
+                    {
+                        return StdEnumerator.read_dir(path);
+                    }
+
+                    return Err(err);
+                }
+
+                if count == 0 {
+                    break;
+                }
+
+                let mut offset = 0;
+
+                for _ in 0..count {
+                    let Some(length) = read_u32(&buffer, offset).map(|length| length as usize)
+                    else {
+                        complete = false;
+                        break;
+                    };
+
+                    match buffer.get(offset..offset + length).and_then(parse_entry) {
+                        Some(entry) => entries.push(entry),
+                        None => complete = false,
+                    }
+
+                    offset += length;
+                }
+            }
+
+            Ok(DirListing {
+                entries,
+                complete,
+                syscalls,
+                elapsed: started.elapsed(),
+            })
+        }
+    }
+}
+
+#[cfg(windows)]
+pub use find_file::FindFileEnumerator;
+
+/// `FindFirstFileExW` backend with `FIND_FIRST_EX_LARGE_FETCH`; sizes come with the listing
+#[cfg(windows)]
+mod find_file {
+    use super::{DirEntryInfo, DirEnumerator, DirListing, EntryKind, StdEnumerator};
+    use std::ffi::OsString;
+    use std::io;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use std::path::Path;
+    use std::time::{Duration, Instant, SystemTime};
+    use windows_sys::Win32::Foundation::{
+        ERROR_INVALID_PARAMETER, ERROR_NO_MORE_FILES, INVALID_HANDLE_VALUE,
+    };
+    use windows_sys::Win32::Storage::FileSystem::{
+        FindClose, FindExInfoBasic, FindExSearchNameMatch, FindFirstFileExW, FindNextFileW,
+        FILE_ATTRIBUTE_DEVICE, FILE_ATTRIBUTE_DIRECTORY, FILE_ATTRIBUTE_REPARSE_POINT,
+        FIND_FIRST_EX_LARGE_FETCH, WIN32_FIND_DATAW,
+    };
+    use windows_sys::Win32::System::SystemServices::{
+        IO_REPARSE_TAG_MOUNT_POINT, IO_REPARSE_TAG_SYMLINK,
+    };
+
+    pub struct FindFileEnumerator;
+
+    /// 100ns intervals between 1601-01-01 (FILETIME epoch) and 1970-01-01
+    const FILETIME_UNIX_OFFSET: u64 = 116_444_736_000_000_000;
+
+    fn to_entry(data: &WIN32_FIND_DATAW) -> Option<DirEntryInfo> {
+        let name_length = data
+            .cFileName
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(data.cFileName.len());
+        let name = &data.cFileName[..name_length];
+
+        if name == [b'.' as u16] || name == [b'.' as u16, b'.' as u16] {
+            return None;
+        }
+
+        // Only symlinks and junctions are links; OneDrive placeholders, deduplicated files
+        // and other reparse points are ordinary files and folders. The tag is in
+        // `dwReserved0` for reparse points.
+        let is_link = data.dwFileAttributes & FILE_ATTRIBUTE_REPARSE_POINT != 0
+            && matches!(
+                data.dwReserved0,
+                IO_REPARSE_TAG_SYMLINK | IO_REPARSE_TAG_MOUNT_POINT
+            );
+        let kind = if is_link {
+            EntryKind::Symlink
+        } else if data.dwFileAttributes & FILE_ATTRIBUTE_DIRECTORY != 0 {
+            EntryKind::Dir
+        } else if data.dwFileAttributes & FILE_ATTRIBUTE_DEVICE != 0 {
+            EntryKind::Other
+        } else {
+            EntryKind::File
+        };
+
+        let len = (u64::from(data.nFileSizeHigh) << 32) | u64::from(data.nFileSizeLow);
+        let write_time = (u64::from(data.ftLastWriteTime.dwHighDateTime) << 32)
+            | u64::from(data.ftLastWriteTime.dwLowDateTime);
+        let modified = write_time
+            .checked_sub(FILETIME_UNIX_OFFSET)
+            .map(|ticks| SystemTime::UNIX_EPOCH + Duration::from_nanos(ticks.saturating_mul(100)));
+
+        Some(DirEntryInfo {
+            name: OsString::from_wide(name),
+            kind,
+            len: if kind == EntryKind::File { len } else { 0 },
+            modified: if kind == EntryKind::File {
+                modified
+            } else {
+                None
+            },
+        })
+    }
+
+    impl DirEnumerator for FindFileEnumerator {
+        fn name(&self) -> &'static str {
+            "FindFirstFileExW"
+        }
+
+        fn read_dir_or_give_up(
+            &self,
+            path: &Path,
+            give_up: &dyn Fn() -> bool,
+        ) -> io::Result<DirListing> {
+            super::read_on_reader(&FindFileEnumerator, path, give_up)
+        }
+
+        fn read_dir(&self, path: &Path) -> io::Result<DirListing> {
+            let started = Instant::now();
+            let pattern: Vec<u16> = path
+                .join("*")
+                .as_os_str()
+                .encode_wide()
+                .chain(std::iter::once(0))
+                .collect();
+            let mut data: WIN32_FIND_DATAW = unsafe { std::mem::zeroed() };
+
+            let handle = unsafe {
+                FindFirstFileExW(
+                    pattern.as_ptr(),
+                    FindExInfoBasic,
+                    &mut data as *mut WIN32_FIND_DATAW as *mut core::ffi::c_void,
+                    FindExSearchNameMatch,
+                    std::ptr::null(),
+                    FIND_FIRST_EX_LARGE_FETCH,
+                )
+            };
+
+            if handle == INVALID_HANDLE_VALUE {
+                let err = io::Error::last_os_error();
+
+                // Some redirectors reject the basic info level or large fetch
+                if err.raw_os_error() == Some(ERROR_INVALID_PARAMETER as i32) {
+                    return StdEnumerator.read_dir(path);
+                }
+
+                return Err(err);
+            }
+
+            let mut entries = Vec::new();
+            let mut result = Ok(());
+            // FindFirstFileExW and FindClose
+            let mut syscalls = 2;
+
+            loop {
+                entries.extend(to_entry(&data));
+                syscalls += 1;
+
+                if unsafe { FindNextFileW(handle, &mut data) } == 0 {
+                    let err = io::Error::last_os_error();
+                    if err.raw_os_error() != Some(ERROR_NO_MORE_FILES as i32) {
+                        result = Err(err);
+                    }
+                    break;
+                }
+            }
+
+            unsafe {
+                FindClose(handle);
+            }
+
+            result.map(|()| DirListing {
+                entries,
+                complete: true,
+                syscalls,
+                elapsed: started.elapsed(),
+            })
+        }
+    }
+}
+
+/// Every backend usable on this platform, the default first
+pub fn enumerators() -> Vec<&'static dyn DirEnumerator> {
+    let mut enumerators = vec![default_enumerator()];
+
+    if default_enumerator().name() != StdEnumerator.name() {
+        enumerators.push(&StdEnumerator);
+    }
+
+    enumerators
+}
+
+/// Fastest enumeration backend for this platform
+pub fn default_enumerator() -> &'static dyn DirEnumerator {
+    #[cfg(target_os = "macos")]
+    return &BulkAttrEnumerator;
+
+    #[cfg(windows)]
+    return &FindFileEnumerator;
+
+    #[cfg(not(any(target_os = "macos", windows)))]
+    return &StdEnumerator;
 }
 
 /// Get list of available disks/volumes
 pub fn get_disks() -> Vec<DiskInfo> {
     let mut disk_infos = Vec::new();
     let disks = Disks::new_with_refreshed_list();
+    let mut ram_disks = RamDisks::default();
 
     for disk in disks.list() {
         let name = disk.name().to_string_lossy().to_string();
@@ -60,30 +661,352 @@ pub fn get_disks() -> Vec<DiskInfo> {
             }
         };
 
+        log::debug!("Found disk {} at {}", display_name, path);
+        let inodes = inode_counts(Path::new(&path));
+        let container = (disk.file_system() == "apfs")
+            .then(|| apfs::container(Path::new(&path), total_space, available_space))
+            .flatten();
+
         disk_infos.push(DiskInfo {
             name: display_name,
-            path,
             total_space,
             available_space,
+            purgeable_space: crate::timemachine::purgeable_space(&path, available_space),
+            full_in_days: None,
+            total_inodes: inodes.map(|(total, _)| total),
+            free_inodes: inodes.map(|(_, free)| free),
+            mtp: false,
+            ram_disk: ram_disks.contains(disk),
+            original_name: None,
+            default_folder: None,
+            container,
+            virtual_disk: None,
+            path,
         });
     }
 
+    apfs::mark_shared(&mut disk_infos);
+    disk_infos.extend(crate::mtp::mtp_disks());
+    disk_infos.extend(crate::wsl::wsl_disks());
     disk_infos
 }
 
-fn is_hidden(path: &Path) -> bool {
-    path.file_name()
-        .map(|name| name.to_string_lossy().starts_with('.'))
-        .unwrap_or(false)
+/// Disks for the overview and disk totals, without RAM disks unless the settings ask,
+/// and with the names and default folders the user gave them
+pub fn overview_disks() -> Vec<DiskInfo> {
+    let settings = crate::settings::load();
+    let mut disks = get_disks();
+    if !settings.include_ram_disks {
+        disks.retain(|disk| !disk.ram_disk);
+    }
+
+    for disk in &mut disks {
+        let Some(custom) = settings.disks.get(&disk.path) else {
+            continue;
+        };
+        if let Some(name) = custom.name.as_ref().filter(|name| !name.trim().is_empty()) {
+            disk.original_name = Some(std::mem::replace(&mut disk.name, name.clone()));
+        }
+        // A folder since moved or deleted falls back to the mount point
+        let mount = Path::new(&disk.path);
+        disk.default_folder = custom
+            .default_folder
+            .as_deref()
+            .map(|folder| mount.join(folder.trim_start_matches(['/', '\\'])))
+            .filter(|folder| {
+                folder.starts_with(mount)
+                    && !folder
+                        .components()
+                        .any(|part| part == std::path::Component::ParentDir)
+                    && folder.is_dir()
+            })
+            .map(|folder| folder.to_string_lossy().into_owned());
+    }
+
+    disks
+}
+
+/// Memory-backed filesystems, and RAM disks that look like any other volume
+#[derive(Default)]
+struct RamDisks {
+    /// Devices behind `hdiutil attach ram://` images, read on first use
+    #[cfg(target_os = "macos")]
+    images: Option<Vec<String>>,
+}
+
+impl RamDisks {
+    #[cfg(target_os = "macos")]
+    fn contains(&mut self, disk: &sysinfo::Disk) -> bool {
+        const IMAGE_PATH: &str = "<key>image-path</key>";
+        const DEV_ENTRY: &str = "<key>dev-entry</key>";
+
+        if is_memory_filesystem(disk) {
+            return true;
+        }
+        let Some(device) = mounted_from(disk.mount_point()) else {
+            return false;
+        };
+        let images = self.images.get_or_insert_with(|| {
+            let Ok(output) = std::process::Command::new("hdiutil")
+                .args(["info", "-plist"])
+                .output()
+            else {
+                return Vec::new();
+            };
+            // Each image's `image-path` comes before its `system-entities` in the plist
+            String::from_utf8_lossy(&output.stdout)
+                .split(IMAGE_PATH)
+                .skip(1)
+                .filter(|image| image.trim_start().starts_with("<string>ram://"))
+                .flat_map(|image| {
+                    image.split(DEV_ENTRY).skip(1).filter_map(|entry| {
+                        let start = entry.find("<string>")? + "<string>".len();
+                        let end = entry.find("</string>")?;
+                        entry.get(start..end).map(str::to_string)
+                    })
+                })
+                .collect()
+        });
+        images.contains(&device)
+    }
+
+    #[cfg(windows)]
+    fn contains(&mut self, disk: &sysinfo::Disk) -> bool {
+        use std::os::windows::ffi::OsStrExt;
+        use windows_sys::Win32::Storage::FileSystem::GetDriveTypeW;
+
+        /// From `WinBase.h`; RAM disk drivers such as ImDisk report it
+        const DRIVE_RAMDISK: u32 = 6;
+
+        let root: Vec<u16> = disk
+            .mount_point()
+            .as_os_str()
+            .encode_wide()
+            .chain([0])
+            .collect();
+        is_memory_filesystem(disk) || unsafe { GetDriveTypeW(root.as_ptr()) } == DRIVE_RAMDISK
+    }
+
+    #[cfg(not(any(target_os = "macos", windows)))]
+    fn contains(&mut self, disk: &sysinfo::Disk) -> bool {
+        is_memory_filesystem(disk)
+    }
+}
+
+fn is_memory_filesystem(disk: &sysinfo::Disk) -> bool {
+    let filesystem = disk.file_system().to_string_lossy().to_lowercase();
+    filesystem == "tmpfs" || filesystem == "ramfs"
+}
+
+/// Device a volume is mounted from, such as `/dev/disk4`
+#[cfg(target_os = "macos")]
+pub fn mounted_from(mount: &Path) -> Option<String> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(mount.as_os_str().as_bytes()).ok()?;
+    let mut stats: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(path.as_ptr(), &mut stats) } != 0 {
+        return None;
+    }
+    let from = unsafe { std::ffi::CStr::from_ptr(stats.f_mntfromname.as_ptr()) };
+    Some(from.to_string_lossy().into_owned())
+}
+
+/// Total and free inodes of the volume mounted at `path`
+#[cfg(unix)]
+fn inode_counts(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 {
+        return None;
+    }
+
+    // Filesystems that allocate inodes on demand, like btrfs, report zero
+    #[allow(clippy::unnecessary_cast)] // The field types differ between platforms
+    let (total, free) = (stats.f_files as u64, stats.f_ffree as u64);
+    (total > 0).then_some((total, free))
+}
+
+#[cfg(not(unix))]
+fn inode_counts(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+/// The volume with the longest mount point containing `path`
+pub fn disk_for(path: &Path) -> Option<DiskInfo> {
+    get_disks()
+        .into_iter()
+        .filter(|disk| path.starts_with(&disk.path))
+        .max_by_key(|disk| disk.path.len())
 }
 
-struct FolderSize {
-    bytes: u64,
-    complete: bool,
+/// Network shares, FUSE mounts and removable drives, where a read can block for minutes
+/// or for good once the server goes away or the device is pulled
+pub fn may_hang(path: &Path) -> bool {
+    #[cfg(windows)]
+    if path.as_os_str().to_string_lossy().starts_with(r"\\") {
+        return true;
+    }
+
+    let disks = Disks::new_with_refreshed_list();
+    let Some(disk) = disks
+        .list()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+    else {
+        return false;
+    };
+
+    let filesystem = disk.file_system().to_string_lossy().to_lowercase();
+    let network = [
+        "nfs", "cifs", "smb", "afp", "webdav", "davfs", "9p", "ceph", "gluster",
+    ]
+    .iter()
+    .any(|prefix| filesystem.starts_with(prefix))
+        || filesystem.starts_with("fuse")
+        || filesystem.contains("sshfs");
+
+    network || disk.is_removable() || is_remote_drive(disk)
+}
+
+#[cfg(windows)]
+fn is_remote_drive(disk: &sysinfo::Disk) -> bool {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDriveTypeW;
+
+    /// From `WinBase.h`, mapped network drives
+    const DRIVE_REMOTE: u32 = 4;
+
+    let root: Vec<u16> = disk
+        .mount_point()
+        .as_os_str()
+        .encode_wide()
+        .chain([0])
+        .collect();
+    unsafe { GetDriveTypeW(root.as_ptr()) == DRIVE_REMOTE }
+}
+
+#[cfg(not(windows))]
+fn is_remote_drive(_disk: &sysinfo::Disk) -> bool {
+    false
+}
+
+/// Filesystem of the volume holding `path`, such as `apfs`, `ntfs` or `ext4`, lowercase
+pub fn filesystem_of(path: &Path) -> Option<String> {
+    Disks::new_with_refreshed_list()
+        .list()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| disk.file_system().to_string_lossy().to_lowercase())
+}
+
+pub fn is_hidden_name(name: &OsStr) -> bool {
+    name.to_string_lossy().starts_with('.')
+}
+
+/// `path` if it is a folder, else the error listings give for it
+pub fn require_dir(path: &str) -> Result<&Path, DuneError> {
+    let dir_path = Path::new(path);
+    if dir_path.is_dir() {
+        return Ok(dir_path);
+    }
+
+    Err(if dir_path.exists() {
+        DuneError::NotADirectory { path: path.into() }
+    } else {
+        DuneError::NotFound { path: path.into() }
+    })
+}
+
+/// Whether `walk` passes on dot-files and descends into dot-folders
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hidden {
+    /// The same rules as listings
+    Skip,
+    /// For analyzers where hidden files use the volume all the same
+    Include,
+}
+
+/// What `walk` calls as it goes; closures taking the entry's path and the entry work as
+/// visitors that only look at entries
+pub trait Visitor {
+    /// Every entry listed, folders included; returns whether to descend into a folder
+    fn entry(&mut self, path: &Path, entry: &DirEntryInfo) -> bool;
+
+    /// `dir` was read, before its entries are visited
+    fn enter(&mut self, _dir: &Path) {}
+
+    /// `dir` and everything under it were visited
+    fn leave(&mut self, _dir: &Path) {}
+}
+
+impl<F: FnMut(&Path, &DirEntryInfo) -> bool> Visitor for F {
+    fn entry(&mut self, path: &Path, entry: &DirEntryInfo) -> bool {
+        self(path, entry)
+    }
+}
+
+/// Hand `entry`, found at `path`, to the visitor, and walk it if it is a folder the
+/// visitor descends into; true when everything under it could be read
+pub fn visit(
+    enumerator: &dyn DirEnumerator,
+    path: &Path,
+    entry: &DirEntryInfo,
+    hidden: Hidden,
+    visitor: &mut impl Visitor,
+) -> bool {
+    if visitor.entry(path, entry) && entry.kind == EntryKind::Dir {
+        walk(enumerator, path, hidden, visitor)
+    } else {
+        true
+    }
+}
+
+/// Visit everything under `dir` depth first, never following links; true when every
+/// folder could be read completely
+pub fn walk(
+    enumerator: &dyn DirEnumerator,
+    dir: &Path,
+    hidden: Hidden,
+    visitor: &mut impl Visitor,
+) -> bool {
+    let listing = match enumerator.read_dir(dir) {
+        Ok(listing) => listing,
+        Err(err) => {
+            log::debug!("Could not read {}: {}", dir.display(), err);
+            return false;
+        }
+    };
+
+    visitor.enter(dir);
+    let mut complete = listing.complete;
+    for entry in &listing.entries {
+        if hidden == Hidden::Skip && is_hidden_name(&entry.name) {
+            continue;
+        }
+        complete &= visit(enumerator, &dir.join(&entry.name), entry, hidden, visitor);
+    }
+    visitor.leave(dir);
+
+    complete
+}
+
+pub struct FolderSize {
+    pub bytes: u64,
+    pub complete: bool,
 }
 
 /// Calculate folder size recursively until the UI responsiveness budget expires.
-fn calculate_folder_size(path: &Path, deadline: Instant) -> FolderSize {
+fn calculate_folder_size(
+    enumerator: &dyn DirEnumerator,
+    path: &Path,
+    deadline: Instant,
+) -> FolderSize {
     let mut size = 0u64;
     let mut complete = true;
 
@@ -94,9 +1017,10 @@ fn calculate_folder_size(path: &Path, deadline: Instant) -> FolderSize {
         };
     }
 
-    let entries = match fs::read_dir(path) {
-        Ok(entries) => entries,
-        Err(_) => {
+    let listing = match enumerator.read_dir(path) {
+        Ok(listing) => listing,
+        Err(err) => {
+            log::debug!("Could not read {}: {}", path.display(), err);
             return FolderSize {
                 bytes: 0,
                 complete: false,
@@ -104,33 +1028,27 @@ fn calculate_folder_size(path: &Path, deadline: Instant) -> FolderSize {
         }
     };
 
-    for entry in entries.flatten() {
+    complete &= listing.complete;
+
+    for entry in listing.entries {
         if Instant::now() >= deadline {
             complete = false;
             break;
         }
 
-        let entry_path = entry.path();
-
-        if is_hidden(&entry_path) {
+        if is_hidden_name(&entry.name) {
             continue;
         }
 
-        let Ok(file_type) = entry.file_type() else {
-            complete = false;
-            continue;
-        };
-
-        if file_type.is_file() {
-            if let Ok(metadata) = entry.metadata() {
-                size = size.saturating_add(metadata.len());
-            } else {
-                complete = false;
+        match entry.kind {
+            EntryKind::File => size = size.saturating_add(entry.len),
+            EntryKind::Dir => {
+                let child_size =
+                    calculate_folder_size(enumerator, &path.join(&entry.name), deadline);
+                size = size.saturating_add(child_size.bytes);
+                complete &= child_size.complete;
             }
-        } else if file_type.is_dir() {
-            let child_size = calculate_folder_size(&entry_path, deadline);
-            size = size.saturating_add(child_size.bytes);
-            complete &= child_size.complete;
+            EntryKind::Symlink | EntryKind::Other => {}
         }
     }
 
@@ -140,70 +1058,144 @@ fn calculate_folder_size(path: &Path, deadline: Instant) -> FolderSize {
     }
 }
 
-/// List contents of a directory
-pub fn list_directory(path: &str) -> Result<Vec<FileEntry>, String> {
-    let dir_path = Path::new(path);
-
-    if !dir_path.exists() {
-        return Err(format!("Path does not exist: {}", path));
+/// Size of a file or a whole folder without the interactive time budget; missing paths are empty
+pub fn path_size(path: &Path) -> FolderSize {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => calculate_folder_size(
+            default_enumerator(),
+            path,
+            Instant::now() + MAX_BACKGROUND_SCAN_DURATION,
+        ),
+        Ok(metadata) => FolderSize {
+            bytes: if metadata.is_file() {
+                metadata.len()
+            } else {
+                0
+            },
+            complete: true,
+        },
+        Err(err) => FolderSize {
+            bytes: 0,
+            complete: err.kind() == io::ErrorKind::NotFound,
+        },
     }
+}
 
-    if !dir_path.is_dir() {
-        return Err(format!("Path is not a directory: {}", path));
-    }
+/// Measure a whole folder without the interactive time budget, for background work
+pub fn measure_folder(path: &str, enumerator: &dyn DirEnumerator) -> Result<FileEntry, DuneError> {
+    let dir_path = require_dir(path)?;
 
-    let mut entries = Vec::new();
-    let list_deadline = Instant::now() + MAX_DIRECTORY_LIST_SCAN_DURATION;
+    let started = Instant::now();
+    let folder_size =
+        calculate_folder_size(enumerator, dir_path, started + MAX_BACKGROUND_SCAN_DURATION);
 
-    let read_result = fs::read_dir(dir_path).map_err(|e| e.to_string())?;
+    log::info!(
+        "Measured {}: {} bytes (complete: {}) in {:?}",
+        path,
+        folder_size.bytes,
+        folder_size.complete,
+        started.elapsed()
+    );
 
-    for entry in read_result.flatten() {
-        let entry_path = entry.path();
-        let name = entry_path
-            .file_name()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_string();
+    let file_name = dir_path.file_name().unwrap_or_default();
+    let name = crate::names::display(file_name);
 
-        if is_hidden(&entry_path) {
-            continue;
+    Ok(FileEntry {
+        raw_name_bytes: crate::names::raw_bytes(file_name, &name),
+        name,
+        path: path.to_string(),
+        is_dir: true,
+        size: folder_size.bytes,
+        size_complete: folder_size.complete,
+        tags: crate::tags::finder_tags(dir_path),
+        custom_tags: Vec::new(),
+        note: None,
+        storage: StorageFlags::default(),
+        last_opened: None,
+    })
+}
+
+/// List contents of a directory
+/// Fill in Finder tags and storage flags; an extended attribute read and a stat per
+/// entry, so listings only pay for them when asked
+pub fn apply_attributes(entries: &mut [FileEntry]) {
+    for entry in entries {
+        let path = Path::new(&entry.path);
+        entry.tags = crate::tags::finder_tags(path);
+        if !entry.is_dir {
+            entry.storage =
+                allocation::of(path).map_or_else(StorageFlags::default, |file| file.flags);
         }
+    }
+}
 
-        let file_type = match entry.file_type() {
-            Ok(file_type) => file_type,
-            Err(_) => continue, // Skip inaccessible items
-        };
+pub fn list_directory(path: &str) -> Result<Vec<FileEntry>, DuneError> {
+    let dir_path = require_dir(path)?;
 
-        let is_dir = file_type.is_dir();
-        let (size, size_complete) = if is_dir {
-            let now = Instant::now();
-            let folder_deadline = now + MAX_FOLDER_SCAN_DURATION;
-            let deadline = if folder_deadline < list_deadline {
-                folder_deadline
-            } else {
-                list_deadline
-            };
-            let folder_size = calculate_folder_size(&entry_path, deadline);
+    let mut entries = Vec::new();
+    let started = Instant::now();
+    let list_deadline = started + MAX_DIRECTORY_LIST_SCAN_DURATION;
 
-            (folder_size.bytes, folder_size.complete)
-        } else if file_type.is_file() {
-            match entry.metadata() {
-                Ok(metadata) => (metadata.len(), true),
-                Err(_) => continue,
-            }
-        } else {
+    let listing = default_enumerator()
+        .read_dir(dir_path)
+        .map_err(|e| DuneError::io(dir_path, e))?;
+
+    for entry in listing.entries {
+        if is_hidden_name(&entry.name) {
             continue;
+        }
+
+        let is_dir = match entry.kind {
+            EntryKind::Dir => true,
+            EntryKind::File => false,
+            EntryKind::Symlink | EntryKind::Other => continue,
         };
+        let entry_path = dir_path.join(&entry.name);
+        let name = crate::names::display(&entry.name);
 
         entries.push(FileEntry {
+            raw_name_bytes: crate::names::raw_bytes(&entry.name, &name),
             name,
             path: entry_path.to_string_lossy().to_string(),
             is_dir,
-            size,
-            size_complete,
+            size: entry.len,
+            size_complete: !is_dir,
+            tags: Vec::new(),
+            custom_tags: Vec::new(),
+            note: None,
+            storage: StorageFlags::default(),
+            last_opened: None,
         });
     }
 
+    let size_folder = |entry: &mut FileEntry| {
+        let folder_deadline = Instant::now() + MAX_FOLDER_SCAN_DURATION;
+        let deadline = folder_deadline.min(list_deadline);
+        let folder_size =
+            calculate_folder_size(default_enumerator(), Path::new(&entry.path), deadline);
+
+        if !folder_size.complete {
+            log::debug!("Size of {} is a lower bound", entry.path);
+        }
+
+        entry.size = folder_size.bytes;
+        entry.size_complete = folder_size.complete;
+    };
+
+    // Size folders in parallel on the current rayon pool, except on phones and cameras
+    // where parallel reads only queue up on the device
+    if crate::mtp::is_slow_device(path) {
+        entries
+            .iter_mut()
+            .filter(|entry| entry.is_dir)
+            .for_each(size_folder);
+    } else {
+        entries
+            .par_iter_mut()
+            .filter(|entry| entry.is_dir)
+            .for_each(size_folder);
+    }
+
     // Sort: folders first, then files, by size descending within each group
     entries.sort_by(|a, b| {
         match (a.is_dir, b.is_dir) {
@@ -213,5 +1205,12 @@ pub fn list_directory(path: &str) -> Result<Vec<FileEntry>, String> {
         }
     });
 
+    log::debug!(
+        "Listed {} entries in {} in {:?}",
+        entries.len(),
+        path,
+        started.elapsed()
+    );
+
     Ok(entries)
 }
@@ -0,0 +1,514 @@
+use crate::error::DuneError;
+use crate::query::{Query, Subject, TextMode};
+use crate::scan::{self, ScanTree, TreeNode};
+use crate::snapshot::{self, SnapshotInfo};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Nodes matched per work item; each item with hits becomes one `search://results` event
+const CHUNK_NODES: usize = 16_384;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchOptions {
+    /// Match the query's characters in order with gaps, e.g. `bgmkv` finds `big_movie.mkv`
+    pub fuzzy: bool,
+    /// Bare words are regular expressions
+    pub regex: bool,
+    pub include_dirs: bool,
+    pub min_size: u64,
+    /// Snapshot roots to search; every stored root when empty
+    pub roots: Vec<String>,
+    /// Stop after this many hits
+    pub limit: usize,
+}
+
+impl SearchOptions {
+    pub fn text_mode(&self) -> TextMode {
+        TextMode {
+            fuzzy: self.fuzzy,
+            regex: self.regex,
+        }
+    }
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            fuzzy: false,
+            regex: false,
+            include_dirs: true,
+            min_size: 0,
+            roots: Vec::new(),
+            limit: 1_000,
+        }
+    }
+}
+
+/// Payload of `search://started`
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchStarted {
+    pub search_id: u64,
+    pub query: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub snapshot_id: String,
+    pub path: String,
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    /// Higher is a better match; only comparable within one search
+    pub score: u32,
+}
+
+/// Payload of `search://results`; batches arrive unordered, so sort by `score`
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchBatch {
+    pub search_id: u64,
+    pub hits: Vec<SearchHit>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchSummary {
+    pub search_id: u64,
+    pub matches: u64,
+    pub searched_nodes: u64,
+    /// Snapshots searched; hits are only as fresh as these
+    pub snapshots: Vec<SnapshotInfo>,
+    /// Stopped at `limit`
+    pub truncated: bool,
+    /// A newer search started before this one finished
+    pub superseded: bool,
+    pub elapsed_ms: u64,
+}
+
+/// Every match of a search gathered into a tree the ring view can show like a scan
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchTree {
+    pub summary: SearchSummary,
+    pub tree: Arc<ScanTree>,
+}
+
+/// A loaded snapshot with the nodes that a newer snapshot of a nested root covers
+struct IndexedTree {
+    info: SnapshotInfo,
+    tree: Arc<ScanTree>,
+    covered: Vec<bool>,
+}
+
+/// Decompressed snapshot trees kept between searches, refreshed when snapshots change
+#[derive(Default)]
+pub struct SearchIndex {
+    trees: Mutex<Vec<Arc<IndexedTree>>>,
+    generation: AtomicU64,
+}
+
+/// Newest snapshot of each root, minus roots a newer snapshot of an enclosing folder covers
+fn select_snapshots(infos: Vec<SnapshotInfo>, roots: &[String]) -> Vec<SnapshotInfo> {
+    let mut seen = HashSet::new();
+    let mut selected: Vec<SnapshotInfo> = Vec::new();
+
+    // `list` returns newest first
+    for info in infos {
+        if !roots.is_empty() && !roots.contains(&info.root) {
+            continue;
+        }
+        if !seen.insert(info.root.clone()) {
+            continue;
+        }
+        if selected
+            .iter()
+            .any(|newer| Path::new(&info.root).starts_with(&newer.root))
+        {
+            continue;
+        }
+        selected.push(info);
+    }
+
+    selected
+}
+
+/// Mark nodes of `tree` inside any of `nested`, which newer snapshots already cover
+fn covered_nodes(tree: &ScanTree, nested: &[&str]) -> Vec<bool> {
+    let mut covered = vec![false; tree.nodes.len()];
+
+    for root in nested {
+        if let Some(node) = tree.find(Path::new(root)) {
+            covered[node as usize] = true;
+        }
+    }
+
+    // Parents always precede children
+    for index in 1..tree.nodes.len() {
+        if let Some(parent) = tree.nodes[index].parent {
+            covered[index] |= covered[parent as usize];
+        }
+    }
+
+    covered
+}
+
+impl SearchIndex {
+    /// Snapshot trees held for searching, and their rough heap size
+    pub fn memory(&self) -> (usize, u64) {
+        let trees = self.trees.lock().unwrap();
+        let bytes = trees
+            .iter()
+            .map(|indexed| indexed.tree.heap_bytes() + indexed.covered.capacity() as u64)
+            .sum();
+        (trees.len(), bytes)
+    }
+
+    /// Drop the held trees; the next search loads them again
+    pub fn clear(&self) {
+        self.trees.lock().unwrap().clear();
+    }
+
+    /// Id for a new search; any search still running stops at its next chunk
+    pub fn begin(&self) -> u64 {
+        self.generation.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Bring the cached trees in line with the stored snapshots, loading only new ones
+    fn refresh(
+        &self,
+        snapshots: &Path,
+        roots: &[String],
+    ) -> Result<Vec<Arc<IndexedTree>>, DuneError> {
+        let selected = select_snapshots(snapshot::list(snapshots)?, roots);
+        let cached = self.trees.lock().unwrap().clone();
+
+        let unchanged = selected.len() == cached.len()
+            && selected
+                .iter()
+                .zip(&cached)
+                .all(|(info, tree)| info.id == tree.info.id);
+        if unchanged {
+            return Ok(cached);
+        }
+
+        let trees: Vec<Arc<IndexedTree>> = selected
+            .par_iter()
+            .enumerate()
+            .filter_map(|(position, info)| {
+                let tree = match cached.iter().find(|tree| tree.info.id == info.id) {
+                    Some(tree) => tree.tree.clone(),
+                    None => Arc::new(
+                        snapshot::load(snapshots, &info.id)
+                            .inspect_err(|err| log::warn!("Skipping snapshot {}: {}", info.id, err))
+                            .ok()?
+                            .tree,
+                    ),
+                };
+                // Only roots of newer snapshots, which come earlier in the list
+                let nested: Vec<&str> = selected[..position]
+                    .iter()
+                    .map(|newer| newer.root.as_str())
+                    .filter(|root| Path::new(root).starts_with(&info.root))
+                    .collect();
+
+                Some(Arc::new(IndexedTree {
+                    info: info.clone(),
+                    covered: covered_nodes(&tree, &nested),
+                    tree,
+                }))
+            })
+            .collect();
+
+        log::info!("Search index holds {} snapshots", trees.len());
+        *self.trees.lock().unwrap() = trees.clone();
+
+        Ok(trees)
+    }
+
+    /// Run `query` over every uncovered node, handing each chunk's matches to `on_chunk`
+    /// as the tree's position and (node, score) pairs. Returns the matches, capped at `limit`.
+    fn match_nodes(
+        &self,
+        trees: &[Arc<IndexedTree>],
+        query: &Query,
+        search_id: u64,
+        options: &SearchOptions,
+        on_chunk: &(dyn Fn(usize, Vec<(u32, u32)>) + Sync),
+    ) -> usize {
+        let matches = AtomicUsize::new(0);
+        let full = || matches.load(Ordering::Relaxed) >= options.limit;
+
+        for (position, indexed) in trees.iter().enumerate() {
+            indexed
+                .tree
+                .nodes
+                .par_chunks(CHUNK_NODES)
+                .enumerate()
+                .for_each(|(chunk, nodes)| {
+                    if self.superseded(search_id) || full() {
+                        return;
+                    }
+
+                    let first = chunk * CHUNK_NODES;
+                    let mut found: Vec<(u32, u32)> = nodes
+                        .iter()
+                        .enumerate()
+                        .filter(|(offset, node)| {
+                            !indexed.covered[first + offset]
+                                && (options.include_dirs || !node.is_dir)
+                                && node.size >= options.min_size
+                        })
+                        .filter_map(|(offset, _)| {
+                            let index = (first + offset) as u32;
+                            let score = query.score(&Subject::new(&indexed.tree, index))?;
+                            Some((index, score))
+                        })
+                        .collect();
+
+                    let before = matches.fetch_add(found.len(), Ordering::Relaxed);
+                    found.truncate(options.limit.saturating_sub(before));
+                    if !found.is_empty() {
+                        on_chunk(position, found);
+                    }
+                });
+        }
+
+        matches.load(Ordering::Relaxed).min(options.limit)
+    }
+
+    fn superseded(&self, search_id: u64) -> bool {
+        self.generation.load(Ordering::Relaxed) != search_id
+    }
+
+    fn summary(
+        &self,
+        search_id: u64,
+        trees: &[Arc<IndexedTree>],
+        matches: usize,
+        options: &SearchOptions,
+        started: Instant,
+    ) -> SearchSummary {
+        SearchSummary {
+            search_id,
+            matches: matches as u64,
+            searched_nodes: trees
+                .iter()
+                .map(|tree| tree.covered.iter().filter(|&&covered| !covered).count() as u64)
+                .sum(),
+            truncated: matches >= options.limit,
+            superseded: self.superseded(search_id),
+            snapshots: trees.iter().map(|tree| tree.info.clone()).collect(),
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        }
+    }
+
+    /// Match names in stored snapshots, delivering hits in batches
+    pub fn search(
+        &self,
+        snapshots: &Path,
+        search_id: u64,
+        query: &str,
+        options: &SearchOptions,
+        on_batch: &(dyn Fn(SearchBatch) + Sync),
+    ) -> Result<SearchSummary, DuneError> {
+        let started = Instant::now();
+        let parsed = Query::parse(query, options.text_mode())?;
+        let trees = self.refresh(snapshots, &options.roots)?;
+
+        let matches = self.match_nodes(&trees, &parsed, search_id, options, &|position, found| {
+            let indexed = &trees[position];
+            let hits = found
+                .into_iter()
+                .map(|(index, score)| {
+                    let node = &indexed.tree.nodes[index as usize];
+                    SearchHit {
+                        snapshot_id: indexed.info.id.clone(),
+                        path: indexed.tree.path(index).to_string_lossy().to_string(),
+                        name: crate::names::display(OsStr::new(&*node.name)),
+                        is_dir: node.is_dir,
+                        size: node.size,
+                        score,
+                    }
+                })
+                .collect();
+            on_batch(SearchBatch { search_id, hits });
+        });
+        let summary = self.summary(search_id, &trees, matches, options, started);
+
+        log::info!(
+            "Search {} for {:?} found {} matches in {} ms",
+            search_id,
+            query,
+            summary.matches,
+            summary.elapsed_ms
+        );
+
+        Ok(summary)
+    }
+
+    /// Collect every match into one tree, with folders summing only the matches inside them.
+    /// The tree gets `scan_id` so it can be kept and exported like a scan.
+    pub fn search_tree(
+        &self,
+        snapshots: &Path,
+        search_id: u64,
+        scan_id: u64,
+        query: &str,
+        options: &SearchOptions,
+    ) -> Result<SearchTree, DuneError> {
+        let started = Instant::now();
+        let parsed = Query::parse(query, options.text_mode())?;
+        let trees = self.refresh(snapshots, &options.roots)?;
+
+        let found = Mutex::new(Vec::new());
+        let matches = self.match_nodes(&trees, &parsed, search_id, options, &|position, hits| {
+            let paths: Vec<(PathBuf, usize, u32)> = hits
+                .into_iter()
+                .map(|(index, _)| (trees[position].tree.path(index), position, index))
+                .collect();
+            found.lock().unwrap().extend(paths);
+        });
+
+        // Sorting by path puts every folder before its contents
+        let mut found = found.into_inner().unwrap();
+        found.sort_unstable();
+        let tree = materialize(scan_id, &trees, &found);
+        let summary = self.summary(search_id, &trees, matches, options, started);
+
+        log::info!(
+            "Search tree {} for {:?} holds {} matches in {} nodes",
+            search_id,
+            query,
+            summary.matches,
+            tree.nodes.len()
+        );
+
+        Ok(SearchTree {
+            summary,
+            tree: Arc::new(tree),
+        })
+    }
+}
+
+/// Deepest folder containing every path, `None` if they share no root (e.g. two Windows drives)
+fn common_ancestor<'a>(mut paths: impl Iterator<Item = &'a Path>) -> Option<PathBuf> {
+    let mut ancestor = paths.next()?.to_path_buf();
+    for path in paths {
+        while !path.starts_with(&ancestor) {
+            if !ancestor.pop() {
+                return None;
+            }
+        }
+    }
+    Some(ancestor)
+}
+
+/// Build a tree holding only the matched nodes and the folders leading to them.
+/// `found` is sorted by path; anything inside a matched folder is already counted in it.
+fn materialize(
+    scan_id: u64,
+    trees: &[Arc<IndexedTree>],
+    found: &[(PathBuf, usize, u32)],
+) -> ScanTree {
+    let mut roots: Vec<usize> = found.iter().map(|(_, position, _)| *position).collect();
+    roots.sort_unstable();
+    roots.dedup();
+    let root = common_ancestor(
+        roots
+            .iter()
+            .map(|&position| Path::new(&trees[position].info.root)),
+    )
+    .unwrap_or_default();
+
+    let mut nodes = vec![TreeNode {
+        parent: None,
+        name: root
+            .file_name()
+            .map(|name| Arc::from(name.to_string_lossy()))
+            .unwrap_or_else(|| Arc::from(root.to_string_lossy())),
+        is_dir: true,
+        size: 0,
+        file_count: 0,
+        size_complete: true,
+        modified: None,
+    }];
+    let mut matched = vec![false];
+    let mut children: HashMap<(u32, Arc<str>), u32> = HashMap::new();
+
+    'hits: for (path, position, index) in found {
+        let source = &trees[*position].tree.nodes[*index as usize];
+        let matched_node = TreeNode {
+            file_count: if source.is_dir { source.file_count } else { 1 },
+            ..source.clone()
+        };
+        let Ok(relative) = path.strip_prefix(&root) else {
+            continue;
+        };
+        let names: Vec<Arc<str>> = relative
+            .components()
+            .map(|component| Arc::from(component.as_os_str().to_string_lossy()))
+            .collect();
+
+        // The common root itself matched
+        if names.is_empty() {
+            nodes[0] = TreeNode {
+                parent: None,
+                ..matched_node
+            };
+            matched[0] = true;
+            continue;
+        }
+
+        let mut current = 0u32;
+        for (depth, name) in names.iter().enumerate() {
+            if matched[current as usize] {
+                continue 'hits;
+            }
+            let key = (current, name.clone());
+            if let Some(&child) = children.get(&key) {
+                current = child;
+                continue;
+            }
+
+            let is_leaf = depth + 1 == names.len();
+            nodes.push(if is_leaf {
+                TreeNode {
+                    parent: Some(current),
+                    ..matched_node.clone()
+                }
+            } else {
+                TreeNode {
+                    parent: Some(current),
+                    name: name.clone(),
+                    is_dir: true,
+                    size: 0,
+                    file_count: 0,
+                    size_complete: true,
+                    modified: None,
+                }
+            });
+            matched.push(is_leaf);
+            current = (nodes.len() - 1) as u32;
+            children.insert(key, current);
+        }
+    }
+
+    scan::aggregate(&mut nodes);
+
+    ScanTree {
+        scan_id,
+        root: root.to_string_lossy().to_string(),
+        nodes,
+        allocation: None,
+        subvolumes: Vec::new(),
+        ignored: Vec::new(),
+        small_items: Vec::new(),
+        skipped: Vec::new(),
+        stats: None,
+        elevated: false,
+    }
+}
@@ -0,0 +1,909 @@
+use crate::age::{self, AgeHistogram};
+use crate::apps::{self, AppsReport};
+use crate::browsers::{self, BrowserReport};
+use crate::category::{self, CategoryStats};
+use crate::compare::{self, CompareOptions, DirDiff};
+use crate::compression::{self, CompressionEstimate};
+use crate::containers::{self, ContainersReport};
+use crate::disk_map::{self, DiskMap};
+use crate::elevated;
+use crate::error::DuneError;
+use crate::favorites;
+use crate::grep::{self, GrepFile, GrepOptions, GrepSummary};
+use crate::health::{self, HealthReport};
+use crate::import::{self, ImportFormat};
+use crate::inodes::{self, InodeReport};
+use crate::logs::{self, LogReport};
+use crate::memory::{self, CacheMemory, MemoryStats, ScanMemory};
+use crate::messaging::{self, MessagingReport};
+use crate::metrics::ScanRecord;
+use crate::mtp;
+use crate::multi;
+use crate::os_search::{self, OsSearchResult};
+use crate::owner::{self, OwnerStats};
+use crate::packages::{self, PackageReport};
+use crate::power::{self, OpenFileLimit, ThrottledEnumerator};
+use crate::query::Query;
+use crate::remote;
+use crate::ring::{self, ChildIndex, RingLevel, RingStyle};
+use crate::s3;
+use crate::scan::{self, ScanHandle, ScanOptions, ScanProgress, ScanStalled, ScanTree};
+use crate::scanner::{self, FileEntry};
+use crate::search::{SearchBatch, SearchIndex, SearchOptions, SearchSummary, SearchTree};
+use crate::settings::{self, PerformanceSettings, PowerMode, Settings};
+use crate::similar_images::{self, SimilarImagesReport};
+use crate::verify::{self, VerifyOptions, VerifyProgress, VerifySummary};
+use lru::LruCache;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tauri::{AppHandle, Emitter};
+
+/// Cached listings older than this are re-read even without a watcher event
+const LISTING_TTL: Duration = Duration::from_secs(60);
+const BYTES_PER_MB: u64 = 1024 * 1024;
+/// Finished trees kept so they can be snapshotted or queried after `scan_tree` returns
+const KEEP_FINISHED_SCANS: usize = 4;
+/// Roots whose latest scan is remembered for `/metrics`
+const KEEP_SCAN_RECORDS: usize = 64;
+
+/// The listing cache budget in bytes; settings.json is edited by hand, so a huge value
+/// means no limit rather than an overflow
+fn cache_budget(settings: &Settings) -> usize {
+    usize::try_from(settings.scan.cache_budget_mb.saturating_mul(BYTES_PER_MB))
+        .unwrap_or(usize::MAX)
+}
+
+struct CachedListing {
+    entries: Vec<FileEntry>,
+    bytes: usize,
+    cached_at: Instant,
+}
+
+/// Directory listings kept under a memory budget, least recently used evicted first
+pub struct ListingCache {
+    listings: LruCache<String, CachedListing>,
+    used_bytes: usize,
+    budget_bytes: usize,
+}
+
+impl ListingCache {
+    fn new(budget_bytes: usize) -> Self {
+        Self {
+            listings: LruCache::unbounded(),
+            used_bytes: 0,
+            budget_bytes,
+        }
+    }
+
+    fn get(&mut self, path: &str) -> Option<Vec<FileEntry>> {
+        let fresh = self
+            .listings
+            .get(path)
+            .map(|listing| listing.cached_at.elapsed() < LISTING_TTL)?;
+
+        if !fresh {
+            self.remove(path);
+            return None;
+        }
+
+        self.listings
+            .get(path)
+            .map(|listing| listing.entries.clone())
+    }
+
+    fn insert(&mut self, path: String, entries: Vec<FileEntry>) {
+        let bytes = entries
+            .iter()
+            .map(|entry| std::mem::size_of::<FileEntry>() + entry.name.len() + entry.path.len())
+            .sum::<usize>()
+            + path.len();
+
+        if bytes > self.budget_bytes {
+            return;
+        }
+
+        self.remove(&path);
+        self.used_bytes += bytes;
+        self.listings.put(
+            path,
+            CachedListing {
+                entries,
+                bytes,
+                cached_at: Instant::now(),
+            },
+        );
+        self.evict();
+    }
+
+    fn remove(&mut self, path: &str) {
+        if let Some(listing) = self.listings.pop(path) {
+            self.used_bytes -= listing.bytes;
+        }
+    }
+
+    /// Drop `path` and every ancestor listing, since their folder sizes changed too
+    fn invalidate(&mut self, path: &Path) {
+        for ancestor in path.ancestors() {
+            self.remove(&ancestor.to_string_lossy());
+        }
+    }
+
+    fn memory(&self) -> CacheMemory {
+        CacheMemory {
+            entries: self.listings.len(),
+            bytes: self.used_bytes as u64,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.listings.clear();
+        self.used_bytes = 0;
+    }
+
+    fn set_budget(&mut self, budget_bytes: usize) {
+        self.budget_bytes = budget_bytes;
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.used_bytes > self.budget_bytes {
+            let Some((_, listing)) = self.listings.pop_lru() else {
+                break;
+            };
+            self.used_bytes -= listing.bytes;
+        }
+    }
+}
+
+/// Scanner state shared by all commands, held in `tauri::Builder::manage()`
+pub struct ScannerService {
+    pool: rayon::ThreadPool,
+    scans: Mutex<HashMap<u64, Arc<ScanHandle>>>,
+    finished: Mutex<LruCache<u64, Arc<ScanTree>>>,
+    /// Child lists of finished trees, built on the first ring request
+    child_indexes: Mutex<LruCache<u64, Arc<ChildIndex>>>,
+    last_scans: Mutex<LruCache<String, ScanRecord>>,
+    next_scan_id: AtomicU64,
+    watchers: Mutex<HashMap<PathBuf, RecommendedWatcher>>,
+    /// Watched folders whose totals are kept fresh, see `watch_favorite`
+    favorites: Mutex<HashSet<PathBuf>>,
+    listings: Arc<Mutex<ListingCache>>,
+    power_mode: Mutex<PowerMode>,
+    performance: Mutex<PerformanceSettings>,
+    open_files: OpenFileLimit,
+    search: SearchIndex,
+}
+
+impl ScannerService {
+    pub fn new(settings: &Settings) -> Result<Self, DuneError> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .thread_name(|index| format!("scanner-{}", index))
+            .build()
+            .map_err(DuneError::internal)?;
+
+        log::info!(
+            "Scanner using {} threads with {} enumeration",
+            pool.current_num_threads(),
+            scanner::default_enumerator().name()
+        );
+
+        Ok(Self {
+            pool,
+            scans: Mutex::new(HashMap::new()),
+            finished: Mutex::new(LruCache::new(
+                NonZeroUsize::new(KEEP_FINISHED_SCANS).unwrap(),
+            )),
+            child_indexes: Mutex::new(LruCache::new(
+                NonZeroUsize::new(KEEP_FINISHED_SCANS).unwrap(),
+            )),
+            last_scans: Mutex::new(LruCache::new(NonZeroUsize::new(KEEP_SCAN_RECORDS).unwrap())),
+            next_scan_id: AtomicU64::new(1),
+            watchers: Mutex::new(HashMap::new()),
+            favorites: Mutex::new(HashSet::new()),
+            listings: Arc::new(Mutex::new(ListingCache::new(cache_budget(settings)))),
+            power_mode: Mutex::new(settings.scan.power_mode),
+            performance: Mutex::new(settings.performance),
+            open_files: OpenFileLimit::new(settings.performance.max_open_files),
+            search: SearchIndex::default(),
+        })
+    }
+
+    pub fn apply_settings(&self, settings: &Settings) {
+        self.listings
+            .lock()
+            .unwrap()
+            .set_budget(cache_budget(settings));
+        *self.power_mode.lock().unwrap() = settings.scan.power_mode;
+        *self.performance.lock().unwrap() = settings.performance;
+        self.open_files.set_max(settings.performance.max_open_files);
+    }
+
+    /// List a directory, served from the cache when a fresh listing exists
+    pub fn list_directory(&self, path: &str) -> Result<Vec<FileEntry>, DuneError> {
+        if let Some(entries) = self.listings.lock().unwrap().get(path) {
+            log::debug!("Listing cache hit for {}", path);
+            return Ok(entries);
+        }
+
+        let entries = self.pool.install(|| scanner::list_directory(path))?;
+
+        // Lower-bound sizes would stick around until the TTL, so only cache exact listings
+        if entries.iter().all(|entry| entry.size_complete) {
+            self.listings
+                .lock()
+                .unwrap()
+                .insert(path.to_string(), entries.clone());
+        }
+
+        Ok(entries)
+    }
+
+    /// Bucket the files under `path` by age on the scanner threads
+    pub fn age_histogram(&self, path: &str) -> Result<AgeHistogram, DuneError> {
+        self.pool.install(|| age::age_histogram(path))
+    }
+
+    /// Bytes per file category under `path`, on the scanner threads
+    pub fn category_stats(&self, path: &str) -> Result<CategoryStats, DuneError> {
+        self.pool.install(|| category::category_stats(path))
+    }
+
+    /// Bytes per file owner under `path`, on the scanner threads
+    pub fn owner_stats(&self, path: &str) -> Result<OwnerStats, DuneError> {
+        self.pool.install(|| owner::owner_stats(path))
+    }
+
+    /// Long paths, unportable names and odd permissions under `path`, on the scanner threads
+    pub fn health_report(&self, path: &str) -> Result<HealthReport, DuneError> {
+        self.pool.install(|| health::health_report(path))
+    }
+
+    pub fn installed_apps_report(&self) -> AppsReport {
+        self.pool.install(apps::installed_apps_report)
+    }
+
+    pub fn estimate_compression(&self, path: &str) -> Result<CompressionEstimate, DuneError> {
+        self.pool
+            .install(|| compression::estimate_compression(path))
+    }
+
+    pub fn similar_images(
+        &self,
+        path: &str,
+        max_distance: Option<u32>,
+    ) -> Result<SimilarImagesReport, DuneError> {
+        self.pool
+            .install(|| similar_images::similar_images(path, max_distance))
+    }
+
+    pub fn inode_report(&self, path: &str) -> Result<InodeReport, DuneError> {
+        self.pool.install(|| inodes::inode_report(path))
+    }
+
+    /// Log files under `path`, with growth rates from the snapshots in `snapshots`
+    pub fn log_report(&self, path: &str, snapshots: &Path) -> Result<LogReport, DuneError> {
+        self.pool.install(|| logs::log_report(path, snapshots))
+    }
+
+    pub fn containers_report(&self) -> ContainersReport {
+        self.pool.install(containers::containers_report)
+    }
+
+    pub fn browser_report(&self) -> BrowserReport {
+        self.pool.install(browsers::browser_report)
+    }
+
+    pub fn package_report(&self) -> PackageReport {
+        self.pool.install(packages::package_report)
+    }
+
+    pub fn messaging_report(&self) -> MessagingReport {
+        self.pool.install(messaging::messaging_report)
+    }
+
+    /// Id for a new index search, abandoning the previous one
+    pub fn begin_search(&self) -> u64 {
+        self.search.begin()
+    }
+
+    /// Search file names in the snapshots under `snapshots` on the scanner threads
+    pub fn search_index(
+        &self,
+        snapshots: &Path,
+        search_id: u64,
+        query: &str,
+        options: &SearchOptions,
+        on_batch: &(dyn Fn(SearchBatch) + Sync),
+    ) -> Result<SearchSummary, DuneError> {
+        self.pool.install(|| {
+            self.search
+                .search(snapshots, search_id, query, options, on_batch)
+        })
+    }
+
+    /// Search the index and the OS search index side by side, merging the hits by path
+    pub fn os_search(
+        &self,
+        snapshots: &Path,
+        search_id: u64,
+        query: &str,
+        options: &SearchOptions,
+    ) -> Result<OsSearchResult, DuneError> {
+        let parsed = Query::parse(query, options.text_mode())?;
+        let index_hits = Mutex::new(Vec::new());
+        let (index, os) = self.pool.install(|| {
+            rayon::join(
+                || {
+                    self.search
+                        .search(snapshots, search_id, query, options, &|batch| {
+                            index_hits.lock().unwrap().extend(batch.hits)
+                        })
+                },
+                || os_search::os_hits(&parsed, options),
+            )
+        });
+
+        Ok(os_search::merge(
+            index?,
+            index_hits.into_inner().unwrap(),
+            os,
+            options,
+        ))
+    }
+
+    /// Gather the matches of an index search into one tree, on the scanner threads;
+    /// the tree is kept under a fresh scan id like a finished scan
+    pub fn search_tree(
+        &self,
+        snapshots: &Path,
+        search_id: u64,
+        query: &str,
+        options: &SearchOptions,
+    ) -> Result<SearchTree, DuneError> {
+        let scan_id = self.next_scan_id.fetch_add(1, Ordering::Relaxed);
+        let result = self.pool.install(|| {
+            self.search
+                .search_tree(snapshots, search_id, scan_id, query, options)
+        })?;
+
+        // Kept like a finished scan so it can be snapshotted or exported
+        self.finished
+            .lock()
+            .unwrap()
+            .put(scan_id, result.tree.clone());
+
+        Ok(result)
+    }
+
+    /// Running scans stuck on one folder for longer than the stall timeout, each stall
+    /// once
+    pub fn stalled_scans(&self) -> Vec<ScanStalled> {
+        let seconds = self.performance.lock().unwrap().stall_timeout_seconds;
+        if seconds == 0 {
+            return Vec::new();
+        }
+
+        let timeout = Duration::from_secs(u64::from(seconds));
+        self.scans
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|handle| handle.stalled(timeout))
+            .collect()
+    }
+
+    pub fn register_scan(&self, root: String) -> Arc<ScanHandle> {
+        let id = self.next_scan_id.fetch_add(1, Ordering::Relaxed);
+        let handle = Arc::new(ScanHandle::new(id, root));
+
+        self.scans.lock().unwrap().insert(id, handle.clone());
+
+        handle
+    }
+
+    /// Grep the text files under a registered handle's root on the scanner threads
+    pub fn run_grep(
+        &self,
+        handle: &ScanHandle,
+        pattern: &str,
+        options: &GrepOptions,
+        on_file: &(dyn Fn(GrepFile) + Sync),
+    ) -> Result<GrepSummary, DuneError> {
+        let result = self
+            .pool
+            .install(|| grep::grep(handle, pattern, options, on_file));
+
+        self.scans.lock().unwrap().remove(&handle.id);
+
+        result
+    }
+
+    /// Compare the handle's root with `b` on the scanner threads
+    pub fn compare_dirs(
+        &self,
+        handle: &ScanHandle,
+        b: &str,
+        options: &CompareOptions,
+    ) -> Result<DirDiff, DuneError> {
+        let result = self
+            .pool
+            .install(|| compare::compare_dirs(handle, b, options));
+
+        self.scans.lock().unwrap().remove(&handle.id);
+
+        result
+    }
+
+    /// Verify a backup of the handle's root on the scanner threads
+    pub fn verify_backup(
+        &self,
+        handle: &ScanHandle,
+        backup: &str,
+        options: &VerifyOptions,
+        on_progress: &(dyn Fn(VerifyProgress) + Sync),
+    ) -> Result<VerifySummary, DuneError> {
+        let result = self
+            .pool
+            .install(|| verify::verify_backup(handle, backup, options, on_progress));
+
+        self.scans.lock().unwrap().remove(&handle.id);
+
+        result
+    }
+
+    /// Run a registered scan to completion, with fewer threads and paced IO when saving power
+    pub fn run_scan(
+        &self,
+        handle: &ScanHandle,
+        options: &ScanOptions,
+        on_progress: &(dyn Fn(ScanProgress) + Sync),
+    ) -> Result<Arc<ScanTree>, DuneError> {
+        let performance = *self.performance.lock().unwrap();
+        let threads = if mtp::is_slow_device(&handle.root) {
+            mtp::DEVICE_SCAN_THREADS
+        } else {
+            performance
+                .scan_threads
+                .unwrap_or_else(|| self.pool.current_num_threads())
+        };
+        let budget = power::scan_budget(*self.power_mode.lock().unwrap(), threads);
+        let enumerator = ThrottledEnumerator::new(scanner::default_enumerator(), &budget)
+            .with_limit(&self.open_files);
+        let options = &ScanOptions {
+            progress_events_per_second: options
+                .progress_events_per_second
+                .min(performance.max_events_per_second),
+            ..options.clone()
+        };
+
+        if budget.throttled() {
+            log::info!("Scan {} throttled to {} threads", handle.id, budget.threads);
+        }
+
+        let started = Instant::now();
+        let result = if remote::is_remote(&handle.root) {
+            remote::scan_tree(handle, options, on_progress)
+        } else if s3::is_bucket(&handle.root) {
+            s3::scan_tree(handle, options, on_progress)
+        } else if handle.root.starts_with(mtp::SCHEME) {
+            mtp::scan_tree(handle, options, on_progress)
+        } else {
+            scan::scan_tree(handle, options, budget.threads, &enumerator, on_progress)
+        };
+
+        self.scans.lock().unwrap().remove(&handle.id);
+
+        let mut tree = result?;
+        elevated::forget(&handle.root);
+        tree.apply_noise_floor(options.noise_floor);
+        let tree = Arc::new(tree);
+        self.finished.lock().unwrap().put(handle.id, tree.clone());
+        if let Some(root) = tree.nodes.first() {
+            self.record_scan(
+                &handle.root,
+                ScanRecord {
+                    bytes: root.size,
+                    files: Some(root.file_count),
+                    duration: started.elapsed(),
+                    complete: root.size_complete,
+                    finished_at: scan::millis_since_epoch(SystemTime::now()).unwrap_or(0),
+                },
+            );
+        }
+
+        Ok(tree)
+    }
+
+    /// Load another tool's report as a finished scan, so it can be shown, snapshotted or exported
+    pub fn import_scan(
+        &self,
+        path: &str,
+        format: ImportFormat,
+    ) -> Result<Arc<ScanTree>, DuneError> {
+        let id = self.next_scan_id.fetch_add(1, Ordering::Relaxed);
+        let tree = Arc::new(import::import_scan(id, Path::new(path), format)?);
+        self.finished.lock().unwrap().put(id, tree.clone());
+
+        Ok(tree)
+    }
+
+    /// Scan `path` as administrator, see `elevated::scan`; kept like any other finished scan
+    pub fn elevated_scan(
+        &self,
+        work_dir: &Path,
+        path: &str,
+        options: &ScanOptions,
+    ) -> Result<Arc<ScanTree>, DuneError> {
+        let id = self.next_scan_id.fetch_add(1, Ordering::Relaxed);
+        let mut tree = elevated::scan(work_dir, id, path, options)?;
+        tree.apply_noise_floor(options.noise_floor);
+        let tree = Arc::new(tree);
+        self.finished.lock().unwrap().put(id, tree.clone());
+
+        Ok(tree)
+    }
+
+    /// Join finished scans under one synthetic root, kept like any other finished scan
+    pub fn combine_scans(&self, trees: &[Arc<ScanTree>]) -> Arc<ScanTree> {
+        let id = self.next_scan_id.fetch_add(1, Ordering::Relaxed);
+        let tree = Arc::new(multi::combine(id, trees));
+        self.finished.lock().unwrap().put(id, tree.clone());
+
+        tree
+    }
+
+    /// Remember the outcome of a scan of `root`, replacing the previous one
+    pub fn record_scan(&self, root: &str, record: ScanRecord) {
+        self.last_scans
+            .lock()
+            .unwrap()
+            .put(root.to_string(), record);
+    }
+
+    pub fn last_scan(&self, root: &str) -> Option<ScanRecord> {
+        self.last_scans.lock().unwrap().peek(root).cloned()
+    }
+
+    /// Folders with a live watcher
+    pub fn watched_roots(&self) -> Vec<String> {
+        self.watchers
+            .lock()
+            .unwrap()
+            .keys()
+            .map(|root| root.to_string_lossy().to_string())
+            .collect()
+    }
+
+    /// The node at `path` in the newest finished scan that covers it, scanning `path`
+    /// when none does
+    pub fn subtree(&self, path: &str) -> Result<(Arc<ScanTree>, u32), DuneError> {
+        let cached = self.finished.lock().unwrap().iter().find_map(|(_, tree)| {
+            tree.find(Path::new(path))
+                .map(|index| (tree.clone(), index))
+        });
+        if let Some(found) = cached {
+            return Ok(found);
+        }
+
+        Ok((self.rescan(path)?, 0))
+    }
+
+    /// A new scan of `path` with its saved options, for callers that must not see an
+    /// older one
+    pub fn rescan(&self, path: &str) -> Result<Arc<ScanTree>, DuneError> {
+        let options = settings::load().scan.options_for(path, None)?;
+        let handle = self.register_scan(path.to_string());
+        self.run_scan(&handle, &options, &|_| {})
+    }
+
+    /// The finished scan used most recently
+    pub fn latest_scan(&self) -> Option<Arc<ScanTree>> {
+        self.finished
+            .lock()
+            .unwrap()
+            .iter()
+            .next()
+            .map(|(_, tree)| tree.clone())
+    }
+
+    /// A recently finished scan, if it has not been evicted yet
+    pub fn finished_scan(&self, scan_id: u64) -> Result<Arc<ScanTree>, DuneError> {
+        self.finished
+            .lock()
+            .unwrap()
+            .get(&scan_id)
+            .cloned()
+            .ok_or_else(|| DuneError::NotFound {
+                path: format!("scan {}", scan_id),
+            })
+    }
+
+    /// A finished scan with its child lists, built on first use
+    fn indexed_scan(&self, scan_id: u64) -> Result<(Arc<ScanTree>, Arc<ChildIndex>), DuneError> {
+        let tree = self.finished_scan(scan_id)?;
+        let cached = self.child_indexes.lock().unwrap().get(&scan_id).cloned();
+        let index = match cached {
+            Some(index) => index,
+            None => {
+                let index = Arc::new(ChildIndex::new(&tree));
+                self.child_indexes
+                    .lock()
+                    .unwrap()
+                    .put(scan_id, index.clone());
+                index
+            }
+        };
+
+        Ok((tree, index))
+    }
+
+    /// Ring segments of a finished scan around `focus_path`, see `ring::level`
+    pub fn ring_level(
+        &self,
+        scan_id: u64,
+        focus_path: &str,
+        depth: u32,
+        min_angle: f64,
+        style: &RingStyle,
+    ) -> Result<RingLevel, DuneError> {
+        let (tree, index) = self.indexed_scan(scan_id)?;
+        ring::level(&tree, &index, focus_path, depth, min_angle, style)
+    }
+
+    /// Hilbert-curve block map of a finished scan, see `disk_map::map`
+    pub fn disk_map(
+        &self,
+        scan_id: u64,
+        focus_path: Option<&str>,
+        order: u32,
+        style: &RingStyle,
+    ) -> Result<DiskMap, DuneError> {
+        let (tree, index) = self.indexed_scan(scan_id)?;
+        disk_map::map(
+            &tree,
+            &index,
+            focus_path.unwrap_or(&tree.root),
+            order,
+            style,
+        )
+    }
+
+    pub fn cancel_scan(&self, scan_id: u64) -> bool {
+        match self.scans.lock().unwrap().get(&scan_id) {
+            Some(handle) => {
+                handle.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Give up on `path` in scan `scan_id`, see `ScanHandle::skip`
+    pub fn skip_path(&self, scan_id: u64, path: &str) -> bool {
+        match self.scans.lock().unwrap().get(&scan_id) {
+            Some(handle) => {
+                let skipped = handle.skip(Path::new(path));
+                if skipped {
+                    log::info!("Skipping {} in scan {}", path, scan_id);
+                }
+                skipped
+            }
+            None => false,
+        }
+    }
+
+    /// Prioritize `path` in every running scan that contains it
+    pub fn boost_priority(&self, path: &str) -> bool {
+        let path = Path::new(path);
+        let mut boosted = false;
+
+        for handle in self.scans.lock().unwrap().values() {
+            if handle.boost(path) {
+                log::debug!("Boosted {} in scan {}", path.display(), handle.id);
+                boosted = true;
+            }
+        }
+
+        boosted
+    }
+
+    /// Forget cached listings of `path` and its ancestors after the app changed it
+    pub fn invalidate_listings(&self, path: &Path) {
+        self.listings.lock().unwrap().invalidate(path);
+    }
+
+    /// Watch `path` recursively, invalidating cached listings and emitting `watch://changed`
+    pub fn watch(&self, app: AppHandle, path: &str) -> Result<(), DuneError> {
+        if self.watchers.lock().unwrap().contains_key(Path::new(path)) {
+            return Ok(());
+        }
+
+        self.start_watcher(app, path, None)
+    }
+
+    /// Watch `path` like `watch`, and also keep its totals fresh: changes re-measure only
+    /// the child they happened in, and `favorites://updated` carries the new totals
+    pub fn watch_favorite(&self, app: AppHandle, path: &str) -> Result<(), DuneError> {
+        let root = PathBuf::from(path);
+        if self.favorites.lock().unwrap().contains(&root) {
+            return Ok(());
+        }
+
+        let cached = self
+            .finished
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(_, tree)| tree.find(&root).is_some())
+            .map(|(_, tree)| tree.clone());
+        let updater = favorites::spawn_updater(app.clone(), root.clone(), cached);
+        // Replaces a plain watcher on the same folder
+        self.start_watcher(app, path, Some(updater))?;
+        self.favorites.lock().unwrap().insert(root);
+
+        Ok(())
+    }
+
+    fn start_watcher(
+        &self,
+        app: AppHandle,
+        path: &str,
+        favorite: Option<std::sync::mpsc::Sender<Vec<PathBuf>>>,
+    ) -> Result<(), DuneError> {
+        let root = PathBuf::from(path);
+        let mut watchers = self.watchers.lock().unwrap();
+
+        let listings = self.listings.clone();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else {
+                    return;
+                };
+
+                {
+                    let mut listings = listings.lock().unwrap();
+                    for changed in &event.paths {
+                        listings.invalidate(changed);
+                    }
+                }
+
+                if let Some(updater) = &favorite {
+                    let _ = updater.send(event.paths.clone());
+                }
+
+                let paths: Vec<String> = event
+                    .paths
+                    .iter()
+                    .map(|changed| changed.to_string_lossy().to_string())
+                    .collect();
+                let _ = app.emit("watch://changed", paths);
+            })
+            .map_err(DuneError::internal)?;
+
+        watcher
+            .watch(&root, RecursiveMode::Recursive)
+            .map_err(|e| match e.kind {
+                notify::ErrorKind::PathNotFound => DuneError::NotFound { path: path.into() },
+                _ => DuneError::internal(e),
+            })?;
+
+        log::info!("Watching {}", path);
+        watchers.insert(root, watcher);
+
+        Ok(())
+    }
+
+    /// Rough heap use of scan trees, indexes and caches, and the app's resident size
+    pub fn memory_stats(&self) -> MemoryStats {
+        let mut scans: Vec<ScanMemory> = self
+            .scans
+            .lock()
+            .unwrap()
+            .values()
+            .map(|handle| ScanMemory {
+                scan_id: handle.id,
+                root: handle.root.clone(),
+                nodes: handle.nodes(),
+                bytes: handle.nodes() * std::mem::size_of::<scan::TreeNode>() as u64,
+                running: true,
+            })
+            .collect();
+        scans.sort_by_key(|scan| scan.scan_id);
+        scans.extend(
+            self.finished
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(_, tree)| ScanMemory {
+                    scan_id: tree.scan_id,
+                    root: tree.root.clone(),
+                    nodes: tree.nodes.len() as u64,
+                    bytes: tree.heap_bytes(),
+                    running: false,
+                }),
+        );
+
+        let child_indexes = {
+            let indexes = self.child_indexes.lock().unwrap();
+            CacheMemory {
+                entries: indexes.len(),
+                bytes: indexes.iter().map(|(_, index)| index.heap_bytes()).sum(),
+            }
+        };
+        let listings = self.listings.lock().unwrap().memory();
+        let (trees, bytes) = self.search.memory();
+
+        MemoryStats::new(
+            scans,
+            child_indexes,
+            listings,
+            CacheMemory {
+                entries: trees,
+                bytes,
+            },
+        )
+    }
+
+    /// Drop cached listings, ring indexes, search trees and every finished scan but the
+    /// newest, which the ring view is most likely showing; running scans are untouched
+    pub fn trim_memory(&self) -> MemoryStats {
+        let before = self.memory_stats().tracked_bytes;
+        {
+            let mut finished = self.finished.lock().unwrap();
+            // Most recently used first
+            let newest = finished.iter().next().map(|(id, tree)| (*id, tree.clone()));
+            finished.clear();
+            if let Some((id, tree)) = newest {
+                finished.put(id, tree);
+            }
+        }
+        self.child_indexes.lock().unwrap().clear();
+        self.listings.lock().unwrap().clear();
+        self.search.clear();
+        memory::release_freed();
+
+        let stats = self.memory_stats();
+        log::info!(
+            "Trimmed memory from about {} to {} bytes",
+            before,
+            stats.tracked_bytes
+        );
+        stats
+    }
+
+    /// Cancel scans and stop watchers under `mount`, whose open folders would keep the
+    /// volume from ejecting
+    pub fn release_volume(&self, mount: &Path) {
+        for handle in self.scans.lock().unwrap().values() {
+            if Path::new(&handle.root).starts_with(mount) {
+                handle.cancel();
+            }
+        }
+        self.favorites
+            .lock()
+            .unwrap()
+            .retain(|root| !root.starts_with(mount));
+        self.watchers
+            .lock()
+            .unwrap()
+            .retain(|root, _| !root.starts_with(mount));
+    }
+
+    pub fn unwatch(&self, path: &str) {
+        self.favorites.lock().unwrap().remove(Path::new(path));
+        if self
+            .watchers
+            .lock()
+            .unwrap()
+            .remove(Path::new(path))
+            .is_some()
+        {
+            log::info!("Stopped watching {}", path);
+        }
+    }
+}
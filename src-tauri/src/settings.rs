@@ -0,0 +1,412 @@
+use crate::cleanup::CleanupRule;
+use crate::colors::Theme;
+use crate::error::DuneError;
+use crate::format::Units;
+use crate::scan::ScanOptions;
+use crate::webhooks::Webhook;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub pixel_size: f32,
+    pub dither_strength: f32,
+    pub gloom: f32,
+    pub contrast: f32,
+    pub theme: Theme,
+    /// Threshold textures and color steps, see `generate_dither_assets`
+    pub dither: DitherSettings,
+    /// KiB or KB in every size the app writes out
+    pub units: Units,
+    /// Locale of decimal marks and digit grouping, such as `de-DE`; empty follows the system
+    pub locale: String,
+    /// Language of messages, notifications and reports, such as `de`; empty follows the
+    /// locale, then the system
+    pub language: String,
+    pub background: BackgroundSettings,
+    pub scan: ScanSettings,
+    pub performance: PerformanceSettings,
+    pub monitoring: MonitoringSettings,
+    pub recently_removed: RemovedSettings,
+    pub api: ApiSettings,
+    /// List tmpfs and RAM disks with the other disks instead of leaving them out
+    pub include_ram_disks: bool,
+    /// Names and default folders the user gave disks, keyed by mount point
+    pub disks: BTreeMap<String, DiskOverride>,
+    /// Called from the background scheduler on scans, low space and growth
+    pub webhooks: Vec<Webhook>,
+    /// Suggest cleaning up files that match, see `cleanup`
+    pub cleanup_rules: Vec<CleanupRule>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            pixel_size: 3.0,
+            dither_strength: 0.9,
+            gloom: 0.15,
+            contrast: 0.75,
+            theme: Theme::default(),
+            dither: DitherSettings::default(),
+            units: Units::Binary,
+            locale: String::new(),
+            language: String::new(),
+            background: BackgroundSettings::default(),
+            scan: ScanSettings::default(),
+            performance: PerformanceSettings::default(),
+            monitoring: MonitoringSettings::default(),
+            recently_removed: RemovedSettings::default(),
+            api: ApiSettings::default(),
+            include_ram_disks: false,
+            disks: BTreeMap::new(),
+            webhooks: Vec::new(),
+            cleanup_rules: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DitherPattern {
+    /// Ordered dithering with a regular cross-hatch look
+    #[default]
+    Bayer,
+    /// Finer grain without visible repetition
+    BlueNoise,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DitherSettings {
+    pub pattern: DitherPattern,
+    /// Side of the Bayer matrix, a power of two from 2 to 16
+    pub bayer_size: u32,
+    /// Side of the blue-noise tile, 16 to 128
+    pub noise_size: u32,
+    /// Color steps per channel after dithering
+    pub levels: u32,
+}
+
+impl Default for DitherSettings {
+    fn default() -> Self {
+        Self {
+            pattern: DitherPattern::Bayer,
+            bayer_size: 4,
+            noise_size: 64,
+            levels: 6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackgroundSettings {
+    /// Keep running in the tray with scheduled scans when the window is closed
+    pub enabled: bool,
+    /// Start the app at login, minimized to the tray
+    pub launch_at_login: bool,
+    pub scan_interval_minutes: u32,
+    /// Folders re-measured on every scheduled scan
+    pub scheduled_paths: Vec<String>,
+}
+
+impl Default for BackgroundSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            launch_at_login: false,
+            scan_interval_minutes: 60,
+            scheduled_paths: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanSettings {
+    /// Memory budget for cached directory listings
+    pub cache_budget_mb: u64,
+    pub power_mode: PowerMode,
+    /// Named scan options, such as following links for projects or skipping hidden
+    /// files on a NAS
+    pub profiles: BTreeMap<String, ScanOptions>,
+    /// Profile by scan root, applied whenever that root is scanned without options
+    pub root_profiles: BTreeMap<String, String>,
+    /// Allow deleting, trashing and moving files found by an elevated scan; off by default
+    pub elevated_file_operations: bool,
+}
+
+impl Default for ScanSettings {
+    fn default() -> Self {
+        Self {
+            cache_budget_mb: 64,
+            power_mode: PowerMode::Auto,
+            profiles: BTreeMap::new(),
+            root_profiles: BTreeMap::new(),
+            elevated_file_operations: false,
+        }
+    }
+}
+
+/// `/data/` and `/data` are the same root, but `/` stays `/`
+fn root_key(root: &str) -> &str {
+    let trimmed = root.trim_end_matches(['/', '\\']);
+    if trimmed.is_empty() || trimmed.ends_with(':') {
+        root
+    } else {
+        trimmed
+    }
+}
+
+impl ScanSettings {
+    /// Options of the `profile` asked for, else of the profile chosen for `root`, else
+    /// the defaults
+    pub fn options_for(&self, root: &str, profile: Option<&str>) -> Result<ScanOptions, DuneError> {
+        if let Some(name) = profile {
+            return self
+                .profiles
+                .get(name)
+                .cloned()
+                .ok_or_else(|| DuneError::InvalidInput(format!("No scan profile named {}", name)));
+        }
+
+        let key = root_key(root);
+        let chosen = self
+            .root_profiles
+            .iter()
+            .find(|(profile_root, _)| root_key(profile_root) == key);
+        match chosen {
+            Some((_, name)) => Ok(self.profiles.get(name).cloned().unwrap_or_else(|| {
+                log::warn!("Scan profile {} of {} no longer exists", name, root);
+                ScanOptions::default()
+            })),
+            None => Ok(ScanOptions::default()),
+        }
+    }
+}
+
+/// How hard scans may push the machine; applies from the next scan, no restart needed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PerformanceSettings {
+    /// Directory-reading threads per scan; `None` uses one per CPU
+    pub scan_threads: Option<usize>,
+    /// Directories open at once across all scans, each holding a file descriptor
+    pub max_open_files: usize,
+    /// Cap on `scan://progress` events per second, whatever a scan's options ask for
+    pub max_events_per_second: u32,
+    /// A folder read taking longer than this sends `scan://stalled`; 0 never does
+    pub stall_timeout_seconds: u32,
+}
+
+impl PerformanceSettings {
+    pub const SCAN_THREADS: RangeInclusive<usize> = 1..=128;
+    /// macOS starts apps with a limit of 256 descriptors, which the rest of the app
+    /// shares
+    pub const MAX_OPEN_FILES: RangeInclusive<usize> = 8..=4096;
+    pub const MAX_EVENTS_PER_SECOND: RangeInclusive<u32> = 1..=60;
+    pub const STALL_TIMEOUT_SECONDS: RangeInclusive<u32> = 0..=3600;
+
+    pub fn validate(&self) -> Result<(), DuneError> {
+        fn check<T: PartialOrd + std::fmt::Display>(
+            name: &str,
+            value: T,
+            range: RangeInclusive<T>,
+        ) -> Result<(), DuneError> {
+            if range.contains(&value) {
+                return Ok(());
+            }
+            Err(DuneError::InvalidInput(format!(
+                "performance.{} must be between {} and {}, not {}",
+                name,
+                range.start(),
+                range.end(),
+                value
+            )))
+        }
+
+        if let Some(threads) = self.scan_threads {
+            check("scan_threads", threads, Self::SCAN_THREADS)?;
+        }
+        check("max_open_files", self.max_open_files, Self::MAX_OPEN_FILES)?;
+        check(
+            "max_events_per_second",
+            self.max_events_per_second,
+            Self::MAX_EVENTS_PER_SECOND,
+        )?;
+        check(
+            "stall_timeout_seconds",
+            self.stall_timeout_seconds,
+            Self::STALL_TIMEOUT_SECONDS,
+        )
+    }
+}
+
+impl Default for PerformanceSettings {
+    fn default() -> Self {
+        Self {
+            scan_threads: None,
+            max_open_files: 128,
+            max_events_per_second: 30,
+            stall_timeout_seconds: 30,
+        }
+    }
+}
+
+/// The app's "Recently removed" folder that `soft_delete_paths` moves items into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemovedSettings {
+    /// Days an item stays restorable before it is deleted for good
+    pub retention_days: u32,
+}
+
+impl Default for RemovedSettings {
+    fn default() -> Self {
+        Self { retention_days: 7 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MonitoringSettings {
+    /// Check free space on every scheduler tick, window open or not
+    pub enabled: bool,
+    /// Applies to disks without an entry in `disks`
+    pub default_threshold: SpaceThreshold,
+    /// Per-disk thresholds, keyed by mount point
+    pub disks: BTreeMap<String, SpaceThreshold>,
+    /// Fire the `growth` webhook when a scheduled folder grows by more than this
+    /// between two scheduled scans
+    pub growth_threshold_gb: Option<f64>,
+    /// Most bytes each folder should hold, keyed by path; checked after scheduled scans,
+    /// see `budgets`
+    pub budgets: BTreeMap<String, u64>,
+}
+
+impl Default for MonitoringSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            default_threshold: SpaceThreshold {
+                min_free_gb: Some(10.0),
+                min_free_percent: None,
+            },
+            disks: BTreeMap::new(),
+            growth_threshold_gb: None,
+            budgets: BTreeMap::new(),
+        }
+    }
+}
+
+impl MonitoringSettings {
+    pub fn threshold_for(&self, mount_point: &str) -> &SpaceThreshold {
+        self.disks
+            .get(mount_point)
+            .unwrap_or(&self.default_threshold)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiskOverride {
+    /// Shown instead of the volume label, such as `Backup HDD` for `Elements 2620`
+    pub name: Option<String>,
+    /// Opened when the disk is clicked, relative to its mount point
+    pub default_folder: Option<String>,
+}
+
+/// Localhost HTTP API for scripts and monitoring tools
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiSettings {
+    pub enabled: bool,
+    /// Bound on 127.0.0.1 only
+    pub port: u16,
+    /// Expected as `Authorization: Bearer <token>`; generated when the API is first enabled
+    pub token: String,
+    /// Also serve Prometheus metrics at `/metrics`, behind the same token
+    pub metrics: bool,
+}
+
+impl Default for ApiSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 47_815,
+            token: String::new(),
+            metrics: false,
+        }
+    }
+}
+
+/// A disk is low once it drops below either limit; unset limits are ignored
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpaceThreshold {
+    pub min_free_gb: Option<f64>,
+    pub min_free_percent: Option<f64>,
+}
+
+impl SpaceThreshold {
+    /// Free bytes below which a disk of `total_space` bytes counts as low
+    pub fn limit_bytes(&self, total_space: u64) -> u64 {
+        let by_size = self
+            .min_free_gb
+            .map_or(0, |gb| (gb.max(0.0) * 1024.0 * 1024.0 * 1024.0) as u64);
+        let by_percent = self.min_free_percent.map_or(0, |percent| {
+            (total_space as f64 * percent.clamp(0.0, 100.0) / 100.0) as u64
+        });
+
+        by_size.max(by_percent)
+    }
+}
+
+/// How much of the machine scans may use
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerMode {
+    /// Throttle on battery or under thermal pressure
+    #[default]
+    Auto,
+    /// Always use every core
+    Performance,
+    /// Always throttle
+    Saver,
+}
+
+fn get_settings_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|p| p.join("spaceringscene").join("settings.json"))
+}
+
+pub fn load() -> Settings {
+    let Some(path) = get_settings_path() else {
+        return Settings::default();
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+        Err(_) => Settings::default(),
+    }
+}
+
+pub fn save(settings: &Settings) -> Result<(), DuneError> {
+    let Some(path) = get_settings_path() else {
+        return Err(DuneError::Internal(
+            "Could not determine config directory".into(),
+        ));
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| DuneError::io(parent, e))?;
+    }
+
+    let json = serde_json::to_string_pretty(settings).map_err(DuneError::internal)?;
+    fs::write(&path, json).map_err(|e| DuneError::io(&path, e))?;
+
+    Ok(())
+}
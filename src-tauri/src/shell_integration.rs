@@ -0,0 +1,256 @@
+use crate::error::DuneError;
+use std::path::Path;
+
+#[cfg(target_os = "macos")]
+use std::fs;
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+use std::path::PathBuf;
+#[cfg(target_os = "windows")]
+use std::process::Command;
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+const MENU_LABEL: &str = "Scan with Dunefiles";
+
+#[cfg(target_os = "windows")]
+const REGISTRY_KEYS: [&str; 2] = [
+    r"HKCU\Software\Classes\Directory\shell\Dunefiles",
+    r"HKCU\Software\Classes\Directory\Background\shell\Dunefiles",
+];
+
+/// Explorer quotes a drive root as `"C:\"`, whose `\"` escapes the closing quote and
+/// arrives as `C:"`; quotes cannot appear in Windows paths, so it is always that backslash
+fn unquoted(arg: &str) -> String {
+    match arg.strip_suffix('"') {
+        Some(drive) if cfg!(windows) => format!("{}\\", drive),
+        _ => arg.to_string(),
+    }
+}
+
+/// Pick the folder the app was asked to open from launch arguments
+pub fn folder_from_args(args: &[String]) -> Option<String> {
+    args.iter()
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-'))
+        .map(|arg| unquoted(arg))
+        .find(|arg| Path::new(arg).is_dir())
+}
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+fn current_exe() -> Result<PathBuf, DuneError> {
+    std::env::current_exe().map_err(DuneError::internal)
+}
+
+#[cfg(target_os = "windows")]
+fn reg(args: &[&str]) -> Result<(), DuneError> {
+    let status = Command::new("reg")
+        .args(args)
+        .status()
+        .map_err(DuneError::internal)?;
+
+    if !status.success() {
+        return Err(DuneError::Internal(format!(
+            "reg {} failed with {}",
+            args[0], status
+        )));
+    }
+
+    Ok(())
+}
+
+/// Register the "Scan with Dunefiles" entry in the OS folder context menu
+#[cfg(target_os = "windows")]
+pub fn install() -> Result<(), DuneError> {
+    let exe = current_exe()?.to_string_lossy().to_string();
+
+    // Folder entries receive the folder as %1, background entries as %V
+    for (key, placeholder) in REGISTRY_KEYS.iter().zip(["%1", "%V"]) {
+        let command_key = format!(r"{}\command", key);
+        let command = format!("\"{}\" \"{}\"", exe, placeholder);
+
+        reg(&["add", key, "/ve", "/d", MENU_LABEL, "/f"])?;
+        reg(&["add", key, "/v", "Icon", "/d", &exe, "/f"])?;
+        reg(&["add", &command_key, "/ve", "/d", &command, "/f"])?;
+    }
+
+    Ok(())
+}
+
+/// Remove the context menu entry registered by `install`
+#[cfg(target_os = "windows")]
+pub fn uninstall() -> Result<(), DuneError> {
+    for key in REGISTRY_KEYS {
+        // Missing keys are fine, the entry is already gone
+        let _ = reg(&["delete", key, "/f"]);
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn workflow_dir() -> Result<PathBuf, DuneError> {
+    let Some(home) = dirs::home_dir() else {
+        return Err(DuneError::Internal(
+            "Could not determine home directory".into(),
+        ));
+    };
+
+    Ok(home
+        .join("Library")
+        .join("Services")
+        .join(format!("{}.workflow", MENU_LABEL)))
+}
+
+/// Locate the enclosing .app bundle so the service can hand folders to `open -a`
+#[cfg(target_os = "macos")]
+fn app_bundle() -> Result<PathBuf, DuneError> {
+    let exe = current_exe()?;
+
+    exe.ancestors()
+        .find(|ancestor| ancestor.extension().is_some_and(|ext| ext == "app"))
+        .map(Path::to_path_buf)
+        .ok_or_else(|| {
+            DuneError::Unsupported("Shell integration requires running from an app bundle".into())
+        })
+}
+
+#[cfg(target_os = "macos")]
+fn workflow_info_plist() -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>NSServices</key>
+	<array>
+		<dict>
+			<key>NSMenuItem</key>
+			<dict>
+				<key>default</key>
+				<string>{}</string>
+			</dict>
+			<key>NSMessage</key>
+			<string>runWorkflowAsService</string>
+			<key>NSRequiredContext</key>
+			<dict>
+				<key>NSApplicationIdentifier</key>
+				<string>com.apple.finder</string>
+			</dict>
+			<key>NSSendFileTypes</key>
+			<array>
+				<string>public.folder</string>
+			</array>
+		</dict>
+	</array>
+</dict>
+</plist>
+"#,
+        MENU_LABEL
+    )
+}
+
+#[cfg(target_os = "macos")]
+fn workflow_document(bundle: &Path) -> String {
+    // `open -n` starts a second instance, which the single-instance plugin
+    // forwards to the running app before exiting
+    let script = format!(
+        "for f in \"$@\"; do open -n -a '{}' --args \"$f\"; done",
+        bundle.to_string_lossy().replace('\'', r"'\''")
+    );
+    let script = script
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>AMApplicationBuild</key>
+	<string>521</string>
+	<key>AMApplicationVersion</key>
+	<string>2.10</string>
+	<key>AMDocumentVersion</key>
+	<string>2</string>
+	<key>actions</key>
+	<array>
+		<dict>
+			<key>action</key>
+			<dict>
+				<key>AMActionVersion</key>
+				<string>2.0.3</string>
+				<key>ActionBundlePath</key>
+				<string>/System/Library/Automator/Run Shell Script.action</string>
+				<key>ActionName</key>
+				<string>Run Shell Script</string>
+				<key>ActionParameters</key>
+				<dict>
+					<key>COMMAND_STRING</key>
+					<string>{}</string>
+					<key>inputMethod</key>
+					<integer>1</integer>
+					<key>shell</key>
+					<string>/bin/bash</string>
+				</dict>
+				<key>BundleIdentifier</key>
+				<string>com.apple.RunShellScript</string>
+			</dict>
+		</dict>
+	</array>
+	<key>workflowMetaData</key>
+	<dict>
+		<key>serviceInputTypeIdentifier</key>
+		<string>com.apple.Automator.fileSystemObject.folder</string>
+		<key>serviceApplicationBundleID</key>
+		<string>com.apple.finder</string>
+		<key>workflowTypeIdentifier</key>
+		<string>com.apple.Automator.servicesMenu</string>
+	</dict>
+</dict>
+</plist>
+"#,
+        script
+    )
+}
+
+/// Install a Finder Quick Action that opens the selected folder in the app
+#[cfg(target_os = "macos")]
+pub fn install() -> Result<(), DuneError> {
+    let bundle = app_bundle()?;
+    let contents = workflow_dir()?.join("Contents");
+
+    let info_plist = contents.join("Info.plist");
+    let document = contents.join("document.wflow");
+
+    fs::create_dir_all(&contents).map_err(|e| DuneError::io(&contents, e))?;
+    fs::write(&info_plist, workflow_info_plist()).map_err(|e| DuneError::io(&info_plist, e))?;
+    fs::write(&document, workflow_document(&bundle)).map_err(|e| DuneError::io(&document, e))?;
+
+    Ok(())
+}
+
+/// Remove the Finder Quick Action installed by `install`
+#[cfg(target_os = "macos")]
+pub fn uninstall() -> Result<(), DuneError> {
+    let dir = workflow_dir()?;
+
+    if dir.exists() {
+        fs::remove_dir_all(&dir).map_err(|e| DuneError::io(&dir, e))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub fn install() -> Result<(), DuneError> {
+    Err(DuneError::Unsupported(
+        "Shell integration is not supported on this platform".into(),
+    ))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub fn uninstall() -> Result<(), DuneError> {
+    Err(DuneError::Unsupported(
+        "Shell integration is not supported on this platform".into(),
+    ))
+}
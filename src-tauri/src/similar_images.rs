@@ -0,0 +1,198 @@
+use crate::error::DuneError;
+use crate::filetype::{self, FileCategory};
+use crate::scanner::{self, DirEntryInfo, DirEnumerator, EntryKind, Hidden};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Hashes at most this many bits apart are treated as the same shot
+const DEFAULT_MAX_DISTANCE: u32 = 6;
+/// Pairwise comparison is quadratic, so very large libraries are cut off
+const MAX_IMAGES: usize = 20_000;
+/// Tiny files are icons and thumbnails, not photos worth grouping
+const MIN_IMAGE_BYTES: u64 = 16 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimilarImage {
+    pub path: String,
+    pub bytes: u64,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimilarGroup {
+    /// Suggested keeper first: highest resolution, then largest file
+    pub images: Vec<SimilarImage>,
+    pub total_bytes: u64,
+    /// Space freed by keeping only the first image
+    pub reclaimable_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimilarImagesReport {
+    pub path: String,
+    /// Most reclaimable first
+    pub groups: Vec<SimilarGroup>,
+    pub images_hashed: u64,
+    /// Photos in formats that cannot be decoded here, e.g. HEIC and camera raw
+    pub images_skipped: u64,
+    pub reclaimable_bytes: u64,
+    /// False if folders were unreadable or the image limit was hit
+    pub complete: bool,
+}
+
+struct Candidate {
+    path: PathBuf,
+    bytes: u64,
+}
+
+struct Hashed {
+    image: SimilarImage,
+    hash: u64,
+}
+
+fn collect_photos(enumerator: &dyn DirEnumerator, path: &Path, found: &mut Vec<Candidate>) -> bool {
+    scanner::walk(
+        enumerator,
+        path,
+        Hidden::Skip,
+        &mut |path: &Path, entry: &DirEntryInfo| {
+            if entry.kind == EntryKind::File
+                && entry.len >= MIN_IMAGE_BYTES
+                && filetype::category_for(path) == FileCategory::Photos
+            {
+                found.push(Candidate {
+                    path: path.to_path_buf(),
+                    bytes: entry.len,
+                });
+            }
+            true
+        },
+    )
+}
+
+/// 64-bit difference hash: brightness gradients of a 9x8 grayscale thumbnail
+fn difference_hash(path: &Path) -> Option<(u64, u32, u32)> {
+    let decoded = image::ImageReader::open(path)
+        .ok()?
+        .with_guessed_format()
+        .ok()?
+        .decode()
+        .inspect_err(|err| log::debug!("Could not decode {}: {}", path.display(), err))
+        .ok()?;
+    let thumbnail = decoded.thumbnail_exact(9, 8).into_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = thumbnail.get_pixel(x, y)[0];
+            let right = thumbnail.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(left > right);
+        }
+    }
+
+    Some((hash, decoded.width(), decoded.height()))
+}
+
+fn find_root(parents: &mut [usize], mut index: usize) -> usize {
+    while parents[index] != index {
+        parents[index] = parents[parents[index]];
+        index = parents[index];
+    }
+    index
+}
+
+/// Group photos under `path` that look almost the same: bursts, re-exports, resized copies
+pub fn similar_images(
+    path: &str,
+    max_distance: Option<u32>,
+) -> Result<SimilarImagesReport, DuneError> {
+    let dir_path = scanner::require_dir(path)?;
+    let max_distance = max_distance.unwrap_or(DEFAULT_MAX_DISTANCE);
+    let mut candidates = Vec::new();
+    let mut complete = collect_photos(scanner::default_enumerator(), dir_path, &mut candidates);
+
+    if candidates.len() > MAX_IMAGES {
+        log::warn!(
+            "{} photos under {}, only comparing the first {}",
+            candidates.len(),
+            path,
+            MAX_IMAGES
+        );
+        candidates.truncate(MAX_IMAGES);
+        complete = false;
+    }
+
+    let hashed: Vec<Hashed> = candidates
+        .par_iter()
+        .filter_map(|candidate| {
+            let (hash, width, height) = difference_hash(&candidate.path)?;
+            Some(Hashed {
+                image: SimilarImage {
+                    path: candidate.path.to_string_lossy().to_string(),
+                    bytes: candidate.bytes,
+                    width,
+                    height,
+                },
+                hash,
+            })
+        })
+        .collect();
+    let images_skipped = (candidates.len() - hashed.len()) as u64;
+
+    let mut parents: Vec<usize> = (0..hashed.len()).collect();
+    for i in 0..hashed.len() {
+        for j in i + 1..hashed.len() {
+            if (hashed[i].hash ^ hashed[j].hash).count_ones() <= max_distance {
+                let (a, b) = (find_root(&mut parents, i), find_root(&mut parents, j));
+                parents[b] = a;
+            }
+        }
+    }
+
+    let mut members: Vec<Vec<SimilarImage>> = (0..hashed.len()).map(|_| Vec::new()).collect();
+    let images_hashed = hashed.len() as u64;
+    for (index, entry) in hashed.into_iter().enumerate() {
+        let root = find_root(&mut parents, index);
+        members[root].push(entry.image);
+    }
+
+    let mut groups: Vec<SimilarGroup> = members
+        .into_iter()
+        .filter(|images| images.len() > 1)
+        .map(|mut images| {
+            images.sort_by_key(|image| {
+                std::cmp::Reverse((
+                    u64::from(image.width) * u64::from(image.height),
+                    image.bytes,
+                ))
+            });
+            let total_bytes: u64 = images.iter().map(|image| image.bytes).sum();
+
+            SimilarGroup {
+                reclaimable_bytes: total_bytes - images[0].bytes,
+                total_bytes,
+                images,
+            }
+        })
+        .collect();
+
+    groups.sort_by_key(|group| std::cmp::Reverse(group.reclaimable_bytes));
+
+    log::info!(
+        "Found {} groups of similar photos among {} under {}",
+        groups.len(),
+        images_hashed,
+        path
+    );
+
+    Ok(SimilarImagesReport {
+        path: path.to_string(),
+        reclaimable_bytes: groups.iter().map(|group| group.reclaimable_bytes).sum(),
+        groups,
+        images_hashed,
+        images_skipped,
+        complete,
+    })
+}
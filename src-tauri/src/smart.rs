@@ -0,0 +1,365 @@
+use crate::error::DuneError;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Good,
+    /// Still working, but wearing out or remapping bad sectors
+    Warning,
+    /// The drive itself predicts failure
+    Failing,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiskHealth {
+    /// Mount point the readout was asked for
+    pub disk: String,
+    /// Physical drive behind it, such as `/dev/sda`, `disk0` or `PhysicalDisk1`
+    pub device: Option<String>,
+    pub model: Option<String>,
+    pub status: HealthStatus,
+    pub temperature_celsius: Option<i64>,
+    /// Bytes written over the drive's life
+    pub bytes_written: Option<u64>,
+    pub power_on_hours: Option<u64>,
+    /// Share of rated endurance used, past 100 on worn out SSDs
+    pub wear_percent: Option<u8>,
+    /// `smartctl`, `diskutil` or `storage` (Windows storage reliability counters)
+    pub source: String,
+}
+
+#[cfg(unix)]
+mod smartctl {
+    use super::*;
+    use serde::Deserialize;
+    use std::process::Command;
+
+    /// NVMe data units are thousands of 512 byte sectors
+    const NVME_DATA_UNIT: u64 = 512_000;
+    /// Reallocated, pending and uncorrectable sector counts on ATA drives
+    const BAD_SECTOR_ATTRIBUTES: [u32; 3] = [5, 197, 198];
+    /// `Total_LBAs_Written`
+    const LBAS_WRITTEN_ATTRIBUTE: u32 = 241;
+    /// Exit status bits for a bad command line or a device that could not be opened
+    const FAILED_TO_RUN: i32 = 0b11;
+
+    #[derive(Deserialize)]
+    struct Passed {
+        passed: bool,
+    }
+
+    #[derive(Deserialize)]
+    struct Temperature {
+        current: Option<i64>,
+    }
+
+    #[derive(Deserialize)]
+    struct PowerOnTime {
+        hours: Option<u64>,
+    }
+
+    #[derive(Deserialize)]
+    struct NvmeLog {
+        critical_warning: Option<u32>,
+        percentage_used: Option<u8>,
+        data_units_written: Option<u64>,
+    }
+
+    #[derive(Deserialize)]
+    struct Raw {
+        value: u64,
+    }
+
+    #[derive(Deserialize)]
+    struct Attribute {
+        id: u32,
+        raw: Raw,
+    }
+
+    #[derive(Deserialize)]
+    struct Attributes {
+        table: Vec<Attribute>,
+    }
+
+    #[derive(Deserialize)]
+    struct Report {
+        model_name: Option<String>,
+        smart_status: Option<Passed>,
+        temperature: Option<Temperature>,
+        power_on_time: Option<PowerOnTime>,
+        logical_block_size: Option<u64>,
+        nvme_smart_health_information_log: Option<NvmeLog>,
+        ata_smart_attributes: Option<Attributes>,
+    }
+
+    /// Read SMART data of `device` with smartmontools, which needs root for most drives
+    pub fn read(disk: &str, device: &str) -> Result<DiskHealth, DuneError> {
+        let output = Command::new("smartctl")
+            .args(["--json=c", "--all", device])
+            .output()
+            .map_err(|err| {
+                DuneError::Unsupported(format!(
+                    "Reading drive health needs smartctl from smartmontools: {}",
+                    err
+                ))
+            })?;
+        // A failing drive sets other status bits while still printing its report
+        if output
+            .status
+            .code()
+            .map_or(true, |code| code & FAILED_TO_RUN != 0)
+        {
+            return Err(DuneError::PermissionDenied {
+                path: device.to_string(),
+            });
+        }
+        let report: Report = serde_json::from_slice(&output.stdout)
+            .map_err(|e| DuneError::Internal(format!("Unexpected smartctl output: {}", e)))?;
+
+        let attribute = |id: u32| {
+            report
+                .ata_smart_attributes
+                .as_ref()
+                .and_then(|attributes| attributes.table.iter().find(|a| a.id == id))
+                .map(|attribute| attribute.raw.value)
+        };
+        let nvme = report.nvme_smart_health_information_log.as_ref();
+        let wear_percent = nvme.and_then(|log| log.percentage_used);
+        let bytes_written = match nvme.and_then(|log| log.data_units_written) {
+            Some(units) => Some(units.saturating_mul(NVME_DATA_UNIT)),
+            None => attribute(LBAS_WRITTEN_ATTRIBUTE)
+                .map(|lbas| lbas.saturating_mul(report.logical_block_size.unwrap_or(512))),
+        };
+        let worn = nvme.and_then(|log| log.critical_warning).unwrap_or(0) != 0
+            || wear_percent.is_some_and(|wear| wear >= 100)
+            || BAD_SECTOR_ATTRIBUTES
+                .iter()
+                .any(|&id| attribute(id).unwrap_or(0) > 0);
+        let status = match &report.smart_status {
+            Some(Passed { passed: false }) => HealthStatus::Failing,
+            _ if worn => HealthStatus::Warning,
+            Some(Passed { passed: true }) => HealthStatus::Good,
+            None => HealthStatus::Unknown,
+        };
+
+        Ok(DiskHealth {
+            disk: disk.to_string(),
+            device: Some(device.to_string()),
+            model: report.model_name,
+            status,
+            temperature_celsius: report.temperature.and_then(|t| t.current),
+            bytes_written,
+            power_on_hours: report.power_on_time.and_then(|time| time.hours),
+            wear_percent,
+            source: "smartctl".into(),
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    /// Block device mounted at `disk`, from the kernel's mount table
+    pub fn mounted_device(disk: &str) -> Option<String> {
+        let mounts = fs::read_to_string("/proc/self/mounts").ok()?;
+        mounts.lines().rev().find_map(|line| {
+            let mut fields = line.split_whitespace();
+            let device = fields.next()?;
+            let mount = fields.next()?.replace("\\040", " ");
+            (mount == disk && device.starts_with("/dev/")).then(|| device.to_string())
+        })
+    }
+
+    /// `/dev/sda` for `/dev/sda1`, `/dev/nvme0n1` for `/dev/nvme0n1p2`; SMART belongs to
+    /// the whole drive, and sysfs nests each partition under it
+    pub fn whole_drive(device: &str) -> String {
+        let path = fs::canonicalize(device).unwrap_or_else(|_| device.into());
+        let Some(name) = path.file_name() else {
+            return device.to_string();
+        };
+        let block = Path::new("/sys/class/block").join(name);
+        if !block.join("partition").exists() {
+            return path.to_string_lossy().into_owned();
+        }
+        fs::canonicalize(&block)
+            .ok()
+            .and_then(|sys| sys.parent()?.file_name().map(|parent| parent.to_owned()))
+            .map_or_else(
+                || device.to_string(),
+                |parent| {
+                    Path::new("/dev")
+                        .join(parent)
+                        .to_string_lossy()
+                        .into_owned()
+                },
+            )
+    }
+
+    pub fn read(disk: &str) -> Result<DiskHealth, DuneError> {
+        let device = mounted_device(disk).ok_or_else(|| DuneError::NotFound {
+            path: disk.to_string(),
+        })?;
+        smartctl::read(disk, &whole_drive(&device))
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub use platform::plist_string;
+#[cfg(target_os = "linux")]
+pub use platform::{mounted_device, whole_drive};
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::*;
+    use std::process::Command;
+
+    /// The `<string>` after `<key>key</key>` in a plist as `diskutil -plist` prints it
+    pub fn plist_string(plist: &str, key: &str) -> Option<String> {
+        let after = &plist[plist.find(&format!("<key>{}</key>", key))?..];
+        let start = after.find("<string>")? + "<string>".len();
+        let end = after[start..].find("</string>")?;
+        Some(after[start..start + end].to_string())
+    }
+
+    /// `diskutil` knows only whether SMART says the drive is failing; smartctl, when
+    /// installed, fills in the rest
+    pub fn read(disk: &str) -> Result<DiskHealth, DuneError> {
+        let output = Command::new("diskutil")
+            .args(["info", "-plist", disk])
+            .output()
+            .map_err(|e| DuneError::Internal(format!("Could not run diskutil: {}", e)))?;
+        if !output.status.success() {
+            return Err(DuneError::NotFound {
+                path: disk.to_string(),
+            });
+        }
+        let plist = String::from_utf8_lossy(&output.stdout);
+        let device = plist_string(&plist, "ParentWholeDisk");
+        let status = match plist_string(&plist, "SMARTStatus").as_deref() {
+            Some("Verified") => HealthStatus::Good,
+            Some("Failing") => HealthStatus::Failing,
+            _ => HealthStatus::Unknown,
+        };
+
+        if let Some(device) = &device {
+            match smartctl::read(disk, &format!("/dev/{}", device)) {
+                Ok(mut health) => {
+                    if status == HealthStatus::Failing {
+                        health.status = status;
+                    }
+                    return Ok(health);
+                }
+                Err(err) => log::debug!("No smartctl readout for {}: {}", device, err),
+            }
+        }
+
+        Ok(DiskHealth {
+            disk: disk.to_string(),
+            device,
+            model: plist_string(&plist, "MediaName"),
+            status,
+            temperature_celsius: None,
+            bytes_written: None,
+            power_on_hours: None,
+            wear_percent: None,
+            source: "diskutil".into(),
+        })
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::*;
+    use crate::console;
+    use serde::Deserialize;
+
+    /// Health and reliability counters of the drive holding a drive letter, as JSON;
+    /// the counters need administrator rights and come back empty without them
+    const HEALTH_SCRIPT: &str = "$ErrorActionPreference = 'Stop'; \
+        $number = (Get-Partition -DriveLetter $env:DUNE_DRIVE | Get-Disk).Number; \
+        $physical = Get-PhysicalDisk | Where-Object { $_.DeviceId -eq [string]$number }; \
+        $counter = $null; try { $counter = $physical | Get-StorageReliabilityCounter } catch {}; \
+        @{ device = \"PhysicalDisk$number\"; model = $physical.FriendlyName; health = [string]$physical.HealthStatus; \
+        temperature = $counter.Temperature; hours = $counter.PowerOnHours; wear = $counter.Wear } | ConvertTo-Json -Compress";
+
+    #[derive(Deserialize)]
+    struct Readout {
+        device: String,
+        model: Option<String>,
+        health: String,
+        temperature: Option<i64>,
+        hours: Option<u64>,
+        wear: Option<u8>,
+    }
+
+    /// Windows keeps no lifetime write count outside vendor tools, so `bytes_written`
+    /// stays empty
+    pub fn read(disk: &str) -> Result<DiskHealth, DuneError> {
+        let letter = disk
+            .chars()
+            .next()
+            .filter(char::is_ascii_alphabetic)
+            .ok_or_else(|| DuneError::InvalidInput(format!("{} is not a drive", disk)))?;
+        let output = console::powershell(HEALTH_SCRIPT)
+            .env("DUNE_DRIVE", letter.to_string())
+            .output()
+            .map_err(|e| DuneError::Internal(format!("Could not run PowerShell: {}", e)))?;
+        if !output.status.success() {
+            log::debug!(
+                "Drive health query failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return Err(DuneError::NotFound {
+                path: disk.to_string(),
+            });
+        }
+        let readout: Readout = serde_json::from_slice(&output.stdout)
+            .map_err(|e| DuneError::Internal(format!("Unexpected drive health output: {}", e)))?;
+
+        let status = match readout.health.as_str() {
+            "Healthy" if readout.wear.is_some_and(|wear| wear >= 100) => HealthStatus::Warning,
+            "Healthy" => HealthStatus::Good,
+            "Warning" => HealthStatus::Warning,
+            "Unhealthy" => HealthStatus::Failing,
+            _ => HealthStatus::Unknown,
+        };
+
+        Ok(DiskHealth {
+            disk: disk.to_string(),
+            device: Some(readout.device),
+            model: readout.model,
+            status,
+            temperature_celsius: readout.temperature.filter(|&t| t > 0),
+            bytes_written: None,
+            power_on_hours: readout.hours,
+            wear_percent: readout.wear,
+            source: "storage".into(),
+        })
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+mod platform {
+    use super::*;
+
+    pub fn read(_disk: &str) -> Result<DiskHealth, DuneError> {
+        Err(DuneError::Unsupported(
+            "Drive health is not available on this platform".into(),
+        ))
+    }
+}
+
+/// SMART health of the drive mounted at `disk`: status, temperature and lifetime writes
+pub fn disk_health(disk: &str) -> Result<DiskHealth, DuneError> {
+    let health = platform::read(disk)?;
+    if matches!(health.status, HealthStatus::Warning | HealthStatus::Failing) {
+        log::warn!("Drive behind {} reports {:?}", disk, health.status);
+    }
+
+    Ok(health)
+}
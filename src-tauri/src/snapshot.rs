@@ -0,0 +1,283 @@
+use crate::app_data;
+use crate::error::DuneError;
+use crate::scan::{self, ScanTree};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Runtime};
+
+/// File layout: magic, version (u16 LE), info length (u32 LE), MessagePack
+/// `SnapshotInfo`, then the zstd-compressed MessagePack `ScanTree`.
+///
+/// The info block stays uncompressed so listing snapshots never inflates a tree.
+const MAGIC: &[u8; 8] = b"DUNESNAP";
+const FORMAT_VERSION: u16 = 1;
+const HEADER_LEN: usize = MAGIC.len() + 2 + 4;
+const ZSTD_LEVEL: i32 = 3;
+const EXTENSION: &str = "dsnap";
+
+/// Summary of a stored snapshot, readable without decompressing the tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    pub id: String,
+    pub root: String,
+    /// Milliseconds since the Unix epoch
+    pub created_at: u64,
+    pub total_size: u64,
+    pub file_count: u64,
+    pub node_count: u64,
+}
+
+impl SnapshotInfo {
+    fn new(id: String, created_at: u64, tree: &ScanTree) -> Self {
+        let root = tree.nodes.first();
+
+        Self {
+            id,
+            root: tree.root.clone(),
+            created_at,
+            total_size: root.map_or(0, |node| node.size),
+            file_count: root.map_or(0, |node| node.file_count),
+            node_count: tree.nodes.len() as u64,
+        }
+    }
+}
+
+/// Snapshot as exchanged in JSON exports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub info: SnapshotInfo,
+    pub tree: ScanTree,
+}
+
+pub fn snapshot_dir<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, DuneError> {
+    app_data::path(app, "snapshots")
+}
+
+fn snapshot_path(dir: &Path, id: &str) -> Result<PathBuf, DuneError> {
+    // Ids come from the frontend, so keep them to plain file stems
+    if id.is_empty()
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(DuneError::NotFound { path: id.into() });
+    }
+
+    Ok(dir.join(format!("{}.{}", id, EXTENSION)))
+}
+
+fn invalid(path: &Path, message: &str) -> DuneError {
+    DuneError::Io {
+        path: Some(path.to_string_lossy().to_string()),
+        message: message.into(),
+    }
+}
+
+/// A tree from a file shares the invariants of a scanned one, or later path building and
+/// aggregation would index past the end or chase a parent cycle: the root first and alone
+/// without a parent, every other parent earlier than its child
+fn check_tree(path: &Path, tree: &ScanTree) -> Result<(), DuneError> {
+    match tree.nodes.first() {
+        None => return Err(invalid(path, "Snapshot has no nodes")),
+        Some(root) if root.parent.is_some() => {
+            return Err(invalid(path, "Snapshot root has a parent"))
+        }
+        Some(_) => {}
+    }
+
+    let misplaced = tree
+        .nodes
+        .iter()
+        .enumerate()
+        .skip(1)
+        .position(|(index, node)| node.parent.map_or(true, |parent| parent as usize >= index));
+    if let Some(offset) = misplaced {
+        return Err(invalid(
+            path,
+            &format!("Snapshot node {} has no parent before it", offset + 1),
+        ));
+    }
+    if tree
+        .small_items
+        .iter()
+        .any(|&index| index as usize >= tree.nodes.len())
+    {
+        return Err(invalid(path, "Snapshot lists a node it does not have"));
+    }
+
+    Ok(())
+}
+
+fn encode(snapshot: &Snapshot) -> Result<Vec<u8>, DuneError> {
+    let info = rmp_serde::to_vec_named(&snapshot.info).map_err(DuneError::internal)?;
+    let tree = rmp_serde::to_vec_named(&snapshot.tree).map_err(DuneError::internal)?;
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + info.len() + tree.len() / 4);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(info.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&info);
+
+    let mut encoder = zstd::Encoder::new(bytes, ZSTD_LEVEL).map_err(DuneError::internal)?;
+    encoder.write_all(&tree).map_err(DuneError::internal)?;
+    encoder.finish().map_err(DuneError::internal)
+}
+
+/// Check the header and return the info block length
+fn read_header(path: &Path, header: &[u8]) -> Result<usize, DuneError> {
+    if header.len() < HEADER_LEN || &header[..MAGIC.len()] != MAGIC {
+        return Err(invalid(path, "Not a dunefiles snapshot"));
+    }
+
+    let version = u16::from_le_bytes([header[8], header[9]]);
+    if version > FORMAT_VERSION {
+        return Err(invalid(
+            path,
+            &format!("Snapshot format {} is newer than this app", version),
+        ));
+    }
+
+    Ok(u32::from_le_bytes([header[10], header[11], header[12], header[13]]) as usize)
+}
+
+fn decode(path: &Path, bytes: &[u8]) -> Result<Snapshot, DuneError> {
+    let info_len = read_header(path, bytes)?;
+    let Some(info) = bytes.get(HEADER_LEN..HEADER_LEN + info_len) else {
+        return Err(invalid(path, "Snapshot is truncated"));
+    };
+
+    let info: SnapshotInfo =
+        rmp_serde::from_slice(info).map_err(|e| invalid(path, &e.to_string()))?;
+    let tree = zstd::decode_all(&bytes[HEADER_LEN + info_len..])
+        .map_err(|e| invalid(path, &e.to_string()))?;
+    let tree: ScanTree = rmp_serde::from_slice(&tree).map_err(|e| invalid(path, &e.to_string()))?;
+    check_tree(path, &tree)?;
+
+    Ok(Snapshot { info, tree })
+}
+
+fn read_info(path: &Path) -> Result<SnapshotInfo, DuneError> {
+    let mut file = fs::File::open(path).map_err(|e| DuneError::io(path, e))?;
+    let mut header = [0u8; HEADER_LEN];
+    file.read_exact(&mut header)
+        .map_err(|_| invalid(path, "Not a dunefiles snapshot"))?;
+
+    let mut info = vec![0u8; read_header(path, &header)?];
+    file.read_exact(&mut info)
+        .map_err(|_| invalid(path, "Snapshot is truncated"))?;
+
+    rmp_serde::from_slice(&info).map_err(|e| invalid(path, &e.to_string()))
+}
+
+fn write_snapshot(dir: &Path, snapshot: &Snapshot) -> Result<(), DuneError> {
+    fs::create_dir_all(dir).map_err(|e| DuneError::io(dir, e))?;
+
+    let path = snapshot_path(dir, &snapshot.info.id)?;
+    fs::write(&path, encode(snapshot)?).map_err(|e| DuneError::io(&path, e))?;
+    log::info!(
+        "Saved snapshot {} of {} ({} nodes)",
+        snapshot.info.id,
+        snapshot.info.root,
+        snapshot.info.node_count
+    );
+
+    Ok(())
+}
+
+/// Store `tree` as a new snapshot
+pub fn save(dir: &Path, tree: &ScanTree) -> Result<SnapshotInfo, DuneError> {
+    let created_at = scan::now_millis();
+    let info = SnapshotInfo::new(format!("snapshot_{}", created_at), created_at, tree);
+    let snapshot = Snapshot {
+        info,
+        tree: tree.clone(),
+    };
+
+    write_snapshot(dir, &snapshot)?;
+
+    Ok(snapshot.info)
+}
+
+pub fn load(dir: &Path, id: &str) -> Result<Snapshot, DuneError> {
+    let path = snapshot_path(dir, id)?;
+    let bytes = fs::read(&path).map_err(|e| DuneError::io(&path, e))?;
+
+    decode(&path, &bytes)
+}
+
+/// Summary of one stored snapshot without loading its tree
+pub fn info(dir: &Path, id: &str) -> Result<SnapshotInfo, DuneError> {
+    read_info(&snapshot_path(dir, id)?)
+}
+
+/// Stored snapshots, newest first; unreadable files are skipped
+pub fn list(dir: &Path) -> Result<Vec<SnapshotInfo>, DuneError> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(DuneError::io(dir, err)),
+    };
+
+    let mut snapshots: Vec<SnapshotInfo> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == EXTENSION))
+        .filter_map(|path| {
+            read_info(&path)
+                .inspect_err(|err| log::warn!("Skipping snapshot {}: {}", path.display(), err))
+                .ok()
+        })
+        .collect();
+
+    snapshots.sort_by_key(|info| std::cmp::Reverse(info.created_at));
+
+    Ok(snapshots)
+}
+
+pub fn delete(dir: &Path, id: &str) -> Result<(), DuneError> {
+    let path = snapshot_path(dir, id)?;
+
+    fs::remove_file(&path).map_err(|e| DuneError::io(&path, e))
+}
+
+/// Write a stored snapshot out as pretty JSON
+pub fn export_json(dir: &Path, id: &str, dest: &Path) -> Result<(), DuneError> {
+    let snapshot = load(dir, id)?;
+    let json = serde_json::to_vec_pretty(&snapshot).map_err(DuneError::internal)?;
+
+    fs::write(dest, json).map_err(|e| DuneError::io(dest, e))
+}
+
+/// A tree that was never stored, such as a finished scan or search result, in export form
+pub fn unsaved(tree: &ScanTree) -> Result<Snapshot, DuneError> {
+    let created_at = scan::now_millis();
+
+    Ok(Snapshot {
+        info: SnapshotInfo::new(format!("snapshot_{}", created_at), created_at, tree),
+        tree: tree.clone(),
+    })
+}
+
+/// Write a tree that was never stored as JSON
+pub fn export_tree_json(tree: &ScanTree, dest: &Path) -> Result<(), DuneError> {
+    let json = serde_json::to_vec_pretty(&unsaved(tree)?).map_err(DuneError::internal)?;
+
+    fs::write(dest, json).map_err(|e| DuneError::io(dest, e))
+}
+
+/// Store a JSON export as a new snapshot, keeping its original timestamp
+pub fn import_json(dir: &Path, source: &Path) -> Result<SnapshotInfo, DuneError> {
+    let json = fs::read(source).map_err(|e| DuneError::io(source, e))?;
+    let mut snapshot: Snapshot =
+        serde_json::from_slice(&json).map_err(|e| invalid(source, &e.to_string()))?;
+    check_tree(source, &snapshot.tree)?;
+
+    // Recompute the summary and pick a fresh id so imports never overwrite local snapshots
+    let id = format!("snapshot_{}", scan::now_millis());
+    snapshot.info = SnapshotInfo::new(id, snapshot.info.created_at, &snapshot.tree);
+    write_snapshot(dir, &snapshot)?;
+
+    Ok(snapshot.info)
+}
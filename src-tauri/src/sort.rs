@@ -0,0 +1,148 @@
+use crate::scanner::FileEntry;
+use icu_normalizer::DecomposingNormalizerBorrowed;
+use serde::Deserialize;
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    #[default]
+    Size,
+    Name,
+}
+
+/// How `list_directory` orders entries; the default is the app's usual largest-first
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SortOptions {
+    pub key: SortKey,
+    /// Defaults to largest first for sizes and A to Z for names
+    pub descending: Option<bool>,
+    pub folders_first: bool,
+    /// Compare runs of digits by value, so `file2` comes before `file10`
+    pub natural: bool,
+    pub case_sensitive: bool,
+    /// Dictionary order: letters sort with their unaccented forms (`é` with `e`) before
+    /// accents and then case break ties. Covers Latin, Greek and Cyrillic without
+    /// per-language tailoring.
+    pub collate: bool,
+}
+
+impl Default for SortOptions {
+    fn default() -> Self {
+        Self {
+            key: SortKey::Size,
+            descending: None,
+            folders_first: true,
+            natural: true,
+            case_sensitive: false,
+            collate: true,
+        }
+    }
+}
+
+/// Numbers before text, as Finder and Explorer order them
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Segment {
+    /// Digits without leading zeros, so length first orders them by value
+    Number(usize, String),
+    Text(String),
+}
+
+/// Compared level by level: base letters, then accents, then the name as written
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct NameKey {
+    primary: Vec<Segment>,
+    secondary: Vec<Segment>,
+    raw: String,
+}
+
+/// Combining marks left over after canonical decomposition
+fn is_mark(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036f}'
+        | '\u{1ab0}'..='\u{1aff}'
+        | '\u{1dc0}'..='\u{1dff}'
+        | '\u{20d0}'..='\u{20ff}'
+        | '\u{fe20}'..='\u{fe2f}')
+}
+
+fn segments(name: &str, natural: bool) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut rest = name;
+
+    while !rest.is_empty() {
+        let digits = if natural {
+            rest.find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len())
+        } else {
+            0
+        };
+        if digits > 0 {
+            let value = rest[..digits].trim_start_matches('0');
+            let value = if value.is_empty() { "0" } else { value };
+            segments.push(Segment::Number(value.len(), value.to_string()));
+            rest = &rest[digits..];
+            continue;
+        }
+
+        let text = if natural {
+            rest.find(|c: char| c.is_ascii_digit())
+                .unwrap_or(rest.len())
+        } else {
+            rest.len()
+        };
+        segments.push(Segment::Text(rest[..text].to_string()));
+        rest = &rest[text..];
+    }
+
+    segments
+}
+
+fn name_key(name: &str, options: &SortOptions) -> NameKey {
+    let folded = if options.case_sensitive {
+        name.to_string()
+    } else {
+        name.to_lowercase()
+    };
+    let base = if options.collate {
+        DecomposingNormalizerBorrowed::new_nfd()
+            .normalize_iter(folded.chars())
+            .filter(|&c| !is_mark(c))
+            .collect()
+    } else {
+        folded.clone()
+    };
+
+    NameKey {
+        primary: segments(&base, options.natural),
+        secondary: segments(&folded, options.natural),
+        raw: name.to_string(),
+    }
+}
+
+/// Sort a listing; sizes tie-break on names so equal sizes keep a stable order
+pub fn sort_entries(entries: &mut Vec<FileEntry>, options: &SortOptions) {
+    let descending = options.descending.unwrap_or(options.key == SortKey::Size);
+    let directed = |order: Ordering| if descending { order.reverse() } else { order };
+
+    let mut keyed: Vec<(NameKey, FileEntry)> = entries
+        .drain(..)
+        .map(|entry| (name_key(&entry.name, options), entry))
+        .collect();
+    keyed.sort_by(|(a_name, a), (b_name, b)| {
+        let group = if options.folders_first {
+            b.is_dir.cmp(&a.is_dir)
+        } else {
+            Ordering::Equal
+        };
+        let order = match options.key {
+            SortKey::Size => directed(a.size.cmp(&b.size)).then_with(|| a_name.cmp(b_name)),
+            SortKey::Name => directed(a_name.cmp(b_name)),
+        };
+
+        group.then(order)
+    });
+
+    entries.extend(keyed.into_iter().map(|(_, entry)| entry));
+}
@@ -0,0 +1,248 @@
+use crate::error::DuneError;
+use crate::origin::{self, DownloadOrigin};
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// An extended attribute, or an NTFS alternate data stream
+#[derive(Debug, Clone, Serialize)]
+pub struct NamedStream {
+    /// `com.apple.ResourceFork`, `user.xdg.origin.url` or `Zone.Identifier`
+    pub name: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileDetails {
+    pub path: String,
+    pub is_dir: bool,
+    /// Length of the main data, what listings and scans count
+    pub size: u64,
+    /// Blocks allocated for the main data plus the size of every stream below
+    pub size_on_disk: u64,
+    /// Empty unless asked for, or where the filesystem keeps none
+    pub streams: Vec<NamedStream>,
+    pub streams_size: u64,
+    /// Where a downloaded file came from, see `origin::read`
+    pub origin: Option<DownloadOrigin>,
+}
+
+/// Size and size on disk of one file or folder, optionally with its extended
+/// attributes (which hold macOS resource forks) or alternate data streams
+pub fn file_details(path: &str, include_streams: bool) -> Result<FileDetails, DuneError> {
+    let file_path = Path::new(path);
+    let metadata = fs::symlink_metadata(file_path).map_err(|e| DuneError::io(file_path, e))?;
+
+    let streams = if include_streams {
+        match platform::streams(file_path) {
+            Ok(streams) => streams,
+            // FAT drives, network shares and the like keep no streams at all
+            Err(err) if err.kind() == io::ErrorKind::Unsupported => Vec::new(),
+            Err(err) => return Err(DuneError::io(file_path, err)),
+        }
+    } else {
+        Vec::new()
+    };
+    let streams_size = streams.iter().map(|stream| stream.size).sum::<u64>();
+
+    Ok(FileDetails {
+        path: path.to_string(),
+        is_dir: metadata.is_dir(),
+        size: if metadata.is_dir() { 0 } else { metadata.len() },
+        size_on_disk: crate::containers::allocated(&metadata).saturating_add(streams_size),
+        streams,
+        streams_size,
+        origin: if metadata.is_dir() {
+            None
+        } else {
+            origin::read(file_path)
+        },
+    })
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod platform {
+    use super::NamedStream;
+    use std::ffi::{CStr, CString};
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    #[cfg(target_os = "linux")]
+    unsafe fn list(path: &CStr, buffer: *mut libc::c_char, size: usize) -> isize {
+        libc::llistxattr(path.as_ptr(), buffer, size)
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn list(path: &CStr, buffer: *mut libc::c_char, size: usize) -> isize {
+        libc::listxattr(path.as_ptr(), buffer, size, libc::XATTR_NOFOLLOW)
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe fn value_size(path: &CStr, name: &CStr) -> isize {
+        libc::lgetxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0)
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn value_size(path: &CStr, name: &CStr) -> isize {
+        libc::getxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            std::ptr::null_mut(),
+            0,
+            0,
+            libc::XATTR_NOFOLLOW,
+        )
+    }
+
+    fn not_supported(err: io::Error) -> io::Error {
+        if err.raw_os_error() == Some(libc::ENOTSUP) {
+            io::Error::from(io::ErrorKind::Unsupported)
+        } else {
+            err
+        }
+    }
+
+    /// NUL-separated attribute names, retried if attributes are added in between calls
+    fn names(path: &CStr) -> io::Result<Vec<u8>> {
+        for _ in 0..3 {
+            let size = unsafe { list(path, std::ptr::null_mut(), 0) };
+            if size < 0 {
+                return Err(not_supported(io::Error::last_os_error()));
+            }
+
+            let mut buffer = vec![0u8; size as usize];
+            let written = unsafe { list(path, buffer.as_mut_ptr().cast(), buffer.len()) };
+            if written >= 0 {
+                buffer.truncate(written as usize);
+                return Ok(buffer);
+            }
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::ERANGE) {
+                return Err(not_supported(err));
+            }
+        }
+
+        Err(io::Error::from_raw_os_error(libc::ERANGE))
+    }
+
+    pub fn streams(path: &Path) -> io::Result<Vec<NamedStream>> {
+        let path = CString::new(path.as_os_str().as_bytes())?;
+        let mut streams = Vec::new();
+
+        for name in names(&path)?.split(|&byte| byte == 0) {
+            if name.is_empty() {
+                continue;
+            }
+            let name = CString::new(name)?;
+            let size = unsafe { value_size(&path, &name) };
+            // Removed since it was listed
+            if size < 0 {
+                continue;
+            }
+
+            streams.push(NamedStream {
+                name: name.to_string_lossy().into_owned(),
+                size: size as u64,
+            });
+        }
+
+        Ok(streams)
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::NamedStream;
+    use std::ffi::OsString;
+    use std::io;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use std::path::Path;
+    use windows_sys::Win32::Foundation::{ERROR_HANDLE_EOF, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::Storage::FileSystem::{
+        FindClose, FindFirstStreamW, FindNextStreamW, FindStreamInfoStandard,
+        WIN32_FIND_STREAM_DATA,
+    };
+
+    /// `:Zone.Identifier:$DATA` as `Zone.Identifier`; `None` for the main `::$DATA` stream
+    fn stream_name(data: &WIN32_FIND_STREAM_DATA) -> Option<String> {
+        let length = data
+            .cStreamName
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(data.cStreamName.len());
+        let name = OsString::from_wide(&data.cStreamName[..length])
+            .to_string_lossy()
+            .into_owned();
+        let name = name.strip_prefix(':').unwrap_or(&name);
+        let name = name.strip_suffix(":$DATA").unwrap_or(name);
+
+        (!name.is_empty()).then(|| name.to_string())
+    }
+
+    pub fn streams(path: &Path) -> io::Result<Vec<NamedStream>> {
+        let wide: Vec<u16> = path
+            .as_os_str()
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        let mut data: WIN32_FIND_STREAM_DATA = unsafe { std::mem::zeroed() };
+
+        let handle = unsafe {
+            FindFirstStreamW(
+                wide.as_ptr(),
+                FindStreamInfoStandard,
+                &mut data as *mut WIN32_FIND_STREAM_DATA as *mut core::ffi::c_void,
+                0,
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            let err = io::Error::last_os_error();
+            // Folders usually have no stream at all
+            if err.raw_os_error() == Some(ERROR_HANDLE_EOF as i32) {
+                return Ok(Vec::new());
+            }
+            return Err(err);
+        }
+
+        let mut streams = Vec::new();
+        let mut result = Ok(());
+        loop {
+            if let Some(name) = stream_name(&data) {
+                streams.push(NamedStream {
+                    name,
+                    size: data.StreamSize.max(0) as u64,
+                });
+            }
+
+            let found = unsafe {
+                FindNextStreamW(
+                    handle,
+                    &mut data as *mut WIN32_FIND_STREAM_DATA as *mut core::ffi::c_void,
+                )
+            };
+            if found == 0 {
+                let err = io::Error::last_os_error();
+                if err.raw_os_error() != Some(ERROR_HANDLE_EOF as i32) {
+                    result = Err(err);
+                }
+                break;
+            }
+        }
+        unsafe { FindClose(handle) };
+
+        result.map(|_| streams)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+mod platform {
+    use super::NamedStream;
+    use std::io;
+    use std::path::Path;
+
+    pub fn streams(_path: &Path) -> io::Result<Vec<NamedStream>> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
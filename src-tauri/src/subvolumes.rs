@@ -0,0 +1,323 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubvolumeKind {
+    BtrfsSubvolume,
+    ZfsDataset,
+    /// A dataset's `.zfs` folder, where its snapshots are mounted
+    ZfsSnapshots,
+}
+
+/// A folder where the scan crossed into another subvolume or dataset
+#[derive(Debug, Clone)]
+pub struct Boundary {
+    pub path: PathBuf,
+    pub kind: SubvolumeKind,
+    /// Copy-on-write copies of data counted elsewhere; not descended into unless
+    /// `ScanOptions.snapshots` is set
+    pub snapshot: bool,
+    device: u64,
+    /// btrfs subvolume id, the `0/<id>` of its qgroup
+    tree_id: Option<u64>,
+}
+
+/// A subvolume or dataset found under a scan root, with what the filesystem reports for it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subvolume {
+    /// Index of the folder in the scan tree
+    pub node: u32,
+    pub path: String,
+    pub kind: SubvolumeKind,
+    pub snapshot: bool,
+    /// Bytes the subvolume or dataset refers to, shared or not
+    pub referenced: Option<u64>,
+    /// btrfs exclusive bytes; ZFS `used`, children and snapshots included, or
+    /// `usedbysnapshots` for `.zfs`
+    pub used: Option<u64>,
+    pub quota: Option<u64>,
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{Boundary, SubvolumeKind};
+    use std::ffi::{CString, OsStr};
+    use std::fs;
+    use std::os::fd::AsRawFd;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::Path;
+
+    const BTRFS_SUPER_MAGIC: i64 = 0x9123_683e;
+    const ZFS_SUPER_MAGIC: i64 = 0x2fc1_2fc1;
+    /// Inode of every btrfs subvolume's root folder
+    const BTRFS_FIRST_FREE_OBJECTID: u64 = 256;
+    /// `_IOR(0x94, 60, struct btrfs_ioctl_get_subvol_info_args)`, allowed without root
+    const BTRFS_IOC_GET_SUBVOL_INFO: u64 = 0x81f8_943c;
+
+    fn filesystem_type(path: &Path) -> Option<i64> {
+        let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+        let mut stats: libc::statfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statfs(c_path.as_ptr(), &mut stats) } != 0 {
+            return None;
+        }
+        Some(stats.f_type as i64)
+    }
+
+    pub fn is_cow(path: &Path) -> bool {
+        matches!(
+            filesystem_type(path),
+            Some(BTRFS_SUPER_MAGIC | ZFS_SUPER_MAGIC)
+        )
+    }
+
+    /// Subvolume id, and whether it is a snapshot, which is one with a parent UUID
+    fn subvolume_info(path: &Path) -> Option<(u64, bool)> {
+        let dir = fs::File::open(path).ok()?;
+        // 504 bytes: tree id, name, ids and generations, then the uuid, parent uuid at 312
+        let mut args = [0u64; 63];
+        let result = unsafe {
+            libc::ioctl(
+                dir.as_raw_fd(),
+                BTRFS_IOC_GET_SUBVOL_INFO as _,
+                args.as_mut_ptr(),
+            )
+        };
+        if result != 0 {
+            return None;
+        }
+
+        Some((args[0], args[39] != 0 || args[40] != 0))
+    }
+
+    pub fn boundary(
+        path: &Path,
+        name: &OsStr,
+        metadata: &fs::Metadata,
+        parent_device: Option<u64>,
+    ) -> Option<Boundary> {
+        let device = metadata.dev();
+        let crossed = parent_device.is_some_and(|parent| parent != device);
+
+        if metadata.ino() == BTRFS_FIRST_FREE_OBJECTID
+            && filesystem_type(path) == Some(BTRFS_SUPER_MAGIC)
+        {
+            let info = subvolume_info(path);
+            return Some(Boundary {
+                path: path.to_path_buf(),
+                kind: SubvolumeKind::BtrfsSubvolume,
+                snapshot: info.is_some_and(|(_, snapshot)| snapshot),
+                device,
+                tree_id: info.map(|(tree_id, _)| tree_id),
+            });
+        }
+
+        let kind = if name == ".zfs" {
+            SubvolumeKind::ZfsSnapshots
+        } else if crossed {
+            SubvolumeKind::ZfsDataset
+        } else {
+            return None;
+        };
+        (filesystem_type(path) == Some(ZFS_SUPER_MAGIC)).then(|| Boundary {
+            path: path.to_path_buf(),
+            kind,
+            snapshot: kind == SubvolumeKind::ZfsSnapshots,
+            device,
+            tree_id: None,
+        })
+    }
+}
+
+/// Subvolumes only exist on Linux btrfs and ZFS
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use super::Boundary;
+    use std::ffi::OsStr;
+    use std::fs;
+    use std::path::Path;
+
+    pub fn is_cow(_path: &Path) -> bool {
+        false
+    }
+
+    pub fn boundary(
+        _path: &Path,
+        _name: &OsStr,
+        _metadata: &fs::Metadata,
+        _parent_device: Option<u64>,
+    ) -> Option<Boundary> {
+        None
+    }
+}
+
+/// Whether a scan of `path` should stat every folder to look for subvolume boundaries
+pub fn is_cow_filesystem(path: &Path) -> bool {
+    platform::is_cow(path)
+}
+
+/// The boundary `path` starts, given the folder's metadata and the device of its parent
+pub fn boundary(
+    path: &Path,
+    name: &OsStr,
+    metadata: &fs::Metadata,
+    parent_device: Option<u64>,
+) -> Option<Boundary> {
+    platform::boundary(path, name, metadata, parent_device)
+}
+
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .inspect_err(|err| log::debug!("Could not run {}: {}", program, err))
+        .ok()?;
+    if !output.status.success() {
+        // Quotas are off, or the tool needs root
+        log::debug!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// `none` and ZFS's `0` mean no limit
+fn limit(value: &str) -> Option<u64> {
+    value.parse().ok().filter(|&limit| limit > 0)
+}
+
+/// What a filesystem tool reports for one subvolume or dataset, see `Subvolume`
+#[derive(Debug, Clone, Copy, Default)]
+struct Usage {
+    referenced: u64,
+    used: u64,
+    quota: Option<u64>,
+    /// ZFS `usedbysnapshots`
+    snapshots: u64,
+}
+
+/// By subvolume id, from `btrfs qgroup show --raw -re`
+fn btrfs_qgroups(path: &Path) -> HashMap<u64, Usage> {
+    let Some(output) = run(
+        "btrfs",
+        &["qgroup", "show", "--raw", "-re", &path.to_string_lossy()],
+    ) else {
+        return HashMap::new();
+    };
+
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let id = fields.first()?.strip_prefix("0/")?.parse().ok()?;
+            let usage = Usage {
+                referenced: fields.get(1)?.parse().ok()?,
+                used: fields.get(2)?.parse().ok()?,
+                quota: fields.get(3).and_then(|value| limit(value)),
+                snapshots: 0,
+            };
+            Some((id, usage))
+        })
+        .collect()
+}
+
+/// By mountpoint, from `zfs list`
+fn zfs_datasets() -> HashMap<PathBuf, Usage> {
+    let Some(output) = run(
+        "zfs",
+        &[
+            "list",
+            "-H",
+            "-p",
+            "-t",
+            "filesystem",
+            "-o",
+            "mountpoint,used,referenced,quota,usedbysnapshots",
+        ],
+    ) else {
+        return HashMap::new();
+    };
+
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [mountpoint, used, referenced, quota, snapshots] = fields[..] else {
+                return None;
+            };
+            let usage = Usage {
+                referenced: referenced.parse().ok()?,
+                used: used.parse().ok()?,
+                quota: limit(quota),
+                snapshots: snapshots.parse().unwrap_or(0),
+            };
+            Some((PathBuf::from(mountpoint), usage))
+        })
+        .collect()
+}
+
+/// Sizes for the boundaries a scan found, asking each filesystem's tool once
+pub fn describe(boundaries: Vec<(u32, Boundary)>) -> Vec<Subvolume> {
+    // btrfs qgroups by filesystem device
+    let mut qgroups: HashMap<u64, HashMap<u64, Usage>> = HashMap::new();
+    let datasets = boundaries
+        .iter()
+        .any(|(_, boundary)| boundary.kind != SubvolumeKind::BtrfsSubvolume)
+        .then(zfs_datasets)
+        .unwrap_or_default();
+
+    boundaries
+        .into_iter()
+        .map(|(node, boundary)| {
+            let (usage, snapshot_dir) = match boundary.kind {
+                SubvolumeKind::BtrfsSubvolume => {
+                    let groups = qgroups
+                        .entry(boundary.device)
+                        .or_insert_with(|| btrfs_qgroups(&boundary.path));
+                    (
+                        boundary.tree_id.and_then(|id| groups.get(&id).copied()),
+                        false,
+                    )
+                }
+                SubvolumeKind::ZfsDataset => (datasets.get(&boundary.path).copied(), false),
+                SubvolumeKind::ZfsSnapshots => {
+                    let parent = boundary.path.parent();
+                    (
+                        parent.and_then(|parent| datasets.get(parent)).copied(),
+                        true,
+                    )
+                }
+            };
+
+            Subvolume {
+                node,
+                path: boundary.path.to_string_lossy().into_owned(),
+                kind: boundary.kind,
+                snapshot: boundary.snapshot,
+                referenced: usage
+                    .filter(|_| !snapshot_dir)
+                    .map(|usage| usage.referenced),
+                used: usage.map(|usage| {
+                    if snapshot_dir {
+                        usage.snapshots
+                    } else {
+                        usage.used
+                    }
+                }),
+                quota: usage
+                    .filter(|_| !snapshot_dir)
+                    .and_then(|usage| usage.quota),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,224 @@
+use crate::error::DuneError;
+use serde::Serialize;
+
+/// A file the OS keeps at the volume root, locked so scans cannot open it
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemFile {
+    /// `pagefile.sys`, `hiberfil.sys` or `swapfile.sys`
+    pub name: String,
+    pub path: String,
+    pub bytes: u64,
+}
+
+/// Volume Shadow Copy space, kept in `System Volume Information`
+#[derive(Debug, Clone, Serialize)]
+pub struct ShadowStorage {
+    pub used_bytes: u64,
+    pub allocated_bytes: u64,
+    /// `None` when unbounded
+    pub max_bytes: Option<u64>,
+    pub shadow_copies: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemSpaceReport {
+    pub volume: String,
+    /// Total minus available, as the drive reports it
+    pub used_bytes: u64,
+    pub files: Vec<SystemFile>,
+    /// `None` without administrator rights, which the shadow copy provider requires
+    pub shadow_storage: Option<ShadowStorage>,
+    /// The files plus allocated shadow storage, none of which normal scans can see
+    pub hidden_bytes: u64,
+    /// From the last complete scan of the volume root, if there was one
+    pub scanned_bytes: Option<u64>,
+    /// Used space neither scanned nor hidden: filesystem metadata, other protected
+    /// folders, and whatever changed since the scan
+    pub unaccounted_bytes: Option<u64>,
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::*;
+    use crate::console;
+    use serde::Deserialize;
+    use std::fs;
+    use std::path::Path;
+
+    const SYSTEM_FILES: [&str; 3] = ["pagefile.sys", "hiberfil.sys", "swapfile.sys"];
+
+    /// Shadow storage and shadow copies per volume, as one JSON object
+    const SHADOW_SCRIPT: &str = "$ErrorActionPreference = 'Stop'; \
+        $volumes = @(Get-CimInstance Win32_Volume | ForEach-Object { @{ id = $_.DeviceID; name = $_.Name } }); \
+        $storage = @(Get-CimInstance Win32_ShadowStorage | ForEach-Object { @{ volume = $_.Volume.DeviceID; used = $_.UsedSpace; allocated = $_.AllocatedSpace; max = $_.MaxSpace } }); \
+        $copies = @(Get-CimInstance Win32_ShadowCopy | ForEach-Object { $_.VolumeName }); \
+        @{ volumes = $volumes; storage = $storage; copies = $copies } | ConvertTo-Json -Depth 3 -Compress";
+
+    #[derive(Deserialize)]
+    struct Volume {
+        id: String,
+        name: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct Storage {
+        volume: Option<String>,
+        used: u64,
+        allocated: u64,
+        max: u64,
+    }
+
+    #[derive(Deserialize)]
+    struct Shadows {
+        volumes: Vec<Volume>,
+        storage: Vec<Storage>,
+        copies: Vec<String>,
+    }
+
+    /// `c:` and `C:\` alike
+    fn same_volume(a: &str, b: &str) -> bool {
+        a.trim_end_matches('\\')
+            .eq_ignore_ascii_case(b.trim_end_matches('\\'))
+    }
+
+    /// The listing reads sizes from the directory, which works even though the files are locked
+    fn system_files(volume: &str) -> Vec<SystemFile> {
+        let Ok(entries) = fs::read_dir(volume) else {
+            return Vec::new();
+        };
+
+        entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let known = SYSTEM_FILES
+                    .iter()
+                    .any(|file| file.eq_ignore_ascii_case(&name));
+                let metadata = entry.metadata().ok().filter(|_| known)?;
+                Some(SystemFile {
+                    path: Path::new(volume).join(&name).to_string_lossy().into_owned(),
+                    name,
+                    bytes: metadata.len(),
+                })
+            })
+            .collect()
+    }
+
+    fn shadow_storage(volume: &str) -> Option<ShadowStorage> {
+        let output = console::powershell(SHADOW_SCRIPT)
+            .output()
+            .inspect_err(|err| log::debug!("Could not run PowerShell: {}", err))
+            .ok()?;
+        if !output.status.success() {
+            log::debug!(
+                "Shadow storage query failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return None;
+        }
+        let shadows: Shadows = serde_json::from_slice(&output.stdout)
+            .inspect_err(|err| log::debug!("Unexpected shadow storage output: {}", err))
+            .ok()?;
+
+        // WMI names volumes `\\?\Volume{guid}\`; the drive letter is only on Win32_Volume
+        let id = shadows
+            .volumes
+            .iter()
+            .find(|candidate| {
+                candidate
+                    .name
+                    .as_deref()
+                    .is_some_and(|name| same_volume(name, volume))
+            })?
+            .id
+            .clone();
+        let shadow_copies = shadows
+            .copies
+            .iter()
+            .filter(|copy| copy.eq_ignore_ascii_case(&id))
+            .count() as u32;
+
+        let storage = shadows
+            .storage
+            .iter()
+            .find(|storage| {
+                storage
+                    .volume
+                    .as_deref()
+                    .is_some_and(|candidate| candidate.eq_ignore_ascii_case(&id))
+            })
+            .map_or(
+                ShadowStorage {
+                    used_bytes: 0,
+                    allocated_bytes: 0,
+                    max_bytes: None,
+                    shadow_copies,
+                },
+                |storage| ShadowStorage {
+                    used_bytes: storage.used,
+                    allocated_bytes: storage.allocated,
+                    max_bytes: (storage.max != u64::MAX).then_some(storage.max),
+                    shadow_copies,
+                },
+            );
+
+        Some(storage)
+    }
+
+    pub fn report(
+        volume: &str,
+        scanned_bytes: Option<u64>,
+    ) -> Result<SystemSpaceReport, DuneError> {
+        let disk = crate::scanner::get_disks()
+            .into_iter()
+            .find(|disk| same_volume(&disk.path, volume))
+            .ok_or_else(|| DuneError::NotFound {
+                path: volume.into(),
+            })?;
+        let used_bytes = disk.total_space.saturating_sub(disk.available_space);
+
+        let files = system_files(&disk.path);
+        let shadow_storage = shadow_storage(&disk.path);
+        let hidden_bytes = files.iter().map(|file| file.bytes).sum::<u64>()
+            + shadow_storage
+                .as_ref()
+                .map_or(0, |storage| storage.allocated_bytes);
+
+        log::info!(
+            "{}: {} bytes in system files and shadow storage",
+            disk.path,
+            hidden_bytes
+        );
+
+        Ok(SystemSpaceReport {
+            volume: disk.path,
+            used_bytes,
+            files,
+            shadow_storage,
+            hidden_bytes,
+            scanned_bytes,
+            unaccounted_bytes: scanned_bytes
+                .map(|scanned| used_bytes.saturating_sub(scanned.saturating_add(hidden_bytes))),
+        })
+    }
+}
+
+#[cfg(not(windows))]
+mod platform {
+    use super::*;
+
+    pub fn report(
+        _volume: &str,
+        _scanned_bytes: Option<u64>,
+    ) -> Result<SystemSpaceReport, DuneError> {
+        Err(DuneError::Unsupported(
+            "Shadow copies and the page file are only reported on Windows".into(),
+        ))
+    }
+}
+
+/// Space on a Windows volume that scans cannot see, set against the drive's used space
+/// and `scanned_bytes` from the last scan of its root
+pub fn report(volume: &str, scanned_bytes: Option<u64>) -> Result<SystemSpaceReport, DuneError> {
+    platform::report(volume, scanned_bytes)
+}
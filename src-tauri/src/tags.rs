@@ -0,0 +1,266 @@
+use crate::app_data;
+use crate::error::DuneError;
+use crate::scanner::FileEntry;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Runtime};
+
+const FILE_NAME: &str = "tags.json";
+
+/// A path carrying a tag, as returned by `find_by_tag`
+#[derive(Debug, Clone, Serialize)]
+pub struct TaggedPath {
+    pub path: String,
+    /// Tags from the app's own store
+    pub tags: Vec<String>,
+    /// Finder tags, macOS only
+    pub finder_tags: Vec<String>,
+    pub is_dir: bool,
+    /// False for tagged paths that were moved or deleted since
+    pub exists: bool,
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::*;
+    use objc2::rc::Retained;
+    use objc2::runtime::AnyObject;
+    use objc2_foundation::{NSArray, NSString, NSURLTagNamesKey, NSURL};
+    use std::os::unix::ffi::OsStrExt;
+    use std::process::Command;
+
+    const TAGS_XATTR: &[u8] = b"com.apple.metadata:_kMDItemUserTags\0";
+
+    /// Most files have no tags; the xattr check is far cheaper than asking Foundation
+    fn has_tags(path: &Path) -> bool {
+        let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+            return false;
+        };
+
+        unsafe {
+            libc::getxattr(
+                path.as_ptr(),
+                TAGS_XATTR.as_ptr().cast(),
+                std::ptr::null_mut(),
+                0,
+                0,
+                libc::XATTR_NOFOLLOW,
+            ) > 0
+        }
+    }
+
+    pub fn finder_tags(path: &Path) -> Vec<String> {
+        if !has_tags(path) {
+            return Vec::new();
+        }
+
+        let url = NSURL::fileURLWithPath(&NSString::from_str(&path.to_string_lossy()));
+        let mut value: Option<Retained<AnyObject>> = None;
+        if unsafe { url.getResourceValue_forKey_error(&mut value, NSURLTagNamesKey) }.is_err() {
+            return Vec::new();
+        }
+
+        let Some(names) = value.and_then(|value| value.downcast::<NSArray>().ok()) else {
+            return Vec::new();
+        };
+        names
+            .iter()
+            .filter_map(|name| name.downcast::<NSString>().ok())
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    /// Paths Spotlight knows to carry the Finder tag, matched case-insensitively
+    pub fn find_finder_tag(tag: &str) -> Vec<PathBuf> {
+        let escaped = tag.replace('\\', "\\\\").replace('"', "\\\"");
+        let output = match Command::new("mdfind")
+            .arg(format!("kMDItemUserTags == \"{}\"c", escaped))
+            .output()
+        {
+            Ok(output) if output.status.success() => output,
+            Ok(output) => {
+                log::warn!(
+                    "mdfind for tag {:?} failed: {}",
+                    tag,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+                return Vec::new();
+            }
+            Err(err) => {
+                log::warn!("Could not run mdfind: {}", err);
+                return Vec::new();
+            }
+        };
+
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(PathBuf::from)
+            .collect()
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod platform {
+    use super::*;
+
+    pub fn finder_tags(_path: &Path) -> Vec<String> {
+        Vec::new()
+    }
+
+    pub fn find_finder_tag(_tag: &str) -> Vec<PathBuf> {
+        Vec::new()
+    }
+}
+
+/// Finder tags of `path`; always empty outside macOS
+pub fn finder_tags(path: &Path) -> Vec<String> {
+    platform::finder_tags(path)
+}
+
+pub fn store_path<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, DuneError> {
+    app_data::path(app, FILE_NAME)
+}
+
+/// Tags by path, each list sorted
+fn read(path: &Path) -> Result<BTreeMap<String, Vec<String>>, DuneError> {
+    match fs::read(path) {
+        Ok(json) => serde_json::from_slice(&json).map_err(|e| DuneError::Io {
+            path: Some(path.to_string_lossy().to_string()),
+            message: e.to_string(),
+        }),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(err) => Err(DuneError::io(path, err)),
+    }
+}
+
+fn write(path: &Path, tags: &BTreeMap<String, Vec<String>>) -> Result<(), DuneError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| DuneError::io(parent, e))?;
+    }
+
+    let json = serde_json::to_vec_pretty(tags).map_err(DuneError::internal)?;
+    fs::write(path, json).map_err(|e| DuneError::io(path, e))
+}
+
+fn same_tag(a: &str, b: &str) -> bool {
+    a.to_lowercase() == b.to_lowercase()
+}
+
+/// Trimmed, non-empty tags
+fn clean(tags: &[String]) -> Result<Vec<String>, DuneError> {
+    let tags: Vec<String> = tags
+        .iter()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+
+    if tags.is_empty() {
+        return Err(DuneError::InvalidInput("No tag given".into()));
+    }
+    Ok(tags)
+}
+
+/// Add `tags` to every path; tags differing only in case count as the same
+pub fn tag_paths(store: &Path, paths: &[String], tags: &[String]) -> Result<(), DuneError> {
+    let tags = clean(tags)?;
+    let mut stored = read(store)?;
+
+    for path in paths {
+        let existing = stored.entry(path.clone()).or_default();
+        for tag in &tags {
+            if !existing.iter().any(|other| same_tag(other, tag)) {
+                existing.push(tag.clone());
+            }
+        }
+        existing.sort_by_key(|tag| tag.to_lowercase());
+    }
+
+    write(store, &stored)?;
+    log::info!("Tagged {} paths with {:?}", paths.len(), tags);
+
+    Ok(())
+}
+
+/// Remove `tags` from every path, or all of their tags when `tags` is `None`
+pub fn untag_paths(
+    store: &Path,
+    paths: &[String],
+    tags: Option<&[String]>,
+) -> Result<(), DuneError> {
+    let tags = tags.map(clean).transpose()?;
+    let mut stored = read(store)?;
+
+    for path in paths {
+        let Some(existing) = stored.get_mut(path) else {
+            continue;
+        };
+        match &tags {
+            Some(tags) => existing.retain(|tag| !tags.iter().any(|other| same_tag(other, tag))),
+            None => existing.clear(),
+        }
+        if existing.is_empty() {
+            stored.remove(path);
+        }
+    }
+
+    write(store, &stored)
+}
+
+/// Fill in `custom_tags` from the store
+pub fn apply(store: &Path, entries: &mut [FileEntry]) -> Result<(), DuneError> {
+    let stored = read(store)?;
+    if stored.is_empty() {
+        return Ok(());
+    }
+
+    for entry in entries {
+        if let Some(tags) = stored.get(&entry.path) {
+            entry.custom_tags = tags.clone();
+        }
+    }
+
+    Ok(())
+}
+
+/// Paths carrying `tag` in the app's store or, on macOS, as a Finder tag; sorted by path
+pub fn find_by_tag(store: &Path, tag: &str) -> Result<Vec<TaggedPath>, DuneError> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Err(DuneError::InvalidInput("No tag given".into()));
+    }
+
+    let mut stored = read(store)?;
+    let mut paths: Vec<String> = stored
+        .iter()
+        .filter(|(_, tags)| tags.iter().any(|other| same_tag(other, tag)))
+        .map(|(path, _)| path.clone())
+        .collect();
+    paths.extend(
+        platform::find_finder_tag(tag)
+            .into_iter()
+            .map(|path| path.to_string_lossy().to_string()),
+    );
+    paths.sort();
+    paths.dedup();
+
+    let found: Vec<TaggedPath> = paths
+        .into_iter()
+        .map(|path| {
+            let metadata = fs::symlink_metadata(&path).ok();
+            TaggedPath {
+                tags: stored.remove(&path).unwrap_or_default(),
+                finder_tags: finder_tags(Path::new(&path)),
+                is_dir: metadata.as_ref().is_some_and(|metadata| metadata.is_dir()),
+                exists: metadata.is_some(),
+                path,
+            }
+        })
+        .collect();
+
+    log::info!("Found {} paths tagged {:?}", found.len(), tag);
+
+    Ok(found)
+}
@@ -0,0 +1,141 @@
+use crate::error::DuneError;
+use serde::Serialize;
+
+/// A local APFS snapshot kept by Time Machine
+#[derive(Debug, Clone, Serialize)]
+pub struct LocalSnapshot {
+    pub name: String,
+    /// `YYYY-MM-DD HH:MM:SS` taken from the snapshot name, when it has one
+    pub created: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotReport {
+    pub volume: String,
+    /// Oldest first, as listed by `tmutil`
+    pub snapshots: Vec<LocalSnapshot>,
+    /// Space macOS can free on demand: snapshots plus purgeable caches.
+    /// APFS does not report what each snapshot holds on its own.
+    pub purgeable_bytes: u64,
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::*;
+    use objc2::rc::Retained;
+    use objc2::runtime::AnyObject;
+    use objc2_foundation::{
+        NSNumber, NSString, NSURLVolumeAvailableCapacityForImportantUsageKey, NSURL,
+    };
+    use std::process::Command;
+
+    /// Reads the date out of names like `com.apple.TimeMachine.2024-05-01-093000.local`
+    fn snapshot_date(name: &str) -> Option<String> {
+        let stamp = name
+            .split('.')
+            .find(|part| part.len() == 17 && part.as_bytes()[4] == b'-')?;
+        let (date, time) = stamp.split_at(10);
+        let time = time.strip_prefix('-')?;
+
+        if !time.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        Some(format!(
+            "{} {}:{}:{}",
+            date,
+            &time[0..2],
+            &time[2..4],
+            &time[4..6]
+        ))
+    }
+
+    /// Free space counting what macOS would purge for an important write
+    fn important_capacity(volume: &str) -> Option<u64> {
+        let url = NSURL::fileURLWithPath(&NSString::from_str(volume));
+        let mut value: Option<Retained<AnyObject>> = None;
+
+        unsafe {
+            url.getResourceValue_forKey_error(
+                &mut value,
+                NSURLVolumeAvailableCapacityForImportantUsageKey,
+            )
+        }
+        .ok()?;
+
+        let number = value?.downcast::<NSNumber>().ok()?;
+        u64::try_from(number.longLongValue()).ok()
+    }
+
+    pub fn purgeable_space(volume: &str, available: u64) -> u64 {
+        important_capacity(volume).map_or(0, |capacity| capacity.saturating_sub(available))
+    }
+
+    fn list_snapshots(volume: &str) -> Result<Vec<LocalSnapshot>, DuneError> {
+        let output = Command::new("tmutil")
+            .arg("listlocalsnapshots")
+            .arg(volume)
+            .output()
+            .map_err(DuneError::internal)?;
+
+        if !output.status.success() {
+            return Err(DuneError::Internal(format!(
+                "tmutil failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            // The first line is a "Snapshots for disk /:" header on recent macOS
+            .filter(|line| !line.is_empty() && !line.ends_with(':'))
+            .map(|name| LocalSnapshot {
+                name: name.to_string(),
+                created: snapshot_date(name),
+            })
+            .collect())
+    }
+
+    pub fn snapshot_report() -> Result<SnapshotReport, DuneError> {
+        let volume = "/";
+        let snapshots = list_snapshots(volume)?;
+        let purgeable_bytes = crate::scanner::get_disks()
+            .into_iter()
+            .find(|disk| disk.path == volume)
+            .map_or(0, |disk| disk.purgeable_space);
+
+        log::info!("Found {} local snapshots on {}", snapshots.len(), volume);
+
+        Ok(SnapshotReport {
+            volume: volume.to_string(),
+            snapshots,
+            purgeable_bytes,
+        })
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod platform {
+    use super::*;
+
+    pub fn purgeable_space(_volume: &str, _available: u64) -> u64 {
+        0
+    }
+
+    pub fn snapshot_report() -> Result<SnapshotReport, DuneError> {
+        Err(DuneError::Unsupported(
+            "Local snapshots are only reported on macOS".into(),
+        ))
+    }
+}
+
+/// Bytes on `volume` that the OS can free on demand; zero where there is no such notion
+pub fn purgeable_space(volume: &str, available: u64) -> u64 {
+    platform::purgeable_space(volume, available)
+}
+
+/// Local Time Machine snapshots on the startup volume and the space they can give back
+pub fn snapshot_report() -> Result<SnapshotReport, DuneError> {
+    platform::snapshot_report()
+}
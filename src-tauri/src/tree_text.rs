@@ -0,0 +1,197 @@
+use crate::format;
+use crate::i18n::tr;
+use crate::scan::ScanTree;
+use serde::Deserialize;
+use std::fmt::Write;
+
+/// Folders list their largest children; the rest are summed up on one line
+const MAX_CHILDREN: usize = 20;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextFormat {
+    /// Indented with box-drawing lines, like `tree`
+    #[default]
+    Tree,
+    /// A table of relative paths, sizes and shares of the whole
+    Markdown,
+}
+
+/// Folders whose size is only a lower bound get a `>`, as in the listing
+fn node_size(tree: &ScanTree, index: u32) -> String {
+    let node = &tree.nodes[index as usize];
+    let size = format::size(node.size);
+    if node.is_dir && !node.size_complete {
+        format!(">{}", size)
+    } else {
+        size
+    }
+}
+
+/// Child lists of every node, largest first
+fn children(tree: &ScanTree) -> Vec<Vec<u32>> {
+    let mut children = vec![Vec::new(); tree.nodes.len()];
+    for (index, node) in tree.nodes.iter().enumerate() {
+        if let Some(parent) = node.parent {
+            children[parent as usize].push(index as u32);
+        }
+    }
+    for list in &mut children {
+        list.sort_by_key(|&child| std::cmp::Reverse(tree.nodes[child as usize].size));
+    }
+
+    children
+}
+
+/// What a folder lists: its largest children, and the count and size of the rest
+fn shown(tree: &ScanTree, children: &[u32]) -> (usize, Option<(usize, u64)>) {
+    if children.len() <= MAX_CHILDREN {
+        return (children.len(), None);
+    }
+
+    let rest = &children[MAX_CHILDREN..];
+    let rest_size = rest
+        .iter()
+        .map(|&child| tree.nodes[child as usize].size)
+        .sum();
+    (MAX_CHILDREN, Some((rest.len(), rest_size)))
+}
+
+struct Renderer<'a> {
+    tree: &'a ScanTree,
+    children: Vec<Vec<u32>>,
+    max_depth: u32,
+    total: u64,
+    out: String,
+}
+
+impl Renderer<'_> {
+    fn tree_lines(&mut self, index: u32, prefix: &str, depth: u32) {
+        if depth >= self.max_depth {
+            return;
+        }
+
+        let children = std::mem::take(&mut self.children[index as usize]);
+        let (count, rest) = shown(self.tree, &children);
+        for (position, &child) in children[..count].iter().enumerate() {
+            let last = position + 1 == count && rest.is_none();
+            let node = &self.tree.nodes[child as usize];
+            let _ = writeln!(
+                self.out,
+                "{}{}{}{}  {}",
+                prefix,
+                if last { "└── " } else { "├── " },
+                node.name,
+                if node.is_dir { "/" } else { "" },
+                node_size(self.tree, child)
+            );
+
+            let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+            self.tree_lines(child, &prefix, depth + 1);
+        }
+        if let Some((more, size)) = rest {
+            let _ = writeln!(
+                self.out,
+                "{}└── {}  {}",
+                prefix,
+                tr("report.more", &[("count", &more.to_string())]),
+                format::size(size)
+            );
+        }
+    }
+
+    fn share(&self, size: u64) -> String {
+        if self.total == 0 {
+            return "-".into();
+        }
+        format!("{:.1}%", size as f64 * 100.0 / self.total as f64)
+    }
+
+    fn table_rows(&mut self, index: u32, relative: &str, depth: u32) {
+        if depth >= self.max_depth {
+            return;
+        }
+
+        let children = std::mem::take(&mut self.children[index as usize]);
+        let (count, rest) = shown(self.tree, &children);
+        for &child in &children[..count] {
+            let node = &self.tree.nodes[child as usize];
+            let path = if relative.is_empty() {
+                node.name.to_string()
+            } else {
+                format!("{}/{}", relative, node.name)
+            };
+            let _ = writeln!(
+                self.out,
+                "| `{}{}` | {} | {} | {} |",
+                path.replace('`', "'").replace('|', "\\|"),
+                if node.is_dir { "/" } else { "" },
+                node_size(self.tree, child),
+                self.share(node.size),
+                format::count(node.file_count)
+            );
+
+            self.table_rows(child, &path, depth + 1);
+        }
+        if let Some((more, size)) = rest {
+            let folder = if relative.is_empty() { "." } else { relative };
+            let folder = format!("`{}`", folder.replace('`', "'").replace('|', "\\|"));
+            let _ = writeln!(
+                self.out,
+                "| {} | {} | {} | |",
+                tr(
+                    "report.more_in",
+                    &[("count", &more.to_string()), ("folder", &folder)]
+                ),
+                format::size(size),
+                self.share(size)
+            );
+        }
+    }
+}
+
+/// `depth` levels of the subtree at `index`, as plain text for pasting elsewhere
+pub fn render(tree: &ScanTree, index: u32, depth: u32, text_format: TextFormat) -> String {
+    let root = &tree.nodes[index as usize];
+    let mut renderer = Renderer {
+        tree,
+        children: children(tree),
+        max_depth: depth,
+        total: root.size,
+        out: String::new(),
+    };
+    let path = tree.path(index).to_string_lossy().to_string();
+
+    match text_format {
+        TextFormat::Tree => {
+            let _ = writeln!(renderer.out, "{}  {}", path, node_size(tree, index));
+            renderer.tree_lines(index, "", 0);
+        }
+        TextFormat::Markdown => {
+            let _ = writeln!(
+                renderer.out,
+                "**`{}`**: {}\n",
+                path,
+                tr(
+                    "report.summary",
+                    &[
+                        ("size", &node_size(tree, index)),
+                        ("files", &format::count(root.file_count)),
+                    ],
+                )
+            );
+            let _ = writeln!(
+                renderer.out,
+                "| {} | {} | {} | {} |",
+                tr("report.path", &[]),
+                tr("report.size", &[]),
+                tr("report.share", &[]),
+                tr("report.files", &[])
+            );
+            renderer.out.push_str("| --- | ---: | ---: | ---: |\n");
+            renderer.table_rows(index, "", 0);
+        }
+    }
+
+    renderer.out
+}
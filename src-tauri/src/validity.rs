@@ -0,0 +1,141 @@
+use crate::error::DuneError;
+use crate::health::{self, Problem, MAX_NAME};
+use crate::names;
+use crate::scanner;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// Naming rules a filesystem enforces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NameRules {
+    /// NTFS, exFAT and FAT: reserved names and characters, UTF-16 lengths, any case matches
+    Windows,
+    /// APFS and HFS+: no `:`, byte lengths, case and normalization do not make names differ
+    Apple,
+    /// ext4, btrfs, XFS and the like: anything but `/` and NUL, byte lengths
+    Unix,
+}
+
+impl NameRules {
+    fn for_filesystem(filesystem: &str) -> Option<Self> {
+        match filesystem {
+            "ntfs" | "ntfs3" | "exfat" | "fat" | "fat16" | "fat32" | "vfat" | "msdos" | "refs" => {
+                Some(Self::Windows)
+            }
+            "apfs" | "hfs" | "hfsplus" => Some(Self::Apple),
+            "" | "fuseblk" => None,
+            _ => Some(Self::Unix),
+        }
+    }
+
+    fn host() -> Self {
+        if cfg!(windows) {
+            Self::Windows
+        } else if cfg!(target_os = "macos") {
+            Self::Apple
+        } else {
+            Self::Unix
+        }
+    }
+
+    /// Case-sensitive APFS exists but is rare enough that the default is assumed
+    fn case_sensitive(self) -> bool {
+        self == Self::Unix
+    }
+
+    fn problems(self, name: &str) -> Vec<Problem> {
+        if name.is_empty() || name == "." || name == ".." {
+            return vec![Problem::Empty];
+        }
+
+        let mut problems = Vec::new();
+        let length = match self {
+            Self::Windows => name.encode_utf16().count(),
+            Self::Apple | Self::Unix => name.len(),
+        };
+        if length > MAX_NAME {
+            problems.push(Problem::LongName { length });
+        }
+        match self {
+            Self::Windows => problems.extend(health::windows_name_problems(name)),
+            Self::Apple | Self::Unix => {
+                let reserved: &[char] = if self == Self::Apple {
+                    &['/', '\0', ':']
+                } else {
+                    &['/', '\0']
+                };
+                if let Some(character) = name.chars().find(|c| reserved.contains(c)) {
+                    problems.push(Problem::ReservedCharacter { character });
+                }
+            }
+        }
+
+        problems
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NameCheck {
+    pub folder: String,
+    pub name: String,
+    /// As the OS reports it, such as `apfs` or `ntfs`; `None` when the volume is unknown
+    pub filesystem: Option<String>,
+    pub rules: NameRules,
+    pub case_sensitive: bool,
+    pub problems: Vec<Problem>,
+    pub valid: bool,
+}
+
+/// Check `name` against the rules of the filesystem `folder` is on and against the
+/// names already in it; `replacing` is the name being renamed away from, which does
+/// not count as taken
+pub fn check_name(
+    folder: &str,
+    name: &str,
+    replacing: Option<&str>,
+) -> Result<NameCheck, DuneError> {
+    let dir = Path::new(folder);
+    let metadata = fs::metadata(dir).map_err(|e| DuneError::io(dir, e))?;
+    if !metadata.is_dir() {
+        return Err(DuneError::NotADirectory {
+            path: folder.into(),
+        });
+    }
+
+    let filesystem = scanner::filesystem_of(&fs::canonicalize(dir).unwrap_or(dir.into()));
+    let rules = filesystem
+        .as_deref()
+        .and_then(NameRules::for_filesystem)
+        .unwrap_or_else(NameRules::host);
+    let case_sensitive = rules.case_sensitive();
+    let mut problems = rules.problems(name);
+
+    let folded = names::fold(name);
+    let entries = fs::read_dir(dir).map_err(|e| DuneError::io(dir, e))?;
+    for entry in entries.flatten() {
+        let other = entry.file_name().to_string_lossy().into_owned();
+        if Some(other.as_str()) == replacing {
+            continue;
+        }
+        if other == name {
+            problems.push(Problem::AlreadyExists);
+            break;
+        }
+        if !case_sensitive && names::fold(&other) == folded {
+            problems.push(Problem::CaseCollision { other });
+            break;
+        }
+    }
+
+    Ok(NameCheck {
+        folder: folder.to_string(),
+        name: name.to_string(),
+        filesystem,
+        rules,
+        case_sensitive,
+        valid: problems.is_empty(),
+        problems,
+    })
+}
@@ -0,0 +1,299 @@
+use crate::compare;
+use crate::error::DuneError;
+use crate::scan::ScanHandle;
+use crate::scanner::{DirEnumerator, EntryKind};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+/// Problems listed in the summary; the counts keep going past this
+const MAX_LISTED_PROBLEMS: usize = 1_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VerifyOptions {
+    /// Read every byte instead of sampling large files
+    pub full: bool,
+    /// Files up to this size are always read in full
+    pub full_below_mb: u64,
+    /// Blocks compared in each larger file, spread evenly and always including both ends
+    pub sample_blocks: u32,
+    pub block_kb: u64,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        Self {
+            full: false,
+            full_below_mb: 8,
+            sample_blocks: 16,
+            block_kb: 64,
+        }
+    }
+}
+
+/// Payload of `verify://started`; cancel with `cancel_scan(verify_id)`
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyStarted {
+    pub verify_id: u64,
+    pub source: String,
+    pub backup: String,
+}
+
+/// Payload of `verify://progress`
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyProgress {
+    pub verify_id: u64,
+    pub files_checked: u64,
+    pub files_total: u64,
+    pub bytes_checked: u64,
+    pub bytes_total: u64,
+    pub problems: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProblemKind {
+    Missing,
+    /// Present with a different size
+    SizeMismatch,
+    /// Same size, different bytes in a compared block
+    ContentMismatch,
+    /// Either copy could not be read
+    Unreadable,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyProblem {
+    /// Relative to the source and backup roots
+    pub path: String,
+    pub kind: ProblemKind,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifySummary {
+    pub verify_id: u64,
+    pub source: String,
+    pub backup: String,
+    pub files_checked: u64,
+    pub bytes_checked: u64,
+    pub missing: u64,
+    pub corrupted: u64,
+    pub unreadable: u64,
+    /// The first `MAX_LISTED_PROBLEMS` problems, largest first
+    pub problems: Vec<VerifyProblem>,
+    /// Some large files were only sampled
+    pub sampled: bool,
+    /// False if some source folders could not be listed
+    pub complete: bool,
+    /// Every source file was found intact; safe to rely on the backup
+    pub ok: bool,
+    pub elapsed_ms: u64,
+}
+
+struct SourceFile {
+    relative: PathBuf,
+    len: u64,
+}
+
+fn collect_files(
+    enumerator: &dyn DirEnumerator,
+    root: &Path,
+    relative: &Path,
+    files: &mut Vec<SourceFile>,
+) -> bool {
+    let Ok(listing) = enumerator.read_dir(&root.join(relative)) else {
+        return false;
+    };
+    let mut complete = listing.complete;
+
+    for entry in listing.entries {
+        let path = relative.join(&entry.name);
+        match entry.kind {
+            EntryKind::File => files.push(SourceFile {
+                relative: path,
+                len: entry.len,
+            }),
+            EntryKind::Dir => complete &= collect_files(enumerator, root, &path, files),
+            _ => {}
+        }
+    }
+
+    complete
+}
+
+/// Compare `blocks` evenly spaced blocks; the last one ends at the end of the file
+fn same_blocks(a: &Path, b: &Path, len: u64, blocks: u32, block: u64) -> io::Result<bool> {
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    let block = block.min(len);
+    let mut buf_a = vec![0u8; block as usize];
+    let mut buf_b = vec![0u8; block as usize];
+    let last_start = len - block;
+    let steps = u64::from(blocks.max(2) - 1);
+
+    for step in 0..=steps {
+        let offset = (u128::from(last_start) * u128::from(step) / u128::from(steps)) as u64;
+        a.seek(SeekFrom::Start(offset))?;
+        b.seek(SeekFrom::Start(offset))?;
+        a.read_exact(&mut buf_a)?;
+        b.read_exact(&mut buf_b)?;
+        if buf_a != buf_b {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Check that every file under the handle's root (the source) is intact in `backup`.
+/// Extra files in the backup are ignored.
+pub fn verify_backup(
+    handle: &ScanHandle,
+    backup: &str,
+    options: &VerifyOptions,
+    on_progress: &(dyn Fn(VerifyProgress) + Sync),
+) -> Result<VerifySummary, DuneError> {
+    let started = Instant::now();
+    let (source, backup_root) = (Path::new(&handle.root), Path::new(backup));
+    for path in [source, backup_root] {
+        if !path.is_dir() {
+            return Err(DuneError::NotADirectory {
+                path: path.to_string_lossy().to_string(),
+            });
+        }
+    }
+
+    let mut files = Vec::new();
+    let complete = collect_files(
+        crate::scanner::default_enumerator(),
+        source,
+        Path::new(""),
+        &mut files,
+    );
+    let files_total = files.len() as u64;
+    let bytes_total: u64 = files.iter().map(|file| file.len).sum();
+
+    let full_below = options.full_below_mb.saturating_mul(1024 * 1024);
+    let block = options.block_kb.max(1) * 1024;
+    let checked = AtomicU64::new(0);
+    let bytes_checked = AtomicU64::new(0);
+    let problem_count = AtomicU64::new(0);
+    let sampled = AtomicBool::new(false);
+    let last_progress = Mutex::new(Instant::now());
+
+    let mut problems: Vec<VerifyProblem> = files
+        .par_iter()
+        .filter_map(|file| {
+            if handle.is_cancelled() {
+                return None;
+            }
+
+            let (a, b) = (
+                source.join(&file.relative),
+                backup_root.join(&file.relative),
+            );
+            let kind = match fs::metadata(&b) {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Some(ProblemKind::Missing),
+                Err(_) => Some(ProblemKind::Unreadable),
+                Ok(metadata) if !metadata.is_file() => Some(ProblemKind::Missing),
+                Ok(metadata) if metadata.len() != file.len => Some(ProblemKind::SizeMismatch),
+                Ok(_) => {
+                    let same = if options.full || file.len <= full_below {
+                        compare::same_contents(&a, &b)
+                    } else {
+                        sampled.store(true, Ordering::Relaxed);
+                        same_blocks(&a, &b, file.len, options.sample_blocks, block)
+                    };
+                    match same {
+                        Ok(true) => None,
+                        Ok(false) => Some(ProblemKind::ContentMismatch),
+                        Err(err) => {
+                            log::debug!("Could not verify {}: {}", a.display(), err);
+                            Some(ProblemKind::Unreadable)
+                        }
+                    }
+                }
+            };
+
+            let files_checked = checked.fetch_add(1, Ordering::Relaxed) + 1;
+            let bytes = bytes_checked.fetch_add(file.len, Ordering::Relaxed) + file.len;
+            let problems = problem_count.load(Ordering::Relaxed) + u64::from(kind.is_some());
+            if kind.is_some() {
+                problem_count.fetch_add(1, Ordering::Relaxed);
+            }
+
+            if let Ok(mut last) = last_progress.try_lock() {
+                if last.elapsed() >= PROGRESS_INTERVAL {
+                    *last = Instant::now();
+                    on_progress(VerifyProgress {
+                        verify_id: handle.id,
+                        files_checked,
+                        files_total,
+                        bytes_checked: bytes,
+                        bytes_total,
+                        problems,
+                    });
+                }
+            }
+
+            kind.map(|kind| VerifyProblem {
+                path: file.relative.to_string_lossy().to_string(),
+                kind,
+                size: file.len,
+            })
+        })
+        .collect();
+
+    if handle.is_cancelled() {
+        log::info!("Verify {} of {} cancelled", handle.id, handle.root);
+        return Err(DuneError::Cancelled);
+    }
+
+    let count = |kinds: &[ProblemKind]| {
+        problems
+            .iter()
+            .filter(|problem| kinds.contains(&problem.kind))
+            .count() as u64
+    };
+    let missing = count(&[ProblemKind::Missing]);
+    let corrupted = count(&[ProblemKind::SizeMismatch, ProblemKind::ContentMismatch]);
+    let unreadable = count(&[ProblemKind::Unreadable]);
+
+    problems.sort_by_key(|problem| std::cmp::Reverse(problem.size));
+    problems.truncate(MAX_LISTED_PROBLEMS);
+
+    let summary = VerifySummary {
+        verify_id: handle.id,
+        source: handle.root.clone(),
+        backup: backup.to_string(),
+        files_checked: checked.into_inner(),
+        bytes_checked: bytes_checked.into_inner(),
+        missing,
+        corrupted,
+        unreadable,
+        problems,
+        sampled: sampled.into_inner(),
+        complete,
+        ok: complete && missing + corrupted + unreadable == 0,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    };
+
+    log::info!(
+        "Verified {} against {}: {} files, {} missing, {} corrupted, {} unreadable",
+        summary.source,
+        summary.backup,
+        summary.files_checked,
+        summary.missing,
+        summary.corrupted,
+        summary.unreadable
+    );
+
+    Ok(summary)
+}
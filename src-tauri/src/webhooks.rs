@@ -0,0 +1,214 @@
+use crate::console;
+use crate::error::DuneError;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::thread;
+
+/// Posts to Discord's `content` and Slack's `text` alike
+pub const DEFAULT_TEMPLATE: &str =
+    r#"{"event":"{{event}}","content":"{{message}}","text":"{{message}}"}"#;
+/// Seconds before curl gives up on a slow endpoint
+const TIMEOUT_SECONDS: &str = "15";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// A scheduled scan of a folder finished
+    ScanCompleted,
+    /// A disk dropped below its free space threshold
+    LowSpace,
+    /// A scheduled folder grew past `Settings.monitoring.growth_threshold_gb` between two scans
+    Growth,
+    /// A folder went over its `Settings.monitoring.budgets` entry
+    OverBudget,
+}
+
+impl WebhookEvent {
+    fn name(self) -> &'static str {
+        match self {
+            Self::ScanCompleted => "scan_completed",
+            Self::LowSpace => "low_space",
+            Self::Growth => "growth",
+            Self::OverBudget => "over_budget",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Webhook {
+    pub enabled: bool,
+    /// http:// or https:// endpoint that receives a POST
+    pub url: String,
+    /// Events this hook fires on; empty means all of them
+    pub events: Vec<WebhookEvent>,
+    /// JSON body with `{{placeholder}}`s, filled with JSON-escaped values, so they
+    /// belong inside string literals. Empty uses `DEFAULT_TEMPLATE`.
+    ///
+    /// Every event has `event`, `message`, `name` and `path`; `scan_completed` adds
+    /// `size_bytes`, `low_space` adds `available_bytes`, `total_bytes` and
+    /// `threshold_bytes`, `growth` adds `size_bytes`, `previous_bytes` and `growth_bytes`,
+    /// `over_budget` adds `size_bytes`, `budget_bytes` and `over_bytes`.
+    pub template: String,
+}
+
+impl Default for Webhook {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            url: String::new(),
+            events: Vec::new(),
+            template: String::new(),
+        }
+    }
+}
+
+impl Webhook {
+    fn wants(&self, event: WebhookEvent) -> bool {
+        self.enabled && (self.events.is_empty() || self.events.contains(&event))
+    }
+}
+
+/// Something a webhook can report, with the values its template may use
+pub struct Notice {
+    pub event: WebhookEvent,
+    pub message: String,
+    pub fields: Vec<(&'static str, String)>,
+}
+
+/// The value as it appears between the quotes of a JSON string
+fn json_escape(value: &str) -> String {
+    let quoted = serde_json::to_string(value).unwrap_or_default();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+fn render(template: &str, notice: &Notice) -> Result<String, DuneError> {
+    let template = if template.trim().is_empty() {
+        DEFAULT_TEMPLATE
+    } else {
+        template
+    };
+
+    let mut body = template
+        .replace("{{event}}", notice.event.name())
+        .replace("{{message}}", &json_escape(&notice.message));
+    for (key, value) in &notice.fields {
+        body = body.replace(&format!("{{{{{}}}}}", key), &json_escape(value));
+    }
+
+    serde_json::from_str::<serde_json::Value>(&body).map_err(|err| {
+        DuneError::InvalidInput(format!("Webhook template is not valid JSON: {}", err))
+    })?;
+
+    Ok(body)
+}
+
+/// Scheme and host only; Discord and Slack webhook URLs carry their secret in the path
+fn redacted(url: &str) -> &str {
+    let host_start = url.find("://").map_or(0, |scheme| scheme + 3);
+    match url[host_start..].find('/') {
+        Some(path) => &url[..host_start + path],
+        None => url,
+    }
+}
+
+/// A quoted value for a curl config file
+fn config_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// POST the body with curl, which ships with macOS, Windows 10 and most Linux systems
+fn post(url: &str, body: &str) -> Result<(), DuneError> {
+    if !(url.starts_with("https://") || url.starts_with("http://")) {
+        return Err(DuneError::InvalidInput(format!(
+            "Webhook URL must start with http:// or https://: {}",
+            redacted(url)
+        )));
+    }
+
+    let mut command = console::command("curl");
+    command
+        .args(["--silent", "--show-error", "--fail", "--max-time"])
+        .arg(TIMEOUT_SECONDS)
+        .args(["--request", "POST", "--header"])
+        .arg("Content-Type: application/json")
+        .arg("--output")
+        .arg(if cfg!(windows) { "NUL" } else { "/dev/null" })
+        // The URL and body go in a config read from stdin so neither shows up in the
+        // process list; the URL carries the secret
+        .args(["--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+
+    let mut child = command
+        .spawn()
+        .map_err(|err| DuneError::Unsupported(format!("Could not run curl: {}", err)))?;
+    let config = format!(
+        "url = {}\ndata-binary = {}\n",
+        config_string(url),
+        config_string(body)
+    );
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(config.as_bytes())
+            .map_err(DuneError::internal)?;
+    }
+
+    let mut stderr = String::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        let _ = pipe.read_to_string(&mut stderr);
+    }
+    let status = child.wait().map_err(DuneError::internal)?;
+    if !status.success() {
+        return Err(DuneError::Io {
+            path: None,
+            message: format!(
+                "Webhook to {} failed ({}): {}",
+                redacted(url),
+                status,
+                stderr.trim()
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+/// Send a notice to one webhook right away, whatever its events
+pub fn send(webhook: &Webhook, notice: &Notice) -> Result<(), DuneError> {
+    post(&webhook.url, &render(&webhook.template, notice)?)
+}
+
+/// Send a notice to every webhook that wants it, each on its own thread so a slow
+/// endpoint never holds up the scheduler
+pub fn fire(webhooks: &[Webhook], notice: Notice) {
+    let notice = Arc::new(notice);
+
+    for webhook in webhooks
+        .iter()
+        .filter(|webhook| webhook.wants(notice.event))
+    {
+        let (webhook, notice) = (webhook.clone(), notice.clone());
+        thread::spawn(move || {
+            if let Err(err) = send(&webhook, &notice) {
+                log::warn!("{}", err);
+            }
+        });
+    }
+}
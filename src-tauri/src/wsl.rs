@@ -0,0 +1,269 @@
+use crate::scanner::DiskInfo;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Share Windows serves each running distro's files under, `\\wsl$\Ubuntu\home`
+pub const SHARE: &str = r"\\wsl$\";
+/// Newer name of the same share
+pub const LOCALHOST_SHARE: &str = r"\\wsl.localhost\";
+
+/// Top-level folders of a distro that are other filesystems: Windows drives under
+/// `mnt`, and the kernel's views of processes and devices
+const FOREIGN_FOLDERS: &[&str] = &["mnt", "proc", "sys", "dev"];
+
+/// The virtual disk behind a WSL 2 distro listed as a disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VirtualDisk {
+    pub distro: String,
+    /// The `ext4.vhdx` holding the distro's files
+    pub image: String,
+    /// Space the image takes on the volume holding it; it grows as the distro writes and
+    /// does not shrink on its own when files are deleted
+    pub allocated: u64,
+    /// Bytes in use inside the distro, only known while it runs
+    pub used: Option<u64>,
+    /// What compacting the image would give back, `allocated` less `used`
+    pub reclaimable: Option<u64>,
+    pub running: bool,
+}
+
+/// `\\wsl$\<distro>` of a path on either share
+pub fn distro_root(path: &str) -> Option<String> {
+    let rest = [SHARE, LOCALHOST_SHARE].iter().find_map(|share| {
+        path.get(..share.len())
+            .filter(|prefix| prefix.eq_ignore_ascii_case(share))
+            .map(|_| &path[share.len()..])
+    })?;
+    let distro = rest
+        .split(['\\', '/'])
+        .next()
+        .filter(|name| !name.is_empty())?;
+    let share = &path[..path.len() - rest.len()];
+
+    Some(format!("{}{}", share, distro))
+}
+
+/// Folders a scan of a distro lists but does not read, see `FOREIGN_FOLDERS`
+pub fn foreign_mounts(root: &Path) -> Vec<PathBuf> {
+    let Some(distro) = distro_root(&root.to_string_lossy()) else {
+        return Vec::new();
+    };
+
+    FOREIGN_FOLDERS
+        .iter()
+        .map(|folder| Path::new(&distro).join(folder))
+        .collect()
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::*;
+    use crate::console;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+    use windows_sys::Win32::System::Registry::{
+        RegCloseKey, RegEnumKeyExW, RegGetValueW, RegOpenKeyExW, HKEY, HKEY_CURRENT_USER, KEY_READ,
+        RRF_RT_REG_SZ,
+    };
+
+    /// Where WSL registers each of the user's distros, one subkey per distro
+    const LXSS_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Lxss";
+    const IMAGE_NAME: &str = "ext4.vhdx";
+    /// Disk lists are read every few seconds; `wsl.exe` is too slow to start each time
+    const CACHE_TTL: Duration = Duration::from_secs(30);
+
+    static CACHE: Mutex<Option<(Instant, Vec<DiskInfo>)>> = Mutex::new(None);
+
+    fn wide(text: &str) -> Vec<u16> {
+        std::ffi::OsStr::new(text)
+            .encode_wide()
+            .chain([0])
+            .collect()
+    }
+
+    fn string_value(key: HKEY, subkey: &[u16], name: &str) -> Option<String> {
+        let subkey: Vec<u16> = subkey.iter().copied().chain([0]).collect();
+        let mut buffer = [0u16; 1024];
+        let mut size = (buffer.len() * 2) as u32;
+        let status = unsafe {
+            RegGetValueW(
+                key,
+                subkey.as_ptr(),
+                wide(name).as_ptr(),
+                RRF_RT_REG_SZ,
+                ptr::null_mut(),
+                buffer.as_mut_ptr().cast(),
+                &mut size,
+            )
+        };
+        // The size counts the trailing NUL
+        (status == 0)
+            .then(|| String::from_utf16_lossy(&buffer[..(size as usize / 2).saturating_sub(1)]))
+    }
+
+    /// Name and folder of every distro registered for the user
+    fn registered() -> Vec<(String, PathBuf)> {
+        let mut key: HKEY = ptr::null_mut();
+        if unsafe {
+            RegOpenKeyExW(
+                HKEY_CURRENT_USER,
+                wide(LXSS_KEY).as_ptr(),
+                0,
+                KEY_READ,
+                &mut key,
+            )
+        } != 0
+        {
+            return Vec::new();
+        }
+
+        let mut distros = Vec::new();
+        for index in 0.. {
+            let mut name = [0u16; 256];
+            let mut length = name.len() as u32;
+            let status = unsafe {
+                RegEnumKeyExW(
+                    key,
+                    index,
+                    name.as_mut_ptr(),
+                    &mut length,
+                    ptr::null(),
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                )
+            };
+            if status != 0 {
+                break;
+            }
+
+            let subkey = &name[..length as usize];
+            if let (Some(distro), Some(base)) = (
+                string_value(key, subkey, "DistributionName"),
+                string_value(key, subkey, "BasePath"),
+            ) {
+                let base = base.strip_prefix(r"\\?\").unwrap_or(&base).to_string();
+                distros.push((distro, PathBuf::from(base)));
+            }
+        }
+        unsafe { RegCloseKey(key) };
+
+        distros
+    }
+
+    /// Asked of `wsl.exe`, since reaching a stopped distro's share would start it
+    fn running() -> Vec<String> {
+        let output = match console::command("wsl.exe")
+            .args(["--list", "--running", "--quiet"])
+            .output()
+        {
+            Ok(output) if output.status.success() => output,
+            Ok(_) => return Vec::new(),
+            Err(err) => {
+                log::debug!("Could not run wsl.exe: {}", err);
+                return Vec::new();
+            }
+        };
+
+        // wsl.exe writes UTF-16
+        let units: Vec<u16> = output
+            .stdout
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+            .lines()
+            .map(|line| line.trim().trim_start_matches('\u{feff}').to_string())
+            .filter(|line| !line.is_empty())
+            .collect()
+    }
+
+    /// Total and free bytes of the distro's own filesystem, through its share
+    fn capacity(share: &str) -> Option<(u64, u64)> {
+        let (mut available, mut total, mut free) = (0u64, 0u64, 0u64);
+        let ok = unsafe {
+            GetDiskFreeSpaceExW(
+                wide(&format!("{}\\", share)).as_ptr(),
+                &mut available,
+                &mut total,
+                &mut free,
+            )
+        };
+        (ok != 0 && total > 0).then_some((total, free))
+    }
+
+    fn distro_disks() -> Vec<DiskInfo> {
+        let running = running();
+
+        registered()
+            .into_iter()
+            .filter_map(|(distro, base)| {
+                // WSL 1 distros keep plain files under the folder, no virtual disk
+                let image = base.join(IMAGE_NAME);
+                let allocated = std::fs::metadata(&image).ok()?.len();
+                let path = format!("{}{}", SHARE, distro);
+                let is_running = running
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(&distro));
+                let capacity = is_running.then(|| capacity(&path)).flatten();
+                let used = capacity.map(|(total, free)| total.saturating_sub(free));
+
+                Some(DiskInfo {
+                    name: format!("WSL: {}", distro),
+                    path,
+                    total_space: capacity.map_or(0, |(total, _)| total),
+                    available_space: capacity.map_or(0, |(_, free)| free),
+                    purgeable_space: 0,
+                    full_in_days: None,
+                    total_inodes: None,
+                    free_inodes: None,
+                    mtp: false,
+                    ram_disk: false,
+                    original_name: None,
+                    default_folder: None,
+                    container: None,
+                    virtual_disk: Some(VirtualDisk {
+                        distro,
+                        image: image.to_string_lossy().into_owned(),
+                        allocated,
+                        used,
+                        reclaimable: used.map(|used| allocated.saturating_sub(used)),
+                        running: is_running,
+                    }),
+                })
+            })
+            .collect()
+    }
+
+    pub fn disks() -> Vec<DiskInfo> {
+        let mut cache = CACHE.lock().unwrap();
+        if let Some((read_at, disks)) = cache.as_ref() {
+            if read_at.elapsed() < CACHE_TTL {
+                return disks.clone();
+            }
+        }
+
+        let disks = distro_disks();
+        *cache = Some((Instant::now(), disks.clone()));
+        disks
+    }
+}
+
+#[cfg(not(windows))]
+mod platform {
+    use super::*;
+
+    pub fn disks() -> Vec<DiskInfo> {
+        Vec::new()
+    }
+}
+
+/// A disk for each WSL 2 distro, at its `\\wsl$\` share so it can be scanned like any
+/// other. Its space is part of the volume holding the image, see `VirtualDisk`; sizes
+/// from inside are only filled in while the distro runs.
+pub fn wsl_disks() -> Vec<DiskInfo> {
+    platform::disks()
+}
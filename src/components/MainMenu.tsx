@@ -114,6 +114,20 @@ export default function MainMenu({
                 >
                   NEON BLUE
                 </button>
+                <button
+                  type="button"
+                  className={theme === "colorblind-safe" ? "is-active" : ""}
+                  onClick={() => onThemeChange("colorblind-safe")}
+                >
+                  COLORBLIND
+                </button>
+                <button
+                  type="button"
+                  className={theme === "high-contrast" ? "is-active" : ""}
+                  onClick={() => onThemeChange("high-contrast")}
+                >
+                  HIGH CONTRAST
+                </button>
               </div>
             </div>
 
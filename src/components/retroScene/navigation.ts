@@ -40,6 +40,8 @@ export function createNavigationHandlers({
 
   async function loadDirectory(path: string) {
     showLoading(LOADING_OVERLAY_AUTO_HIDE_MS);
+    // Let any running scan read this folder's subtree next
+    invoke("boost_priority", { path }).catch(() => {});
     try {
       const entries = await invoke<FileEntry[]>("list_directory", { path });
 
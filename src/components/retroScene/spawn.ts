@@ -23,6 +23,11 @@ export type DiskInfo = {
   path: string;
   total_space: number;
   available_space: number;
+  purgeable_space: number;
+  full_in_days: number | null;
+  total_inodes: number | null;
+  free_inodes: number | null;
+  mtp: boolean;
 };
 
 type SpawnFactory = {
@@ -243,7 +248,10 @@ export function createSpawnFactory({ scene, world, defaultMaterial, sceneObjects
 
     console.log(`Disk ${disk.name}: ${availableGB.toFixed(1)} GB free of ${totalGB.toFixed(1)} GB total`);
 
-    const sizeLabel = availableGB < 0.1 ? "empty" : `${availableGB.toFixed(1)} GB free`;
+    const freeLabel = availableGB < 0.1 ? "empty" : `${availableGB.toFixed(1)} GB free`;
+    // Only set when stored snapshots project the disk filling up soon
+    const sizeLabel =
+      disk.full_in_days != null ? `${freeLabel} · full in ~${Math.max(1, Math.round(disk.full_in_days))}d` : freeLabel;
 
     mesh.castShadow = true;
     mesh.scale.set(scale, scale, scale);
@@ -3,10 +3,18 @@ import { create } from "zustand";
 // File entry from Rust backend
 export type FileEntry = {
   name: string;
+  // Exact bytes of the name when they differ from the normalized `name`
+  raw_name_bytes?: number[];
   path: string;
   is_dir: boolean;
   size: number;
   size_complete?: boolean;
+  // Finder tags (macOS) and tags from the app's own store
+  tags?: string[];
+  custom_tags?: string[];
+  note?: string | null;
+  // Only present when the file is cloned, sparse or compressed
+  storage?: { cloned: boolean; sparse: boolean; compressed: boolean };
 };
 
 // History entry includes path, entries, and camera/scene state
@@ -1,4 +1,4 @@
-export type ThemeName = "red" | "green" | "neon-blue";
+export type ThemeName = "red" | "green" | "neon-blue" | "colorblind-safe" | "high-contrast";
 
 export type ThemePalette = {
   name: ThemeName;
@@ -58,6 +58,34 @@ const PALETTES: Record<ThemeName, ThemePalette> = {
     meshEmissiveHex: 0x051220,
     mechaTintHex: 0x114466,
   },
+  "colorblind-safe": {
+    name: "colorblind-safe",
+    primaryHex: 0x56b4e9,
+    accentHex: 0xe69f00,
+    softHex: 0xb3dcf5,
+    dimHex: 0x0072b2,
+    backgroundHex: 0x030507,
+    deepBackgroundHex: 0x0b0f14,
+    fogHex: 0x14202c,
+    planeHex: 0x0a0e12,
+    meshBaseHex: 0x2a3440,
+    meshEmissiveHex: 0x08121c,
+    mechaTintHex: 0x1c4a66,
+  },
+  "high-contrast": {
+    name: "high-contrast",
+    primaryHex: 0xffff00,
+    accentHex: 0x00ffff,
+    softHex: 0xffffff,
+    dimHex: 0xcccc00,
+    backgroundHex: 0x000000,
+    deepBackgroundHex: 0x000000,
+    fogHex: 0x000000,
+    planeHex: 0x0a0a0a,
+    meshBaseHex: 0x303030,
+    meshEmissiveHex: 0x000000,
+    mechaTintHex: 0x666600,
+  },
 };
 
 let currentTheme: ThemeName = "red";